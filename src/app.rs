@@ -2,12 +2,41 @@
 //!
 //! Central state management and event-driven architecture for ratatalk.
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use chrono::{DateTime, Utc};
+use crossterm::event::KeyEvent;
+use ratatui::text::Line;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+use tracing::{debug, warn};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 use uuid::Uuid;
 
-use crate::config::Config;
-use crate::ollama::{ChatMessage, GenerationOptions, ModelInfo, Role};
+use crate::cache::{CacheEntry, ResponseCache};
+use crate::clipboard;
+use crate::commands::{DatasetScope, FollowUp};
+use crate::config::{Config, TimestampStyle};
+use crate::dryrun;
+use crate::error::{AttachmentError, ClipboardError, PersistenceError};
+use crate::search::{self, SearchHit};
+use crate::middleware::{post_process, PostProcessor};
+use crate::ollama::{ChatMessage, FunctionCall, GenerationOptions, ModelInfo, Role, ToolCall};
+use crate::i18n::Locale;
+use crate::keymap::{self, ActionDef, Keymap};
+use crate::theme::Theme;
+use crate::storage;
+use crate::store::ChatStore;
+use crate::patch::FilePatch;
+use crate::picker::{FilePicker, PickPurpose};
+use crate::prompts::PromptLibrary;
+use crate::persistence::{self, MarkdownOptions};
+use crate::redact::Redactor;
 
 // ============================================================================
 // Core Data Structures
@@ -23,6 +52,143 @@ pub struct Message {
     /// True if this message is still being streamed
     #[serde(default)]
     pub streaming: bool,
+    /// Image files attached to this message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<PathBuf>,
+    /// Reasoning from thinking models, shown apart from the answer
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub thinking: String,
+    /// True if generation was cancelled before the response completed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// True if the model stopped at the token limit or a stop sequence
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Marked as a bad exchange, left out of dataset exports
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub flagged: bool,
+    /// Rating and note left on an assistant message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<Feedback>,
+    /// Model that wrote this response (older sessions: unknown)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Tools called by the model before it answered (agent mode)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_steps: Vec<ToolStep>,
+    /// Answered from the response cache instead of the model
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    /// Text files sent along with this message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Generation options of the request that produced this response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<GenerationOptions>,
+    /// Milliseconds from sending the request to the first token of this
+    /// response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_token_ms: Option<u64>,
+    /// Show the reasoning in full instead of collapsed to one line
+    #[serde(skip)]
+    pub thinking_expanded: bool,
+    /// Bumped by [`Message::touch`] whenever the text changes, so the chat
+    /// can tell a message needs laying out again without reading it
+    #[serde(skip)]
+    pub revision: u64,
+}
+
+/// Largest text file that can be attached
+const MAX_ATTACHMENT_BYTES: u64 = 256 * 1024;
+
+/// A text file attached to a user message
+///
+/// Stored with the message so the chat can show it collapsed; the model
+/// gets it as a fenced block after the message text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// File name, without the directory
+    pub name: String,
+    pub content: String,
+}
+
+impl Attachment {
+    /// Read a text file, refusing binary and oversized files
+    pub fn load(path: &Path) -> Result<Self, AttachmentError> {
+        let size = std::fs::metadata(path).map_err(AttachmentError::Read)?.len();
+        if size > MAX_ATTACHMENT_BYTES {
+            return Err(AttachmentError::TooLarge(size / 1024, MAX_ATTACHMENT_BYTES / 1024));
+        }
+        let bytes = std::fs::read(path).map_err(AttachmentError::Read)?;
+        let content = String::from_utf8(bytes).map_err(|_| AttachmentError::Binary)?;
+        if content.contains('\0') {
+            return Err(AttachmentError::Binary);
+        }
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        Ok(Self { name, content })
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.content.lines().count()
+    }
+
+    /// The file as a fenced block labelled with its name
+    ///
+    /// The fence is made longer than any backtick run in the file.
+    pub fn fenced(&self) -> String {
+        let longest = self
+            .content
+            .split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest.max(2) + 1);
+        format!("{}{}\n{}\n{}", fence, self.name, self.content.trim_end_matches('\n'), fence)
+    }
+}
+
+/// A tool call made during an agent run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolStep {
+    pub name: String,
+    pub arguments: serde_json::Value,
+    /// Output returned to the model; `None` while the tool runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+impl ToolStep {
+    /// Arguments as `key: value` pairs on one line
+    pub fn arguments_summary(&self) -> String {
+        match &self.arguments {
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect::<Vec<_>>()
+                .join(", "),
+            serde_json::Value::Null => String::new(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Thumbs up or down on a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+/// Feedback on an assistant message, for dataset curation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Feedback {
+    pub rating: Rating,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
 impl Message {
@@ -33,6 +199,20 @@ impl Message {
             content: content.into(),
             timestamp: Utc::now(),
             streaming: false,
+            images: Vec::new(),
+            thinking: String::new(),
+            interrupted: false,
+            truncated: false,
+            flagged: false,
+            feedback: None,
+            model: None,
+            tool_steps: Vec::new(),
+            cached: false,
+            attachments: Vec::new(),
+            options: None,
+            first_token_ms: None,
+            thinking_expanded: false,
+            revision: 0,
         }
     }
 
@@ -58,12 +238,69 @@ impl Message {
             content: String::new(),
             timestamp: Utc::now(),
             streaming: true,
+            images: Vec::new(),
+            thinking: String::new(),
+            interrupted: false,
+            truncated: false,
+            flagged: false,
+            feedback: None,
+            model: None,
+            tool_steps: Vec::new(),
+            cached: false,
+            attachments: Vec::new(),
+            options: None,
+            first_token_ms: None,
+            thinking_expanded: false,
+            revision: 0,
         }
     }
 
+    pub fn rating(&self) -> Option<Rating> {
+        self.feedback.as_ref().map(|f| f.rating)
+    }
+
+    /// Whether this is the placeholder left by a failed request
+    pub fn is_error(&self) -> bool {
+        self.role == Role::Assistant && self.content.starts_with("[Error:")
+    }
+
+    /// Whether dataset exports should leave this response out
+    pub fn excluded_from_datasets(&self) -> bool {
+        self.flagged || self.rating() == Some(Rating::Down)
+    }
+
+    /// Note a change to the content, reasoning, tool results or feedback
+    pub fn touch(&mut self) {
+        self.revision = self.revision.wrapping_add(1);
+    }
+
     /// Append content to this message (for streaming)
     pub fn append(&mut self, text: &str) {
         self.content.push_str(text);
+        self.touch();
+    }
+
+    /// Append reasoning to this message (for streaming)
+    pub fn append_thinking(&mut self, text: &str) {
+        self.thinking.push_str(text);
+        self.touch();
+    }
+
+    /// The reasoning and the answer: the `thinking` field and the content,
+    /// or a `<think>…</think>` block leading the content and what follows
+    ///
+    /// A block still open while streaming is all reasoning so far.
+    pub fn reasoning(&self) -> (&str, &str) {
+        if !self.thinking.is_empty() || self.role != Role::Assistant {
+            return (&self.thinking, &self.content);
+        }
+        let Some(rest) = self.content.trim_start().strip_prefix("<think>") else {
+            return ("", &self.content);
+        };
+        match rest.split_once("</think>") {
+            Some((thinking, answer)) => (thinking, answer.trim_start()),
+            None => (rest, ""),
+        }
     }
 
     /// Mark streaming as complete
@@ -71,14 +308,140 @@ impl Message {
         self.streaming = false;
     }
 
+    /// Estimated tokens this message takes up in a request, tool calls
+    /// and attachments included
+    pub fn estimated_tokens(&self) -> usize {
+        let attached: usize = self.attachments.iter().map(|a| dryrun::estimate_text_tokens(&a.fenced())).sum();
+        let steps: usize = self
+            .tool_steps
+            .iter()
+            .map(|s| {
+                dryrun::estimate_text_tokens(&s.arguments.to_string())
+                    + s.result.as_deref().map_or(0, dryrun::estimate_text_tokens)
+            })
+            .sum();
+        dryrun::estimate_text_tokens(&self.content) + attached + steps
+    }
+
     /// Convert to Ollama ChatMessage
+    ///
+    /// Attached images are read from disk and base64-encoded; unreadable
+    /// files are skipped. Attached text files follow the message text as
+    /// fenced blocks. Reasoning is not sent back to the model.
     pub fn to_chat_message(&self) -> ChatMessage {
+        let images: Vec<String> = self
+            .images
+            .iter()
+            .filter_map(|path| match std::fs::read(path) {
+                Ok(bytes) => Some(STANDARD.encode(bytes)),
+                Err(e) => {
+                    warn!("Failed to read image {:?}: {}", path, e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut content = self.content.clone();
+        for attachment in &self.attachments {
+            content.push_str("\n\n");
+            content.push_str(&attachment.fenced());
+        }
+
         ChatMessage {
             role: self.role,
-            content: self.content.clone(),
-            images: None,
+            content,
+            images: if images.is_empty() { None } else { Some(images) },
+            thinking: None,
+            tool_calls: None,
+        }
+    }
+}
+
+/// File extensions recognized as image attachments
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+/// Whether `path` has an image extension
+pub fn is_image_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Find `@path` references to existing image files in a message
+pub fn extract_image_refs(content: &str) -> Vec<PathBuf> {
+    content
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(Path::new)
+        .filter(|path| is_image_path(path) && path.is_file())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// A fenced code block in a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Language from the opening fence (may be empty)
+    pub lang: String,
+    pub code: String,
+}
+
+/// Extract fenced code blocks from message text, in order
+///
+/// An unterminated final block runs to the end of the text.
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+    for line in text.split('\n') {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut current, fence) {
+            (None, Some(lang)) => current = Some((lang.trim().to_string(), Vec::new())),
+            (Some(_), Some(_)) => {
+                if let Some((lang, lines)) = current.take() {
+                    blocks.push(CodeBlock { lang, code: lines.join("\n") });
+                }
+            }
+            (Some((_, lines)), None) => lines.push(line),
+            (None, None) => {}
         }
     }
+    if let Some((lang, lines)) = current {
+        blocks.push(CodeBlock { lang, code: lines.join("\n") });
+    }
+    blocks
+}
+
+/// Name given to new sessions, e.g. "Chat 2025-01-01 09:00"
+const DEFAULT_NAME_FORMAT: &str = "Chat %Y-%m-%d %H:%M";
+
+/// Instruction appended to the first exchange to get a session title
+const TITLE_PROMPT: &str = "Give this conversation a short title of at most six words. \
+    Reply with the title only, without quotes or punctuation at the end.";
+
+/// Longest title accepted from the model, in characters
+const MAX_TITLE_CHARS: usize = 60;
+
+/// Tidy a title suggested by the model
+///
+/// Skips inline `<think>` reasoning, takes the first non-empty line, drops
+/// a "Title:" prefix, surrounding quotes and markdown emphasis, and a
+/// trailing period. Returns `None` when nothing usable is left.
+pub fn clean_title(raw: &str) -> Option<String> {
+    let raw = raw.rsplit_once("</think>").map_or(raw, |(_, rest)| rest);
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line);
+    let title = line
+        .trim()
+        .trim_matches(|c: char| matches!(c, '"' | '\'' | '*' | '#' | '`' | '“' | '”'))
+        .trim_end_matches('.')
+        .trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some(title.chars().take(MAX_TITLE_CHARS).collect::<String>().trim_end().to_string())
 }
 
 /// A chat session containing a conversation with a model
@@ -93,9 +456,38 @@ pub struct ChatSession {
     /// Optional system prompt for this session
     #[serde(default)]
     pub system_prompt: Option<String>,
+    /// Name of the library prompt `system_prompt` came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_name: Option<String>,
     /// Session-specific generation options
     #[serde(default)]
     pub options: Option<GenerationOptions>,
+    /// Session-specific post-processors (None = config default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_processors: Option<Vec<PostProcessor>>,
+    /// Jump marks: letter to the id of the marked message
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub marks: BTreeMap<char, Uuid>,
+    /// Free-form tags, used in exports
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Kept at the top of the sidebar and never pruned
+    #[serde(default)]
+    pub pinned: bool,
+    /// Content hash as stored on disk; written by the persistence layer
+    #[serde(default, skip_serializing)]
+    pub checksum: Option<String>,
+    /// Oldest messages left out of the last request to fit the context window
+    #[serde(skip)]
+    pub not_sent: usize,
+    /// Messages dropped from memory to stay under `[memory] max_mb`; the
+    /// copy on disk is current and is read back when the session is opened
+    #[serde(skip)]
+    pub unloaded: bool,
+    /// Tokens the conversation took up after the last response (prompt
+    /// plus reply), as the server counted them
+    #[serde(skip)]
+    pub context_used: Option<u32>,
 }
 
 impl ChatSession {
@@ -109,26 +501,130 @@ impl ChatSession {
             created_at: now,
             updated_at: now,
             system_prompt: None,
+            prompt_name: None,
             options: None,
+            post_processors: None,
+            marks: BTreeMap::new(),
+            tags: Vec::new(),
+            pinned: false,
+            checksum: None,
+            not_sent: 0,
+            unloaded: false,
+            context_used: None,
+        }
+    }
+
+    /// Hash of the conversation content (FNV-1a), to detect corruption
+    ///
+    /// Covers identity and messages; view state such as marks is left out.
+    pub fn content_hash(&self) -> String {
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |field: &[u8]| {
+            // Length-prefixed, so fields can't run together
+            let len = (field.len() as u64).to_le_bytes();
+            for byte in len.iter().chain(field) {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(PRIME);
+            }
+        };
+
+        feed(self.id.as_bytes());
+        feed(self.name.as_bytes());
+        feed(self.model.as_bytes());
+        feed(self.system_prompt.as_deref().unwrap_or_default().as_bytes());
+        for message in &self.messages {
+            feed(message.id.as_bytes());
+            feed(message.role.to_string().as_bytes());
+            feed(message.content.as_bytes());
+            feed(message.thinking.as_bytes());
+            for image in &message.images {
+                feed(image.to_string_lossy().as_bytes());
+            }
         }
+        format!("{:016x}", hash)
+    }
+
+    /// Approximate bytes held by the messages: their text plus a fixed
+    /// overhead each
+    pub fn memory_size(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|m| {
+                std::mem::size_of::<Message>()
+                    + m.content.len()
+                    + m.thinking.len()
+                    + m.attachments.iter().map(|a| a.name.len() + a.content.len()).sum::<usize>()
+                    + m.tool_steps.iter().map(|t| t.result.as_ref().map_or(0, String::len)).sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Whether the stored checksum, if any, matches the content
+    pub fn verify(&self) -> bool {
+        self.checksum.as_ref().is_none_or(|c| *c == self.content_hash())
     }
 
     /// Create with a default name based on timestamp
     pub fn with_default_name(model: impl Into<String>) -> Self {
         let now = Utc::now();
-        let name = now.format("Chat %Y-%m-%d %H:%M").to_string();
+        let name = now.format(DEFAULT_NAME_FORMAT).to_string();
         Self::new(name, model)
     }
 
+    /// Whether the session still has its generated timestamp name
+    pub fn has_default_name(&self) -> bool {
+        chrono::NaiveDateTime::parse_from_str(&self.name, DEFAULT_NAME_FORMAT).is_ok()
+    }
+
+    /// Whether the session should be titled by the model
+    ///
+    /// True right after the first exchange completes, while the session
+    /// still has its generated name.
+    pub fn wants_auto_title(&self) -> bool {
+        match self.messages.as_slice() {
+            [first, reply] => {
+                self.has_default_name()
+                    && first.role == Role::User
+                    && reply.role == Role::Assistant
+                    && !reply.streaming
+                    && !reply.interrupted
+                    && !reply.content.trim().is_empty()
+                    && !reply.is_error()
+            }
+            _ => false,
+        }
+    }
+
+    /// Messages asking the model to title this conversation
+    pub fn title_request(&self) -> Vec<ChatMessage> {
+        let mut messages: Vec<ChatMessage> = self.messages.iter().map(Message::to_chat_message).collect();
+        messages.push(ChatMessage::user(TITLE_PROMPT));
+        messages
+    }
+
     /// Add a user message to the session
     pub fn add_user_message(&mut self, content: impl Into<String>) {
-        self.messages.push(Message::user(content));
+        self.add_user_message_with_images(content, Vec::new());
+    }
+
+    /// Add a user message with attached images
+    pub fn add_user_message_with_images(&mut self, content: impl Into<String>, images: Vec<PathBuf>) {
+        let mut message = Message::user(content);
+        message.images = images;
+        self.messages.push(message);
         self.updated_at = Utc::now();
     }
 
     /// Start a new streaming assistant response
+    ///
+    /// The response is attributed to the session's current model, so earlier
+    /// answers keep theirs when the model is switched.
     pub fn start_assistant_response(&mut self) -> usize {
-        self.messages.push(Message::assistant_streaming());
+        self.messages.push(Message {
+            model: Some(self.model.clone()),
+            ..Message::assistant_streaming()
+        });
         self.updated_at = Utc::now();
         self.messages.len() - 1
     }
@@ -143,6 +639,50 @@ impl ChatSession {
         }
     }
 
+    /// Append reasoning to the current streaming response
+    pub fn append_thinking_to_response(&mut self, text: &str) {
+        if let Some(msg) = self.messages.last_mut() {
+            if msg.streaming {
+                msg.append_thinking(text);
+                self.updated_at = Utc::now();
+            }
+        }
+    }
+
+    /// Record a tool call on the streaming response
+    pub fn add_tool_step(&mut self, name: String, arguments: serde_json::Value) {
+        if let Some(msg) = self.messages.last_mut() {
+            if msg.streaming {
+                msg.tool_steps.push(ToolStep { name, arguments, result: None });
+                msg.touch();
+                self.updated_at = Utc::now();
+            }
+        }
+    }
+
+    /// Fill in the result of the latest tool call
+    pub fn finish_tool_step(&mut self, result: String) {
+        let Some(msg) = self.messages.last_mut().filter(|msg| msg.streaming) else {
+            return;
+        };
+        if let Some(step) = msg.tool_steps.last_mut() {
+            step.result = Some(result);
+            msg.touch();
+            self.updated_at = Utc::now();
+        }
+    }
+
+    /// Stop the current streaming response, keeping the partial text
+    pub fn interrupt_response(&mut self) {
+        if let Some(msg) = self.messages.last_mut() {
+            if msg.streaming {
+                msg.interrupted = true;
+                msg.finish_streaming();
+                self.updated_at = Utc::now();
+            }
+        }
+    }
+
     /// Finish the current streaming response
     pub fn finish_response(&mut self) {
         if let Some(msg) = self.messages.last_mut() {
@@ -151,8 +691,107 @@ impl ChatSession {
         }
     }
 
+    /// Keep what the server reported about the last response: whether it
+    /// was cut off, its time to first token and how full the context got
+    pub fn record_stats(&mut self, stats: &ResponseStats) {
+        if stats.truncated {
+            self.mark_truncated();
+        }
+        if let Some(msg) = self.messages.last_mut().filter(|m| m.role == Role::Assistant) {
+            msg.first_token_ms = stats.first_token_ms.or(msg.first_token_ms);
+        }
+        self.context_used = stats.context_used().or(self.context_used);
+    }
+
+    /// Note that the last response was cut off, so it can be continued
+    pub fn mark_truncated(&mut self) {
+        if let Some(msg) = self.messages.last_mut().filter(|m| m.role == Role::Assistant) {
+            msg.truncated = true;
+        }
+    }
+
+    /// Remove a failed turn, the question and its error placeholder, so it
+    /// can be sent again
+    ///
+    /// Returns the question; `None` leaves the session untouched.
+    pub fn take_failed_turn(&mut self) -> Option<Message> {
+        match self.messages.as_slice() {
+            [.., question, reply] if question.role == Role::User && reply.is_error() && !reply.streaming => {
+                self.messages.pop();
+                self.updated_at = Utc::now();
+                self.messages.pop()
+            }
+            _ => None,
+        }
+    }
+
+    /// Reopen a cut-off response so its continuation streams onto the end
+    ///
+    /// Returns false when the last message isn't a cut-off response.
+    pub fn resume_response(&mut self) -> bool {
+        match self.messages.last_mut() {
+            Some(msg) if msg.role == Role::Assistant && msg.truncated && !msg.streaming => {
+                msg.truncated = false;
+                msg.streaming = true;
+                self.updated_at = Utc::now();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Run post-processors over the last message once it has finished
+    pub fn post_process_response(&mut self, processors: &[PostProcessor]) {
+        if let Some(msg) = self.messages.last_mut() {
+            if msg.role == Role::Assistant && !msg.streaming {
+                post_process(processors, msg);
+            }
+        }
+    }
+
+    /// Estimated prompt tokens of the whole conversation
+    pub fn estimated_tokens(&self) -> usize {
+        let system = self.system_prompt.as_deref().map_or(0, dryrun::estimate_text_tokens);
+        system + self.messages.iter().map(Message::estimated_tokens).sum::<usize>()
+    }
+
     /// Get messages formatted for Ollama API
     pub fn to_chat_messages(&self) -> Vec<ChatMessage> {
+        self.to_chat_messages_from(0)
+    }
+
+    /// Messages for the API, leaving out the oldest so the prompt fits in
+    /// about `budget` tokens
+    ///
+    /// The system prompt and everything from the newest user message on are
+    /// always kept. Returns the messages and how many were left out.
+    pub fn to_chat_messages_within(&self, budget: usize) -> (Vec<ChatMessage>, usize) {
+        let start = self.first_sent(budget, 0);
+        (self.to_chat_messages_from(start), start)
+    }
+
+    /// Index of the oldest message that fits in about `budget` tokens, with
+    /// a new user message of `pending` tokens still to be added
+    pub fn first_sent(&self, budget: usize, pending: usize) -> usize {
+        let newest_user = if pending > 0 {
+            self.messages.len()
+        } else {
+            self.messages.iter().rposition(|m| m.role == Role::User).unwrap_or(0)
+        };
+        let mut used = self.system_prompt.as_deref().map_or(0, dryrun::estimate_text_tokens) + pending;
+        let mut start = self.messages.len();
+        while start > 0 {
+            let cost = self.messages[start - 1].estimated_tokens();
+            if start <= newest_user && used + cost > budget {
+                break;
+            }
+            used += cost;
+            start -= 1;
+        }
+        start
+    }
+
+    fn to_chat_messages_from(&self, start: usize) -> Vec<ChatMessage> {
         let mut messages = Vec::new();
         
         // Add system prompt if present
@@ -160,14 +799,30 @@ impl ChatSession {
             messages.push(ChatMessage::system(system.clone()));
         }
         
-        // Add all conversation messages
-        for msg in &self.messages {
-            messages.push(msg.to_chat_message());
+        for msg in &self.messages[start..] {
+            messages.extend(Self::replay(msg));
         }
         
         messages
     }
 
+    /// A message as sent, with its agent tool calls replayed before the
+    /// answer they led to
+    fn replay(msg: &Message) -> Vec<ChatMessage> {
+        let mut messages = Vec::new();
+        for step in &msg.tool_steps {
+            messages.push(ChatMessage {
+                tool_calls: Some(vec![ToolCall {
+                    function: FunctionCall { name: step.name.clone(), arguments: step.arguments.clone() },
+                }]),
+                ..ChatMessage::assistant("")
+            });
+            messages.push(ChatMessage::tool(step.result.clone().unwrap_or_default()));
+        }
+        messages.push(msg.to_chat_message());
+        messages
+    }
+
     /// Get message count
     pub fn message_count(&self) -> usize {
         self.messages.len()
@@ -178,6 +833,117 @@ impl ChatSession {
         self.messages.last().map(|m| m.streaming).unwrap_or(false)
     }
 
+    /// Get the most recent completed assistant message
+    pub fn last_assistant_message(&self) -> Option<&Message> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::Assistant && !m.streaming)
+    }
+
+    fn last_response_mut(&mut self) -> Option<&mut Message> {
+        self.messages
+            .iter_mut()
+            .rev()
+            .find(|m| m.role == Role::Assistant && !m.streaming)
+    }
+
+    /// Flag or unflag the last completed response; returns whether it is now flagged
+    pub fn toggle_flag_last_response(&mut self) -> Option<bool> {
+        let msg = self.last_response_mut()?;
+        msg.flagged = !msg.flagged;
+        Some(msg.flagged)
+    }
+
+    /// Rate the last completed response
+    ///
+    /// Repeating the current rating without a note clears it. Returns the
+    /// rating now set, or `None` if there is no response to rate.
+    pub fn rate_last_response(&mut self, rating: Rating, note: Option<String>) -> Option<Option<Rating>> {
+        let msg = self.last_response_mut()?;
+        if note.is_none() && msg.rating() == Some(rating) {
+            msg.feedback = None;
+        } else {
+            // Keep an existing note when only the rating changes
+            let note = note.or_else(|| msg.feedback.take().and_then(|f| f.note));
+            msg.feedback = Some(Feedback { rating, note });
+        }
+        msg.touch();
+        Some(msg.rating())
+    }
+
+    /// A new session with the messages in `range`
+    ///
+    /// Settings and tags carry over; messages get fresh ids, and marks on
+    /// the copied messages follow them.
+    pub fn fork(&self, range: RangeInclusive<usize>) -> Option<ChatSession> {
+        let kept = self.messages.get(range)?;
+        let mut fork = ChatSession::new(format!("{} (fork)", self.name), &self.model);
+        fork.system_prompt = self.system_prompt.clone();
+        fork.prompt_name = self.prompt_name.clone();
+        fork.options = self.options.clone();
+        fork.post_processors = self.post_processors.clone();
+        fork.tags = self.tags.clone();
+
+        for message in kept {
+            let mut copy = message.clone();
+            copy.id = Uuid::new_v4();
+            copy.streaming = false;
+            for (letter, id) in &self.marks {
+                if *id == message.id {
+                    fork.marks.insert(*letter, copy.id);
+                }
+            }
+            fork.messages.push(copy);
+        }
+        Some(fork)
+    }
+
+    /// A copy of this session holding only the messages in `range`
+    pub fn excerpt(&self, range: RangeInclusive<usize>) -> Option<ChatSession> {
+        let messages = self.messages.get(range)?.to_vec();
+        Some(ChatSession { messages, ..self.clone() })
+    }
+
+    /// Remove the messages in `range`, returning how many were removed
+    pub fn delete_messages(&mut self, range: RangeInclusive<usize>) -> usize {
+        let (start, end) = range.into_inner();
+        let end = end.min(self.messages.len().saturating_sub(1));
+        (start..=end).filter_map(|_| self.delete_message(start)).count()
+    }
+
+    /// Remove a message, along with any marks on it
+    pub fn delete_message(&mut self, idx: usize) -> Option<Message> {
+        if idx >= self.messages.len() {
+            return None;
+        }
+        let message = self.messages.remove(idx);
+        self.marks.retain(|_, id| *id != message.id);
+        self.updated_at = Utc::now();
+        Some(message)
+    }
+
+    /// Add a tag, or remove it if present; returns whether it is now set
+    pub fn toggle_tag(&mut self, tag: &str) -> bool {
+        if let Some(pos) = self.tags.iter().position(|t| t == tag) {
+            self.tags.remove(pos);
+            false
+        } else {
+            self.tags.push(tag.to_string());
+            true
+        }
+    }
+
+    /// Change the name; returns false, leaving it unchanged, if the new name is blank
+    pub fn rename(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        if name.is_empty() {
+            return false;
+        }
+        self.name = name.to_string();
+        true
+    }
+
     /// Get a preview of the last message for sidebar display
     #[allow(dead_code)]
     pub fn preview(&self) -> &str {
@@ -190,12 +956,31 @@ impl ChatSession {
     }
 }
 
+/// One part of the next request, as listed by the context inspector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextEntry {
+    /// Role of the message, or `guardrail`, `system` or `draft`
+    pub label: String,
+    /// First non-blank line of the text
+    pub preview: String,
+    pub tokens: usize,
+    /// Whether it fits in the prompt budget and will be sent
+    pub included: bool,
+}
+
+impl ContextEntry {
+    fn new(label: impl Into<String>, text: &str, tokens: usize, included: bool) -> Self {
+        let preview = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+        Self { label: label.into(), preview: preview.to_string(), tokens, included }
+    }
+}
+
 // ============================================================================
 // Application State
 // ============================================================================
 
 /// Input mode for the application
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum InputMode {
     #[default]
     Normal,
@@ -205,58 +990,472 @@ pub enum InputMode {
     SessionSelect,
     Help,
     DeleteConfirm,
+    PatchConfirm,
+    Downloads,
+    PullConfirm,
+    CodePicker,
+    /// Browsing previously copied text
+    Yanks,
+    /// Picking a saved snippet to insert
+    Snippets,
+    /// Picking a system prompt from the library
+    Prompts,
+    /// Comparing an anonymized export with the original
+    RedactReview,
+    /// Seeing which messages the next request would carry
+    ContextInspect,
+    /// Browsing cached responses
+    Cache,
+    /// Reading the request a dry run would have sent
+    DryRun,
+    /// Moving a highlight between messages
+    MessageSelect,
+    /// Details of the selected message
+    MessageInspect,
+    /// Typing a new name for the active session
+    Rename,
+    /// Browsing for a file or folder
+    FilePicker,
+    /// Searching across sessions
+    Search,
+    /// Typing a query to find in the chat
+    Find,
+    /// Running an action by name from the command palette
+    Palette,
 }
 
-/// Focus area in the UI
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub enum FocusArea {
-    #[default]
-    Chat,
-    Input,
-    #[allow(dead_code)]
-    Sidebar,
+/// Models offered in the downloads popup
+pub const POPULAR_MODELS: &[&str] = &[
+    "llama3.2",
+    "llama3.1:8b",
+    "qwen2.5-coder:7b",
+    "mistral",
+    "gemma2:9b",
+    "phi3.5",
+    "deepseek-r1:8b",
+    "nomic-embed-text",
+];
+
+/// State of a queued model pull
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadStatus {
+    Queued,
+    Active,
+    Done,
+    Failed(String),
 }
 
-/// Statistics from the last response
-#[derive(Debug, Clone, Default)]
-pub struct ResponseStats {
-    pub tokens: u32,
-    pub tokens_per_second: f64,
-    #[allow(dead_code)]
-    pub total_duration_ms: u64,
+/// A model pull in the download queue
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub model: String,
+    pub status: DownloadStatus,
+    /// Latest status line reported by Ollama
+    pub detail: String,
+    pub completed: u64,
+    pub total: u64,
 }
 
-/// Central application state
+impl Download {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            status: DownloadStatus::Queued,
+            detail: String::new(),
+            completed: 0,
+            total: 0,
+        }
+    }
+
+    /// Whether the pull is waiting or running
+    pub fn is_pending(&self) -> bool {
+        matches!(self.status, DownloadStatus::Queued | DownloadStatus::Active)
+    }
+
+    /// Progress of the current layer, 0.0 - 1.0
+    pub fn progress(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.completed as f64 / self.total as f64).min(1.0)
+        }
+    }
+}
+
+/// Scroll events closer together than this count as one held scroll
+const SCROLL_REPEAT_WINDOW: Duration = Duration::from_millis(150);
+
+/// Repeated scroll events per speed step
+const SCROLL_EVENTS_PER_STEP: u32 = 6;
+
+/// Upper bound on the scroll multiplier
+const MAX_SCROLL_MULTIPLIER: usize = 8;
+
+/// Frames of the spinners shown while streaming or refreshing
+const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How far along a long operation is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// What is running, like `pull llama3.2`; also its label
+    pub id: String,
+    pub done: u64,
+    /// 0 while unknown
+    pub total: u64,
+}
+
+impl Progress {
+    /// Fraction done, 0 while the total is unknown
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.done as f64 / self.total as f64).min(1.0)
+        }
+    }
+}
+
+/// An in-flight reload of the model list
 #[derive(Debug)]
-pub struct AppState {
-    /// Configuration
-    pub config: Config,
-    
-    /// Available models from Ollama
-    pub models: Vec<ModelInfo>,
-    
-    /// All chat sessions
-    pub sessions: Vec<ChatSession>,
-    
-    /// Index of the currently active session
-    pub active_session_idx: usize,
-    
-    /// Index of the currently selected model (for model picker)
-    pub selected_model_idx: usize,
-    
-    /// User input buffer
-    pub input: String,
-    
-    /// Cursor position in input
-    pub cursor_position: usize,
-    
-    /// Current input mode
-    pub input_mode: InputMode,
-    
-    /// Current focus area
-    #[allow(dead_code)]
-    pub focus: FocusArea,
-    
+pub struct ModelRefresh {
+    pub abort: AbortHandle,
+}
+
+/// An anonymized copy of a session awaiting review before export
+#[derive(Debug, Clone)]
+pub struct RedactReview {
+    /// Tags model pass results, so a closed review ignores late ones
+    pub id: Uuid,
+    pub before: ChatSession,
+    pub after: ChatSession,
+    /// Message shown in the review popup
+    pub idx: usize,
+    /// Messages still waiting for the model pass
+    pub pending: usize,
+    /// Messages the model pass failed on, left as the rules made them
+    pub failed: usize,
+    redactor: Redactor,
+}
+
+impl RedactReview {
+    pub fn new(session: &ChatSession, redactor: Redactor) -> Self {
+        let mut review = Self {
+            id: Uuid::new_v4(),
+            before: session.clone(),
+            after: redactor.redact_session(session),
+            idx: 0,
+            pending: 0,
+            failed: 0,
+            redactor,
+        };
+        review.idx = review.changed().first().copied().unwrap_or(0);
+        review
+    }
+
+    /// Indices of messages the redaction changed
+    pub fn changed(&self) -> Vec<usize> {
+        self.before
+            .messages
+            .iter()
+            .zip(&self.after.messages)
+            .enumerate()
+            .filter(|(_, (before, after))| before.content != after.content)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Take the model's rewrite of a message, running the rules over it again
+    pub fn apply_anonymized(&mut self, idx: usize, text: &str) {
+        if let Some(message) = self.after.messages.get_mut(idx) {
+            message.content = self.redactor.redact(text.trim());
+            message.touch();
+        }
+    }
+
+    /// Move to the next (or previous) changed message, wrapping around
+    pub fn step(&mut self, forward: bool) {
+        let changed = self.changed();
+        let next = if forward {
+            changed.iter().find(|&&i| i > self.idx).or(changed.first())
+        } else {
+            changed.iter().rev().find(|&&i| i < self.idx).or(changed.last())
+        };
+        if let Some(&idx) = next {
+            self.idx = idx;
+        }
+    }
+}
+
+/// Tracks held scrolling to accelerate it
+#[derive(Debug, Clone, Default)]
+pub struct ScrollAccel {
+    last: Option<(Instant, bool)>,
+    streak: u32,
+}
+
+impl ScrollAccel {
+    /// Register a scroll event and return the multiplier to apply
+    ///
+    /// The streak resets after a pause or a change of direction, so single
+    /// taps always move by the base amount.
+    pub fn step(&mut self, now: Instant, up: bool) -> usize {
+        let repeated = self.last.is_some_and(|(at, last_up)| {
+            last_up == up && now.saturating_duration_since(at) <= SCROLL_REPEAT_WINDOW
+        });
+        self.streak = if repeated { self.streak + 1 } else { 0 };
+        self.last = Some((now, up));
+        (1 + (self.streak / SCROLL_EVENTS_PER_STEP) as usize).min(MAX_SCROLL_MULTIPLIER)
+    }
+}
+
+/// Chat line layout from the last render
+///
+/// Lets actions map between scroll offsets and messages without knowing
+/// the terminal width.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChatViewport {
+    /// First line of each message
+    pub message_starts: Vec<usize>,
+    pub total_lines: usize,
+    pub visible_lines: usize,
+    /// Line of each in-chat find match, in order
+    pub find_matches: Vec<usize>,
+    /// Height key and line count of each message, reused by the next render
+    pub heights: Vec<(u64, usize)>,
+    /// Settled content lines of the message being streamed
+    pub stream: StreamLayout,
+}
+
+/// Content lines of a streaming message that later tokens can't change
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamLayout {
+    /// Identifies the message, width and settled text the lines are for
+    pub key: u64,
+    /// Bytes of content settled
+    pub bytes: usize,
+    /// Source lines settled
+    pub source_lines: usize,
+    /// Settled lines, in the chunks they settled in; each frame's layout
+    /// shares them with the next instead of copying
+    pub chunks: Vec<Arc<[Line<'static>]>>,
+}
+
+impl ChatViewport {
+    fn max_scroll(&self) -> usize {
+        self.total_lines.saturating_sub(self.visible_lines)
+    }
+
+    /// Index of the message at the top of the view for a scroll offset
+    pub fn message_at(&self, scroll: usize) -> Option<usize> {
+        let top = self.top_line(scroll);
+        self.message_starts.iter().rposition(|start| *start <= top)
+    }
+
+    /// Scroll offset that puts a message at the top of the view
+    pub fn scroll_for(&self, message_idx: usize) -> Option<usize> {
+        let start = *self.message_starts.get(message_idx)?;
+        Some(self.max_scroll().saturating_sub(start))
+    }
+
+    /// Lines of a message, excluding the separator after it
+    pub fn message_lines(&self, message_idx: usize) -> Option<std::ops::Range<usize>> {
+        let start = *self.message_starts.get(message_idx)?;
+        let end = match self.message_starts.get(message_idx + 1) {
+            Some(next) => next.saturating_sub(1),
+            None => self.total_lines,
+        };
+        Some(start..end.max(start))
+    }
+
+    /// Smallest scroll change from `scroll` that brings a message into view
+    ///
+    /// Messages taller than the view are shown from their first line.
+    pub fn scroll_to_show(&self, message_idx: usize, scroll: usize) -> Option<usize> {
+        let lines = self.message_lines(message_idx)?;
+        Some(self.scroll_to_lines(lines, scroll))
+    }
+
+    /// Line at the top of the view for a scroll offset
+    pub fn top_line(&self, scroll: usize) -> usize {
+        self.max_scroll() - scroll.min(self.max_scroll())
+    }
+
+    /// Smallest scroll change from `scroll` that brings a range of lines into view
+    pub fn scroll_to_lines(&self, lines: std::ops::Range<usize>, scroll: usize) -> usize {
+        let max = self.max_scroll();
+        let top = max - scroll.min(max);
+        let new_top = if lines.start < top || lines.len() > self.visible_lines {
+            lines.start
+        } else if lines.end > top + self.visible_lines {
+            lines.end - self.visible_lines
+        } else {
+            top
+        };
+        max.saturating_sub(new_top)
+    }
+}
+
+/// Two sessions side by side
+///
+/// The focused pane always shows the active session, so input, scrolling
+/// and every session action apply to it as usual. The other pane keeps its
+/// own scroll offset; switching focus swaps the two without moving them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitView {
+    /// Session shown in the pane without focus
+    pub other: Uuid,
+    /// Scroll offset of the other pane
+    pub scroll: usize,
+    /// Line layout of the other pane from the last render
+    pub viewport: ChatViewport,
+    /// Whether the focused pane is the right one
+    pub focus_right: bool,
+}
+
+/// Compare mode: every prompt also goes to a second model
+///
+/// Its answers land in a session of their own, a copy of the active one
+/// shown beside it in the split view, so both models keep their own side
+/// of the conversation.
+#[derive(Debug)]
+pub struct Compare {
+    /// The second model
+    pub model: String,
+    /// Session whose prompts are also sent to the second model
+    pub source: Uuid,
+    /// Session receiving the second model's answers
+    pub session: Uuid,
+    /// The second model's request, while it streams
+    pub abort: Option<AbortHandle>,
+    /// Stats of each side's last answer, by session
+    pub stats: HashMap<Uuid, ResponseStats>,
+}
+
+/// First key of a two-key normal mode command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingKey {
+    /// `m` - set a mark
+    Mark,
+    /// `'` - jump to a mark
+    Jump,
+    /// `Q` - record a macro
+    Record,
+    /// `@` - replay a macro
+    Replay,
+}
+
+/// Most keys one replay may feed, nested macros included, so a macro that
+/// replays itself stops
+const MAX_REPLAY_KEYS: usize = 10_000;
+
+/// Focus area in the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FocusArea {
+    #[default]
+    Chat,
+    Input,
+    #[allow(dead_code)]
+    Sidebar,
+}
+
+/// Most warning and error banners kept at once
+const MAX_BANNERS: usize = 5;
+
+/// How serious a notice is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Shown in the status bar
+    Info,
+    /// Banner that something was refused or skipped
+    Warning,
+    /// Banner that something failed
+    Error,
+    /// Banner with news, kept until dismissed
+    Notice,
+}
+
+impl Severity {
+    /// How long a banner stays up unless dismissed
+    fn ttl(self) -> Option<Duration> {
+        match self {
+            Severity::Info => None,
+            Severity::Warning => Some(Duration::from_secs(8)),
+            Severity::Error => Some(Duration::from_secs(30)),
+            Severity::Notice => None,
+        }
+    }
+}
+
+/// A message for the user
+#[derive(Debug, Clone)]
+pub struct Notice {
+    pub severity: Severity,
+    pub text: String,
+    pub created: Instant,
+}
+
+/// Undo steps kept
+const UNDO_LIMIT: usize = 50;
+
+/// The UI state an undo puts back: which session is open and its model
+///
+/// Conversation content is not part of it; deleting messages or sessions
+/// has its own confirmations and backups. Banners aren't either, as an
+/// undo shouldn't bring back an error that has since been resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiSnapshot {
+    session: Option<Uuid>,
+    model: Option<String>,
+}
+
+/// Statistics from the last response
+#[derive(Debug, Clone, Default)]
+pub struct ResponseStats {
+    pub tokens: u32,
+    /// Tokens in the prompt, as the server counted them (0 if not reported)
+    pub prompt_tokens: u32,
+    pub tokens_per_second: f64,
+    #[allow(dead_code)]
+    pub total_duration_ms: u64,
+    /// Stopped by the token limit or a stop sequence
+    pub truncated: bool,
+    /// Milliseconds from sending the request to the first token
+    pub first_token_ms: Option<u64>,
+}
+
+impl ResponseStats {
+    /// Tokens of context the exchange filled, if the server reported the
+    /// prompt size
+    pub fn context_used(&self) -> Option<u32> {
+        (self.prompt_tokens > 0).then(|| self.prompt_tokens + self.tokens)
+    }
+}
+
+/// Central application state
+#[derive(Debug)]
+pub struct AppState {
+    /// Configuration
+    pub config: Config,
+    
+    /// Sessions and models
+    pub chat: ChatStore,
+    
+    /// Index of the currently selected model (for model picker)
+    pub selected_model_idx: usize,
+    
+    /// User input buffer
+    pub input: String,
+    
+    /// Cursor position in input
+    pub cursor_position: usize,
+    
+    /// Current input mode
+    pub input_mode: InputMode,
+    
+    /// Current focus area
+    #[allow(dead_code)]
+    pub focus: FocusArea,
+    
     /// Scroll offset for chat history
     pub chat_scroll: usize,
     
@@ -264,17 +1463,32 @@ pub struct AppState {
     #[allow(dead_code)]
     pub sidebar_scroll: usize,
     
-    /// Status message (shown in status bar)
-    pub status_message: Option<String>,
-    
-    /// Error message (shown prominently)
-    pub error_message: Option<String>,
+    /// Status, warning and error messages, newest first
+    pub notices: VecDeque<Notice>,
+
+    /// UI states to go back to with undo, oldest first
+    pub undo_history: VecDeque<UiSnapshot>,
+
+    /// States undone, to go forward to again
+    pub redo_history: Vec<UiSnapshot>,
     
     /// Whether we're currently loading (models, sending, etc.)
     pub loading: bool,
     
     /// Whether a response is currently streaming
     pub streaming: bool,
+
+    /// Long operations running, oldest first
+    pub progress: Vec<Progress>,
+
+    /// Frame of the spinners, stepped by ticks while something animates
+    pub spinner_frame: usize,
+
+    /// When the spinners last stepped
+    last_spin: Instant,
+
+    /// Minute of the last tick, to keep relative timestamps current
+    clock_minute: i64,
     
     /// Stats from the last completed response
     pub last_response_stats: Option<ResponseStats>,
@@ -284,17 +1498,215 @@ pub struct AppState {
     
     /// Whether Ollama server is connected
     pub server_connected: bool,
+
+    /// Round-trip time of the latest health check, while connected
+    pub server_latency: Option<Duration>,
+
+    /// Whether the terminal has focus (assumed until it reports otherwise)
+    pub terminal_focused: bool,
+
+    /// Whether the terminal can draw inline image previews
+    pub image_previews: bool,
+
+    /// Images whose preview couldn't be drawn (missing or unreadable files)
+    pub broken_images: HashSet<PathBuf>,
+
+    /// Show the output of agent tool calls instead of a one-line summary
+    pub tools_expanded: bool,
+
+    /// Read-only transcript view: the chat fills the screen and takes no input
+    pub transcript: bool,
+
+    /// Zen mode: no sidebar, tabs or status bar, and the chat at a readable width
+    pub zen: bool,
+
+    /// Diff awaiting confirmation before being applied
+    pub pending_patch: Option<Vec<FilePatch>>,
+
+    /// Action to run when the current response completes
+    pub pending_follow_up: Option<FollowUp>,
+
+    /// Model pulls, in the order they were queued
+    pub downloads: Vec<Download>,
+
+    /// Model name typed in the downloads popup
+    pub pull_input: String,
+
+    /// Highlighted entry in the popular models list
+    pub pull_selected_idx: usize,
+
+    /// Held-scroll tracking for acceleration
+    pub scroll_accel: ScrollAccel,
+
+    /// Chat line layout from the last render
+    pub chat_viewport: ChatViewport,
+
+    /// Second session shown beside the active one
+    pub split: Option<SplitView>,
+
+    /// Second model answering every prompt, when comparing
+    pub compare: Option<Compare>,
+
+    /// First key of a two-key command awaiting its second key
+    pub pending_key: Option<PendingKey>,
+
+    /// Macro being recorded: its register and the keys so far
+    pub recording: Option<(char, Vec<KeyEvent>)>,
+
+    /// Recorded macros by register
+    pub macros: HashMap<char, Vec<KeyEvent>>,
+
+    /// Register replayed last, for `@@`
+    pub last_macro: Option<char>,
+
+    /// Keys of a replaying macro, fed to the main loop as if typed
+    pub replay: VecDeque<KeyEvent>,
+
+    /// Keys queued since the outermost macro started replaying
+    replayed_keys: usize,
+
+    /// Model a chat request failed for because it isn't installed
+    pub missing_model: Option<String>,
+
+    /// Input as typed for the message being generated, before command expansion
+    pub last_prompt: Option<String>,
+
+    /// Handle to cancel the streaming task
+    pub stream_abort: Option<AbortHandle>,
+
+    /// Model list reload in progress, at most one at a time
+    pub model_refresh: Option<ModelRefresh>,
+
+    /// Replaying a demo script: sessions are never saved
+    pub demo: bool,
+
+    /// Highlighted message in select mode
+    pub selected_message: usize,
+
+    /// Other end of the visual range in select mode
+    pub select_anchor: Option<usize>,
+
+    /// Code blocks offered by the copy picker
+    pub code_picker: Vec<CodeBlock>,
+
+    /// Highlighted entry in the copy picker
+    pub code_picker_idx: usize,
+
+    /// Text copied from the chat, newest first
+    pub yanks: VecDeque<String>,
+
+    /// Highlighted entry in the yank history
+    pub yank_idx: usize,
+
+    /// Saved text snippets, in the order they were added
+    pub snippets: Vec<String>,
+
+    /// Highlighted entry in the snippet picker
+    pub snippet_idx: usize,
+
+    /// Named system prompts shared by all sessions
+    pub prompts: PromptLibrary,
+
+    /// Highlighted entry in the prompt picker
+    pub prompt_idx: usize,
+
+    /// Anonymized export under review
+    pub redact_review: Option<RedactReview>,
+
+    /// Answers to earlier identical requests
+    pub cache: ResponseCache,
+
+    /// Highlighted entry in the cache inspector
+    pub cache_idx: usize,
+
+    /// Entry to store once the streaming response completes
+    pub cache_pending: Option<CacheEntry>,
+
+    /// Describe chat requests instead of sending them (`--dry-run`)
+    pub dry_run: bool,
+
+    /// Description of the last request not sent in dry-run mode
+    pub dry_run_report: Option<String>,
+
+    /// Lines scrolled in the dry-run report
+    pub dry_run_scroll: u16,
+
+    /// Scroll offset of the context inspector
+    pub context_scroll: u16,
+
+    /// Newer release announced by the update check
+    pub update_available: Option<String>,
+
+    /// Translations for the interface language
+    pub locale: Locale,
+
+    /// Keys bound to chat actions
+    pub keymap: Keymap,
+
+    /// Colors the interface is drawn with
+    pub theme: Theme,
+
+    /// Files to send with the next message
+    pub pending_attachments: Vec<Attachment>,
+
+    /// Open file picker
+    pub file_picker: Option<FilePicker>,
+
+    /// Name typed in the rename popup
+    pub rename_input: String,
+
+    /// Query typed in the search popup
+    pub search_query: String,
+
+    /// Results for the search query, best first
+    pub search_results: Vec<SearchHit>,
+
+    /// Highlighted search result
+    pub search_idx: usize,
+
+    /// Query typed in the command palette
+    pub palette_query: String,
+
+    /// Highlighted palette entry
+    pub palette_idx: usize,
+
+    /// Message to scroll to once the chat has been laid out
+    pub reveal_message: Option<usize>,
+
+    /// Text highlighted in the chat by find
+    pub find_query: String,
+
+    /// Current find match
+    pub find_idx: usize,
+
+    /// Jump to the nearest match once the chat has been laid out
+    pub find_jump: bool,
+}
+
+/// Remove the last grapheme cluster of a one-line input (backspace)
+///
+/// Returns false when the input was already empty.
+pub fn pop_grapheme(text: &mut String) -> bool {
+    match text.grapheme_indices(true).next_back() {
+        Some((start, _)) => {
+            text.truncate(start);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Whether an input grapheme cluster is part of a word: a letter, digit or
+/// underscore, with any combining marks
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(|c| c.is_alphanumeric() || c == '_')
 }
 
 impl AppState {
     pub fn new(config: Config) -> Self {
-        let default_model = config.model.default_model.clone();
-        
         Self {
+            chat: ChatStore::new(config.model.default_model.clone(), storage::open(&config.storage)),
             config,
-            models: Vec::new(),
-            sessions: vec![ChatSession::with_default_name(&default_model)],
-            active_session_idx: 0,
             selected_model_idx: 0,
             input: String::new(),
             cursor_position: 0,
@@ -302,142 +1714,855 @@ impl AppState {
             focus: FocusArea::Input,
             chat_scroll: 0,
             sidebar_scroll: 0,
-            status_message: None,
-            error_message: None,
+            notices: VecDeque::new(),
+            undo_history: VecDeque::new(),
+            redo_history: Vec::new(),
             loading: false,
             streaming: false,
+            progress: Vec::new(),
+            spinner_frame: 0,
+            last_spin: Instant::now(),
+            clock_minute: 0,
             last_response_stats: None,
             should_quit: false,
             server_connected: false,
+            server_latency: None,
+            terminal_focused: true,
+            image_previews: false,
+            broken_images: HashSet::new(),
+            tools_expanded: false,
+            transcript: false,
+            zen: false,
+            pending_patch: None,
+            pending_follow_up: None,
+            downloads: Vec::new(),
+            pull_input: String::new(),
+            pull_selected_idx: 0,
+            scroll_accel: ScrollAccel::default(),
+            chat_viewport: ChatViewport::default(),
+            split: None,
+            compare: None,
+            pending_key: None,
+            recording: None,
+            macros: HashMap::new(),
+            last_macro: None,
+            replay: VecDeque::new(),
+            replayed_keys: 0,
+            missing_model: None,
+            last_prompt: None,
+            stream_abort: None,
+            model_refresh: None,
+            demo: false,
+            selected_message: 0,
+            select_anchor: None,
+            code_picker: Vec::new(),
+            code_picker_idx: 0,
+            yanks: VecDeque::new(),
+            snippets: Vec::new(),
+            snippet_idx: 0,
+            prompts: PromptLibrary::starter(),
+            prompt_idx: 0,
+            redact_review: None,
+            cache: ResponseCache::default(),
+            cache_idx: 0,
+            cache_pending: None,
+            dry_run: false,
+            dry_run_report: None,
+            dry_run_scroll: 0,
+            context_scroll: 0,
+            update_available: None,
+            locale: Locale::default(),
+            keymap: Keymap::default(),
+            theme: Theme::default(),
+            pending_attachments: Vec::new(),
+            file_picker: None,
+            yank_idx: 0,
+            rename_input: String::new(),
+            search_query: String::new(),
+            search_results: Vec::new(),
+            search_idx: 0,
+            palette_query: String::new(),
+            palette_idx: 0,
+            reveal_message: None,
+            find_query: String::new(),
+            find_idx: 0,
+            find_jump: false,
+        }
+    }
+
+    /// Save all sessions to disk (skipped in demo mode)
+    pub fn save_sessions(&self) -> Result<(), PersistenceError> {
+        if self.demo {
+            return Ok(());
+        }
+        self.chat.save()
+    }
+
+    /// Unload inactive sessions while the loaded messages exceed
+    /// `[memory] max_mb`
+    ///
+    /// Only call right after the sessions were saved.
+    pub fn trim_memory(&mut self) {
+        let Some(max_bytes) = self.config.memory.max_bytes().filter(|_| !self.demo) else {
+            return;
+        };
+        // The split view's other pane and the compare session stay loaded
+        // like the active session
+        let keep: Vec<Uuid> = self
+            .split
+            .as_ref()
+            .map(|split| split.other)
+            .into_iter()
+            .chain(self.compare.as_ref().map(|compare| compare.session))
+            .collect();
+        let unloaded = self.chat.unload_over(max_bytes, &keep);
+        if unloaded > 0 {
+            debug!("Unloaded {} sessions to stay under {} bytes", unloaded, max_bytes);
+        }
+    }
+
+    /// Save snippets to disk (skipped in demo mode)
+    pub fn save_snippets(&self) -> Result<(), PersistenceError> {
+        if self.demo {
+            return Ok(());
+        }
+        persistence::save_snippets(&self.snippets)
+    }
+
+    /// Save the response cache to disk (skipped in demo mode)
+    pub fn save_cache(&self) -> Result<(), PersistenceError> {
+        if self.demo {
+            return Ok(());
+        }
+        persistence::save_cache(&self.cache.entries)
+    }
+
+    /// Save text as a snippet, moving an identical one to the end
+    ///
+    /// Returns false for blank text.
+    pub fn add_snippet(&mut self, text: &str) -> bool {
+        if text.trim().is_empty() {
+            return false;
+        }
+        self.snippets.retain(|snippet| snippet != text);
+        self.snippets.push(text.to_string());
+        self.snippet_idx = self.snippets.len() - 1;
+        true
+    }
+
+    /// Give the active session the library prompt at `idx`
+    ///
+    /// Returns the prompt's name, or `None` if there is no such prompt.
+    pub fn use_prompt(&mut self, idx: usize) -> Option<String> {
+        let prompt = self.prompts.prompts.get(idx)?.clone();
+        let session = self.chat.active_session_mut()?;
+        session.system_prompt = Some(prompt.text);
+        session.prompt_name = Some(prompt.name.clone());
+        Some(prompt.name)
+    }
+
+    /// Replace the library after it was edited, updating the sessions that
+    /// use it when `[prompts] propagate` is on; returns how many changed
+    pub fn reload_prompts(&mut self, library: PromptLibrary) -> usize {
+        self.prompts = library;
+        self.prompt_idx = self.prompt_idx.min(self.prompts.prompts.len().saturating_sub(1));
+        if self.config.prompts.propagate {
+            self.prompts.propagate(&mut self.chat.sessions)
+        } else {
+            0
+        }
+    }
+
+    /// Open a split view with the next session beside the active one, or
+    /// close it; returns whether the view is split now
+    pub fn toggle_split(&mut self) -> bool {
+        if self.split.take().is_some() {
+            return false;
+        }
+        let count = self.chat.sessions.len();
+        if count < 2 {
+            return false;
+        }
+        let idx = (self.chat.active_session_idx + 1) % count;
+        self.chat.load(idx);
+        self.split = Some(SplitView { other: self.chat.sessions[idx].id, ..Default::default() });
+        true
+    }
+
+    /// Move focus to the other pane of the split view, making its session
+    /// the active one
+    pub fn switch_split_focus(&mut self) {
+        let Some(split) = self.split.as_mut() else {
+            return;
+        };
+        let Some(idx) = self.chat.sessions.iter().position(|s| s.id == split.other) else {
+            // Its session was deleted
+            self.split = None;
+            return;
+        };
+        let Some(previous) = self.chat.active_session().map(|s| s.id) else {
+            return;
+        };
+        split.other = previous;
+        split.focus_right = !split.focus_right;
+        std::mem::swap(&mut split.scroll, &mut self.chat_scroll);
+        std::mem::swap(&mut split.viewport, &mut self.chat_viewport);
+        self.chat.activate(idx);
+    }
+
+    /// The session in the split view's other pane
+    pub fn split_session(&self) -> Option<&ChatSession> {
+        let split = self.split.as_ref()?;
+        self.chat.sessions.iter().find(|s| s.id == split.other)
+    }
+
+    /// Start comparing the active session's model with `model`; returns the
+    /// session that gets its answers
+    ///
+    /// The new session copies the conversation so far and opens beside the
+    /// active one.
+    pub fn start_compare(&mut self, model: &str) -> Option<&ChatSession> {
+        if model == self.current_model() || self.streaming {
+            return None;
+        }
+        let active = self.active_session()?;
+        let (id, name) = (active.id, format!("{} ({})", active.name, model));
+        let mut session = match active.messages.len() {
+            0 => ChatSession::new(&name, model),
+            len => active.fork(0..=len - 1)?,
+        };
+        session.rename(&name);
+        session.model = model.to_string();
+        let session_id = session.id;
+
+        self.abort_compare();
+        self.chat.insert_after_active(session);
+        if let Some(idx) = self.chat.sessions.iter().position(|s| s.id == id) {
+            self.chat.activate(idx);
         }
+        self.split = Some(SplitView { other: session_id, ..Default::default() });
+        self.compare = Some(Compare {
+            model: model.to_string(),
+            source: id,
+            session: session_id,
+            abort: None,
+            stats: HashMap::new(),
+        });
+        self.split_session()
+    }
+
+    /// Leave compare mode, stopping the second model; the split view stays
+    pub fn stop_compare(&mut self) -> bool {
+        self.abort_compare();
+        self.compare.take().is_some()
+    }
+
+    /// Stop the second model's answer, if it is streaming
+    fn abort_compare(&mut self) {
+        let Some(compare) = self.compare.as_mut() else {
+            return;
+        };
+        if let Some(handle) = compare.abort.take() {
+            handle.abort();
+        }
+        let id = compare.session;
+        if let Some(session) = self.chat.session_mut(id) {
+            session.interrupt_response();
+        }
+    }
+
+    /// Whether the second model is still answering
+    pub fn compare_streaming(&self) -> bool {
+        self.compare.as_ref().is_some_and(|c| c.abort.is_some())
+    }
+
+    /// Whether either model is answering
+    pub fn generating(&self) -> bool {
+        self.streaming || self.compare_streaming()
     }
 
     /// Get the current active session
     pub fn active_session(&self) -> Option<&ChatSession> {
-        self.sessions.get(self.active_session_idx)
+        self.chat.active_session()
     }
 
     /// Get the current active session mutably
     pub fn active_session_mut(&mut self) -> Option<&mut ChatSession> {
-        self.sessions.get_mut(self.active_session_idx)
+        self.chat.active_session_mut()
+    }
+
+    /// Generation options from config, overlaid with the active session's
+    pub fn generation_options(&self) -> GenerationOptions {
+        let model = &self.config.model;
+        let opts = GenerationOptions {
+            temperature: Some(model.temperature),
+            top_k: Some(model.top_k),
+            top_p: Some(model.top_p),
+            num_predict: if model.max_tokens > 0 {
+                Some(model.max_tokens as i32)
+            } else {
+                None
+            },
+            num_ctx: if model.num_ctx > 0 {
+                Some(model.num_ctx)
+            } else {
+                None
+            },
+            ..model.options.clone()
+        };
+        let current = self.current_model();
+        let opts = model
+            .profiles
+            .iter()
+            .filter(|profile| profile.matches(current))
+            .fold(opts, |opts, profile| opts.merged(&profile.options));
+        match self.active_session().and_then(|s| s.options.as_ref()) {
+            Some(session_opts) => opts.merged(session_opts),
+            None => opts,
+        }
+    }
+
+    /// Estimated tokens of the draft: the input plus pending attachments
+    pub fn draft_tokens(&self) -> usize {
+        let attached: usize = self.pending_attachments.iter().map(|a| dryrun::estimate_text_tokens(&a.fenced())).sum();
+        dryrun::estimate_text_tokens(&self.input) + attached
+    }
+
+    /// Estimated prompt tokens if the draft were sent now
+    pub fn context_tokens(&self) -> usize {
+        let history = self.active_session().map_or(0, ChatSession::estimated_tokens);
+        history + self.draft_tokens()
+    }
+
+    /// Context window of the current model: `num_ctx` when configured,
+    /// otherwise what the server reported for it
+    pub fn context_limit(&self) -> Option<u32> {
+        self.context_limit_for(self.current_model())
+    }
+
+    /// Context window of `model`, as for [`Self::context_limit`]
+    pub fn context_limit_for(&self, model: &str) -> Option<u32> {
+        self.generation_options()
+            .num_ctx
+            .filter(|&n| n > 0)
+            .or_else(|| self.chat.context_window(model))
+    }
+
+    /// Share of the context window the active session filled after its
+    /// last response, when both are known
+    pub fn context_usage(&self) -> Option<f64> {
+        let used = self.active_session()?.context_used?;
+        let limit = self.context_limit()?;
+        Some(used as f64 / limit as f64)
+    }
+
+    /// Prompt tokens to fit a request in, leaving room for the response
+    ///
+    /// The room is `max_tokens` when set, otherwise an eighth of the window.
+    pub fn prompt_budget(&self) -> Option<usize> {
+        self.prompt_budget_for(self.current_model())
+    }
+
+    /// Prompt budget of a request to `model`
+    pub fn prompt_budget_for(&self, model: &str) -> Option<usize> {
+        let limit = self.context_limit_for(model)? as usize;
+        let reserve = match self.generation_options().num_predict {
+            Some(n) if n > 0 => n as usize,
+            _ => limit / 8,
+        };
+        Some(limit.saturating_sub(reserve))
+    }
+
+    /// Messages of `session` for a request to `model`: its guardrail, then
+    /// the history, oldest messages left out to fit the prompt budget;
+    /// returns them and how many were left out
+    pub fn request_messages(&self, session: &ChatSession, model: &str) -> (Vec<ChatMessage>, usize) {
+        let guardrail = self.config.guardrail_for(model);
+        let budget = self
+            .prompt_budget_for(model)
+            .map(|b| b.saturating_sub(guardrail.map_or(0, dryrun::estimate_text_tokens)));
+        let (mut messages, not_sent) = match budget {
+            Some(budget) => session.to_chat_messages_within(budget),
+            None => (session.to_chat_messages(), 0),
+        };
+        if let Some(guardrail) = guardrail {
+            messages.insert(0, ChatMessage::system(guardrail));
+        }
+        (messages, not_sent)
+    }
+
+    /// What the next request would carry, part by part: the guardrail, the
+    /// system prompt, each message and the draft, with whether each fits in
+    /// the prompt budget
+    pub fn context_entries(&self) -> Vec<ContextEntry> {
+        let mut entries = Vec::new();
+        let guardrail = self.config.guardrail_for(self.current_model());
+        if let Some(text) = guardrail {
+            entries.push(ContextEntry::new("guardrail", text, dryrun::estimate_text_tokens(text), true));
+        }
+        let Some(session) = self.active_session() else {
+            return entries;
+        };
+        if let Some(text) = &session.system_prompt {
+            entries.push(ContextEntry::new("system", text, dryrun::estimate_text_tokens(text), true));
+        }
+
+        let draft = self.draft_tokens();
+        let reserved = guardrail.map_or(0, dryrun::estimate_text_tokens);
+        let first = self
+            .prompt_budget()
+            .map_or(0, |budget| session.first_sent(budget.saturating_sub(reserved), draft));
+        for (idx, msg) in session.messages.iter().enumerate() {
+            entries.push(ContextEntry::new(msg.role.to_string(), &msg.content, msg.estimated_tokens(), idx >= first));
+        }
+        if draft > 0 {
+            entries.push(ContextEntry::new("draft", &self.input, draft, true));
+        }
+        entries
+    }
+
+    /// Write the active session to a Markdown file per `[export]` settings
+    pub fn export_active_session(&self) -> Option<Result<PathBuf, PersistenceError>> {
+        let dir = match self.export_dir() {
+            Ok(dir) => dir,
+            Err(e) => return Some(Err(e)),
+        };
+        self.export_to(&dir, false)
+    }
+
+    /// Write the active session to a Markdown file in a chosen folder
+    pub fn export_active_session_to(&self, dir: &Path) -> Option<Result<PathBuf, PersistenceError>> {
+        self.export_to(dir, false)
+    }
+
+    /// Folder exports go to: `[export] dir`, or the data directory
+    pub fn export_dir(&self) -> Result<PathBuf, PersistenceError> {
+        match self.config.export.dir() {
+            Some(dir) => Ok(dir),
+            None => persistence::exports_dir(),
+        }
+    }
+
+    /// The active session as Markdown for pasting elsewhere, without front matter
+    ///
+    /// With `range`, only those messages are included.
+    pub fn session_markdown(&self, range: Option<RangeInclusive<usize>>) -> Option<String> {
+        let session = self.active_session()?;
+        let options = MarkdownOptions::default();
+        match range {
+            Some(range) => Some(persistence::export_session_to_markdown(&session.excerpt(range)?, &options)),
+            None => Some(persistence::export_session_to_markdown(session, &options)),
+        }
+    }
+
+    /// Write the selected messages to a Markdown file in the export directory
+    pub fn export_selection(&self) -> Option<Result<PathBuf, PersistenceError>> {
+        let range = self.selection();
+        let mut excerpt = self.active_session()?.excerpt(range.clone())?;
+        excerpt.name = format!("{} (messages {}-{})", excerpt.name, range.start() + 1, range.end() + 1);
+        Some(self.export_copy(&excerpt))
+    }
+
+    /// Export the anonymized session under review
+    pub fn export_redacted(&self) -> Option<Result<PathBuf, PersistenceError>> {
+        let mut copy = self.redact_review.as_ref()?.after.clone();
+        copy.name = format!("{} (anonymized)", copy.name);
+        Some(self.export_copy(&copy))
+    }
+
+    /// Write a derived session to the export directory
+    fn export_copy(&self, session: &ChatSession) -> Result<PathBuf, PersistenceError> {
+        let dir = self.export_dir()?;
+        let options = MarkdownOptions {
+            front_matter: self.config.export.front_matter,
+            ..Default::default()
+        };
+        persistence::export_session_to_file(session, &dir, &options)
+    }
+
+    /// The sessions in `scope` for a dataset export, with all their messages
+    pub fn dataset_sessions(&self, scope: &DatasetScope) -> Result<Vec<ChatSession>, PersistenceError> {
+        let all = match scope {
+            DatasetScope::Current => return Ok(self.active_session().into_iter().cloned().collect()),
+            _ => self.chat.loaded_sessions()?,
+        };
+        Ok(match scope {
+            DatasetScope::Tagged(tag) => all.iter().filter(|s| s.tags.contains(tag)).cloned().collect(),
+            _ => all.into_owned(),
+        })
+    }
+
+    /// Write the active session as a note in the configured vault
+    ///
+    /// Returns `None` when there is no active session or no vault.
+    pub fn export_to_vault(&self) -> Option<Result<PathBuf, PersistenceError>> {
+        let vault = self.config.export.vault()?;
+        self.export_to(&vault, true)
+    }
+
+    fn export_to(&self, dir: &Path, wiki_links: bool) -> Option<Result<PathBuf, PersistenceError>> {
+        let session = self.active_session()?;
+        let export = &self.config.export;
+        let options = MarkdownOptions {
+            front_matter: export.front_matter,
+            settings: export.include_settings.then(|| self.generation_options()),
+            wiki_links,
+        };
+        Some(persistence::export_session_to_file(session, dir, &options))
+    }
+
+    /// Post-processors for the active session
+    pub fn post_processors(&self) -> Vec<PostProcessor> {
+        self.active_session()
+            .and_then(|s| s.post_processors.clone())
+            .unwrap_or_else(|| self.config.middleware.post_processors.clone())
+    }
+
+    /// Toggle a post-processor for the active session, returning whether it is now enabled
+    pub fn toggle_post_processor(&mut self, processor: PostProcessor) -> bool {
+        let mut processors = self.post_processors();
+        let enabled = !processors.contains(&processor);
+        if enabled {
+            processors.push(processor);
+        } else {
+            processors.retain(|p| *p != processor);
+        }
+        if let Some(session) = self.active_session_mut() {
+            session.post_processors = Some(processors);
+        }
+        enabled
     }
 
     /// Get the current model name
     pub fn current_model(&self) -> &str {
-        self.active_session()
-            .map(|s| s.model.as_str())
-            .unwrap_or(&self.config.model.default_model)
+        self.chat.current_model()
     }
 
     /// Create a new session with the current model
     pub fn new_session(&mut self) {
-        let model = self.current_model().to_string();
-        let session = ChatSession::with_default_name(model);
-        self.sessions.push(session);
-        self.active_session_idx = self.sessions.len() - 1;
+        self.chat.new_session();
         self.chat_scroll = 0;
         self.clear_status();
     }
 
+    /// Branch the active session and switch to the copy
+    ///
+    /// The branch holds the visual range, or everything up to the selected message.
+    pub fn fork_at_selected(&mut self) -> Option<&ChatSession> {
+        let range = match self.select_anchor {
+            Some(_) => self.selection(),
+            None => 0..=self.selected_message,
+        };
+        let fork = self.active_session()?.fork(range)?;
+        self.chat_scroll = 0;
+        Some(self.chat.insert_after_active(fork))
+    }
+
+    /// Pin or unpin the active session; returns whether it is now pinned
+    pub fn toggle_pin(&mut self) -> Option<bool> {
+        self.chat.toggle_pin()
+    }
+
+    /// Move pinned sessions to the top, keeping the active session active
+    pub fn sort_pinned(&mut self) {
+        self.chat.sort_pinned();
+    }
+
     /// Switch to the next session
     pub fn next_session(&mut self) {
-        if !self.sessions.is_empty() {
-            self.active_session_idx = (self.active_session_idx + 1) % self.sessions.len();
-            self.chat_scroll = 0;
+        if let Some(idx) = self.chat.neighbour(1) {
+            self.select_session(idx);
         }
     }
 
     /// Switch to the previous session
     pub fn prev_session(&mut self) {
-        if !self.sessions.is_empty() {
-            self.active_session_idx = if self.active_session_idx == 0 {
-                self.sessions.len() - 1
-            } else {
-                self.active_session_idx - 1
-            };
+        if let Some(idx) = self.chat.neighbour(-1) {
+            self.select_session(idx);
+        }
+    }
+
+    /// Make the session at `idx` active
+    ///
+    /// Picking the session shown in the split view's other pane swaps the
+    /// panes, so both never show the same session.
+    pub fn select_session(&mut self, idx: usize) {
+        let other = self.split.as_ref().map(|split| split.other);
+        if other.is_some() && other == self.chat.sessions.get(idx).map(|s| s.id) {
+            self.switch_split_focus();
+        } else if self.chat.activate(idx) {
             self.chat_scroll = 0;
         }
     }
 
     /// Delete the current session
     pub fn delete_current_session(&mut self) {
-        if self.sessions.len() > 1 {
-            self.sessions.remove(self.active_session_idx);
-            if self.active_session_idx >= self.sessions.len() {
-                self.active_session_idx = self.sessions.len() - 1;
-            }
+        if self.chat.delete_active_session() {
             self.chat_scroll = 0;
+            // A split view of one session is no split at all
+            let other = self.split.as_ref().map(|split| split.other);
+            if other.is_some() && other == self.active_session().map(|s| s.id) {
+                self.split = None;
+            }
         }
     }
 
     /// Set the model for the current session
     pub fn set_model(&mut self, model: impl Into<String>) {
-        if let Some(session) = self.active_session_mut() {
-            session.model = model.into();
-        }
+        self.chat.set_model(model);
     }
 
     /// Get the selected model from the model list
     pub fn selected_model(&self) -> Option<&ModelInfo> {
-        self.models.get(self.selected_model_idx)
+        self.chat.models.get(self.selected_model_idx)
     }
 
     /// Select next model in list
     pub fn next_model(&mut self) {
-        if !self.models.is_empty() {
-            self.selected_model_idx = (self.selected_model_idx + 1) % self.models.len();
+        if !self.chat.models.is_empty() {
+            self.selected_model_idx = (self.selected_model_idx + 1) % self.chat.models.len();
         }
     }
 
     /// Select previous model in list
     pub fn prev_model(&mut self) {
-        if !self.models.is_empty() {
+        if !self.chat.models.is_empty() {
             self.selected_model_idx = if self.selected_model_idx == 0 {
-                self.models.len() - 1
+                self.chat.models.len() - 1
             } else {
                 self.selected_model_idx - 1
             };
         }
     }
 
+    /// Move the model selection by `delta`, stopping at either end
+    pub fn move_model_selection(&mut self, delta: isize) {
+        if let Some(last) = self.chat.models.len().checked_sub(1) {
+            self.selected_model_idx = self.selected_model_idx.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    /// Select the next model starting with `letter`, wrapping around
+    ///
+    /// Hosted models are matched without their provider prefix.
+    pub fn jump_to_model(&mut self, letter: char) -> bool {
+        let count = self.chat.models.len();
+        let found = (1..=count)
+            .map(|offset| (self.selected_model_idx + offset) % count)
+            .find(|&idx| {
+                let model = &self.chat.models[idx];
+                let id = if model.model.is_empty() { &model.name } else { &model.model };
+                id.chars().next().is_some_and(|c| c.eq_ignore_ascii_case(&letter))
+            });
+        if let Some(idx) = found {
+            self.selected_model_idx = idx;
+        }
+        found.is_some()
+    }
+
+    /// Model to pull: the typed name, or the highlighted suggestion
+    pub fn pull_target(&self) -> Option<String> {
+        let typed = self.pull_input.trim();
+        if !typed.is_empty() {
+            return Some(typed.to_string());
+        }
+        POPULAR_MODELS.get(self.pull_selected_idx).map(|m| m.to_string())
+    }
+
+    /// Whether a model is already waiting or being pulled
+    pub fn is_pull_pending(&self, model: &str) -> bool {
+        self.downloads.iter().any(|d| d.model == model && d.is_pending())
+    }
+
+    /// Add a model to the download queue unless it is already pending
+    ///
+    /// Returns whether it was queued.
+    pub fn queue_download(&mut self, model: String) -> bool {
+        if self.is_pull_pending(&model) {
+            return false;
+        }
+        self.downloads.push(Download::new(model));
+        true
+    }
+
+    /// Get a queued download by model name, most recent first
+    pub fn download_mut(&mut self, model: &str) -> Option<&mut Download> {
+        self.downloads.iter_mut().rev().find(|d| d.model == model)
+    }
+
+    /// Copy text to the clipboard, keeping it in the yank history
+    ///
+    /// The history is kept even when the system clipboard is unavailable.
+    pub fn yank(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.remember_yank(text);
+        clipboard::copy(text)
+    }
+
+    /// Add text to the front of the yank history; a repeat moves to the front
+    pub fn remember_yank(&mut self, text: &str) {
+        if !text.is_empty() && self.config.ui.yank_history > 0 {
+            self.yanks.retain(|yank| yank != text);
+            self.yanks.push_front(text.to_string());
+            self.yanks.truncate(self.config.ui.yank_history);
+        }
+    }
+
+    /// Insert text at cursor position
+    pub fn insert_str(&mut self, text: &str) {
+        self.input.insert_str(self.cursor_position, text);
+        self.cursor_position += text.len();
+    }
+
     /// Insert character at cursor position
     pub fn insert_char(&mut self, c: char) {
         self.input.insert(self.cursor_position, c);
-        self.cursor_position += 1;
+        self.cursor_position += c.len_utf8();
+    }
+
+    /// Start of the grapheme cluster before the cursor
+    ///
+    /// The cursor moves and deletes by cluster, so an accented letter typed
+    /// as two code points or an emoji sequence counts as one character.
+    fn prev_grapheme(&self) -> Option<usize> {
+        self.input[..self.cursor_position].grapheme_indices(true).next_back().map(|(i, _)| i)
+    }
+
+    /// End of the grapheme cluster after the cursor
+    fn next_grapheme(&self) -> Option<usize> {
+        self.input[self.cursor_position..].graphemes(true).next().map(|g| self.cursor_position + g.len())
     }
 
     /// Delete character before cursor
     pub fn delete_char(&mut self) {
-        if self.cursor_position > 0 {
-            self.cursor_position -= 1;
-            self.input.remove(self.cursor_position);
+        if let Some(start) = self.prev_grapheme() {
+            self.input.replace_range(start..self.cursor_position, "");
+            self.cursor_position = start;
         }
     }
 
     /// Delete character at cursor
     pub fn delete_char_forward(&mut self) {
-        if self.cursor_position < self.input.len() {
-            self.input.remove(self.cursor_position);
+        if let Some(end) = self.next_grapheme() {
+            self.input.replace_range(self.cursor_position..end, "");
         }
     }
 
     /// Move cursor left
     pub fn move_cursor_left(&mut self) {
-        if self.cursor_position > 0 {
-            self.cursor_position -= 1;
+        if let Some(start) = self.prev_grapheme() {
+            self.cursor_position = start;
         }
     }
 
     /// Move cursor right
     pub fn move_cursor_right(&mut self) {
-        if self.cursor_position < self.input.len() {
-            self.cursor_position += 1;
+        if let Some(end) = self.next_grapheme() {
+            self.cursor_position = end;
         }
     }
 
-    /// Move cursor to start
-    pub fn move_cursor_start(&mut self) {
-        self.cursor_position = 0;
+    /// Start of the word before the cursor
+    ///
+    /// Words are runs of letters, digits and underscores; anything between
+    /// them is skipped, as in readline.
+    fn word_start_before(&self) -> usize {
+        let before = &self.input[..self.cursor_position];
+        let mut start = before.len();
+        let mut in_word = false;
+        for (i, grapheme) in before.grapheme_indices(true).rev() {
+            if is_word(grapheme) {
+                in_word = true;
+            } else if in_word {
+                break;
+            }
+            start = i;
+        }
+        start
+    }
+
+    /// End of the word after the cursor
+    fn word_end_after(&self) -> usize {
+        let mut end = self.cursor_position;
+        let mut in_word = false;
+        for grapheme in self.input[self.cursor_position..].graphemes(true) {
+            if is_word(grapheme) {
+                in_word = true;
+            } else if in_word {
+                break;
+            }
+            end += grapheme.len();
+        }
+        end
+    }
+
+    /// Move cursor to the start of the previous word
+    pub fn move_cursor_word_left(&mut self) {
+        self.cursor_position = self.word_start_before();
+    }
+
+    /// Move cursor past the end of the next word
+    pub fn move_cursor_word_right(&mut self) {
+        self.cursor_position = self.word_end_after();
+    }
+
+    /// Delete from the start of the previous word to the cursor
+    pub fn delete_word_back(&mut self) {
+        let start = self.word_start_before();
+        self.input.replace_range(start..self.cursor_position, "");
+        self.cursor_position = start;
+    }
+
+    /// Delete from the cursor to the end of the next word
+    pub fn delete_word_forward(&mut self) {
+        let end = self.word_end_after();
+        self.input.replace_range(self.cursor_position..end, "");
+    }
+
+    /// Line of the input the cursor is on, and its column in display cells
+    pub fn cursor_line_col(&self) -> (usize, usize) {
+        let before = &self.input[..self.cursor_position];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (before.matches('\n').count(), before[line_start..].width())
+    }
+
+    /// Move the cursor `delta` lines, keeping its column where the line allows
+    ///
+    /// Returns false when there is no line in that direction.
+    pub fn move_cursor_line(&mut self, delta: isize) -> bool {
+        let (line, col) = self.cursor_line_col();
+        let Some(target) = line.checked_add_signed(delta) else {
+            return false;
+        };
+        let mut start = 0;
+        for (idx, text) in self.input.split('\n').enumerate() {
+            if idx == target {
+                // Stop before the cluster that would pass the column
+                let mut offset = 0;
+                let mut width = 0;
+                for grapheme in text.graphemes(true) {
+                    width += grapheme.width();
+                    if width > col {
+                        break;
+                    }
+                    offset += grapheme.len();
+                }
+                self.cursor_position = start + offset;
+                return true;
+            }
+            start += text.len() + 1;
+        }
+        false
+    }
+
+    /// Move cursor to the start of its line
+    pub fn move_cursor_line_start(&mut self) {
+        self.cursor_position = self.input[..self.cursor_position].rfind('\n').map_or(0, |i| i + 1);
+    }
+
+    /// Move cursor to the end of its line
+    pub fn move_cursor_line_end(&mut self) {
+        let rest = &self.input[self.cursor_position..];
+        self.cursor_position += rest.find('\n').unwrap_or(rest.len());
     }
 
     /// Move cursor to end
@@ -451,6 +2576,12 @@ impl AppState {
         self.cursor_position = 0;
     }
 
+    /// Replace the input buffer, leaving the cursor at the end
+    pub fn set_input(&mut self, input: String) {
+        self.input = input;
+        self.cursor_position = self.input.len();
+    }
+
     /// Take and clear input, returning the content
     pub fn take_input(&mut self) -> String {
         let input = std::mem::take(&mut self.input);
@@ -458,24 +2589,488 @@ impl AppState {
         input
     }
 
+    /// Take the input to send, if it can be sent now
+    ///
+    /// Nothing is sent while a response is still streaming: the draft stays
+    /// and a notice says why, so an Enter that races the end of a stream is
+    /// neither sent twice nor dropped.
+    pub fn take_submission(&mut self) -> Option<String> {
+        if self.input_mode != InputMode::Editing || self.input.trim().is_empty() {
+            return None;
+        }
+        if self.generating() {
+            self.set_status("Wait for the response to finish (Ctrl+x stops it)");
+            return None;
+        }
+        Some(self.take_input())
+    }
+
+    /// Show a notice
+    ///
+    /// Only the latest status is kept. Warnings and errors stack as banners;
+    /// repeating one moves it back to the top instead of adding a copy.
+    pub fn notify(&mut self, severity: Severity, text: impl Into<String>) {
+        let text = text.into();
+        self.notices
+            .retain(|n| !(n.severity == severity && (severity == Severity::Info || n.text == text)));
+        self.notices.push_front(Notice {
+            severity,
+            text,
+            created: Instant::now(),
+        });
+        while self.banners().count() > MAX_BANNERS {
+            if let Some(oldest) = self.notices.iter().rposition(|n| n.severity != Severity::Info) {
+                self.notices.remove(oldest);
+            }
+        }
+    }
+
     /// Set status message
     pub fn set_status(&mut self, msg: impl Into<String>) {
-        self.status_message = Some(msg.into());
+        self.notify(Severity::Info, msg);
     }
 
     /// Clear status message
     pub fn clear_status(&mut self) {
-        self.status_message = None;
+        self.notices.retain(|n| n.severity != Severity::Info);
+    }
+
+    /// Show a warning banner
+    pub fn set_warning(&mut self, msg: impl Into<String>) {
+        self.notify(Severity::Warning, msg);
+    }
+
+    /// Show a notice banner that stays until dismissed
+    pub fn set_notice(&mut self, msg: impl Into<String>) {
+        self.notify(Severity::Notice, msg);
+    }
+
+    /// Show an error banner
+    pub fn set_error(&mut self, msg: impl Into<String>) {
+        self.notify(Severity::Error, msg);
+    }
+
+    /// The current status message
+    pub fn status(&self) -> Option<&str> {
+        self.notices
+            .iter()
+            .find(|n| n.severity == Severity::Info)
+            .map(|n| n.text.as_str())
+    }
+
+    /// Warning and error banners, newest first
+    pub fn banners(&self) -> impl Iterator<Item = &Notice> {
+        self.notices.iter().filter(|n| n.severity != Severity::Info)
+    }
+
+    /// Dismiss the newest banner; false if there was none
+    pub fn dismiss_banner(&mut self) -> bool {
+        match self.notices.iter().position(|n| n.severity != Severity::Info) {
+            Some(idx) => {
+                self.notices.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Dismiss a banner by its text, e.g. once the problem has gone away
+    pub fn dismiss_banner_text(&mut self, text: &str) {
+        self.notices.retain(|n| n.severity == Severity::Info || n.text != text);
+    }
+
+    /// The parts of the state undo restores
+    pub fn ui_snapshot(&self) -> UiSnapshot {
+        UiSnapshot {
+            session: self.active_session().map(|s| s.id),
+            model: self.active_session().map(|s| s.model.clone()),
+        }
+    }
+
+    /// Put back a snapshot; a session deleted since is skipped
+    fn restore(&mut self, snapshot: UiSnapshot) {
+        if let Some(idx) = self.chat.sessions.iter().position(|s| Some(s.id) == snapshot.session) {
+            if self.chat.activate(idx) {
+                self.chat_scroll = 0;
+            }
+            if let Some(model) = snapshot.model {
+                self.set_model(model);
+            }
+        }
+    }
+
+    /// Remember `before` as an undo step if an action changed the UI state
+    pub fn record_undo(&mut self, before: UiSnapshot) {
+        if before != self.ui_snapshot() {
+            if self.undo_history.len() == UNDO_LIMIT {
+                self.undo_history.pop_front();
+            }
+            self.undo_history.push_back(before);
+            self.redo_history.clear();
+        }
+    }
+
+    /// Step back to the last recorded UI state; false if there is none
+    ///
+    /// Steps that no longer change anything (e.g. their session is gone)
+    /// are passed over.
+    pub fn undo(&mut self) -> bool {
+        let current = self.ui_snapshot();
+        while let Some(snapshot) = self.undo_history.pop_back() {
+            self.restore(snapshot);
+            if self.ui_snapshot() != current {
+                self.redo_history.push(current);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Reapply the last undone step; false if there is none
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.redo_history.pop() else {
+            return false;
+        };
+        self.undo_history.push_back(self.ui_snapshot());
+        self.restore(snapshot);
+        true
+    }
+
+    /// Drop notices past their time; returns whether any were
+    pub fn expire_banners(&mut self) -> bool {
+        let before = self.notices.len();
+        self.notices.retain(|n| n.severity.ttl().is_none_or(|ttl| n.created.elapsed() < ttl));
+        self.notices.len() != before
+    }
+
+    /// Mark the message at the top of the chat view
+    pub fn set_mark(&mut self, letter: char) -> Result<(), String> {
+        let idx = self
+            .chat_viewport
+            .message_at(self.chat_scroll)
+            .ok_or_else(|| "Nothing to mark".to_string())?;
+        let session = self.active_session_mut().ok_or_else(|| "No session".to_string())?;
+        let id = session.messages.get(idx).map(|m| m.id).ok_or_else(|| "Nothing to mark".to_string())?;
+        session.marks.insert(letter, id);
+        Ok(())
+    }
+
+    /// Scroll so the marked message is at the top of the chat view
+    pub fn jump_to_mark(&mut self, letter: char) -> Result<(), String> {
+        let session = self.active_session().ok_or_else(|| "No session".to_string())?;
+        let id = session
+            .marks
+            .get(&letter)
+            .ok_or_else(|| format!("Mark '{}' not set", letter))?;
+        let scroll = session
+            .messages
+            .iter()
+            .position(|m| m.id == *id)
+            .and_then(|idx| self.chat_viewport.scroll_for(idx))
+            .ok_or_else(|| format!("Mark '{}' points to a deleted message", letter))?;
+        self.chat_scroll = scroll;
+        Ok(())
+    }
+
+    /// Add a key to the macro being recorded, if any
+    pub fn record_key(&mut self, key: KeyEvent) {
+        if let Some((_, keys)) = self.recording.as_mut() {
+            keys.push(key);
+        }
+    }
+
+    /// Finish recording, dropping the key that stopped it
+    ///
+    /// Returns the register and how many keys it holds.
+    pub fn stop_recording(&mut self) -> Option<(char, usize)> {
+        let (register, mut keys) = self.recording.take()?;
+        keys.pop();
+        let count = keys.len();
+        self.macros.insert(register, keys);
+        Some((register, count))
+    }
+
+    /// Queue a macro's keys for the main loop; `@` replays the last one
+    pub fn replay_macro(&mut self, register: char) -> Result<usize, String> {
+        let register = match register {
+            '@' => self.last_macro.ok_or_else(|| "No macro replayed yet".to_string())?,
+            c => c,
+        };
+        let keys = self.macros.get(&register).ok_or_else(|| format!("Macro @{} not recorded", register))?;
+        // Nested replays count toward the replay that started them
+        if self.replay.is_empty() {
+            self.replayed_keys = 0;
+        }
+        self.replayed_keys += keys.len();
+        if self.replayed_keys > MAX_REPLAY_KEYS {
+            self.stop_replay();
+            return Err(format!("Stopped replaying @{}: too many keys (does it replay itself?)", register));
+        }
+        // Ahead of whatever is left of a macro that replays this one
+        for key in keys.iter().rev() {
+            self.replay.push_front(*key);
+        }
+        self.last_macro = Some(register);
+        Ok(keys.len())
+    }
+
+    /// Drop the rest of a replaying macro; returns whether one was running
+    pub fn stop_replay(&mut self) -> bool {
+        self.replayed_keys = 0;
+        let running = !self.replay.is_empty();
+        self.replay.clear();
+        running
+    }
+
+    /// Follow-up prompt for a reaction key, if one can be sent now
+    pub fn reaction_prompt(&mut self, key: char) -> Option<String> {
+        let prompt = self.config.reactions.prompt(key)?.to_string();
+        if self.streaming {
+            self.set_status("Wait for the response to finish");
+            return None;
+        }
+        let answered = self
+            .active_session()
+            .and_then(|s| s.messages.last())
+            .is_some_and(|m| m.role == Role::Assistant && !m.content.trim().is_empty());
+        if !answered {
+            self.set_status("No response to follow up on");
+            return None;
+        }
+        Some(prompt)
+    }
+
+    /// Actions the command palette offers for its query
+    pub fn palette_entries(&self) -> Vec<&'static ActionDef> {
+        keymap::palette(&self.palette_query)
+    }
+
+    /// Close the palette and give the highlighted action, if it applies now
+    pub fn take_palette_action(&mut self) -> Option<AppAction> {
+        let def = self.palette_entries().get(self.palette_idx).copied();
+        self.input_mode = InputMode::Normal;
+        let action = def.and_then(|def| (def.action)(self, 0));
+        if let (Some(def), None) = (def, &action) {
+            self.set_status(format!("Nothing to do: {}", def.description));
+        }
+        action
+    }
+
+    /// Rerun the search for the current query
+    pub fn update_search(&mut self) {
+        self.search_results = match self.chat.loaded_sessions() {
+            Ok(sessions) => search::search(&sessions, &self.search_query),
+            Err(e) => {
+                warn!("Failed to load sessions to search: {}", e);
+                search::search(&self.chat.sessions, &self.search_query)
+            }
+        };
+        self.search_idx = 0;
+    }
+
+    /// Switch to the highlighted search result
+    ///
+    /// The chat is scrolled to a matching message after the next render,
+    /// once the session's lines have been laid out.
+    pub fn open_search_result(&mut self) -> Option<&ChatSession> {
+        let hit = self.search_results.get(self.search_idx)?.clone();
+        if hit.session_idx >= self.chat.sessions.len() {
+            return None;
+        }
+        self.chat.activate(hit.session_idx);
+        self.chat_scroll = 0;
+        self.reveal_message = hit.message_idx;
+        self.active_session()
+    }
+
+    /// Scroll to the message awaiting reveal; true if the view changed
+    pub fn apply_reveal(&mut self) -> bool {
+        let Some(idx) = self.reveal_message.take() else {
+            return false;
+        };
+        match self.chat_viewport.scroll_for(idx) {
+            Some(scroll) => {
+                self.chat_scroll = scroll;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move to another find match and scroll it into view
+    pub fn step_find(&mut self, forward: bool) {
+        let count = self.chat_viewport.find_matches.len();
+        if self.find_query.is_empty() {
+            self.set_status("Nothing to find (Ctrl+f)");
+            return;
+        }
+        if count == 0 {
+            self.set_status(format!("No matches for \"{}\"", self.find_query));
+            return;
+        }
+        self.find_idx = if forward {
+            (self.find_idx + 1) % count
+        } else {
+            self.find_idx.checked_sub(1).unwrap_or(count - 1).min(count - 1)
+        };
+        self.scroll_to_find_match();
+    }
+
+    /// Select the first match from the top of the view; true if the view changed
+    ///
+    /// Runs after a render, once matches for a new query are known.
+    pub fn apply_find_jump(&mut self) -> bool {
+        if !std::mem::take(&mut self.find_jump) {
+            return false;
+        }
+        let viewport = &self.chat_viewport;
+        let top = viewport.top_line(self.chat_scroll);
+        self.find_idx = viewport.find_matches.iter().position(|&line| line >= top).unwrap_or(0);
+        self.scroll_to_find_match();
+        true
+    }
+
+    fn scroll_to_find_match(&mut self) {
+        if let Some(&line) = self.chat_viewport.find_matches.get(self.find_idx) {
+            self.chat_scroll = self.chat_viewport.scroll_to_lines(line..line + 1, self.chat_scroll);
+        }
+    }
+
+    /// The message highlighted in select mode
+    pub fn selected(&self) -> Option<&Message> {
+        self.active_session()?.messages.get(self.selected_message)
+    }
+
+    /// Expand or collapse the reasoning of the highlighted message; returns
+    /// whether it is expanded now, or `None` if it has no reasoning
+    pub fn toggle_selected_thinking(&mut self) -> Option<bool> {
+        let idx = self.selected_message;
+        let message = self.active_session_mut()?.messages.get_mut(idx)?;
+        if message.reasoning().0.trim().is_empty() {
+            return None;
+        }
+        message.thinking_expanded = !message.thinking_expanded;
+        Some(message.thinking_expanded)
+    }
+
+    /// Messages acted on in select mode: the visual range, or the highlighted message
+    pub fn selection(&self) -> RangeInclusive<usize> {
+        match self.select_anchor {
+            Some(anchor) => anchor.min(self.selected_message)..=anchor.max(self.selected_message),
+            None => self.selected_message..=self.selected_message,
+        }
+    }
+
+    /// Move the selection, clamped to the session, and scroll it into view
+    pub fn select_message(&mut self, idx: usize) {
+        let count = self.active_session().map_or(0, |s| s.messages.len());
+        self.selected_message = idx.min(count.saturating_sub(1));
+        if let Some(scroll) = self.chat_viewport.scroll_to_show(self.selected_message, self.chat_scroll) {
+            self.chat_scroll = scroll;
+        }
+    }
+
+    /// Track a model list reload, cancelling any already in flight
+    ///
+    /// Returns whether an earlier reload was cancelled.
+    pub fn begin_model_refresh(&mut self, abort: AbortHandle) -> bool {
+        let restarted = self.cancel_model_refresh();
+        self.model_refresh = Some(ModelRefresh { abort });
+        self.loading = true;
+        restarted
+    }
+
+    /// Cancel the model list reload, if any; returns whether one was running
+    pub fn cancel_model_refresh(&mut self) -> bool {
+        let Some(refresh) = self.model_refresh.take() else {
+            return false;
+        };
+        refresh.abort.abort();
+        self.loading = false;
+        true
+    }
+
+    /// Spinner frame while the model list is reloading
+    pub fn refresh_spinner(&self) -> Option<char> {
+        self.model_refresh.as_ref().map(|_| self.spinner())
+    }
+
+    /// Record how far along the operation `id` is, starting to track it if new
+    pub fn update_progress(&mut self, id: &str, done: u64, total: u64) {
+        match self.progress.iter_mut().find(|p| p.id == id) {
+            Some(progress) => (progress.done, progress.total) = (done, total),
+            None => self.progress.push(Progress { id: id.to_string(), done, total }),
+        }
+    }
+
+    /// Stop tracking the operation `id`
+    pub fn finish_progress(&mut self, id: &str) {
+        self.progress.retain(|p| p.id != id);
+    }
+
+    /// Current frame of the spinners
+    pub fn spinner(&self) -> char {
+        SPINNER_FRAMES[self.spinner_frame % SPINNER_FRAMES.len()]
+    }
+
+    /// Whether anything on screen is animating: a response streaming in any
+    /// session or a model list reload
+    pub fn animating(&self) -> bool {
+        self.generating() || self.model_refresh.is_some() || self.chat.sessions.iter().any(ChatSession::is_streaming)
+    }
+
+    /// Handle a tick; returns whether the screen needs a redraw
+    ///
+    /// Spinners step at most once per `[ui] tick_rate_ms`, however often
+    /// ticks arrive. Relative timestamps are redrawn as the minute turns.
+    pub fn tick(&mut self) -> bool {
+        let minute = Utc::now().timestamp() / 60;
+        let minute_turned = std::mem::replace(&mut self.clock_minute, minute) != minute;
+        let relative = self.config.ui.show_timestamps && self.config.ui.timestamp_style == TimestampStyle::Relative;
+        if !self.animating() {
+            return relative && minute_turned;
+        }
+        if self.last_spin.elapsed() >= Duration::from_millis(self.config.ui.tick_rate_ms) {
+            self.spinner_frame = self.spinner_frame.wrapping_add(1);
+            self.last_spin = Instant::now();
+        }
+        true
     }
 
-    /// Set error message
-    pub fn set_error(&mut self, msg: impl Into<String>) {
-        self.error_message = Some(msg.into());
+    /// Cancel the streaming response and put the prompt back into the input
+    pub fn abort_generation(&mut self) {
+        self.abort_compare();
+        if !self.streaming {
+            return;
+        }
+        if let Some(handle) = self.stream_abort.take() {
+            handle.abort();
+        }
+        if let Some(session) = self.active_session_mut() {
+            session.interrupt_response();
+        }
+        self.streaming = false;
+        self.pending_follow_up = None;
+        self.cache_pending = None;
+        if let Some(prompt) = self.last_prompt.take() {
+            self.input = prompt;
+            self.move_cursor_end();
+        }
+        self.input_mode = InputMode::Editing;
     }
 
-    /// Clear error message
-    pub fn clear_error(&mut self) {
-        self.error_message = None;
+    /// Scroll by `amount` lines, accelerated while scrolling is held
+    pub fn scroll_accelerated(&mut self, amount: usize, up: bool) {
+        let amount = if self.config.ui.scroll_acceleration {
+            amount * self.scroll_accel.step(Instant::now(), up)
+        } else {
+            amount
+        };
+        if up {
+            self.scroll_up(amount);
+        } else {
+            self.scroll_down(amount);
+        }
     }
 
     /// Scroll chat up
@@ -519,17 +3114,92 @@ pub enum AppEvent {
     /// Error loading models
     ModelsError(String),
     
+    /// A stream event of a response, for the session it was asked in
+    Stream { session_id: Uuid, event: Box<AppEvent> },
+
     /// New token chunk received from streaming response
     StreamChunk(String),
+
+    /// New reasoning chunk from a thinking model
+    StreamThinking(String),
+
+    /// Agent mode: the model called a tool
+    ToolCalled { name: String, arguments: serde_json::Value },
+
+    /// Agent mode: the latest tool call returned
+    ToolResult(String),
     
     /// Stream completed with stats
     StreamComplete(ResponseStats),
     
     /// Stream error
     StreamError(String),
+
+    /// Chat request failed because the model isn't installed
+    ModelMissing(String),
+
+    /// Compare mode: a stream event of the second model, for its session
+    Compare { session_id: Uuid, event: Box<AppEvent> },
     
     /// Server connection status changed
     ServerStatus(bool),
+
+    /// Round-trip time of the latest successful health check
+    Latency(Duration),
+
+    /// Context window the server reported for a model (0 if unknown)
+    ContextWindow { model: String, tokens: u32 },
+
+    /// The model suggested a title for a session
+    SessionTitled { session_id: Uuid, title: String },
+
+    /// The model anonymized a message of a redaction review
+    Anonymized { review_id: Uuid, idx: usize, text: Option<String> },
+
+    /// Server came back after being unreachable
+    ServerReconnected,
+
+    /// A newer release is available
+    UpdateAvailable(String),
+
+    /// The completion webhook could not be delivered
+    NotifyFailed(String),
+
+    /// Demo mode: the prompt typed so far
+    DemoTyped(String),
+
+    /// Demo mode: send the typed prompt, stamped with the given time
+    DemoSubmit(DateTime<Utc>),
+
+    /// Model finished loading into memory
+    ModelLoaded(String),
+
+    /// Model was unloaded from memory
+    ModelUnloaded(String),
+
+    /// Loading or unloading a model failed
+    ModelCommandError(String),
+
+    /// How far along a long operation is; see [`AppState::update_progress`]
+    Progress { id: String, done: u64, total: u64 },
+
+    /// A dataset export finished: the file and conversations written, or
+    /// `None` when nothing in scope had a usable exchange
+    DatasetExported(Result<Option<(PathBuf, usize)>, String>),
+
+    /// Progress update for a model pull
+    PullProgress {
+        model: String,
+        status: String,
+        completed: u64,
+        total: u64,
+    },
+
+    /// A model pull finished, with an error message on failure
+    PullFinished {
+        model: String,
+        error: Option<String>,
+    },
     
     /// Request to quit
     #[allow(dead_code)]
@@ -548,6 +3218,51 @@ pub enum AppAction {
     RequestDeleteSession,
     ConfirmDeleteSession,
     CancelDeleteSession,
+    RequestRename,
+    TogglePin,
+    ToggleToolOutput,
+
+    // Find in chat
+    OpenFind,
+    FindInsertChar(char),
+    FindDeleteChar,
+    ConfirmFind,
+    CancelFind,
+    FindNext,
+    FindPrev,
+
+    // Search
+    OpenSearch,
+    CloseSearch,
+    SearchInsertChar(char),
+    SearchDeleteChar,
+    NextSearchResult,
+    PrevSearchResult,
+    ConfirmSearch,
+
+    // Command palette
+    OpenPalette,
+    ClosePalette,
+    PaletteInsertChar(char),
+    PaletteDeleteChar,
+    NextPaletteEntry,
+    PrevPaletteEntry,
+    RunPaletteEntry,
+
+    RenameInsertChar(char),
+    RenameDeleteChar,
+    ConfirmRename,
+    CancelRename,
+
+    // File picker
+    OpenFilePicker(PickPurpose),
+    PickerInsertChar(char),
+    PickerBackspace,     // Goes up a folder once the filter is empty
+    NextPickerEntry,
+    PrevPickerEntry,
+    PickerEnter,
+    DetachLast,
+    ClosePicker,
     
     // Model selection
     OpenModelSelect,
@@ -555,19 +3270,36 @@ pub enum AppAction {
     NextModel,
     PrevModel,
     ConfirmModel,
+    #[allow(dead_code)]
     SelectModel(usize),  // Direct model selection (for mouse clicks)
+    ModelPageUp,
+    ModelPageDown,
+    FirstModel,
+    LastModel,
+    JumpToModel(char),   // Next model starting with a letter
+    LoadModel,           // Pre-warm the highlighted model
+    UnloadModel,         // Free the highlighted model's memory
     
     // Input
     EnterEditMode,
     ExitEditMode,
     SubmitMessage,
     InsertChar(char),
+    InsertNewline,
+    Paste(String),
+    OpenInEditor,        // Handled by the main loop, which owns the terminal
     DeleteChar,
     DeleteCharForward,
+    DeleteWordBack,
+    DeleteWordForward,
     MoveCursorLeft,
     MoveCursorRight,
-    MoveCursorStart,
-    MoveCursorEnd,
+    MoveCursorWordLeft,
+    MoveCursorWordRight,
+    MoveCursorUp,
+    MoveCursorDown,
+    MoveCursorStart,     // Of the current line
+    MoveCursorEnd,       // Of the current line
     ClearInput,
     
     // Scrolling
@@ -578,9 +3310,151 @@ pub enum AppAction {
     PageUp,
     PageDown,
     
+    // Marks
+    BeginMark,
+    BeginJump,
+    CancelPendingKey,
+    SetMark(char),
+    JumpToMark(char),
+    
+    // Macros
+    BeginRecord,
+    BeginReplay,
+    StartRecording(char),
+    StopRecording,
+    ReplayMacro(char),
+    
+    // Downloads
+    OpenDownloads,
+    CloseDownloads,
+    PullInsertChar(char),
+    PullDeleteChar,
+    NextPullSuggestion,
+    PrevPullSuggestion,
+    QueuePull,           // Queue the typed or highlighted model
+    ClearFinishedDownloads,
+    
+    // Missing model prompt
+    ConfirmPullMissing,
+    CancelPullMissing,
+    
+    // Patches
+    RequestApplyPatch,
+    ConfirmApplyPatch,
+    CancelApplyPatch,
+    
+    // Generation
+    AbortGeneration,     // Cancel streaming and restore the prompt for editing
+    
     // Misc
+    ExportMarkdown,
+    CopySessionMarkdown,
+    ExportToVault,
+    ToggleFlag,
+    Rate(Rating),
+
+    // Message selection
+    EnterSelectMode,
+    ExitSelectMode,
+    SelectNext,
+    SelectPrev,
+    SelectFirst,
+    SelectLast,
+    CopySelected,
+    DeleteSelected,
+    EditSelected,
+    InspectSelected,
+    ToggleThinking,
+    ToggleRangeSelect,
+    ExportSelected,
+    CopySelectedMarkdown,
+    CloseInspect,
+    ForkAtSelected,
+
+    // Code blocks
+    CopyCodeBlock,
+    NextCodeBlock,
+    PrevCodeBlock,
+    ConfirmCodeBlock,
+    PickCodeBlock(usize),
+    CloseCodePicker,
+
+    // Yank history
+    OpenYanks,
+    NextYank,
+    PrevYank,
+    RecopyYank,
+    InsertYank,
+    DeleteYank,
+    CloseYanks,
+
+    // Saved snippets
+    OpenSnippets,
+    NextSnippet,
+    PrevSnippet,
+    InsertSnippet,
+    SaveInputSnippet,
+    SaveSelectedSnippet,
+    DeleteSnippet,
+    CloseSnippets,
+
+    // System prompt library
+    OpenPrompts,
+    NextPrompt,
+    PrevPrompt,
+    UsePrompt,
+    ClearPrompt,
+    EditPrompts,         // Handled by the main loop, which owns the terminal
+    ClosePrompts,
+
+    // Dry-run report
+    ScrollDryRun(i16),
+    CopyDryRun,
+    CloseDryRun,
+
+    // Compare mode
+    ToggleCompare,
+    CompareWithSelected,
+
+    ToggleZen,
+
+    // Split view
+    ToggleSplit,
+    SwitchPane,
+    ScrollSplit(i16),
+
+    // Context inspector
+    OpenContext,
+    ScrollContext(i16),
+    CloseContext,
+
+    // Response cache
+    OpenCache,
+    NextCacheEntry,
+    PrevCacheEntry,
+    DeleteCacheEntry,
+    ClearCache,
+    CloseCache,
+
+    // Anonymized export
+    OpenRedactReview,
+    NextRedaction,
+    PrevRedaction,
+    AnonymizeWithModel,  // Started by the main loop
+    ExportRedacted,
+    CloseRedactReview,
+    React(char),         // Quick follow-up, sent by the main loop
+    ContinueResponse,    // Continue a cut-off response, sent by the main loop
+    CycleTimestamps,
+    CycleTheme,
+    RetryFailed,         // Resend a failed turn, sent by the main loop
+    OpenInPager,         // Handled by the main loop, which owns the terminal
+    OpenTranscript,
+    CloseTranscript,
     ToggleHelp,
-    ClearError,
+    DismissBanner,
+    Undo,
+    Redo,
     Quit,
     
     // Server
@@ -590,6 +3464,231 @@ pub enum AppAction {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ollama::ShowResponse;
+
+    #[test]
+    fn test_profile_options_layering() {
+        let mut config = Config::default();
+        config.model.profiles = vec![crate::config::OptionProfile {
+            pattern: "qwen*".to_string(),
+            options: GenerationOptions { temperature: Some(0.3), top_k: Some(20), ..Default::default() },
+        }];
+        let mut state = AppState::new(config);
+        assert_eq!(state.generation_options().temperature, Some(0.7));
+
+        state.set_model("qwen2.5:7b");
+        assert_eq!(state.generation_options().temperature, Some(0.3));
+
+        // Session options win over the profile
+        state.active_session_mut().unwrap().options =
+            Some(GenerationOptions { top_k: Some(5), ..Default::default() });
+        let opts = state.generation_options();
+        assert_eq!((opts.temperature, opts.top_k), (Some(0.3), Some(5)));
+    }
+
+    #[test]
+    fn test_model_jumps() {
+        let mut state = AppState::new(Config::default());
+        state.chat.models = ["llama3.2", "mistral", "openai/gpt-4o", "openai/o1", "phi3.5"]
+            .into_iter()
+            .map(ModelInfo::remote)
+            .collect();
+        // Hosted models are keyed without the provider prefix
+        state.chat.models[2].model = "gpt-4o".to_string();
+        state.chat.models[3].model = "o1".to_string();
+
+        assert!(state.jump_to_model('G'));
+        assert_eq!(state.selected_model_idx, 2);
+        assert!(state.jump_to_model('O'));
+        assert_eq!(state.selected_model_idx, 3);
+        assert!(!state.jump_to_model('Z'));
+        assert_eq!(state.selected_model_idx, 3);
+
+        state.move_model_selection(10);
+        assert_eq!(state.selected_model_idx, 4);
+        state.move_model_selection(-10);
+        assert_eq!(state.selected_model_idx, 0);
+    }
+
+    #[tokio::test]
+    async fn test_model_refresh_restarts() {
+        let mut state = AppState::new(Config::default());
+        let first = tokio::spawn(std::future::pending::<()>());
+        assert!(!state.begin_model_refresh(first.abort_handle()));
+        assert!(state.loading && state.refresh_spinner().is_some());
+
+        let second = tokio::spawn(std::future::pending::<()>());
+        assert!(state.begin_model_refresh(second.abort_handle()));
+        assert!(first.await.unwrap_err().is_cancelled());
+
+        assert!(state.cancel_model_refresh());
+        assert!(second.await.unwrap_err().is_cancelled());
+        assert!(!state.loading && state.refresh_spinner().is_none());
+    }
+
+    #[test]
+    fn test_progress_tracks_operations() {
+        let mut state = AppState::new(Config::default());
+        state.update_progress("pull llama3.2", 0, 0);
+        assert_eq!(state.progress[0].ratio(), 0.0);
+        state.update_progress("export", 1, 4);
+        state.update_progress("pull llama3.2", 3, 4);
+        assert_eq!(state.progress.len(), 2);
+        assert_eq!(state.progress[0].ratio(), 0.75);
+
+        state.finish_progress("pull llama3.2");
+        assert_eq!(state.progress.len(), 1);
+        assert_eq!(state.progress[0].id, "export");
+    }
+
+    #[test]
+    fn test_tick_animates_only_while_streaming() {
+        let mut config = Config::default();
+        config.ui.tick_rate_ms = 0;
+        config.ui.timestamp_style = TimestampStyle::Clock;
+        let mut state = AppState::new(config);
+        assert!(!state.tick());
+        assert_eq!(state.spinner_frame, 0);
+
+        state.active_session_mut().unwrap().start_assistant_response();
+        let first = state.spinner();
+        assert!(state.tick());
+        assert_ne!(state.spinner(), first);
+
+        state.active_session_mut().unwrap().finish_response();
+        assert!(!state.tick());
+    }
+
+    #[test]
+    fn test_redact_review_steps_through_changes() {
+        let mut session = ChatSession::new("Support", "llama3.2");
+        session.add_user_message("Write to bob@example.com");
+        session.add_user_message("Nothing private here");
+        session.add_user_message("Or /home/bob/inbox");
+        let redactor = Redactor::new(&crate::config::RedactConfig::default()).unwrap();
+
+        let mut review = RedactReview::new(&session, redactor);
+        assert_eq!(review.changed(), [0, 2]);
+        assert_eq!(review.after.messages[0].content, "Write to [email]");
+        review.step(true);
+        assert_eq!(review.idx, 2);
+        review.step(true);
+        assert_eq!(review.idx, 0);
+
+        // Model output is checked against the rules again
+        review.apply_anonymized(1, " Nothing private, says bob@example.com ");
+        assert_eq!(review.after.messages[1].content, "Nothing private, says [email]");
+        assert_eq!(session.messages[0].content, "Write to bob@example.com");
+    }
+
+    #[test]
+    fn test_auto_title() {
+        let mut session = ChatSession::with_default_name("llama3.2");
+        assert!(session.has_default_name());
+        session.add_user_message("How do lifetimes work?");
+        assert!(!session.wants_auto_title());
+        session.start_assistant_response();
+        session.append_to_response("They describe how long references are valid.");
+        assert!(!session.wants_auto_title());
+        session.finish_response();
+        assert!(session.wants_auto_title());
+        assert_eq!(session.title_request().len(), 3);
+
+        session.rename("Lifetimes");
+        assert!(!session.wants_auto_title());
+
+        assert_eq!(clean_title("\n\"Rust Lifetimes Explained.\"\nextra").as_deref(), Some("Rust Lifetimes Explained"));
+        assert_eq!(clean_title("Title: **Borrowing**").as_deref(), Some("Borrowing"));
+        assert_eq!(clean_title("<think>hmm</think>\nOwnership").as_deref(), Some("Ownership"));
+        assert_eq!(clean_title("  \n \"\" "), None);
+    }
+
+    #[test]
+    fn test_tool_steps_replayed_to_model() {
+        let mut session = ChatSession::new("Agent", "llama3.2");
+        session.add_user_message("What time is it?");
+        session.start_assistant_response();
+        session.add_tool_step("current_time".to_string(), serde_json::Value::Null);
+        session.finish_tool_step("Monday 09:00".to_string());
+        session.append_to_response("It's nine.");
+        session.finish_response();
+
+        let roles: Vec<Role> = session.to_chat_messages().iter().map(|m| m.role).collect();
+        assert_eq!(roles, [Role::User, Role::Assistant, Role::Tool, Role::Assistant]);
+        let messages = session.to_chat_messages();
+        assert_eq!(messages[1].tool_calls.as_ref().unwrap()[0].function.name, "current_time");
+        assert_eq!(messages[2].content, "Monday 09:00");
+
+        // Finished responses take no more steps
+        session.add_tool_step("read_file".to_string(), serde_json::Value::Null);
+        assert_eq!(session.messages[1].tool_steps.len(), 1);
+    }
+
+    #[test]
+    fn test_pinned_sessions_sort_first() {
+        let mut state = AppState::new(Config::default());
+        state.chat.sessions = ["a", "b", "c"].into_iter().map(|n| ChatSession::new(n, "llama3.2")).collect();
+        state.chat.active_session_idx = 2;
+
+        assert_eq!(state.toggle_pin(), Some(true));
+        let names: Vec<&str> = state.chat.sessions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["c", "a", "b"]);
+        assert_eq!(state.chat.active_session_idx, 0);
+
+        assert_eq!(state.toggle_pin(), Some(false));
+        assert_eq!(state.active_session().unwrap().name, "c");
+    }
+
+    #[test]
+    fn test_session_markdown_range() {
+        let mut state = AppState::new(Config::default());
+        let mut session = ChatSession::new("Notes", "llama3.2");
+        session.add_user_message("first question");
+        session.add_user_message("second question");
+        state.chat.sessions = vec![session];
+
+        let all = state.session_markdown(None).unwrap();
+        assert!(all.starts_with("# Notes") && all.contains("first") && all.contains("second"));
+        let one = state.session_markdown(Some(1..=1)).unwrap();
+        assert!(!one.contains("first") && one.contains("second"));
+        assert_eq!(state.session_markdown(Some(1..=5)), None);
+    }
+
+    #[test]
+    fn test_banners_stack() {
+        let mut state = AppState::new(Config::default());
+        state.set_status("Loaded 3 models");
+        state.set_error("Export failed");
+        state.set_warning("Nothing to delete");
+        state.set_error("Export failed");
+        state.set_status("Copied");
+
+        assert_eq!(state.status(), Some("Copied"));
+        let banners: Vec<&str> = state.banners().map(|n| n.text.as_str()).collect();
+        assert_eq!(banners, ["Export failed", "Nothing to delete"]);
+
+        assert!(state.dismiss_banner());
+        assert_eq!(state.banners().next().unwrap().severity, Severity::Warning);
+        state.notices[1].created -= Duration::from_secs(60);
+        state.expire_banners();
+        assert!(!state.dismiss_banner());
+        assert_eq!(state.status(), Some("Copied"));
+
+        for i in 0..MAX_BANNERS + 2 {
+            state.set_error(format!("error {}", i));
+        }
+        assert_eq!(state.banners().count(), MAX_BANNERS);
+        assert_eq!(state.banners().next().unwrap().text, format!("error {}", MAX_BANNERS + 1));
+    }
+
+    #[test]
+    fn test_rename_rejects_blank_names() {
+        let mut session = ChatSession::new("Chat 2025-01-01 09:00", "llama3.2");
+        assert!(!session.rename("   "));
+        assert_eq!(session.name, "Chat 2025-01-01 09:00");
+        assert!(session.rename("  Borrow checker notes "));
+        assert_eq!(session.name, "Borrow checker notes");
+    }
 
     #[test]
     fn test_message_creation() {
@@ -615,6 +3714,412 @@ mod tests {
         assert_eq!(session.messages.last().unwrap().content, "Hello world!");
     }
 
+    #[test]
+    fn test_rate_last_response() {
+        let mut session = ChatSession::new("Test", "llama3.2");
+        assert_eq!(session.rate_last_response(Rating::Up, None), None);
+
+        session.messages.push(Message::user("Hi"));
+        session.messages.push(Message::assistant("Hello"));
+        assert_eq!(session.rate_last_response(Rating::Down, Some("rude".into())), Some(Some(Rating::Down)));
+        assert!(session.messages[1].excluded_from_datasets());
+
+        // Changing the rating keeps the note; repeating it clears both
+        assert_eq!(session.rate_last_response(Rating::Up, None), Some(Some(Rating::Up)));
+        assert_eq!(session.messages[1].feedback.as_ref().unwrap().note.as_deref(), Some("rude"));
+        assert_eq!(session.rate_last_response(Rating::Up, None), Some(None));
+        assert!(session.messages[1].feedback.is_none());
+    }
+
+    #[test]
+    fn test_scroll_to_show() {
+        // Three 4-line messages with separators: starts 0, 5, 10; 14 lines
+        let viewport = ChatViewport {
+            message_starts: vec![0, 5, 10],
+            total_lines: 14,
+            visible_lines: 6,
+            ..Default::default()
+        };
+        assert_eq!(viewport.message_lines(1), Some(5..9));
+        // At the bottom (top line 8) the first message is above the view
+        assert_eq!(viewport.scroll_to_show(0, 0), Some(8));
+        // Already fully visible: no change
+        assert_eq!(viewport.scroll_to_show(2, 0), Some(0));
+        // Scrolled to the top, the middle message's end is below the view
+        assert_eq!(viewport.scroll_to_show(1, 8), Some(5));
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut session = ChatSession::new("Trip", "llama3.2");
+        session.tags = vec!["travel".to_string()];
+        for text in ["a", "b", "c"] {
+            session.messages.push(Message::user(text));
+        }
+        session.marks.insert('a', session.messages[0].id);
+        session.marks.insert('c', session.messages[2].id);
+
+        let fork = session.fork(0..=1).unwrap();
+        assert_eq!(fork.name, "Trip (fork)");
+        assert_eq!(fork.tags, session.tags);
+        let contents: Vec<&str> = fork.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["a", "b"]);
+        assert_ne!(fork.messages[0].id, session.messages[0].id);
+        assert_eq!(fork.marks.get(&'a'), Some(&fork.messages[0].id));
+        assert!(!fork.marks.contains_key(&'c'));
+        assert_eq!(session.messages.len(), 3);
+        assert!(session.fork(0..=3).is_none());
+        let fork = session.fork(1..=2).unwrap();
+        assert_eq!(fork.messages[0].content, "b");
+        assert_eq!(fork.marks.get(&'c'), Some(&fork.messages[1].id));
+    }
+
+    #[test]
+    fn test_delete_message_drops_marks() {
+        let mut session = ChatSession::new("Test", "llama3.2");
+        session.messages.push(Message::user("a"));
+        session.messages.push(Message::assistant("b"));
+        session.marks.insert('a', session.messages[1].id);
+
+        assert_eq!(session.delete_message(1).unwrap().content, "b");
+        assert!(session.marks.is_empty());
+        assert!(session.delete_message(5).is_none());
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        let text = "Try:\n```rust\nfn main() {}\n```\nor\n  ```\nls -la\n\ncd /\n  ```\n```py\nprint(1)";
+        assert_eq!(
+            code_blocks(text),
+            vec![
+                CodeBlock { lang: "rust".into(), code: "fn main() {}".into() },
+                CodeBlock { lang: String::new(), code: "ls -la\n\ncd /".into() },
+                CodeBlock { lang: "py".into(), code: "print(1)".into() },
+            ]
+        );
+        assert!(code_blocks("no code").is_empty());
+    }
+
+    #[test]
+    fn test_extract_image_refs() {
+        let dir = std::env::temp_dir().join(format!("ratatalk-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("cat.png");
+        std::fs::write(&image, b"png").unwrap();
+
+        let content = format!("what is in @{} and @missing.png or @notes.txt?", image.display());
+        assert_eq!(extract_image_refs(&content), vec![image]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_attachment_sent_as_fenced_block() {
+        let dir = std::env::temp_dir().join(format!("ratatalk-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.md");
+        std::fs::write(&file, "# Notes\n```sh\nls\n```\n").unwrap();
+        let binary = dir.join("blob.bin");
+        std::fs::write(&binary, [0u8, 159, 146, 150]).unwrap();
+
+        let attachment = Attachment::load(&file).unwrap();
+        assert_eq!((attachment.name.as_str(), attachment.line_count()), ("notes.md", 4));
+        assert!(matches!(Attachment::load(&binary), Err(AttachmentError::Binary)));
+
+        let mut message = Message::user("Summarize this");
+        message.attachments.push(attachment);
+        assert_eq!(
+            message.to_chat_message().content,
+            "Summarize this\n\n````notes.md\n# Notes\n```sh\nls\n```\n````"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_context_window_drops_oldest() {
+        let mut session = ChatSession::new("Test", "llama3.2");
+        session.system_prompt = Some("Be brief".to_string());
+        for i in 0..4 {
+            session.add_user_message(format!("{} {}", i, "q".repeat(38)));
+            session.messages.push(Message::assistant("a".repeat(40)));
+        }
+        session.add_user_message("x".repeat(400));
+
+        // Everything fits: nothing dropped
+        let (messages, dropped) = session.to_chat_messages_within(10_000);
+        assert_eq!((messages.len(), dropped), (10, 0));
+
+        // Room for the system prompt, the newest question and two more
+        let (messages, dropped) = session.to_chat_messages_within(125);
+        assert_eq!(dropped, 6);
+        assert_eq!(messages[0].role, Role::System);
+        assert!(messages[1].content.starts_with("3 "));
+
+        // The newest question is sent even when it alone is too big
+        let (messages, dropped) = session.to_chat_messages_within(10);
+        assert_eq!((messages.len(), dropped), (2, 8));
+
+        let show: ShowResponse =
+            serde_json::from_str(r#"{"model_info": {"general.architecture": "llama", "llama.context_length": 131072}}"#)
+                .unwrap();
+        assert_eq!(show.context_length(), Some(131072));
+    }
+
+    #[test]
+    fn test_context_entries() {
+        let mut state = AppState::new(Config::default());
+        let model = state.current_model().to_string();
+        let session = state.active_session_mut().unwrap();
+        session.system_prompt = Some("Be brief".to_string());
+        session.add_user_message("o".repeat(400));
+        for _ in 0..2 {
+            session.add_user_message("q".repeat(40));
+            session.messages.push(Message::assistant("a".repeat(40)));
+        }
+        state.input = "x".repeat(200);
+
+        // Unknown window: everything is sent
+        let entries = state.context_entries();
+        assert_eq!(entries.len(), 7);
+        assert!(entries.iter().all(|e| e.included));
+        assert_eq!((entries[0].label.as_str(), entries[6].label.as_str()), ("system", "draft"));
+
+        // The draft counts against the budget, so the oldest message no longer fits
+        state.chat.set_context_window(model, 160);
+        let included: Vec<bool> = state.context_entries().iter().map(|e| e.included).collect();
+        assert_eq!(included, [true, false, true, true, true, true, true]);
+        assert_eq!(state.context_entries()[2].tokens, 10);
+    }
+
+    #[test]
+    fn test_split_view() {
+        let mut state = AppState::new(Config::default());
+        assert!(!state.toggle_split());
+        assert!(state.split.is_none());
+
+        state.new_session();
+        state.chat.activate(0);
+        let (first, second) = (state.chat.sessions[0].id, state.chat.sessions[1].id);
+        assert!(state.toggle_split());
+        assert_eq!(state.split_session().map(|s| s.id), Some(second));
+
+        // Switching focus swaps the sessions along with their scroll offsets
+        state.chat_scroll = 5;
+        state.switch_split_focus();
+        assert_eq!(state.active_session().map(|s| s.id), Some(second));
+        let split = state.split.as_ref().unwrap();
+        assert_eq!((split.other, split.scroll, split.focus_right), (first, 5, true));
+        assert_eq!(state.chat_scroll, 0);
+
+        // Picking the other pane's session from the sidebar swaps back
+        state.select_session(0);
+        assert_eq!(state.active_session().map(|s| s.id), Some(first));
+        assert_eq!(state.split_session().map(|s| s.id), Some(second));
+        assert_eq!(state.chat_scroll, 5);
+
+        // Deleting one of the two sessions closes the split
+        state.delete_current_session();
+        assert!(state.split.is_none());
+        assert!(!state.toggle_split());
+    }
+
+    #[test]
+    fn test_compare_mode() {
+        let mut state = AppState::new(Config::default());
+        let model = state.current_model().to_string();
+        state.active_session_mut().unwrap().add_user_message("Hello");
+        assert!(state.start_compare(&model).is_none());
+
+        let compare = state.start_compare("mistral").unwrap();
+        assert_eq!(compare.model, "mistral");
+        assert_eq!(compare.messages.len(), 1);
+        let compare_id = compare.id;
+
+        // The active session keeps focus, with the copy beside it
+        assert_eq!(state.chat.active_session_idx, 0);
+        assert_eq!(state.chat.sessions.len(), 2);
+        assert_eq!(state.split.as_ref().map(|s| s.other), Some(compare_id));
+        assert_eq!(state.compare.as_ref().map(|c| c.source), state.active_session().map(|s| s.id));
+        assert!(!state.generating());
+
+        // The second model's history is trimmed to its window too
+        state.config.model.num_ctx = 80;
+        let compare = state.chat.session_mut(compare_id).unwrap();
+        compare.add_user_message("x".repeat(400));
+        compare.add_user_message("Latest");
+        let compare = state.chat.sessions.iter().find(|s| s.id == compare_id).unwrap();
+        let (messages, not_sent) = state.request_messages(compare, "mistral");
+        assert_eq!(not_sent, 2);
+        assert_eq!(messages.last().map(|m| m.content.as_str()), Some("Latest"));
+
+        assert!(state.stop_compare());
+        assert!(!state.stop_compare());
+        assert!(state.split.is_some());
+    }
+
+    #[test]
+    fn test_draft_token_estimate() {
+        let mut config = Config::default();
+        config.model.num_ctx = 4096;
+        let mut state = AppState::new(config);
+        state.new_session();
+        state.set_input("x".repeat(40));
+        assert_eq!(state.draft_tokens(), 10);
+
+        state.pending_attachments.push(Attachment { name: "a.txt".into(), content: "y".repeat(80) });
+        assert!(state.draft_tokens() > 30);
+
+        state.active_session_mut().unwrap().add_user_message("z".repeat(400));
+        assert_eq!(state.context_tokens(), state.draft_tokens() + 100);
+        assert_eq!(state.context_limit(), Some(4096));
+
+        assert_eq!(state.context_usage(), None);
+        let stats = ResponseStats { tokens: 24, prompt_tokens: 1000, ..Default::default() };
+        state.active_session_mut().unwrap().context_used = stats.context_used();
+        assert_eq!(state.context_usage(), Some(0.25));
+        assert_eq!(ResponseStats::default().context_used(), None);
+    }
+
+    #[test]
+    fn test_pull_target() {
+        let mut state = AppState::new(Config::default());
+        assert_eq!(state.pull_target().as_deref(), Some(POPULAR_MODELS[0]));
+
+        state.pull_input = " mistral:7b ".to_string();
+        assert_eq!(state.pull_target().as_deref(), Some("mistral:7b"));
+
+        state.downloads.push(Download::new("mistral:7b"));
+        assert!(state.is_pull_pending("mistral:7b"));
+        state.download_mut("mistral:7b").unwrap().status = DownloadStatus::Done;
+        assert!(!state.is_pull_pending("mistral:7b"));
+    }
+
+    #[test]
+    fn test_toggle_post_processor() {
+        let mut config = Config::default();
+        config.middleware.post_processors = vec![PostProcessor::TrimWhitespace];
+        let mut state = AppState::new(config);
+
+        assert!(state.toggle_post_processor(PostProcessor::StripThinking));
+        assert!(!state.toggle_post_processor(PostProcessor::TrimWhitespace));
+        assert_eq!(state.post_processors(), vec![PostProcessor::StripThinking]);
+        // The config default is untouched
+        assert_eq!(state.config.middleware.post_processors, vec![PostProcessor::TrimWhitespace]);
+    }
+
+    #[test]
+    fn test_scroll_acceleration() {
+        let mut accel = ScrollAccel::default();
+        let start = Instant::now();
+        let tick = Duration::from_millis(30);
+
+        // Held: speeds up progressively, up to the cap
+        let speeds: Vec<usize> = (0..100).map(|i| accel.step(start + tick * i, false)).collect();
+        assert_eq!(speeds[0], 1);
+        assert_eq!(speeds[SCROLL_EVENTS_PER_STEP as usize], 2);
+        assert_eq!(*speeds.last().unwrap(), MAX_SCROLL_MULTIPLIER);
+
+        // A pause or a change of direction resets to single steps
+        assert_eq!(accel.step(start + tick * 100 + Duration::from_secs(1), false), 1);
+        assert_eq!(accel.step(start + tick * 101 + Duration::from_secs(1), true), 1);
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut state = AppState::new(Config::default());
+        let session = state.active_session_mut().unwrap();
+        for i in 0..3 {
+            session.messages.push(Message::user(format!("message {}", i)));
+        }
+        // Three 10-line messages in a 10-line view
+        state.chat_viewport = ChatViewport {
+            message_starts: vec![0, 10, 20],
+            total_lines: 30,
+            visible_lines: 10,
+            ..Default::default()
+        };
+
+        state.chat_scroll = 10;
+        state.set_mark('a').unwrap();
+        state.chat_scroll = 0;
+        state.jump_to_mark('a').unwrap();
+        assert_eq!(state.chat_scroll, 10);
+        assert!(state.jump_to_mark('b').is_err());
+    }
+
+    #[test]
+    fn test_take_failed_turn() {
+        let mut session = ChatSession::new("Chat", "llama3");
+        session.add_user_message("Hello");
+        session.messages.push(Message::assistant("Hi!"));
+        assert!(session.take_failed_turn().is_none());
+
+        session.add_user_message("Summarize this");
+        session.messages.push(Message::assistant("[Error: connection reset]"));
+        let question = session.take_failed_turn().unwrap();
+        assert_eq!(question.content, "Summarize this");
+        assert_eq!(session.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_record_stats() {
+        let mut session = ChatSession::new("Poem", "llama3");
+        session.add_user_message("Write a poem");
+        session.start_assistant_response();
+        session.append_to_response("Roses are");
+        session.finish_response();
+        let stats = ResponseStats { tokens: 3, prompt_tokens: 40, first_token_ms: Some(850), ..Default::default() };
+        session.record_stats(&stats);
+
+        let last = session.messages.last().unwrap();
+        assert_eq!(last.first_token_ms, Some(850));
+        assert!(!last.truncated);
+        assert_eq!(session.context_used, Some(43));
+        let json = serde_json::to_string(last).unwrap();
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap().first_token_ms, Some(850));
+    }
+
+    #[test]
+    fn test_resume_truncated_response() {
+        let mut session = ChatSession::new("Poem", "llama3");
+        session.add_user_message("Write a poem");
+        session.start_assistant_response();
+        session.append_to_response("Roses are");
+        session.finish_response();
+        assert!(!session.resume_response());
+
+        session.mark_truncated();
+        assert!(session.resume_response());
+        session.append_to_response(" red");
+        session.finish_response();
+        let last = session.messages.last().unwrap();
+        assert_eq!(last.content, "Roses are red");
+        assert!(!last.truncated);
+        assert_eq!(session.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_abort_generation_restores_prompt() {
+        let mut state = AppState::new(Config::default());
+        let session = state.active_session_mut().unwrap();
+        session.add_user_message("Explain monads");
+        session.start_assistant_response();
+        session.append_to_response("A monad is");
+        state.streaming = true;
+        state.last_prompt = Some("Explain monads".to_string());
+
+        state.abort_generation();
+
+        assert!(!state.streaming);
+        assert_eq!(state.input, "Explain monads");
+        assert_eq!(state.input_mode, InputMode::Editing);
+        let last = state.active_session().unwrap().messages.last().unwrap();
+        assert!(last.interrupted);
+        assert!(!last.streaming);
+        assert_eq!(last.content, "A monad is");
+    }
+
     #[test]
     fn test_app_state_input() {
         let config = Config::default();
@@ -629,4 +4134,84 @@ mod tests {
         state.delete_char();
         assert_eq!(state.input, "h");
     }
+
+    #[test]
+    fn test_multiline_input_cursor() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        state.insert_str("fn main() {\n    é\n}");
+        assert_eq!(state.cursor_line_col(), (2, 1));
+
+        // Up keeps the column, clamped to the line
+        assert!(state.move_cursor_line(-1));
+        assert_eq!(state.cursor_line_col(), (1, 1));
+        state.move_cursor_line_end();
+        assert_eq!(state.cursor_line_col(), (1, 5));
+        state.delete_char();
+        state.insert_char('ü');
+        assert_eq!(state.input, "fn main() {\n    ü\n}");
+        assert!(state.move_cursor_line(1));
+        assert_eq!(state.cursor_line_col(), (2, 1));
+        assert!(!state.move_cursor_line(1));
+
+        state.move_cursor_line(-2);
+        state.move_cursor_line_start();
+        assert_eq!(state.cursor_position, 0);
+    }
+
+    #[test]
+    fn test_grapheme_editing() {
+        let mut state = AppState::new(Config::default());
+        // Decomposed é, a family emoji (ZWJ sequence) and a flag
+        state.insert_str("cafe\u{301} 👨\u{200d}👩\u{200d}👧 🇯🇵");
+
+        state.delete_char();
+        assert_eq!(state.input, "cafe\u{301} 👨\u{200d}👩\u{200d}👧 ");
+        state.move_cursor_left();
+        state.move_cursor_left();
+        assert_eq!(&state.input[state.cursor_position..], "👨\u{200d}👩\u{200d}👧 ");
+        state.delete_char_forward();
+        assert_eq!(state.input, "cafe\u{301}  ");
+
+        state.delete_char();
+        assert_eq!(state.input, "cafe\u{301} ");
+        assert_eq!(state.cursor_line_col(), (0, 4));
+
+        // Word motion keeps the accent with its letter
+        state.move_cursor_line_start();
+        state.move_cursor_word_right();
+        assert_eq!(&state.input[state.cursor_position..], " ");
+
+        // Vertical moves land on a cluster boundary by display column
+        state.set_input("日本語\nabcdef".to_string());
+        state.move_cursor_left();
+        state.move_cursor_line(-1);
+        assert_eq!(&state.input[state.cursor_position..], "語\nabcdef");
+
+        let mut query = "🇯🇵".to_string();
+        assert!(pop_grapheme(&mut query) && query.is_empty());
+        assert!(!pop_grapheme(&mut query));
+    }
+
+    #[test]
+    fn test_word_editing() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        state.insert_str("let café = foo_bar(1);  ");
+
+        state.delete_word_back();
+        assert_eq!(state.input, "let café = foo_bar(");
+        state.move_cursor_word_left();
+        assert_eq!(&state.input[state.cursor_position..], "foo_bar(");
+        state.move_cursor_word_left();
+        assert_eq!(&state.input[state.cursor_position..], "café = foo_bar(");
+        state.move_cursor_word_right();
+        assert_eq!(&state.input[state.cursor_position..], " = foo_bar(");
+
+        state.delete_word_forward();
+        assert_eq!(state.input, "let café(");
+        state.move_cursor_line_start();
+        state.delete_word_back();
+        assert_eq!(state.input, "let café(");
+    }
 }
@@ -0,0 +1,128 @@
+//! Response cache
+//!
+//! Remembers answers by a hash of the whole request: model, options and
+//! every message. Sending the exact same request again is answered from
+//! the cache without contacting the server. Only used when `[cache] enabled`.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ollama::ChatRequest;
+
+/// A cached answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub key: String,
+    pub model: String,
+    /// Last user message of the request, for the inspector
+    pub prompt: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub thinking: String,
+    pub created: DateTime<Utc>,
+    /// Times the entry has answered a request
+    #[serde(default)]
+    pub hits: u32,
+}
+
+impl CacheEntry {
+    pub fn is_expired(&self, ttl: Option<Duration>, now: DateTime<Utc>) -> bool {
+        ttl.is_some_and(|ttl| now - self.created > ttl)
+    }
+}
+
+/// Cached answers, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct ResponseCache {
+    pub entries: Vec<CacheEntry>,
+}
+
+impl ResponseCache {
+    /// Cache key of a request
+    ///
+    /// An FNV-1a hash of the request as sent, so any change to the model,
+    /// options or messages gives a different key.
+    pub fn key(request: &ChatRequest) -> String {
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+        let bytes = serde_json::to_vec(request).unwrap_or_default();
+        let hash = bytes
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(PRIME));
+        format!("{:016x}", hash)
+    }
+
+    /// Fresh entry for `key`, counting the hit
+    pub fn lookup(&mut self, key: &str, ttl: Option<Duration>, now: DateTime<Utc>) -> Option<&CacheEntry> {
+        let entry = self.entries.iter_mut().find(|e| e.key == key && !e.is_expired(ttl, now))?;
+        entry.hits += 1;
+        Some(entry)
+    }
+
+    /// Add or replace an entry, dropping the oldest beyond `max_entries`
+    pub fn insert(&mut self, entry: CacheEntry, max_entries: usize) {
+        self.entries.retain(|e| e.key != entry.key);
+        self.entries.push(entry);
+        let excess = self.entries.len().saturating_sub(max_entries);
+        self.entries.drain(..excess);
+    }
+
+    /// Drop expired entries, returning how many were removed
+    pub fn prune(&mut self, ttl: Option<Duration>, now: DateTime<Utc>) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|e| !e.is_expired(ttl, now));
+        before - self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ollama::{ChatMessage, GenerationOptions};
+
+    fn entry(key: &str, created: DateTime<Utc>) -> CacheEntry {
+        CacheEntry {
+            key: key.to_string(),
+            model: "llama3.2".to_string(),
+            prompt: "hi".to_string(),
+            content: "hello".to_string(),
+            thinking: String::new(),
+            created,
+            hits: 0,
+        }
+    }
+
+    #[test]
+    fn test_key_covers_model_options_and_messages() {
+        let request = ChatRequest::new("llama3.2", vec![ChatMessage::user("hi")]);
+        let key = ResponseCache::key(&request);
+        assert_eq!(key, ResponseCache::key(&request.clone()));
+
+        let other_model = ChatRequest::new("qwen3", vec![ChatMessage::user("hi")]);
+        let other_message = ChatRequest::new("llama3.2", vec![ChatMessage::user("hi!")]);
+        let other_options = request.clone().with_options(GenerationOptions {
+            temperature: Some(0.1),
+            ..Default::default()
+        });
+        for other in [other_model, other_message, other_options] {
+            assert_ne!(ResponseCache::key(&other), key);
+        }
+    }
+
+    #[test]
+    fn test_lookup_respects_ttl_and_capacity() {
+        let now = Utc::now();
+        let ttl = Some(Duration::hours(1));
+        let mut cache = ResponseCache::default();
+        cache.insert(entry("old", now - Duration::hours(2)), 2);
+        cache.insert(entry("new", now), 2);
+
+        assert!(cache.lookup("old", ttl, now).is_none());
+        assert!(cache.lookup("old", None, now).is_some());
+        assert_eq!(cache.lookup("new", ttl, now).unwrap().hits, 1);
+
+        cache.insert(entry("newer", now), 2);
+        let keys: Vec<_> = cache.entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["new", "newer"]);
+        assert_eq!(cache.prune(ttl, now + Duration::hours(2)), 2);
+    }
+}
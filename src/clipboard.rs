@@ -0,0 +1,58 @@
+//! System clipboard access
+//!
+//! Pipes text into the platform's clipboard tool when one is available, and
+//! otherwise falls back to the OSC 52 escape sequence, which most modern
+//! terminals (including over SSH) forward to the local clipboard.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::error::ClipboardError;
+
+/// Clipboard commands to try, in order
+const TOOLS: &[(&str, &[&str])] = &[
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("clip.exe", &[]),
+];
+
+/// Copy text to the system clipboard
+pub fn copy(text: &str) -> Result<(), ClipboardError> {
+    for (tool, args) in TOOLS {
+        if copy_with(tool, args, text).is_ok() {
+            return Ok(());
+        }
+    }
+    copy_osc52(text)
+}
+
+fn copy_with(tool: &str, args: &[&str], text: &str) -> std::io::Result<()> {
+    let mut child = Command::new(tool)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| std::io::Error::other("no stdin"))?
+        .write_all(text.as_bytes())?;
+
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("{} failed", tool)))
+    }
+}
+
+/// Copy via OSC 52 (terminal-mediated clipboard)
+fn copy_osc52(text: &str) -> Result<(), ClipboardError> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text)).map_err(ClipboardError::Io)?;
+    stdout.flush().map_err(ClipboardError::Io)
+}
@@ -0,0 +1,285 @@
+//! Slash commands
+//!
+//! Input starting with `/` is expanded into the message actually sent, e.g.
+//! `/gitdiff explain this` injects the working tree diff after the text.
+
+use std::process::Command;
+
+use crate::app::Rating;
+use crate::error::CommandError;
+use crate::middleware::PostProcessor;
+
+/// Cap on injected command output, to keep prompts within reason
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Default number of commits for `/gitlog`
+const DEFAULT_LOG_COUNT: usize = 10;
+
+/// Prompt used by `/commitmsg`
+const COMMIT_PROMPT: &str = "Write a concise git commit message for the following staged changes. \
+Use a short imperative subject line, a blank line, then a brief body if needed. \
+Reply with only the commit message.";
+
+/// Something the app should do once the response to a command completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowUp {
+    /// Copy the assistant's reply to the clipboard
+    CopyResponse,
+}
+
+/// What a command asks the app to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Send a message to the model
+    Send(Expanded),
+    /// Toggle a post-processor for the current session
+    TogglePostProcessor(PostProcessor),
+    /// Show the current session's post-processors
+    ShowPostProcessors,
+    /// Add or remove a tag on the current session
+    ToggleTag(String),
+    /// Write sessions out as a fine-tuning dataset
+    ExportDataset(DatasetScope),
+    /// Rate the last response, with an optional note
+    Rate(Rating, Option<String>),
+    /// Open the response cache inspector
+    ShowCache,
+}
+
+/// Which sessions `/dataset` exports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DatasetScope {
+    Current,
+    All,
+    Tagged(String),
+}
+
+/// A command expanded into message content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expanded {
+    pub content: String,
+    pub follow_up: Option<FollowUp>,
+    /// Let the model call tools before answering
+    pub agent: bool,
+}
+
+impl Expanded {
+    fn message(content: String) -> Self {
+        Self { content, follow_up: None, agent: false }
+    }
+}
+
+/// Command names and descriptions, for help
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("/gitdiff [text]", "Send the working tree diff"),
+    ("/gitlog [n] [text]", "Send the last n commits"),
+    ("/commitmsg", "Draft a commit message, copied when done"),
+    ("/post [name]", "Toggle a post-processor for this session"),
+    ("/tag <name>", "Toggle a tag on this session (used in exports)"),
+    ("/dataset [all|#tag]", "Export sessions as fine-tuning JSONL"),
+    ("/rate <+|-> [note]", "Rate the last response, with a note"),
+    ("/agent <prompt>", "Answer using tools (files, time)"),
+    ("/cache", "Inspect cached responses"),
+];
+
+/// Expand input into a command outcome
+///
+/// Returns `Ok(None)` when the input is not a command.
+pub fn expand(input: &str) -> Result<Option<Outcome>, CommandError> {
+    let Some(rest) = input.strip_prefix('/') else {
+        return Ok(None);
+    };
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let args = args.trim();
+
+    let expanded = match name {
+        "gitdiff" => {
+            let diff = git(&["diff", "HEAD"])?;
+            if diff.trim().is_empty() {
+                return Err(CommandError::Failed("No changes in the working tree".to_string()));
+            }
+            Expanded::message(with_block(args, "diff", &diff))
+        }
+        "gitlog" => {
+            let mut parts = args.splitn(2, char::is_whitespace);
+            let (count, text) = match parts.next().unwrap_or_default().parse::<usize>() {
+                Ok(n) => (n, parts.next().unwrap_or_default().trim()),
+                Err(_) => (DEFAULT_LOG_COUNT, args),
+            };
+            let count = count.to_string();
+            let log = git(&["log", "-n", &count, "--stat", "--format=commit %h%nAuthor: %an%nDate: %ad%n%n    %s%n"])?;
+            Expanded::message(with_block(text, "", &log))
+        }
+        "commitmsg" => {
+            let diff = git(&["diff", "--cached"])?;
+            if diff.trim().is_empty() {
+                return Err(CommandError::Failed("No staged changes (git add first)".to_string()));
+            }
+            Expanded {
+                content: with_block(COMMIT_PROMPT, "diff", &diff),
+                follow_up: Some(FollowUp::CopyResponse),
+                agent: false,
+            }
+        }
+        "agent" if args.is_empty() => {
+            return Err(CommandError::Failed("Usage: /agent <prompt>".to_string()));
+        }
+        "agent" => Expanded {
+            agent: true,
+            ..Expanded::message(args.to_string())
+        },
+        "post" if args.is_empty() => return Ok(Some(Outcome::ShowPostProcessors)),
+        "post" => {
+            let processor = PostProcessor::from_name(args).ok_or_else(|| {
+                let names: Vec<&str> = PostProcessor::ALL.iter().map(|p| p.name()).collect();
+                CommandError::Failed(format!("Unknown post-processor (expected one of: {})", names.join(", ")))
+            })?;
+            return Ok(Some(Outcome::TogglePostProcessor(processor)));
+        }
+        "tag" if args.is_empty() => {
+            return Err(CommandError::Failed("Usage: /tag <name>".to_string()));
+        }
+        "tag" => return Ok(Some(Outcome::ToggleTag(args.trim_start_matches('#').to_string()))),
+        "rate" => {
+            let (sign, note) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            let rating = match sign {
+                "+" => Rating::Up,
+                "-" => Rating::Down,
+                _ => return Err(CommandError::Failed("Usage: /rate <+|-> [note]".to_string())),
+            };
+            let note = Some(note.trim()).filter(|n| !n.is_empty()).map(str::to_string);
+            return Ok(Some(Outcome::Rate(rating, note)));
+        }
+        "cache" => return Ok(Some(Outcome::ShowCache)),
+        "dataset" => {
+            let scope = match args {
+                "" => DatasetScope::Current,
+                "all" => DatasetScope::All,
+                tag => match tag.strip_prefix('#') {
+                    Some(tag) if !tag.is_empty() => DatasetScope::Tagged(tag.to_string()),
+                    _ => return Err(CommandError::Failed("Usage: /dataset [all|#tag]".to_string())),
+                },
+            };
+            return Ok(Some(Outcome::ExportDataset(scope)));
+        }
+        _ => return Err(CommandError::Unknown(name.to_string())),
+    };
+
+    Ok(Some(Outcome::Send(expanded)))
+}
+
+/// Run git in the current directory and capture stdout
+fn git(args: &[&str]) -> Result<String, CommandError> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| CommandError::Failed(format!("Failed to run git: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(CommandError::Failed(format!("git {}: {}", args[0], stderr.trim())));
+    }
+
+    Ok(truncate(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_OUTPUT_BYTES {
+        let mut end = MAX_OUTPUT_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[... truncated]");
+    }
+    text
+}
+
+/// Combine user text with a fenced block of command output
+fn with_block(text: &str, lang: &str, body: &str) -> String {
+    let block = format!("```{}\n{}\n```", lang, body.trim_end());
+    if text.is_empty() {
+        block
+    } else {
+        format!("{}\n\n{}", text, block)
+    }
+}
+
+/// Strip a surrounding code fence, if the whole reply is one block
+pub fn unfence(text: &str) -> &str {
+    let trimmed = text.trim();
+    trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.split_once('\n'))
+        .and_then(|(_, body)| body.trim_end().strip_suffix("```"))
+        .map(str::trim)
+        .unwrap_or(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_input_is_not_a_command() {
+        assert_eq!(expand("hello").unwrap(), None);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        assert!(matches!(expand("/nope"), Err(CommandError::Unknown(name)) if name == "nope"));
+    }
+
+    #[test]
+    fn test_post_command() {
+        assert_eq!(
+            expand("/post strip_thinking").unwrap(),
+            Some(Outcome::TogglePostProcessor(PostProcessor::StripThinking))
+        );
+        assert_eq!(expand("/post").unwrap(), Some(Outcome::ShowPostProcessors));
+        assert!(matches!(expand("/post nope"), Err(CommandError::Failed(_))));
+    }
+
+    #[test]
+    fn test_tag_command() {
+        assert_eq!(expand("/tag #rust").unwrap(), Some(Outcome::ToggleTag("rust".to_string())));
+        assert!(matches!(expand("/tag"), Err(CommandError::Failed(_))));
+    }
+
+    #[test]
+    fn test_dataset_command() {
+        assert_eq!(expand("/dataset").unwrap(), Some(Outcome::ExportDataset(DatasetScope::Current)));
+        assert_eq!(expand("/dataset all").unwrap(), Some(Outcome::ExportDataset(DatasetScope::All)));
+        assert_eq!(
+            expand("/dataset #good").unwrap(),
+            Some(Outcome::ExportDataset(DatasetScope::Tagged("good".to_string())))
+        );
+        assert!(matches!(expand("/dataset some"), Err(CommandError::Failed(_))));
+    }
+
+    #[test]
+    fn test_rate_command() {
+        assert_eq!(
+            expand("/rate - made up an API").unwrap(),
+            Some(Outcome::Rate(Rating::Down, Some("made up an API".to_string())))
+        );
+        assert_eq!(expand("/rate +").unwrap(), Some(Outcome::Rate(Rating::Up, None)));
+        assert!(matches!(expand("/rate great"), Err(CommandError::Failed(_))));
+    }
+
+    #[test]
+    fn test_agent_command() {
+        let Some(Outcome::Send(expanded)) = expand("/agent what's in src?").unwrap() else {
+            panic!("expected a message");
+        };
+        assert!(expanded.agent);
+        assert_eq!(expanded.content, "what's in src?");
+        assert!(matches!(expand("/agent"), Err(CommandError::Failed(_))));
+    }
+
+    #[test]
+    fn test_unfence() {
+        assert_eq!(unfence("```\nfix: typo\n```"), "fix: typo");
+        assert_eq!(unfence("fix: typo\n"), "fix: typo");
+    }
+}
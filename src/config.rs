@@ -3,9 +3,13 @@
 //! Handles loading and saving config from `~/.config/ratatalk/config.toml`
 
 use crate::error::ConfigError;
-use directories::ProjectDirs;
+use crate::middleware::PostProcessor;
+use crate::ollama::GenerationOptions;
+use crate::tools::Tool;
+use directories::{BaseDirs, ProjectDirs};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -25,6 +29,366 @@ pub struct Config {
     /// Keybinding overrides (future use)
     #[serde(default)]
     pub keybindings: KeybindingsConfig,
+
+    /// Hosted chat providers (OpenAI, Anthropic)
+    #[serde(default)]
+    pub providers: ProvidersConfig,
+
+    /// Message processing pipeline
+    #[serde(default)]
+    pub middleware: MiddlewareConfig,
+
+    /// Markdown export settings
+    #[serde(default)]
+    pub export: ExportConfig,
+
+    /// Automatic pruning of old sessions
+    #[serde(default)]
+    pub retention: RetentionConfig,
+
+    /// One-key follow-up prompts
+    #[serde(default)]
+    pub reactions: ReactionsConfig,
+
+    /// Tool use in `/agent` runs
+    #[serde(default)]
+    pub agent: AgentConfig,
+
+    /// System prompt added for remote models
+    #[serde(default)]
+    pub guardrail: GuardrailConfig,
+
+    /// Anonymized exports
+    #[serde(default)]
+    pub redact: RedactConfig,
+
+    /// Answers reused for identical requests
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    /// Risky features, all off by default
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
+
+    /// Checks for new releases
+    #[serde(default)]
+    pub update: UpdateConfig,
+
+    /// Webhook pinged when a long generation finishes
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    /// Cap on message content kept in memory
+    #[serde(default)]
+    pub memory: MemoryConfig,
+
+    /// Where sessions are kept
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Shared library of named system prompts
+    #[serde(default)]
+    pub prompts: PromptsConfig,
+}
+
+/// Session storage configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// Backend that loads and saves sessions
+    #[serde(default)]
+    pub backend: StorageBackend,
+}
+
+/// Session storage backend; see `storage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// `sessions.json` in the data directory, with checksums and backups
+    #[default]
+    Json,
+    /// Nothing written to disk; sessions last until ratatalk exits
+    Memory,
+}
+
+/// Memory cap for loaded sessions
+///
+/// Off unless `max_mb` is set. Above the cap, the messages of inactive
+/// sessions are dropped from memory and read back from disk when needed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    /// Approximate megabytes of message content to keep loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_mb: Option<usize>,
+}
+
+impl MemoryConfig {
+    /// The cap in bytes, if one is set
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_mb.map(|mb| mb.saturating_mul(1024 * 1024))
+    }
+}
+
+/// System prompt library
+///
+/// The prompts themselves live in `prompts.toml` next to this file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptsConfig {
+    /// When a library prompt is edited, rewrite it in every session using it
+    #[serde(default)]
+    pub propagate: bool,
+}
+
+/// Update check configuration
+///
+/// Off by default, so packaged builds never phone home unless asked to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// Look for newer releases in the background
+    #[serde(default)]
+    pub check: bool,
+
+    /// Which releases count as updates
+    #[serde(default)]
+    pub channel: UpdateChannel,
+
+    /// Hours between checks; results are cached on disk in between
+    #[serde(default = "default_update_interval")]
+    pub interval_hours: u64,
+}
+
+fn default_update_interval() -> u64 {
+    24
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            check: false,
+            channel: UpdateChannel::default(),
+            interval_hours: default_update_interval(),
+        }
+    }
+}
+
+/// Completion notification configuration
+///
+/// The webhook sends nothing unless `webhook_url` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifyConfig {
+    /// URL the JSON payload is POSTed to (ntfy, Slack-compatible)
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+
+    /// ntfy topic; adds the fields ntfy's JSON publishing expects
+    #[serde(default)]
+    pub topic: Option<String>,
+
+    /// Only generations that ran at least this long trigger the webhook
+    #[serde(default = "default_notify_min_secs")]
+    pub min_secs: u64,
+
+    /// When a finished response rings the terminal bell
+    #[serde(default)]
+    pub bell: AlertWhen,
+
+    /// Also show a desktop notification when the bell rings
+    #[serde(default)]
+    pub desktop: bool,
+}
+
+/// When a finished response is announced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertWhen {
+    Never,
+    /// Only while the terminal doesn't have focus
+    #[default]
+    Unfocused,
+    Always,
+}
+
+fn default_notify_min_secs() -> u64 {
+    60
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            topic: None,
+            min_secs: default_notify_min_secs(),
+            bell: AlertWhen::default(),
+            desktop: false,
+        }
+    }
+}
+
+/// Release channel for update checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// Full releases only
+    #[default]
+    Stable,
+    /// Pre-releases as well
+    Prerelease,
+}
+
+/// Capability gate configuration
+///
+/// Checked through `permissions::check`; see that module.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PermissionsConfig {
+    /// Run shell commands
+    #[serde(default)]
+    pub allow_shell: bool,
+
+    /// Let tools make network requests
+    #[serde(default)]
+    pub allow_network_tools: bool,
+
+    /// Let agent tools read files and list directories
+    #[serde(default)]
+    pub allow_file_read: bool,
+
+    /// Write files, e.g. applying a diff from a response
+    #[serde(default)]
+    pub allow_file_write: bool,
+}
+
+/// Response cache configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Seconds an answer stays usable (0 = forever)
+    #[serde(default = "default_cache_ttl")]
+    pub ttl_secs: u64,
+
+    /// Answers kept; the oldest are dropped first
+    #[serde(default = "default_cache_entries")]
+    pub max_entries: usize,
+}
+
+fn default_cache_ttl() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_cache_entries() -> usize {
+    500
+}
+
+impl CacheConfig {
+    /// How long entries stay usable, or `None` if they never expire
+    pub fn ttl(&self) -> Option<chrono::Duration> {
+        (self.ttl_secs > 0).then(|| chrono::Duration::seconds(self.ttl_secs.min(i64::MAX as u64) as i64))
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_cache_ttl(),
+            max_entries: default_cache_entries(),
+        }
+    }
+}
+
+/// Rules for the anonymized export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactConfig {
+    /// Replace email addresses and user names in home directory paths
+    #[serde(default = "default_true")]
+    pub builtin: bool,
+
+    /// Names replaced with `[name]`, matched as whole words ignoring case
+    #[serde(default)]
+    pub names: Vec<String>,
+
+    /// Extra regex rules, applied in order after the others
+    #[serde(default)]
+    pub rules: Vec<RedactRule>,
+
+    /// Model for the optional anonymization pass (default: the session's model)
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            builtin: true,
+            names: Vec::new(),
+            rules: Vec::new(),
+            model: None,
+        }
+    }
+}
+
+/// A regex and what its matches become
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactRule {
+    pub pattern: String,
+
+    /// Replacement text; `$1` and `${name}` refer to capture groups
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+fn default_replacement() -> String {
+    "[redacted]".to_string()
+}
+
+/// Guardrail prepended to conversations with remote or hosted models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardrailConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    #[serde(default = "default_guardrail_prompt")]
+    pub prompt: String,
+}
+
+fn default_guardrail_prompt() -> String {
+    "This conversation is processed by a remote service. Do not ask for passwords, API keys, \
+or other secrets, and point out when the user appears to be sharing personal or confidential data."
+        .to_string()
+}
+
+impl Default for GuardrailConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            prompt: default_guardrail_prompt(),
+        }
+    }
+}
+
+/// Follow-up prompts sent by a single key after a response, keyed by that key
+///
+/// Setting `[reactions]` replaces the defaults; keys taken by built-in
+/// bindings are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReactionsConfig(pub BTreeMap<String, String>);
+
+impl ReactionsConfig {
+    /// Prompt bound to `key`, if any
+    pub fn prompt(&self, key: char) -> Option<&str> {
+        self.0.get(key.encode_utf8(&mut [0; 4])).map(String::as_str)
+    }
+}
+
+impl Default for ReactionsConfig {
+    fn default() -> Self {
+        Self(BTreeMap::from([
+            ("e".to_string(), "Explain that in more detail.".to_string()),
+            ("s".to_string(), "Make that shorter.".to_string()),
+            ("x".to_string(), "Give me a concrete example.".to_string()),
+        ]))
+    }
 }
 
 /// Ollama server configuration
@@ -37,6 +401,30 @@ pub struct ServerConfig {
     /// Connection timeout in seconds
     #[serde(default = "default_timeout")]
     pub timeout_secs: u64,
+
+    /// Seconds between connectivity checks (0 = only at startup)
+    #[serde(default = "default_health_check_secs")]
+    pub health_check_secs: u64,
+
+    /// Treat the server as remote/untrusted (unset: remote unless the host is loopback)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<bool>,
+}
+
+impl ServerConfig {
+    /// Whether conversations with this server leave the machine
+    pub fn is_remote(&self) -> bool {
+        self.remote.unwrap_or_else(|| {
+            let Ok(url) = reqwest::Url::parse(&self.host) else {
+                return true;
+            };
+            match url.host_str().map(|h| h.trim_matches(['[', ']'])) {
+                Some(host) => host != "localhost" && !host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback()),
+                // Unix sockets are always local
+                None => url.scheme() != "unix",
+            }
+        })
+    }
 }
 
 fn default_host() -> String {
@@ -47,11 +435,17 @@ fn default_timeout() -> u64 {
     30
 }
 
+fn default_health_check_secs() -> u64 {
+    15
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             host: default_host(),
             timeout_secs: default_timeout(),
+            health_check_secs: default_health_check_secs(),
+            remote: None,
         }
     }
 }
@@ -82,6 +476,73 @@ pub struct ModelConfig {
     /// Context window size (0 = model default)
     #[serde(default)]
     pub num_ctx: u32,
+
+    /// Reasoning for thinking models (unset = model default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+
+    /// Additional Ollama options (mirostat, num_gpu, min_p, ...), passed through as-is
+    #[serde(default)]
+    pub options: GenerationOptions,
+
+    /// Ask the model to title new sessions after the first exchange
+    #[serde(default = "default_true")]
+    pub auto_title: bool,
+
+    /// Option overrides for models matching a name pattern, applied in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<OptionProfile>,
+}
+
+/// Generation options for a family of models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionProfile {
+    /// Model name pattern; `*` matches any run of characters, `?` one character
+    #[serde(rename = "match")]
+    pub pattern: String,
+
+    /// Options overriding the `[model]` defaults for matching models
+    #[serde(flatten)]
+    pub options: GenerationOptions,
+}
+
+impl OptionProfile {
+    /// Whether this profile applies to `model`
+    pub fn matches(&self, model: &str) -> bool {
+        glob_match(&self.pattern, model)
+    }
+}
+
+/// Match `text` against a pattern with `*` and `?` wildcards
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*`, and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((after_star, tried)) => {
+                    p = after_star;
+                    t = tried + 1;
+                    backtrack = Some((after_star, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 fn default_model() -> String {
@@ -109,6 +570,10 @@ impl Default for ModelConfig {
             top_p: default_top_p(),
             max_tokens: 0,
             num_ctx: 0,
+            think: None,
+            options: GenerationOptions::default(),
+            auto_title: true,
+            profiles: Vec::new(),
         }
     }
 }
@@ -120,14 +585,30 @@ pub struct UiConfig {
     #[serde(default = "default_true")]
     pub show_timestamps: bool,
 
+    /// Clock times or how long ago, for the timestamps in chat
+    #[serde(default)]
+    pub timestamp_style: TimestampStyle,
+
     /// Show token count in status bar
     #[serde(default = "default_true")]
     pub show_token_count: bool,
 
+    /// Show the temperature and seed behind each response in its header
+    #[serde(default)]
+    pub show_settings: bool,
+
     /// Sidebar width in characters
     #[serde(default = "default_sidebar_width")]
     pub sidebar_width: u16,
 
+    /// Show sessions as tabs across the top instead of in the sidebar
+    #[serde(default)]
+    pub tabs: bool,
+
+    /// Widest the chat gets in zen mode, in columns (0 = the whole window)
+    #[serde(default = "default_zen_width")]
+    pub zen_width: u16,
+
     /// Enable mouse support
     #[serde(default = "default_true")]
     pub mouse_support: bool,
@@ -135,6 +616,68 @@ pub struct UiConfig {
     /// Tick rate in milliseconds
     #[serde(default = "default_tick_rate")]
     pub tick_rate_ms: u64,
+
+    /// Frames drawn per second at most
+    #[serde(default = "default_max_fps")]
+    pub max_fps: u32,
+
+    /// Graphics protocol for inline image previews
+    #[serde(default)]
+    pub image_protocol: ImageProtocolSetting,
+
+    /// Height of inline image previews in rows
+    #[serde(default = "default_image_preview_rows")]
+    pub image_preview_rows: u16,
+
+    /// Speed up scrolling while j/k or the wheel is held
+    #[serde(default = "default_true")]
+    pub scroll_acceleration: bool,
+
+    /// Copied snippets kept for the yank history popup
+    #[serde(default = "default_yank_history")]
+    pub yank_history: usize,
+
+    /// Lines the input box grows to before it scrolls
+    #[serde(default = "default_input_max_lines")]
+    pub input_max_lines: u16,
+
+    /// Let Chinese and Japanese text wrap between any two characters
+    #[serde(default = "default_true")]
+    pub wrap_cjk: bool,
+
+    /// Interface language, e.g. "en" or "es"
+    #[serde(default = "default_language")]
+    pub language: String,
+
+    /// Color theme: a preset such as "dark" or "light", or a file in the
+    /// config dir's `themes/`
+    #[serde(default = "default_theme")]
+    pub theme: String,
+}
+
+/// How message timestamps are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampStyle {
+    /// Time of day, e.g. `14:05`
+    #[default]
+    Clock,
+    /// Time since, e.g. `5m ago`, updated as it passes
+    Relative,
+}
+
+/// Graphics protocol selection for image previews
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageProtocolSetting {
+    /// Detect from the terminal environment
+    #[default]
+    Auto,
+    Kitty,
+    Iterm2,
+    Sixel,
+    /// Always use text placeholders
+    None,
 }
 
 fn default_true() -> bool {
@@ -145,28 +688,273 @@ fn default_sidebar_width() -> u16 {
     30
 }
 
+fn default_zen_width() -> u16 {
+    100
+}
+
 fn default_tick_rate() -> u64 {
     100
 }
 
+fn default_max_fps() -> u32 {
+    30
+}
+
+fn default_image_preview_rows() -> u16 {
+    8
+}
+
+fn default_yank_history() -> usize {
+    20
+}
+
+fn default_input_max_lines() -> u16 {
+    8
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_theme() -> String {
+    "dark".to_string()
+}
+
 impl Default for UiConfig {
     fn default() -> Self {
         Self {
             show_timestamps: true,
+            timestamp_style: TimestampStyle::default(),
             show_token_count: true,
+            show_settings: false,
             sidebar_width: default_sidebar_width(),
+            tabs: false,
+            zen_width: default_zen_width(),
             mouse_support: true,
             tick_rate_ms: default_tick_rate(),
+            max_fps: default_max_fps(),
+            image_protocol: ImageProtocolSetting::default(),
+            image_preview_rows: default_image_preview_rows(),
+            scroll_acceleration: true,
+            yank_history: default_yank_history(),
+            input_max_lines: default_input_max_lines(),
+            wrap_cjk: true,
+            language: default_language(),
+            theme: default_theme(),
         }
     }
 }
 
-/// Keybindings configuration (extensible for future use)
+/// Keybindings configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct KeybindingsConfig {
     /// Vim-mode enabled
     #[serde(default)]
     pub vim_mode: bool,
+
+    /// Keys for chat actions by action name, replacing the defaults
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeyList>,
+}
+
+/// One key binding or several
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            KeyList::One(key) => std::slice::from_ref(key),
+            KeyList::Many(keys) => keys,
+        }
+    }
+}
+
+/// Hosted provider configuration
+///
+/// A provider is enabled when its section is present and an API key is
+/// available, either here or via `OPENAI_API_KEY` / `ANTHROPIC_API_KEY`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ProvidersConfig {
+    /// OpenAI (or OpenAI-compatible) endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openai: Option<HostedProviderConfig>,
+
+    /// Anthropic endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anthropic: Option<HostedProviderConfig>,
+}
+
+/// Message processing pipeline configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MiddlewareConfig {
+    /// Processors applied to completed assistant messages (sessions may override)
+    #[serde(default)]
+    pub post_processors: Vec<PostProcessor>,
+}
+
+/// Agent mode configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentConfig {
+    /// Rounds of tool calls allowed before the model must answer
+    #[serde(default = "default_max_steps")]
+    pub max_steps: u32,
+
+    /// Tools offered to the model
+    #[serde(default = "default_tools")]
+    pub tools: Vec<Tool>,
+}
+
+fn default_max_steps() -> u32 {
+    5
+}
+
+fn default_tools() -> Vec<Tool> {
+    Tool::ALL.to_vec()
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            max_steps: default_max_steps(),
+            tools: default_tools(),
+        }
+    }
+}
+
+/// Markdown export configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportConfig {
+    /// Directory for exported files (default: `exports/` in the data directory)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+
+    /// Start exports with YAML front matter
+    #[serde(default = "default_true")]
+    pub front_matter: bool,
+
+    /// Record the system prompt and generation settings in the front matter
+    #[serde(default)]
+    pub include_settings: bool,
+
+    /// Obsidian vault (or any notes folder) to export sessions into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault: Option<PathBuf>,
+
+    /// Re-export the session to the vault after each response
+    #[serde(default)]
+    pub vault_auto_export: bool,
+
+    /// Leave flagged exchanges out of `/dataset` exports
+    #[serde(default = "default_true")]
+    pub dataset_exclude_flagged: bool,
+}
+
+impl ExportConfig {
+    /// Export directory, with `~` expanded
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.dir.as_deref().map(expand_home)
+    }
+
+    /// Vault directory, with `~` expanded
+    pub fn vault(&self) -> Option<PathBuf> {
+        self.vault.as_deref().map(expand_home)
+    }
+}
+
+/// Expand a leading `~` to the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), BaseDirs::new()) {
+        (Ok(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            front_matter: true,
+            include_settings: false,
+            vault: None,
+            vault_auto_export: false,
+            dataset_exclude_flagged: true,
+        }
+    }
+}
+
+/// Session retention policy, applied at startup
+///
+/// Pruning is off unless `max_age_days` or `max_sessions` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Prune sessions not updated for this many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+
+    /// Keep at most this many sessions (most recently updated first)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_sessions: Option<usize>,
+
+    /// Export sessions to the archive before pruning them
+    #[serde(default = "default_true")]
+    pub archive: bool,
+
+    /// Archive file format
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+
+    /// Archive directory (default: `archive/` in the data directory)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_dir: Option<PathBuf>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_sessions: None,
+            archive: true,
+            archive_format: ArchiveFormat::default(),
+            archive_dir: None,
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Archive directory, with `~` expanded
+    pub fn archive_dir(&self) -> Option<PathBuf> {
+        self.archive_dir.as_deref().map(expand_home)
+    }
+}
+
+/// File format for archived sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// Settings for a single hosted provider
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HostedProviderConfig {
+    /// API key (falls back to the provider's environment variable)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+
+    /// Override the API base URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+
+    /// Models to offer (empty = fetch from the provider)
+    #[serde(default)]
+    pub models: Vec<String>,
 }
 
 impl Config {
@@ -225,6 +1013,24 @@ impl Config {
         
         Ok(())
     }
+
+    /// Whether `model` is served from outside this machine
+    ///
+    /// Models of configured hosted providers always are; everything else is
+    /// routed to the Ollama server.
+    pub fn is_remote_model(&self, model: &str) -> bool {
+        let hosted = match model.split_once('/') {
+            Some(("openai", _)) => self.providers.openai.is_some(),
+            Some(("anthropic", _)) => self.providers.anthropic.is_some(),
+            _ => false,
+        };
+        hosted || self.server.is_remote()
+    }
+
+    /// Guardrail prompt to prepend for `model`, if any
+    pub fn guardrail_for(&self, model: &str) -> Option<&str> {
+        (self.guardrail.enabled && self.is_remote_model(model)).then_some(self.guardrail.prompt.as_str())
+    }
 }
 
 #[cfg(test)]
@@ -246,4 +1052,104 @@ mod tests {
         let parsed: Config = toml::from_str(&toml_str).unwrap();
         assert_eq!(config.server.host, parsed.server.host);
     }
+
+    #[test]
+    fn test_option_profiles() {
+        let toml_str = r#"
+            [[model.profiles]]
+            match = "qwen*"
+            temperature = 0.6
+            stop = ["<|im_end|>"]
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let profile = &config.model.profiles[0];
+        assert_eq!(profile.options.temperature, Some(0.6));
+        assert!(profile.matches("qwen2.5-coder:7b"));
+        assert!(!profile.matches("llama3.2:latest"));
+
+        assert!(glob_match("*gpt-4?", "openai/gpt-4o"));
+        assert!(glob_match("llama*:*b", "llama3.1:8b"));
+        assert!(!glob_match("llama*:*b", "llama3.1:latest"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_providers_section_parses() {
+        let toml_str = r#"
+            [providers.openai]
+            models = ["gpt-4o-mini"]
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let openai = config.providers.openai.unwrap();
+        assert_eq!(openai.models, vec!["gpt-4o-mini"]);
+        assert!(openai.api_key.is_none());
+        assert!(config.providers.anthropic.is_none());
+    }
+
+    #[test]
+    fn test_model_options_parse() {
+        let toml_str = r#"
+            [model.options]
+            mirostat = 2
+            mirostat_tau = 4.0
+            num_gpu = 0
+            penalize_newline = false
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let options = &config.model.options;
+        assert_eq!(options.mirostat, Some(2));
+        assert_eq!(options.mirostat_tau, Some(4.0));
+        assert_eq!(options.num_gpu, Some(0));
+        assert_eq!(options.penalize_newline, Some(false));
+        assert_eq!(config.model.temperature, 0.7);
+    }
+
+    #[test]
+    fn test_export_paths_expand_home() {
+        let toml_str = r#"
+            [export]
+            vault = "~/notes"
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let vault = config.export.vault().unwrap();
+        assert!(vault.is_absolute());
+        assert!(vault.ends_with("notes"));
+        assert!(config.export.dir().is_none());
+    }
+
+    #[test]
+    fn test_remote_detection() {
+        let mut config = Config::default();
+        assert!(!config.server.is_remote());
+        assert_eq!(config.guardrail_for("llama3.2"), None);
+
+        config.server.host = "http://[::1]:11434".to_string();
+        assert!(!config.server.is_remote());
+        config.server.host = "unix:///run/ollama.sock".to_string();
+        assert!(!config.server.is_remote());
+        config.server.host = "https://gpu-box.example.com".to_string();
+        assert!(config.server.is_remote());
+        config.server.remote = Some(false);
+        assert!(!config.server.is_remote());
+
+        config.providers.openai = Some(HostedProviderConfig::default());
+        assert!(config.is_remote_model("openai/gpt-4o"));
+        assert!(!config.is_remote_model("hf.co/org/model"));
+        assert!(config.guardrail_for("openai/gpt-4o").is_some());
+        config.guardrail.enabled = false;
+        assert_eq!(config.guardrail_for("openai/gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_middleware_section_parses() {
+        let toml_str = r#"
+            [middleware]
+            post_processors = ["strip_thinking", "collapse_blank_lines"]
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.middleware.post_processors,
+            vec![PostProcessor::StripThinking, PostProcessor::CollapseBlankLines]
+        );
+    }
 }
@@ -0,0 +1,286 @@
+//! Test control socket
+//!
+//! With the hidden `--test-control <addr>` flag, ratatalk listens on a
+//! loopback TCP address for newline-delimited JSON commands, so end-to-end
+//! tests and expect-style scripts can drive the TUI without a terminal
+//! emulator in the loop. The script picks a secret and starts ratatalk with
+//! it in `RATATALK_CONTROL_TOKEN`; a client's first line must present it,
+//! so other local users and web pages posting to the port can't drive the app:
+//!
+//! ```text
+//! {"cmd": "auth", "token": "..."}      first line, always
+//! {"cmd": "key", "key": "ctrl+n"}      press one key
+//! {"cmd": "type", "text": "hello"}     type characters
+//! {"cmd": "snapshot"}                  dump the app state
+//! ```
+//!
+//! Every command gets one JSON line back, `{"ok": true, ...}` or
+//! `{"ok": false, "error": "..."}`. Key commands are answered once the
+//! main loop has handled the keys, so a following snapshot sees their effect.
+//! A wrong token or a line that isn't a command closes the connection.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+use crate::app::AppState;
+use crate::ollama::Role;
+
+/// A command from a control client
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    /// Present the secret; must be the first command
+    Auth { token: String },
+    /// Press a key, e.g. `"enter"`, `"ctrl+c"`, `"shift+tab"`, `"a"`
+    Key { key: String },
+    /// Type each character of `text`
+    Type { text: String },
+    /// Report the app state
+    Snapshot,
+}
+
+/// Environment variable holding the secret clients must present
+pub const TOKEN_VAR: &str = "RATATALK_CONTROL_TOKEN";
+
+/// What the main loop should do for a command
+pub enum Request {
+    /// Feed these events through normal input handling, then reply
+    Events(Vec<Event>, oneshot::Sender<Value>),
+    /// Reply with a snapshot of the state
+    Snapshot(oneshot::Sender<Value>),
+}
+
+/// Parse a key description such as `ctrl+alt+x`, `esc` or `F1`
+pub fn parse_key(spec: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = spec.split('+').collect();
+    // "ctrl++" ends in an empty part: the key is '+'
+    let key = match parts.pop() {
+        Some("") if spec.ends_with('+') => {
+            parts.pop();
+            "+"
+        }
+        Some(key) => key,
+        None => return Err("empty key".to_string()),
+    };
+    for part in parts {
+        modifiers |= match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            other => return Err(format!("unknown modifier: {}", other)),
+        };
+    }
+    let code = match key.to_ascii_lowercase().as_str() {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        lower => match (lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()), key.chars().count()) {
+            (Some(n), _) if (1..=12).contains(&n) => KeyCode::F(n),
+            (_, 1) => {
+                let c = key.chars().next().unwrap_or_default();
+                if c.is_uppercase() {
+                    modifiers |= KeyModifiers::SHIFT;
+                }
+                KeyCode::Char(c)
+            }
+            _ => return Err(format!("unknown key: {}", key)),
+        },
+    };
+    Ok(KeyEvent::new(code, modifiers))
+}
+
+/// Events for typing `text`, one key per character
+fn type_events(text: &str) -> Vec<Event> {
+    text.chars()
+        .map(|c| {
+            let modifiers = if c.is_uppercase() { KeyModifiers::SHIFT } else { KeyModifiers::NONE };
+            Event::Key(KeyEvent::new(KeyCode::Char(c), modifiers))
+        })
+        .collect()
+}
+
+/// The parts of the app state a test can check
+pub fn snapshot(state: &AppState) -> Value {
+    let role = |role: Role| match role {
+        Role::System => "system",
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::Tool => "tool",
+    };
+    let messages: Vec<Value> = state
+        .active_session()
+        .map(|s| {
+            s.messages
+                .iter()
+                .map(|m| json!({ "role": role(m.role), "content": m.content, "streaming": m.streaming }))
+                .collect()
+        })
+        .unwrap_or_default();
+    json!({
+        "ok": true,
+        "mode": format!("{:?}", state.input_mode),
+        "input": state.input,
+        "cursor": state.cursor_position,
+        "streaming": state.streaming,
+        "model": state.current_model(),
+        "models": state.chat.models.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+        "session": state.active_session().map(|s| s.name.as_str()),
+        "sessions": state.chat.sessions.len(),
+        "messages": messages,
+        "notices": state.notices.iter().map(|n| n.text.as_str()).collect::<Vec<_>>(),
+        "server_connected": state.server_connected,
+        "quitting": state.should_quit,
+    })
+}
+
+/// Listen for control clients on `addr`, which must be a loopback address;
+/// clients must first present `token`
+pub async fn listen(addr: SocketAddr, token: String, tx: mpsc::Sender<Request>) -> std::io::Result<()> {
+    if !addr.ip().is_loopback() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("--test-control only binds to loopback addresses, not {}", addr.ip()),
+        ));
+    }
+    let listener = TcpListener::bind(addr).await?;
+    info!("Test control listening on {}", listener.local_addr()?);
+    let token: Arc<str> = token.into();
+    tokio::spawn(async move {
+        while let Ok((stream, peer)) = listener.accept().await {
+            info!("Test control client connected from {}", peer);
+            tokio::spawn(serve(stream, token.clone(), tx.clone()));
+        }
+    });
+    Ok(())
+}
+
+/// Answer one client's commands until it disconnects, or until it sends
+/// something other than a command or starts without the token
+async fn serve(stream: TcpStream, token: Arc<str>, tx: mpsc::Sender<Request>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut authorized = false;
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (reply, keep_open) = match (serde_json::from_str::<Command>(&line), authorized) {
+            (Err(e), _) => (Err(format!("bad command: {}", e)), false),
+            (Ok(Command::Auth { token: given }), false) if *given == *token => {
+                authorized = true;
+                (Ok(json!({ "ok": true })), true)
+            }
+            (Ok(_), false) => (Err("not authorized".to_string()), false),
+            (Ok(command), true) => (handle(command, &tx).await, true),
+        };
+        let reply = reply.unwrap_or_else(|error| json!({ "ok": false, "error": error }));
+        let mut out = reply.to_string();
+        out.push('\n');
+        if let Err(e) = write.write_all(out.as_bytes()).await {
+            warn!("Test control write failed: {}", e);
+            return;
+        }
+        if !keep_open {
+            warn!("Test control client dropped: {}", reply["error"]);
+            return;
+        }
+    }
+}
+
+async fn handle(command: Command, tx: &mpsc::Sender<Request>) -> Result<Value, String> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let request = match command {
+        Command::Auth { .. } => return Ok(json!({ "ok": true })),
+        Command::Key { key } => Request::Events(vec![Event::Key(parse_key(&key)?)], reply_tx),
+        Command::Type { text } => Request::Events(type_events(&text), reply_tx),
+        Command::Snapshot => Request::Snapshot(reply_tx),
+    };
+    tx.send(request).await.map_err(|_| "app is shutting down".to_string())?;
+    reply_rx.await.map_err(|_| "app is shutting down".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        let key = |code, modifiers| Ok(KeyEvent::new(code, modifiers));
+        assert_eq!(parse_key("enter"), key(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(parse_key("ctrl+c"), key(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert_eq!(parse_key("Alt+Enter"), key(KeyCode::Enter, KeyModifiers::ALT));
+        assert_eq!(parse_key("shift+tab"), key(KeyCode::BackTab, KeyModifiers::SHIFT));
+        assert_eq!(parse_key("G"), key(KeyCode::Char('G'), KeyModifiers::SHIFT));
+        assert_eq!(parse_key("ctrl++"), key(KeyCode::Char('+'), KeyModifiers::CONTROL));
+        assert_eq!(parse_key("f1"), key(KeyCode::F(1), KeyModifiers::NONE));
+        assert!(parse_key("hyper+x").is_err());
+        assert!(parse_key("banana").is_err());
+    }
+
+    #[test]
+    fn test_command_json() {
+        let command: Command = serde_json::from_str(r#"{"cmd": "type", "text": "hi"}"#).unwrap();
+        assert_eq!(command, Command::Type { text: "hi".to_string() });
+        assert_eq!(serde_json::from_str::<Command>(r#"{"cmd":"snapshot"}"#).unwrap(), Command::Snapshot);
+        assert!(serde_json::from_str::<Command>(r#"{"cmd":"reboot"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clients_must_authenticate() {
+        use tokio::io::AsyncReadExt;
+
+        let (tx, mut rx) = mpsc::channel(4);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, "secret".into(), tx.clone()));
+            }
+        });
+        tokio::spawn(async move {
+            while let Some(Request::Snapshot(reply)) = rx.recv().await {
+                let _ = reply.send(json!({ "ok": true, "snapshot": true }));
+            }
+        });
+        let exchange = |lines: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(lines.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+            let mut out = String::new();
+            stream.read_to_string(&mut out).await.unwrap();
+            out
+        };
+
+        let out = exchange("{\"cmd\":\"snapshot\"}\n{\"cmd\":\"auth\",\"token\":\"secret\"}\n").await;
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.contains("not authorized"));
+
+        let out = exchange("{\"cmd\":\"auth\",\"token\":\"secret\"}\nname=value\n{\"cmd\":\"snapshot\"}\n").await;
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.contains("bad command"));
+
+        let out = exchange("{\"cmd\":\"auth\",\"token\":\"secret\"}\n{\"cmd\":\"snapshot\"}\n").await;
+        assert!(out.lines().nth(1).unwrap().contains("snapshot"));
+    }
+}
@@ -0,0 +1,221 @@
+//! API keys in the OS credential store
+//!
+//! Hosted provider keys can live in the system keychain (macOS Keychain,
+//! Windows Credential Manager, Secret Service on Linux) instead of the config
+//! file. `ratatalk auth set <provider>` stores one; lookups fall back to the
+//! config and then to the provider's environment variable.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+
+use crate::config::Config;
+use crate::error::CredentialError;
+
+/// Keychain service name the keys are stored under
+const SERVICE: &str = "ratatalk";
+
+/// Providers that take an API key, with their environment variable
+const PROVIDERS: &[(&str, &str)] = &[("openai", "OPENAI_API_KEY"), ("anthropic", "ANTHROPIC_API_KEY")];
+
+/// Environment variable holding `provider`'s key
+pub fn env_var(provider: &str) -> Result<&'static str, CredentialError> {
+    PROVIDERS
+        .iter()
+        .find(|(name, _)| *name == provider)
+        .map(|(_, var)| *var)
+        .ok_or_else(|| CredentialError::UnknownProvider(provider.to_string()))
+}
+
+/// Run a keychain call, stepping off the async runtime if inside one
+///
+/// The Secret Service backend drives its own runtime, which cannot be
+/// entered from a runtime worker without `block_in_place`.
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+fn entry(provider: &str) -> Result<keyring::Entry, CredentialError> {
+    env_var(provider)?;
+    Ok(keyring::Entry::new(SERVICE, provider)?)
+}
+
+/// Key stored for `provider`, if any
+pub fn get(provider: &str) -> Result<Option<String>, CredentialError> {
+    let entry = entry(provider)?;
+    match blocking(|| entry.get_password()) {
+        Ok(key) => Ok(Some(key).filter(|k| !k.is_empty())),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Store `key` for `provider`, replacing any previous one
+pub fn set(provider: &str, key: &str) -> Result<(), CredentialError> {
+    if key.trim().is_empty() {
+        return Err(CredentialError::Empty);
+    }
+    let entry = entry(provider)?;
+    Ok(blocking(|| entry.set_password(key.trim()))?)
+}
+
+/// Remove `provider`'s key, returning whether one was stored
+pub fn delete(provider: &str) -> Result<bool, CredentialError> {
+    let entry = entry(provider)?;
+    match blocking(|| entry.delete_credential()) {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Plaintext key for `provider` in the config file
+fn config_key<'a>(config: &'a Config, provider: &str) -> Option<&'a str> {
+    let cfg = match provider {
+        "openai" => config.providers.openai.as_ref(),
+        "anthropic" => config.providers.anthropic.as_ref(),
+        _ => None,
+    };
+    cfg?.api_key.as_deref().filter(|k| !k.is_empty())
+}
+
+/// `ratatalk auth` subcommands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthCommand {
+    /// Prompt for a key and store it
+    Set(String),
+    /// Remove a stored key
+    Delete(String),
+    /// Show where each provider's key comes from
+    Status,
+}
+
+impl AuthCommand {
+    /// Parse the arguments following `auth`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        const USAGE: &str = "usage: ratatalk auth set|delete <provider> | ratatalk auth status";
+        let provider = |name: Option<&String>| -> Result<String, String> {
+            let name = name.ok_or(USAGE)?;
+            env_var(name).map_err(|e| e.to_string())?;
+            Ok(name.clone())
+        };
+        match args.first().map(String::as_str) {
+            Some("set") if args.len() == 2 => Ok(Self::Set(provider(args.get(1))?)),
+            Some("delete") if args.len() == 2 => Ok(Self::Delete(provider(args.get(1))?)),
+            Some("status") if args.len() == 1 => Ok(Self::Status),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+}
+
+/// Run an auth subcommand, printing the outcome
+pub fn run(command: &AuthCommand, config: &Config) -> Result<(), CredentialError> {
+    match command {
+        AuthCommand::Set(provider) => {
+            let key = read_secret(&format!("API key for {}: ", provider))?;
+            set(provider, &key)?;
+            println!("Stored the {} API key in the system keychain", provider);
+            if config_key(config, provider).is_some() {
+                println!("Note: api_key in the config file still takes precedence; remove it to use the stored key");
+            }
+        }
+        AuthCommand::Delete(provider) => {
+            if delete(provider)? {
+                println!("Removed the {} API key from the system keychain", provider);
+            } else {
+                println!("No {} API key was stored", provider);
+            }
+        }
+        AuthCommand::Status => {
+            for (provider, var) in PROVIDERS {
+                let source = if config_key(config, provider).is_some() {
+                    "config file (plaintext)".to_string()
+                } else {
+                    match get(provider) {
+                        Ok(Some(_)) => "system keychain".to_string(),
+                        Ok(None) if std::env::var(var).is_ok_and(|k| !k.is_empty()) => var.to_string(),
+                        Ok(None) => "not set".to_string(),
+                        Err(e) => format!("keychain unavailable ({})", e),
+                    }
+                };
+                println!("{:<10} {}", provider, source);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read a line without echoing it
+///
+/// Falls back to a plain line read when stdin is not a terminal, so keys
+/// can be piped in.
+fn read_secret(prompt: &str) -> Result<String, CredentialError> {
+    let mut stdin = io::stdin().lock();
+    if !stdin.is_terminal() {
+        let mut line = String::new();
+        stdin.read_line(&mut line).map_err(CredentialError::Read)?;
+        return Ok(line.trim().to_string());
+    }
+    drop(stdin);
+
+    print!("{}", prompt);
+    io::stdout().flush().map_err(CredentialError::Read)?;
+    terminal::enable_raw_mode().map_err(CredentialError::Read)?;
+    let result = read_hidden();
+    terminal::disable_raw_mode().map_err(CredentialError::Read)?;
+    println!();
+    result
+}
+
+fn read_hidden() -> Result<String, CredentialError> {
+    let mut secret = String::new();
+    loop {
+        let Event::Key(key) = event::read().map_err(CredentialError::Read)? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(secret),
+            KeyCode::Esc => return Err(CredentialError::Empty),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Err(CredentialError::Empty)
+            }
+            KeyCode::Backspace => {
+                secret.pop();
+            }
+            KeyCode::Char(c) => secret.push(c),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_auth_command() {
+        assert_eq!(AuthCommand::parse(&args(&["set", "openai"])), Ok(AuthCommand::Set("openai".into())));
+        assert_eq!(
+            AuthCommand::parse(&args(&["delete", "anthropic"])),
+            Ok(AuthCommand::Delete("anthropic".into()))
+        );
+        assert_eq!(AuthCommand::parse(&args(&["status"])), Ok(AuthCommand::Status));
+
+        assert!(AuthCommand::parse(&args(&["set"])).is_err());
+        assert!(AuthCommand::parse(&args(&["set", "ollama"])).unwrap_err().contains("ollama"));
+        assert!(AuthCommand::parse(&args(&["status", "openai"])).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Scripted demo mode
+//!
+//! `ratatalk --demo script.toml` replays canned prompts and responses with
+//! fixed timestamps and pacing, so screen recordings (e.g. asciinema casts)
+//! come out the same every time and need no running model. Nothing is read
+//! from or written to the user's config or sessions.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::app::{AppEvent, ChatSession, ResponseStats};
+use crate::error::DemoError;
+
+/// A demo script, loaded from TOML
+#[derive(Debug, Clone, Deserialize)]
+pub struct DemoScript {
+    /// Session title shown in the sidebar
+    #[serde(default = "default_title")]
+    pub title: String,
+
+    /// Model name shown as in use
+    #[serde(default = "default_model")]
+    pub model: String,
+
+    /// Timestamp of the first message; later exchanges are a minute apart
+    #[serde(default = "default_start")]
+    pub start: DateTime<Utc>,
+
+    /// Delay before the first prompt is typed, in milliseconds
+    #[serde(default = "default_pause_ms")]
+    pub intro_ms: u64,
+
+    /// Delay between typed characters, in milliseconds
+    #[serde(default = "default_typing_ms")]
+    pub typing_ms: u64,
+
+    /// Delay between streamed words, in milliseconds
+    #[serde(default = "default_chunk_ms")]
+    pub chunk_ms: u64,
+
+    /// Pause after each response, in milliseconds
+    #[serde(default = "default_pause_ms")]
+    pub pause_ms: u64,
+
+    /// Exchanges to play, in order
+    pub steps: Vec<DemoStep>,
+}
+
+/// One prompt and its canned response
+#[derive(Debug, Clone, Deserialize)]
+pub struct DemoStep {
+    pub prompt: String,
+    pub response: String,
+    /// Reasoning streamed before the response
+    #[serde(default)]
+    pub thinking: Option<String>,
+}
+
+fn default_title() -> String {
+    "Demo".to_string()
+}
+
+fn default_model() -> String {
+    "llama3.2:latest".to_string()
+}
+
+fn default_start() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_735_722_000, 0).unwrap_or_default() // 2025-01-01 09:00 UTC
+}
+
+fn default_typing_ms() -> u64 {
+    40
+}
+
+fn default_chunk_ms() -> u64 {
+    35
+}
+
+fn default_pause_ms() -> u64 {
+    1500
+}
+
+impl DemoScript {
+    /// Load a script file
+    pub fn load(path: &Path) -> Result<Self, DemoError> {
+        let contents = std::fs::read_to_string(path).map_err(DemoError::Read)?;
+        let script: DemoScript = toml::from_str(&contents).map_err(DemoError::Parse)?;
+        if script.steps.is_empty() {
+            return Err(DemoError::Empty);
+        }
+        Ok(script)
+    }
+
+    /// The session the demo plays into
+    pub fn session(&self) -> ChatSession {
+        let mut session = ChatSession::new(&self.title, &self.model);
+        session.created_at = self.start;
+        session.updated_at = self.start;
+        session
+    }
+
+    /// Timestamp for the messages of step `idx`
+    pub fn timestamp(&self, idx: usize) -> DateTime<Utc> {
+        self.start + ChronoDuration::minutes(idx as i64)
+    }
+
+    /// Stats reported for a response, derived from the script's pacing
+    fn stats(&self, chunks: usize) -> ResponseStats {
+        ResponseStats {
+            tokens: chunks as u32,
+            prompt_tokens: 0,
+            tokens_per_second: 1000.0 / self.chunk_ms.max(1) as f64,
+            total_duration_ms: chunks as u64 * self.chunk_ms,
+            truncated: false,
+            first_token_ms: Some(self.chunk_ms),
+        }
+    }
+}
+
+/// Split text into word-sized chunks, keeping whitespace attached
+pub fn chunks(text: &str) -> Vec<&str> {
+    text.split_inclusive(char::is_whitespace).collect()
+}
+
+/// Play the script, sending the same events a live session would
+pub fn spawn(script: DemoScript, tx: mpsc::Sender<AppEvent>) {
+    tokio::spawn(async move {
+        let sleep = |ms: u64| tokio::time::sleep(Duration::from_millis(ms));
+        sleep(script.intro_ms).await;
+
+        for (idx, step) in script.steps.iter().enumerate() {
+            let mut typed = String::new();
+            for c in step.prompt.chars() {
+                typed.push(c);
+                if tx.send(AppEvent::DemoTyped(typed.clone())).await.is_err() {
+                    return;
+                }
+                sleep(script.typing_ms).await;
+            }
+            sleep(script.pause_ms / 3).await;
+            let _ = tx.send(AppEvent::DemoSubmit(script.timestamp(idx))).await;
+
+            if let Some(thinking) = &step.thinking {
+                for chunk in chunks(thinking) {
+                    let _ = tx.send(AppEvent::StreamThinking(chunk.to_string())).await;
+                    sleep(script.chunk_ms).await;
+                }
+            }
+            let response = chunks(&step.response);
+            for chunk in &response {
+                let _ = tx.send(AppEvent::StreamChunk(chunk.to_string())).await;
+                sleep(script.chunk_ms).await;
+            }
+            let _ = tx.send(AppEvent::StreamComplete(script.stats(response.len()))).await;
+            sleep(script.pause_ms).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_defaults_and_timestamps() {
+        let script: DemoScript = toml::from_str(
+            r#"
+            start = "2025-06-01T12:00:00Z"
+            [[steps]]
+            prompt = "Hi"
+            response = "Hello there"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(script.model, "llama3.2:latest");
+        assert_eq!(script.timestamp(2).to_rfc3339(), "2025-06-01T12:02:00+00:00");
+        assert_eq!(script.session().created_at, script.start);
+        assert_eq!(chunks("Hello  there\nfriend"), vec!["Hello ", " ", "there\n", "friend"]);
+    }
+
+    #[test]
+    fn test_bundled_script_loads() {
+        let script = DemoScript::load(Path::new("assets/demo.toml")).unwrap();
+        assert_eq!(script.steps.len(), 2);
+    }
+}
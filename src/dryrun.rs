@@ -0,0 +1,104 @@
+//! Dry-run reports
+//!
+//! With `--dry-run`, chat requests are described instead of sent: where
+//! they would go, what they contain and roughly how many tokens the prompt
+//! is, followed by the request body itself.
+
+use serde_json::Value;
+
+use crate::ollama::{ChatMessage, ChatRequest, Role};
+
+/// Rough characters per token, for estimates only
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimated tokens of `text`
+pub fn estimate_text_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimated prompt tokens of a conversation
+pub fn estimate_messages_tokens(messages: &[ChatMessage]) -> usize {
+    let chars: usize = messages
+        .iter()
+        .map(|m| m.content.chars().count() + m.thinking.as_deref().map_or(0, |t| t.chars().count()))
+        .sum();
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimated prompt tokens of a request
+pub fn estimate_tokens(request: &ChatRequest) -> usize {
+    estimate_messages_tokens(&request.messages)
+}
+
+/// Describe `request` as it would be sent to `provider`
+///
+/// `rounds` is the most tool-call rounds an agent run may take.
+pub fn report(request: &ChatRequest, provider: &str, rounds: Option<u32>) -> String {
+    let count = |role: Role| request.messages.iter().filter(|m| m.role == role).count();
+    let mut roles = vec![];
+    for (role, name) in [
+        (Role::System, "system"),
+        (Role::User, "user"),
+        (Role::Assistant, "assistant"),
+        (Role::Tool, "tool"),
+    ] {
+        if count(role) > 0 {
+            roles.push(format!("{} {}", count(role), name));
+        }
+    }
+    let images: usize = request.messages.iter().map(|m| m.images.as_ref().map_or(0, Vec::len)).sum();
+
+    let mut lines = vec![
+        format!("Target     {} · {}", provider, request.model),
+        format!("Messages   {} ({})", request.messages.len(), roles.join(", ")),
+        format!("Prompt     ~{} tokens (estimated)", estimate_tokens(request)),
+    ];
+    if images > 0 {
+        lines.push(format!("Images     {}", images));
+    }
+    if let Some(options) = &request.options {
+        lines.push(format!("Options    {}", serde_json::to_string(options).unwrap_or_default()));
+    }
+    if let Some(tools) = &request.tools {
+        let names: Vec<&str> = tools.iter().map(|t| t.function.name.as_str()).collect();
+        let rounds = rounds.map(|n| format!(" (up to {} rounds)", n)).unwrap_or_default();
+        lines.push(format!("Tools      {}{}", names.join(", "), rounds));
+    }
+    lines.push(String::new());
+    lines.push(body(request));
+    lines.join("\n")
+}
+
+/// Pretty-printed request body, with image data replaced by its size
+fn body(request: &ChatRequest) -> String {
+    let mut json = serde_json::to_value(request).unwrap_or_default();
+    if let Some(messages) = json["messages"].as_array_mut() {
+        for images in messages.iter_mut().filter_map(|m| m.get_mut("images")?.as_array_mut()) {
+            for image in images.iter_mut() {
+                let len = image.as_str().map_or(0, str::len);
+                *image = Value::String(format!("<{} bytes of base64>", len));
+            }
+        }
+    }
+    serde_json::to_string_pretty(&json).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ollama::ChatMessage;
+
+    #[test]
+    fn test_report_summarizes_request() {
+        let mut user = ChatMessage::user("What is in this picture?");
+        user.images = Some(vec!["aGVsbG8=".to_string()]);
+        let request = ChatRequest::new("llama3.2", vec![ChatMessage::system("Be brief."), user]);
+
+        let report = report(&request, "ollama", None);
+        assert!(report.starts_with("Target     ollama · llama3.2\n"));
+        assert!(report.contains("Messages   2 (1 system, 1 user)"));
+        assert!(report.contains("Prompt     ~9 tokens"));
+        assert!(report.contains("<8 bytes of base64>"));
+        assert!(!report.contains("aGVsbG8="));
+    }
+}
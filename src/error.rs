@@ -55,16 +55,48 @@ pub enum OllamaError {
     #[error("Ollama server not reachable at {url}")]
     ConnectionFailed { url: String },
 
-    #[allow(dead_code)]
     #[error("Model not found: {model}")]
     ModelNotFound { model: String },
 
+    #[error("Not enough memory to run {model}: {message}")]
+    OutOfMemory { model: String, message: String },
+
+    #[error("Server busy: {message}")]
+    Busy { message: String },
+
     #[allow(dead_code)]
     #[error("Stream ended unexpectedly")]
     StreamEnded,
 
     #[error("API error: {message}")]
     ApiError { message: String },
+
+    #[cfg_attr(unix, allow(dead_code))]
+    #[error("Unix socket host not supported on this platform: {host}")]
+    UnsupportedHost { host: String },
+}
+
+impl OllamaError {
+    /// What the user can do about the error, if anything
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            Self::ConnectionFailed { .. } => Some("start it with `ollama serve` or check [server] host"),
+            Self::ModelNotFound { .. } => Some("download it with `D`"),
+            Self::OutOfMemory { .. } => {
+                Some("try a smaller or more quantized model, lower num_ctx, or unload other models")
+            }
+            Self::Busy { .. } => Some("wait for other requests to finish, then retry"),
+            _ => None,
+        }
+    }
+
+    /// The error followed by its suggestion, for the status bar
+    pub fn with_suggestion(&self) -> String {
+        match self.suggestion() {
+            Some(hint) => format!("{} ({})", self, hint),
+            None => self.to_string(),
+        }
+    }
 }
 
 /// Persistence errors (session history)
@@ -79,6 +111,12 @@ pub enum PersistenceError {
     #[error("Failed to parse sessions: {0}")]
     Parse(#[source] serde_json::Error),
 
+    #[error("Sessions file is corrupt ({source}); moved to {}", moved.display())]
+    Corrupt {
+        moved: std::path::PathBuf,
+        source: serde_json::Error,
+    },
+
     #[error("Failed to serialize sessions: {0}")]
     Serialize(#[source] serde_json::Error),
 
@@ -88,11 +126,200 @@ pub enum PersistenceError {
     #[error("Failed to create data directory: {0}")]
     CreateDir(#[source] std::io::Error),
 
-    #[allow(dead_code)]
     #[error("Session not found: {id}")]
     SessionNotFound { id: String },
 }
 
+/// Errors applying a diff from a chat response
+#[derive(Error, Debug)]
+pub enum PatchError {
+    #[error("No applicable diff found")]
+    NoDiff,
+
+    #[error("Malformed diff: {0}")]
+    Parse(String),
+
+    #[error("Refusing to patch path outside the working directory: {0}")]
+    UnsafePath(std::path::PathBuf),
+
+    #[error("Hunk at line {line} does not match the file")]
+    HunkMismatch { line: usize },
+
+    #[error("Failed to read file: {0}")]
+    Read(#[source] std::io::Error),
+
+    #[error("Failed to write file: {0}")]
+    Write(#[source] std::io::Error),
+}
+
+/// Slash command errors
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("Unknown command: /{0}")]
+    Unknown(String),
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Clipboard errors
+#[derive(Error, Debug)]
+pub enum ClipboardError {
+    #[error("Failed to write to clipboard: {0}")]
+    Io(#[source] std::io::Error),
+}
+
+/// Demo script errors
+#[derive(Error, Debug)]
+pub enum DemoError {
+    #[error("Failed to read demo script: {0}")]
+    Read(#[source] std::io::Error),
+
+    #[error("Failed to parse demo script: {0}")]
+    Parse(#[source] toml::de::Error),
+
+    #[error("Demo script has no steps")]
+    Empty,
+}
+
+/// Credential store errors
+#[derive(Error, Debug)]
+pub enum CredentialError {
+    #[error("Unknown provider: {0} (expected openai or anthropic)")]
+    UnknownProvider(String),
+
+    #[error("No API key entered")]
+    Empty,
+
+    #[error("Credential store error: {0}")]
+    Store(#[from] keyring::Error),
+
+    #[error("Failed to read the API key: {0}")]
+    Read(#[source] std::io::Error),
+}
+
+/// Capability gate errors
+#[derive(Error, Debug)]
+pub enum PermissionError {
+    #[error("{} is disabled (set [permissions] {} = true to enable)", .0.describe(), .0.key())]
+    Denied(crate::permissions::Capability),
+}
+
+/// Locale bundle errors
+#[derive(Error, Debug)]
+pub enum LocaleError {
+    #[error("Failed to read locale file: {0}")]
+    Read(#[source] std::io::Error),
+
+    #[error("Failed to parse locale file: {0}")]
+    Parse(#[source] toml::de::Error),
+
+    #[error("No translation for language '{0}'")]
+    Unknown(String),
+}
+
+/// Theme file errors
+#[derive(Error, Debug)]
+pub enum ThemeError {
+    #[error("Failed to read theme file: {0}")]
+    Read(#[source] std::io::Error),
+
+    #[error("Failed to parse theme file: {0}")]
+    Parse(#[source] toml::de::Error),
+
+    #[error("No theme named '{0}'")]
+    Unknown(String),
+
+    #[error("Unknown theme key '{0}'")]
+    UnknownKey(String),
+
+    #[error("Invalid color {value} for '{key}'")]
+    Color { key: String, value: String },
+}
+
+/// System prompt library errors
+#[derive(Error, Debug)]
+pub enum PromptError {
+    #[error("Failed to read prompt library: {0}")]
+    Read(#[source] std::io::Error),
+
+    #[error("Failed to write prompt library: {0}")]
+    Write(#[source] std::io::Error),
+
+    #[error("Failed to parse prompt library: {0}")]
+    Parse(#[source] toml::de::Error),
+
+    #[error("Failed to serialize prompt library: {0}")]
+    Serialize(#[source] toml::ser::Error),
+
+    #[error("{0}")]
+    Config(#[from] ConfigError),
+}
+
+/// Key binding errors
+#[derive(Error, Debug)]
+pub enum KeymapError {
+    #[error("Unknown key '{0}'")]
+    InvalidKey(String),
+
+    #[error("No action named '{0}' to bind keys to")]
+    UnknownAction(String),
+
+    #[error("'{key}' is bound to both {first} and {second}")]
+    Clash { key: String, first: &'static str, second: &'static str },
+
+    #[error("'{key}' can't be bound to {action}, which works while typing")]
+    Printable { key: String, action: &'static str },
+}
+
+/// Self-update errors
+#[derive(Error, Debug)]
+pub enum UpdateError {
+    #[error("Failed to reach GitHub: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Not a version: {0}")]
+    BadVersion(String),
+
+    #[error("The latest release has no {0} binary")]
+    NoAsset(String),
+
+    #[error("The latest release has no checksum for {0}")]
+    NoChecksum(String),
+
+    #[error("Checksum mismatch (expected {expected}, got {actual}); not installing")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("Failed to install the update: {0}")]
+    Install(#[source] std::io::Error),
+}
+
+/// File attachment errors
+#[derive(Error, Debug)]
+pub enum AttachmentError {
+    #[error("Failed to read file: {0}")]
+    Read(#[source] std::io::Error),
+
+    #[error("Not a text file")]
+    Binary,
+
+    #[error("File is too large to attach ({0} KB, limit {1} KB)")]
+    TooLarge(u64, u64),
+}
+
+/// Completion notification errors
+#[derive(Error, Debug)]
+pub enum NotifyError {
+    #[error("Webhook request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Webhook answered {0}")]
+    Status(reqwest::StatusCode),
+
+    #[error("Desktop notification failed: {0}")]
+    Desktop(#[from] notify_rust::error::Error),
+}
+
 /// Result type alias using anyhow for convenient error handling
 #[allow(dead_code)]
 pub type Result<T> = anyhow::Result<T>;
@@ -4,12 +4,25 @@
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
 use ratatui::layout::Rect;
-use std::time::Duration;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tracing::{info, warn};
 
-use crate::app::{AppAction, AppState, InputMode};
-use crate::persistence;
-use crate::ui::AppLayout;
+use crate::app::{
+    code_blocks, pop_grapheme, AppAction, AppState, Attachment, CodeBlock, Download, InputMode, PendingKey, Rating, RedactReview,
+    Severity, POPULAR_MODELS,
+};
+use crate::commands::COMMANDS;
+use crate::config::TimestampStyle;
+use crate::keymap::{Keymap, View};
+use crate::patch;
+use crate::picker::{FilePicker, Pick, PickPurpose};
+use crate::permissions::{self, Capability};
+use crate::redact::Redactor;
+use crate::theme::{self, Theme};
+use crate::ui::{tab_at, AppLayout};
+use crate::update;
 
 /// Event handler configuration
 pub struct EventHandler {
@@ -23,9 +36,13 @@ impl EventHandler {
         }
     }
 
-    /// Poll for the next event, with timeout
-    pub fn poll(&self) -> std::io::Result<Option<Event>> {
-        if event::poll(self.tick_rate)? {
+    pub fn tick_rate(&self) -> Duration {
+        self.tick_rate
+    }
+
+    /// Poll for the next event, waiting at most `timeout`
+    pub fn poll(&self, timeout: Duration) -> std::io::Result<Option<Event>> {
+        if event::poll(timeout)? {
             Ok(Some(event::read()?))
         } else {
             Ok(None)
@@ -33,117 +50,152 @@ impl EventHandler {
     }
 }
 
+/// Render pacing: at most one frame per interval, and only when something changed
+///
+/// Events are still handled as they arrive; a flood of tokens or keys between
+/// two frames is drawn once.
+pub struct FramePacer {
+    interval: Duration,
+    last_frame: Option<Instant>,
+    dirty: bool,
+}
+
+impl FramePacer {
+    /// Pace frames to `max_fps`, kept within 1–240
+    pub fn new(max_fps: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / max_fps.clamp(1, 240),
+            last_frame: None,
+            dirty: true,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Note that the screen is out of date
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Draw the next frame without waiting out the interval
+    pub fn force(&mut self) {
+        self.dirty = true;
+        self.last_frame = None;
+    }
+
+    /// Whether a frame should be drawn now
+    pub fn due(&self) -> bool {
+        self.dirty && self.until_next().is_zero()
+    }
+
+    pub fn drawn(&mut self) {
+        self.dirty = false;
+        self.last_frame = Some(Instant::now());
+    }
+
+    /// How long to wait for input: until the next frame when one is owed,
+    /// otherwise `idle`
+    pub fn wait(&self, idle: Duration) -> Duration {
+        if self.dirty {
+            self.until_next()
+        } else {
+            idle
+        }
+    }
+
+    fn until_next(&self) -> Duration {
+        self.last_frame.map_or(Duration::ZERO, |last| self.interval.saturating_sub(last.elapsed()))
+    }
+}
+
 /// Map a key event to an application action based on current mode
 pub fn handle_key_event(key: KeyEvent, state: &AppState) -> Option<AppAction> {
     // Global keybindings (work in any mode)
-    match (key.code, key.modifiers) {
-        (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Some(AppAction::Quit),
-        (KeyCode::Char('q'), KeyModifiers::CONTROL) => return Some(AppAction::Quit),
-        _ => {}
+    if let Some(action) = state.keymap.action(&key, View::Any, state) {
+        return Some(action);
     }
 
     // Mode-specific keybindings
     match state.input_mode {
+        InputMode::Normal if state.transcript => handle_transcript_mode(key, state),
         InputMode::Normal => handle_normal_mode(key, state),
-        InputMode::Editing => handle_editing_mode(key),
+        InputMode::Editing => handle_editing_mode(key, state),
         InputMode::ModelSelect => handle_model_select_mode(key),
         InputMode::SessionSelect => handle_session_select_mode(key),
         InputMode::Help => handle_help_mode(key),
         InputMode::DeleteConfirm => handle_delete_confirm_mode(key),
+        InputMode::PatchConfirm => handle_patch_confirm_mode(key),
+        InputMode::Downloads => handle_downloads_mode(key),
+        InputMode::PullConfirm => handle_pull_confirm_mode(key),
+        InputMode::CodePicker => handle_code_picker_mode(key),
+        InputMode::Yanks => handle_yanks_mode(key),
+        InputMode::Snippets => handle_snippets_mode(key),
+        InputMode::Prompts => handle_prompts_mode(key),
+        InputMode::RedactReview => handle_redact_review_mode(key),
+        InputMode::Cache => handle_cache_mode(key),
+        InputMode::DryRun => handle_dry_run_mode(key),
+        InputMode::ContextInspect => handle_context_mode(key),
+        InputMode::MessageSelect => handle_message_select_mode(key, state),
+        InputMode::MessageInspect => Some(AppAction::CloseInspect),
+        InputMode::Rename => handle_rename_mode(key),
+        InputMode::FilePicker => handle_file_picker_mode(key),
+        InputMode::Search => handle_search_mode(key),
+        InputMode::Find => handle_find_mode(key),
+        InputMode::Palette => handle_palette_mode(key),
     }
 }
 
 /// Handle keys in normal mode
-fn handle_normal_mode(key: KeyEvent, _state: &AppState) -> Option<AppAction> {
+fn handle_normal_mode(key: KeyEvent, state: &AppState) -> Option<AppAction> {
+    // Second key of `m<letter>` / `'<letter>`
+    if let Some(pending) = state.pending_key {
+        return match key.code {
+            KeyCode::Char(c) if c.is_ascii_lowercase() => Some(match pending {
+                PendingKey::Mark => AppAction::SetMark(c),
+                PendingKey::Jump => AppAction::JumpToMark(c),
+                PendingKey::Record => AppAction::StartRecording(c),
+                PendingKey::Replay => AppAction::ReplayMacro(c),
+            }),
+            KeyCode::Char('@') if pending == PendingKey::Replay => Some(AppAction::ReplayMacro('@')),
+            _ => Some(AppAction::CancelPendingKey),
+        };
+    }
+
+    if let Some(action) = state.keymap.action(&key, View::Chat, state) {
+        return Some(action);
+    }
+
+    // Quick follow-ups, after the bound keys
     match (key.code, key.modifiers) {
-        // Quit
-        (KeyCode::Char('q'), KeyModifiers::NONE) => Some(AppAction::Quit),
-        
-        // Enter edit mode
-        (KeyCode::Enter, _) | (KeyCode::Char('i'), KeyModifiers::NONE) => {
-            Some(AppAction::EnterEditMode)
-        }
-        
-        // Session navigation
-        (KeyCode::Tab, KeyModifiers::NONE) => Some(AppAction::NextSession),
-        (KeyCode::BackTab, _) => Some(AppAction::PrevSession),
-        (KeyCode::Char('n'), KeyModifiers::CONTROL) => Some(AppAction::NewSession),
-        (KeyCode::Char('w'), KeyModifiers::CONTROL) => Some(AppAction::RequestDeleteSession),
-        
-        // Model selection
-        (KeyCode::Char('m'), KeyModifiers::NONE) => Some(AppAction::OpenModelSelect),
-        
-        // Scrolling
-        (KeyCode::Up, _) | (KeyCode::Char('k'), KeyModifiers::NONE) => {
-            Some(AppAction::ScrollUp(1))
+        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT)
+            if state.config.reactions.prompt(c).is_some() =>
+        {
+            Some(AppAction::React(c))
         }
-        (KeyCode::Down, _) | (KeyCode::Char('j'), KeyModifiers::NONE) => {
-            Some(AppAction::ScrollDown(1))
-        }
-        (KeyCode::PageUp, _) | (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
-            Some(AppAction::PageUp)
-        }
-        (KeyCode::PageDown, _) | (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
-            Some(AppAction::PageDown)
-        }
-        (KeyCode::Home, _) | (KeyCode::Char('g'), KeyModifiers::NONE) => {
-            Some(AppAction::ScrollToTop)
-        }
-        (KeyCode::End, _) | (KeyCode::Char('G'), KeyModifiers::SHIFT) => {
-            Some(AppAction::ScrollToBottom)
-        }
-        
-        // Help
-        (KeyCode::Char('?'), _) | (KeyCode::F(1), _) => Some(AppAction::ToggleHelp),
-        
-        // Refresh models
-        (KeyCode::Char('r'), KeyModifiers::CONTROL) => Some(AppAction::RefreshModels),
-        
-        // Clear error
-        (KeyCode::Esc, _) => Some(AppAction::ClearError),
-        
         _ => None,
     }
 }
 
+/// Handle keys in the read-only transcript view
+///
+/// Only scrolling, find and help: nothing here can type into the session.
+fn handle_transcript_mode(key: KeyEvent, state: &AppState) -> Option<AppAction> {
+    state.keymap.action(&key, View::Transcript, state)
+}
+
 /// Handle keys in editing mode
-fn handle_editing_mode(key: KeyEvent) -> Option<AppAction> {
+fn handle_editing_mode(key: KeyEvent, state: &AppState) -> Option<AppAction> {
+    if let Some(action) = state.keymap.action(&key, View::Editing, state) {
+        return Some(action);
+    }
+
+    // Character input
     match (key.code, key.modifiers) {
-        // Exit edit mode
-        (KeyCode::Esc, _) => Some(AppAction::ExitEditMode),
-        
-        // Submit message
-        (KeyCode::Enter, KeyModifiers::NONE) => Some(AppAction::SubmitMessage),
-        
-        // Character input
         (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
             Some(AppAction::InsertChar(c))
         }
-        
-        // Deletion
-        (KeyCode::Backspace, _) => Some(AppAction::DeleteChar),
-        (KeyCode::Delete, _) => Some(AppAction::DeleteCharForward),
-        (KeyCode::Char('h'), KeyModifiers::CONTROL) => Some(AppAction::DeleteChar),
-        (KeyCode::Char('w'), KeyModifiers::CONTROL) => {
-            // Delete word - for now just clear all
-            Some(AppAction::ClearInput)
-        }
-        (KeyCode::Char('u'), KeyModifiers::CONTROL) => Some(AppAction::ClearInput),
-        
-        // Cursor movement
-        (KeyCode::Left, _) | (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
-            Some(AppAction::MoveCursorLeft)
-        }
-        (KeyCode::Right, _) | (KeyCode::Char('f'), KeyModifiers::CONTROL) => {
-            Some(AppAction::MoveCursorRight)
-        }
-        (KeyCode::Home, _) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
-            Some(AppAction::MoveCursorStart)
-        }
-        (KeyCode::End, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
-            Some(AppAction::MoveCursorEnd)
-        }
-        
         _ => None,
     }
 }
@@ -155,10 +207,22 @@ fn handle_model_select_mode(key: KeyEvent) -> Option<AppAction> {
         KeyCode::Enter => Some(AppAction::ConfirmModel),
         KeyCode::Up | KeyCode::Char('k') => Some(AppAction::PrevModel),
         KeyCode::Down | KeyCode::Char('j') => Some(AppAction::NextModel),
+        KeyCode::PageUp => Some(AppAction::ModelPageUp),
+        KeyCode::PageDown => Some(AppAction::ModelPageDown),
+        KeyCode::Home => Some(AppAction::FirstModel),
+        KeyCode::End => Some(AppAction::LastModel),
+        KeyCode::Char('l') => Some(AppAction::LoadModel),
+        KeyCode::Char('u') => Some(AppAction::UnloadModel),
+        KeyCode::Char('c') => Some(AppAction::CompareWithSelected),
+        // Uppercase letters jump, leaving lowercase free for commands
+        KeyCode::Char(c) if c.is_ascii_uppercase() => Some(AppAction::JumpToModel(c)),
         _ => None,
     }
 }
 
+/// Models skipped by PageUp / PageDown in the model picker
+const MODEL_PAGE: usize = 10;
+
 /// Handle keys in session selection mode
 fn handle_session_select_mode(key: KeyEvent) -> Option<AppAction> {
     match key.code {
@@ -195,12 +259,272 @@ fn handle_delete_confirm_mode(key: KeyEvent) -> Option<AppAction> {
     }
 }
 
+/// Handle keys in the rename popup
+fn handle_rename_mode(key: KeyEvent) -> Option<AppAction> {
+    match (key.code, key.modifiers) {
+        (KeyCode::Esc, _) => Some(AppAction::CancelRename),
+        (KeyCode::Enter, _) => Some(AppAction::ConfirmRename),
+        (KeyCode::Backspace, _) => Some(AppAction::RenameDeleteChar),
+        (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+            Some(AppAction::RenameInsertChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Handle keys in the file picker
+fn handle_file_picker_mode(key: KeyEvent) -> Option<AppAction> {
+    match (key.code, key.modifiers) {
+        (KeyCode::Esc, _) => Some(AppAction::ClosePicker),
+        (KeyCode::Enter, _) | (KeyCode::Tab, _) => Some(AppAction::PickerEnter),
+        (KeyCode::Backspace, _) => Some(AppAction::PickerBackspace),
+        (KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => Some(AppAction::NextPickerEntry),
+        (KeyCode::Up, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => Some(AppAction::PrevPickerEntry),
+        (KeyCode::Char('d'), KeyModifiers::CONTROL) => Some(AppAction::DetachLast),
+        (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+            Some(AppAction::PickerInsertChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Handle keys while typing a find query
+fn handle_find_mode(key: KeyEvent) -> Option<AppAction> {
+    match (key.code, key.modifiers) {
+        (KeyCode::Esc, _) => Some(AppAction::CancelFind),
+        (KeyCode::Enter, _) => Some(AppAction::ConfirmFind),
+        (KeyCode::Backspace, _) => Some(AppAction::FindDeleteChar),
+        (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+            Some(AppAction::FindInsertChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Handle keys in the search popup
+fn handle_search_mode(key: KeyEvent) -> Option<AppAction> {
+    match (key.code, key.modifiers) {
+        (KeyCode::Esc, _) => Some(AppAction::CloseSearch),
+        (KeyCode::Enter, _) => Some(AppAction::ConfirmSearch),
+        (KeyCode::Backspace, _) => Some(AppAction::SearchDeleteChar),
+        (KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => Some(AppAction::NextSearchResult),
+        (KeyCode::Up, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => Some(AppAction::PrevSearchResult),
+        (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+            Some(AppAction::SearchInsertChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Handle keys in the command palette
+fn handle_palette_mode(key: KeyEvent) -> Option<AppAction> {
+    match (key.code, key.modifiers) {
+        (KeyCode::Esc, _) => Some(AppAction::ClosePalette),
+        (KeyCode::Enter, _) => Some(AppAction::RunPaletteEntry),
+        (KeyCode::Backspace, _) => Some(AppAction::PaletteDeleteChar),
+        (KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => Some(AppAction::NextPaletteEntry),
+        (KeyCode::Up, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => Some(AppAction::PrevPaletteEntry),
+        (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+            Some(AppAction::PaletteInsertChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Handle keys in patch confirmation mode
+fn handle_patch_confirm_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+            Some(AppAction::ConfirmApplyPatch)
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+            Some(AppAction::CancelApplyPatch)
+        }
+        _ => None,
+    }
+}
+
+/// Handle keys in the missing model confirmation
+fn handle_pull_confirm_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+            Some(AppAction::ConfirmPullMissing)
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+            Some(AppAction::CancelPullMissing)
+        }
+        _ => None,
+    }
+}
+
+/// Handle keys while selecting messages
+fn handle_message_select_mode(key: KeyEvent, state: &AppState) -> Option<AppAction> {
+    match key.code {
+        // Esc leaves a visual range before leaving select mode
+        KeyCode::Esc if state.select_anchor.is_some() => Some(AppAction::ToggleRangeSelect),
+        KeyCode::Char('v') => Some(AppAction::ToggleRangeSelect),
+        KeyCode::Esc | KeyCode::Char('V') | KeyCode::Char('q') => Some(AppAction::ExitSelectMode),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::SelectNext),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::SelectPrev),
+        KeyCode::Home | KeyCode::Char('g') => Some(AppAction::SelectFirst),
+        KeyCode::End | KeyCode::Char('G') => Some(AppAction::SelectLast),
+        KeyCode::Char('y') => Some(AppAction::CopySelected),
+        KeyCode::Char('C') => Some(AppAction::CopySelectedMarkdown),
+        KeyCode::Char('E') => Some(AppAction::ExportSelected),
+        KeyCode::Char('d') => Some(AppAction::DeleteSelected),
+        KeyCode::Char('e') => Some(AppAction::EditSelected),
+        KeyCode::Enter | KeyCode::Char('i') => Some(AppAction::InspectSelected),
+        KeyCode::Char('b') => Some(AppAction::ForkAtSelected),
+        KeyCode::Char('s') => Some(AppAction::SaveSelectedSnippet),
+        KeyCode::Char('t') => Some(AppAction::ToggleThinking),
+        _ => None,
+    }
+}
+
+/// Handle keys in the snippet picker
+fn handle_snippets_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => Some(AppAction::CloseSnippets),
+        KeyCode::Enter | KeyCode::Tab | KeyCode::Char('i') => Some(AppAction::InsertSnippet),
+        KeyCode::Char('a') => Some(AppAction::SaveInputSnippet),
+        KeyCode::Char('d') => Some(AppAction::DeleteSnippet),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::PrevSnippet),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::NextSnippet),
+        _ => None,
+    }
+}
+
+/// Handle keys in the system prompt picker
+fn handle_prompts_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => Some(AppAction::ClosePrompts),
+        KeyCode::Enter => Some(AppAction::UsePrompt),
+        KeyCode::Char('c') => Some(AppAction::ClearPrompt),
+        KeyCode::Char('e') => Some(AppAction::EditPrompts),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::PrevPrompt),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::NextPrompt),
+        _ => None,
+    }
+}
+
+/// Handle keys in the dry-run report
+fn handle_dry_run_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Some(AppAction::CloseDryRun),
+        KeyCode::Char('y') => Some(AppAction::CopyDryRun),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::ScrollDryRun(-1)),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::ScrollDryRun(1)),
+        KeyCode::PageUp => Some(AppAction::ScrollDryRun(-10)),
+        KeyCode::PageDown => Some(AppAction::ScrollDryRun(10)),
+        _ => None,
+    }
+}
+
+/// Handle keys in the context inspector
+fn handle_context_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Some(AppAction::CloseContext),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::ScrollContext(-1)),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::ScrollContext(1)),
+        KeyCode::PageUp => Some(AppAction::ScrollContext(-10)),
+        KeyCode::PageDown => Some(AppAction::ScrollContext(10)),
+        _ => None,
+    }
+}
+
+/// Handle keys in the response cache inspector
+fn handle_cache_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => Some(AppAction::CloseCache),
+        KeyCode::Char('d') => Some(AppAction::DeleteCacheEntry),
+        KeyCode::Char('D') => Some(AppAction::ClearCache),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::PrevCacheEntry),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::NextCacheEntry),
+        _ => None,
+    }
+}
+
+/// Handle keys in the anonymized export review
+fn handle_redact_review_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => Some(AppAction::CloseRedactReview),
+        KeyCode::Enter | KeyCode::Char('E') => Some(AppAction::ExportRedacted),
+        KeyCode::Char('m') => Some(AppAction::AnonymizeWithModel),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::PrevRedaction),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::NextRedaction),
+        _ => None,
+    }
+}
+
+/// Handle keys in the code block picker
+fn handle_code_picker_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => Some(AppAction::CloseCodePicker),
+        KeyCode::Enter | KeyCode::Char('y') => Some(AppAction::ConfirmCodeBlock),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::PrevCodeBlock),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::NextCodeBlock),
+        KeyCode::Char(c @ '1'..='9') => Some(AppAction::PickCodeBlock(c as usize - '1' as usize)),
+        _ => None,
+    }
+}
+
+/// Handle keys in the yank history popup
+fn handle_yanks_mode(key: KeyEvent) -> Option<AppAction> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => Some(AppAction::CloseYanks),
+        KeyCode::Enter | KeyCode::Char('y') => Some(AppAction::RecopyYank),
+        KeyCode::Tab | KeyCode::Char('i') => Some(AppAction::InsertYank),
+        KeyCode::Char('d') => Some(AppAction::DeleteYank),
+        KeyCode::Up | KeyCode::Char('k') => Some(AppAction::PrevYank),
+        KeyCode::Down | KeyCode::Char('j') => Some(AppAction::NextYank),
+        _ => None,
+    }
+}
+
+/// Handle keys in the downloads popup
+fn handle_downloads_mode(key: KeyEvent) -> Option<AppAction> {
+    match (key.code, key.modifiers) {
+        (KeyCode::Esc, _) => Some(AppAction::CloseDownloads),
+        (KeyCode::Enter, _) => Some(AppAction::QueuePull),
+        (KeyCode::Up, _) => Some(AppAction::PrevPullSuggestion),
+        (KeyCode::Down, _) => Some(AppAction::NextPullSuggestion),
+        (KeyCode::Backspace, _) => Some(AppAction::PullDeleteChar),
+        (KeyCode::Char('l'), KeyModifiers::CONTROL) => Some(AppAction::ClearFinishedDownloads),
+        (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+            Some(AppAction::PullInsertChar(c))
+        }
+        _ => None,
+    }
+}
+
 /// Process an action and update state
+///
+/// Changes to the open session or its model are recorded as undo steps, by
+/// snapshotting that state around [`apply_action`].
 pub fn process_action(action: AppAction, state: &mut AppState) {
-    // Clear transient error messages on most actions
-    match &action {
-        AppAction::ClearError => {}
-        _ => state.clear_error(),
+    match action {
+        AppAction::Undo => {
+            if !state.undo() {
+                state.set_status("Nothing to undo");
+            }
+        }
+        AppAction::Redo => {
+            if !state.redo() {
+                state.set_status("Nothing to redo");
+            }
+        }
+        action => {
+            let before = state.ui_snapshot();
+            apply_action(action, state);
+            state.record_undo(before);
+        }
+    }
+}
+
+/// Apply an action to the state
+fn apply_action(action: AppAction, state: &mut AppState) {
+    if !matches!(action, AppAction::BeginMark | AppAction::BeginJump | AppAction::BeginRecord | AppAction::BeginReplay) {
+        state.pending_key = None;
     }
 
     match action {
@@ -209,105 +533,1008 @@ pub fn process_action(action: AppAction, state: &mut AppState) {
         AppAction::PrevSession => state.prev_session(),
         AppAction::NewSession => state.new_session(),
         AppAction::DeleteSession => state.delete_current_session(),
-        AppAction::SelectSession(idx) => {
-            if idx < state.sessions.len() {
-                state.active_session_idx = idx;
-                state.chat_scroll = 0;
+        AppAction::SelectSession(idx) => state.select_session(idx),
+        AppAction::RequestDeleteSession => {
+            // Check if we can delete (not the last session, not streaming)
+            if state.chat.sessions.len() <= 1 {
+                state.set_warning("Cannot delete the last remaining session");
+            } else if state.streaming {
+                state.set_warning("Cannot delete session while receiving response");
+            } else {
+                state.input_mode = InputMode::DeleteConfirm;
+            }
+        }
+        AppAction::ConfirmDeleteSession => {
+            let session_name = state.active_session()
+                .map(|s| s.name.clone())
+                .unwrap_or_default();
+            
+            state.delete_current_session();
+            info!("Session deleted: {}", session_name);
+            state.set_status(format!("Session deleted: {}", session_name));
+            state.input_mode = InputMode::Normal;
+            
+            // Save sessions after deletion
+            if let Err(e) = state.save_sessions() {
+                warn!("Failed to save sessions after deletion: {}", e);
+            }
+        }
+        AppAction::CancelDeleteSession => {
+            state.input_mode = InputMode::Normal;
+        }
+        AppAction::RequestRename => {
+            if let Some(name) = state.active_session().map(|s| s.name.clone()) {
+                state.rename_input = name;
+                state.input_mode = InputMode::Rename;
+            }
+        }
+        AppAction::CycleTimestamps => {
+            // Clock, then relative, then hidden
+            let ui = &mut state.config.ui;
+            let shown = match (ui.show_timestamps, ui.timestamp_style) {
+                (false, _) => {
+                    ui.show_timestamps = true;
+                    ui.timestamp_style = TimestampStyle::Clock;
+                    "Timestamps: clock"
+                }
+                (true, TimestampStyle::Clock) => {
+                    ui.timestamp_style = TimestampStyle::Relative;
+                    "Timestamps: relative"
+                }
+                (true, TimestampStyle::Relative) => {
+                    ui.show_timestamps = false;
+                    "Timestamps hidden"
+                }
+            };
+            state.set_status(shown);
+        }
+        AppAction::CycleTheme => {
+            let name = theme::next_name(&theme::names(), &state.config.ui.theme);
+            match Theme::load(&name) {
+                Ok(next) => {
+                    state.theme = next;
+                    state.set_status(format!("Theme: {}", name));
+                    state.config.ui.theme = name;
+                }
+                Err(e) => state.set_error(e.to_string()),
+            }
+        }
+        AppAction::OpenTranscript => {
+            state.transcript = true;
+            state.set_status("Read-only view: j/k scroll, / find, q to leave");
+        }
+        AppAction::CloseTranscript => {
+            state.transcript = false;
+            state.find_query.clear();
+        }
+        AppAction::ToggleZen => {
+            state.zen = !state.zen;
+            if state.zen {
+                state.set_status("Zen mode (z to leave)");
+            }
+        }
+        AppAction::ToggleToolOutput => {
+            state.tools_expanded = !state.tools_expanded;
+            let shown = if state.tools_expanded { "expanded" } else { "collapsed" };
+            state.set_status(format!("Tool calls {}", shown));
+        }
+        AppAction::TogglePin => {
+            match state.toggle_pin() {
+                Some(true) => state.set_status("Session pinned"),
+                Some(false) => state.set_status("Session unpinned"),
+                None => {}
+            }
+            if let Err(e) = state.save_sessions() {
+                warn!("Failed to save sessions after pinning: {}", e);
+            }
+        }
+        AppAction::OpenFind => {
+            state.input_mode = InputMode::Find;
+            state.find_query.clear();
+        }
+        AppAction::FindInsertChar(c) => {
+            state.find_query.push(c);
+            state.find_jump = true;
+        }
+        AppAction::FindDeleteChar => {
+            pop_grapheme(&mut state.find_query);
+            state.find_jump = true;
+        }
+        AppAction::ConfirmFind => {
+            state.input_mode = InputMode::Normal;
+            if !state.find_query.is_empty() {
+                state.set_status("n / N: next / previous match, Esc: clear");
+            }
+        }
+        AppAction::CancelFind => {
+            state.input_mode = InputMode::Normal;
+            state.find_query.clear();
+        }
+        AppAction::FindNext => state.step_find(true),
+        AppAction::FindPrev => state.step_find(false),
+        AppAction::OpenSearch => {
+            state.input_mode = InputMode::Search;
+            state.update_search();
+        }
+        AppAction::CloseSearch => state.input_mode = InputMode::Normal,
+        AppAction::SearchInsertChar(c) => {
+            state.search_query.push(c);
+            state.update_search();
+        }
+        AppAction::SearchDeleteChar => {
+            pop_grapheme(&mut state.search_query);
+            state.update_search();
+        }
+        AppAction::NextSearchResult => {
+            if !state.search_results.is_empty() {
+                state.search_idx = (state.search_idx + 1) % state.search_results.len();
+            }
+        }
+        AppAction::PrevSearchResult => {
+            if !state.search_results.is_empty() {
+                state.search_idx = state
+                    .search_idx
+                    .checked_sub(1)
+                    .unwrap_or(state.search_results.len() - 1);
+            }
+        }
+        AppAction::ConfirmSearch => {
+            if let Some(name) = state.open_search_result().map(|s| s.name.clone()) {
+                state.input_mode = InputMode::Normal;
+                state.set_status(format!("Jumped to \"{}\"", name));
+            }
+        }
+        AppAction::OpenPalette => {
+            state.palette_query.clear();
+            state.palette_idx = 0;
+            state.input_mode = InputMode::Palette;
+        }
+        AppAction::ClosePalette => state.input_mode = InputMode::Normal,
+        AppAction::PaletteInsertChar(c) => {
+            state.palette_query.push(c);
+            state.palette_idx = 0;
+        }
+        AppAction::PaletteDeleteChar => {
+            pop_grapheme(&mut state.palette_query);
+            state.palette_idx = 0;
+        }
+        AppAction::NextPaletteEntry => {
+            let len = state.palette_entries().len();
+            if len > 0 {
+                state.palette_idx = (state.palette_idx + 1) % len;
+            }
+        }
+        AppAction::PrevPaletteEntry => {
+            let len = state.palette_entries().len();
+            if len > 0 {
+                state.palette_idx = state.palette_idx.checked_sub(1).unwrap_or(len - 1);
+            }
+        }
+        AppAction::RunPaletteEntry => {
+            if let Some(action) = state.take_palette_action() {
+                apply_action(action, state);
+            }
+        }
+        AppAction::RenameInsertChar(c) => state.rename_input.push(c),
+        AppAction::RenameDeleteChar => {
+            pop_grapheme(&mut state.rename_input);
+        }
+        AppAction::ConfirmRename => {
+            let name = std::mem::take(&mut state.rename_input);
+            if state.active_session_mut().is_some_and(|s| s.rename(&name)) {
+                info!("Session renamed: {}", name.trim());
+                state.set_status(format!("Renamed to \"{}\"", name.trim()));
+                state.input_mode = InputMode::Normal;
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after renaming: {}", e);
+                }
+            } else {
+                state.set_warning("Session name can't be empty");
+            }
+        }
+        AppAction::CancelRename => {
+            state.rename_input.clear();
+            state.input_mode = InputMode::Normal;
+        }
+
+        // File picker
+        AppAction::OpenFilePicker(purpose) => {
+            let start = match purpose {
+                PickPurpose::ExportDir => state.export_dir().ok().filter(|dir| dir.is_dir()),
+                _ => None,
+            };
+            let start = start.or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+            state.file_picker = Some(FilePicker::new(purpose, start));
+            state.input_mode = InputMode::FilePicker;
+        }
+        AppAction::PickerInsertChar(c) => {
+            if let Some(picker) = state.file_picker.as_mut() {
+                picker.push_query(c);
+            }
+        }
+        AppAction::PickerBackspace => {
+            if let Some(picker) = state.file_picker.as_mut() {
+                picker.backspace();
+            }
+        }
+        AppAction::NextPickerEntry => {
+            if let Some(picker) = state.file_picker.as_mut() {
+                picker.next();
+            }
+        }
+        AppAction::PrevPickerEntry => {
+            if let Some(picker) = state.file_picker.as_mut() {
+                picker.prev();
+            }
+        }
+        AppAction::PickerEnter => {
+            let Some(picker) = state.file_picker.as_mut() else {
+                return;
+            };
+            let purpose = picker.purpose;
+            if let Pick::Chosen(path) = picker.enter() {
+                use_picked_path(state, purpose, path);
+            }
+        }
+        AppAction::DetachLast => {
+            if let Some(removed) = state.pending_attachments.pop() {
+                state.set_status(format!("Removed {}", removed.name));
+            }
+        }
+        AppAction::ClosePicker => close_picker(state),
+
+        // Model selection
+        AppAction::OpenModelSelect => {
+            state.input_mode = InputMode::ModelSelect;
+            // Try to select current model in the list
+            if let Some(current) = state.active_session() {
+                if let Some(idx) = state.chat.models.iter().position(|m| m.name == current.model) {
+                    state.selected_model_idx = idx;
+                }
+            }
+        }
+        AppAction::CloseModelSelect => {
+            state.input_mode = InputMode::Normal;
+        }
+        AppAction::NextModel => state.next_model(),
+        AppAction::PrevModel => state.prev_model(),
+        AppAction::ConfirmModel => {
+            if let Some(model) = state.selected_model() {
+                let model_name = model.name.clone();
+                state.set_model(&model_name);
+                state.set_status(format!("Switched to model: {}", model_name));
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after switching model: {}", e);
+                }
+            }
+            state.input_mode = InputMode::Normal;
+        }
+        AppAction::SelectModel(idx) => {
+            if idx < state.chat.models.len() {
+                state.selected_model_idx = idx;
+            }
+        }
+        AppAction::ModelPageUp => state.move_model_selection(-(MODEL_PAGE as isize)),
+        AppAction::ModelPageDown => state.move_model_selection(MODEL_PAGE as isize),
+        AppAction::FirstModel => state.selected_model_idx = 0,
+        AppAction::LastModel => state.move_model_selection(isize::MAX),
+        AppAction::JumpToModel(letter) => {
+            if !state.jump_to_model(letter) {
+                state.set_status(format!("No model starting with '{}'", letter.to_ascii_lowercase()));
+            }
+        }
+        // The request itself is sent by the main loop
+        AppAction::LoadModel => {
+            if let Some(model) = state.selected_model() {
+                let msg = format!("Loading {}...", model.name);
+                state.set_status(msg);
+            }
+        }
+        AppAction::UnloadModel => {
+            if let Some(model) = state.selected_model() {
+                let msg = format!("Unloading {}...", model.name);
+                state.set_status(msg);
+            }
+        }
+
+        // Input
+        AppAction::EnterEditMode => {
+            state.input_mode = InputMode::Editing;
+        }
+        AppAction::ExitEditMode => {
+            state.input_mode = InputMode::Normal;
+        }
+        AppAction::InsertChar(c) => state.insert_char(c),
+        AppAction::InsertNewline => state.insert_char('\n'),
+        AppAction::Paste(text) => {
+            if matches!(state.input_mode, InputMode::Normal | InputMode::Editing) {
+                state.input_mode = InputMode::Editing;
+                state.insert_str(&text.replace("\r\n", "\n").replace('\r', "\n"));
+            }
+        }
+        AppAction::DeleteChar => state.delete_char(),
+        AppAction::DeleteCharForward => state.delete_char_forward(),
+        AppAction::DeleteWordBack => state.delete_word_back(),
+        AppAction::DeleteWordForward => state.delete_word_forward(),
+        AppAction::MoveCursorWordLeft => state.move_cursor_word_left(),
+        AppAction::MoveCursorWordRight => state.move_cursor_word_right(),
+        AppAction::MoveCursorLeft => state.move_cursor_left(),
+        AppAction::MoveCursorRight => state.move_cursor_right(),
+        AppAction::MoveCursorUp => {
+            state.move_cursor_line(-1);
+        }
+        AppAction::MoveCursorDown => {
+            state.move_cursor_line(1);
+        }
+        AppAction::MoveCursorStart => state.move_cursor_line_start(),
+        AppAction::MoveCursorEnd => state.move_cursor_line_end(),
+        AppAction::ClearInput => state.clear_input(),
+
+        // Scrolling
+        AppAction::ScrollUp(n) => state.scroll_accelerated(n, true),
+        AppAction::ScrollDown(n) => state.scroll_accelerated(n, false),
+        AppAction::ScrollToTop => {
+            // Set to max value to show oldest messages
+            state.chat_scroll = usize::MAX / 2;
+        }
+        AppAction::ScrollToBottom => state.scroll_to_bottom(),
+        AppAction::PageUp => state.scroll_up(10),
+        AppAction::PageDown => state.scroll_down(10),
+
+        // Marks
+        AppAction::BeginMark => state.pending_key = Some(PendingKey::Mark),
+        AppAction::BeginJump => state.pending_key = Some(PendingKey::Jump),
+        AppAction::CancelPendingKey => {}
+        AppAction::BeginRecord => state.pending_key = Some(PendingKey::Record),
+        AppAction::BeginReplay => state.pending_key = Some(PendingKey::Replay),
+        AppAction::StartRecording(register) => {
+            state.recording = Some((register, Vec::new()));
+            state.set_status(format!("Recording @{} (Q to stop)", register));
+        }
+        AppAction::StopRecording => {
+            if let Some((register, count)) = state.stop_recording() {
+                state.set_status(format!("Recorded @{} ({} keys)", register, count));
+            }
+        }
+        AppAction::ReplayMacro(register) => {
+            if let Err(e) = state.replay_macro(register) {
+                state.set_warning(e);
+            }
+        }
+        AppAction::SetMark(letter) => match state.set_mark(letter) {
+            Ok(()) => {
+                state.set_status(format!("Mark '{}' set", letter));
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after setting mark: {}", e);
+                }
+            }
+            Err(e) => state.set_error(e),
+        },
+        AppAction::JumpToMark(letter) => match state.jump_to_mark(letter) {
+            Ok(()) => state.set_status(format!("Jumped to mark '{}'", letter)),
+            Err(e) => state.set_error(e),
+        },
+
+        // Downloads
+        AppAction::OpenDownloads => {
+            state.input_mode = InputMode::Downloads;
+        }
+        AppAction::CloseDownloads => {
+            state.input_mode = InputMode::Normal;
+        }
+        AppAction::PullInsertChar(c) => state.pull_input.push(c),
+        AppAction::PullDeleteChar => {
+            pop_grapheme(&mut state.pull_input);
+        }
+        AppAction::NextPullSuggestion => {
+            state.pull_selected_idx = (state.pull_selected_idx + 1) % POPULAR_MODELS.len();
+        }
+        AppAction::PrevPullSuggestion => {
+            state.pull_selected_idx = state
+                .pull_selected_idx
+                .checked_sub(1)
+                .unwrap_or(POPULAR_MODELS.len() - 1);
+        }
+        // The pull itself is started by the main loop's download worker
+        AppAction::QueuePull => {
+            if let Some(model) = state.pull_target() {
+                if state.queue_download(model.clone()) {
+                    info!("Queued pull: {}", model);
+                    state.set_status(format!("Queued {}", model));
+                    state.pull_input.clear();
+                } else {
+                    state.set_warning(format!("{} is already queued", model));
+                }
+            }
+        }
+        AppAction::ClearFinishedDownloads => {
+            state.downloads.retain(Download::is_pending);
+        }
+
+        // Missing model prompt; the pull is started by the main loop
+        AppAction::ConfirmPullMissing => {
+            state.input_mode = InputMode::Normal;
+            if let Some(model) = state.missing_model.take() {
+                info!("Queued pull of missing model: {}", model);
+                state.queue_download(model.clone());
+                state.set_status(format!("Downloading {} (D for progress), then resend", model));
+            }
+        }
+        AppAction::CancelPullMissing => {
+            state.missing_model = None;
+            state.input_mode = InputMode::Normal;
+        }
+
+        // Patches
+        AppAction::RequestApplyPatch => {
+            if let Err(e) = permissions::check(&state.config.permissions, Capability::FileWrite) {
+                state.set_error(e.to_string());
+                return;
+            }
+            let diff = state
+                .active_session()
+                .and_then(|s| s.last_assistant_message())
+                .and_then(|m| patch::extract_diff(&m.content));
+            match diff.as_deref().map(patch::parse) {
+                None => state.set_warning("No diff found in the last response"),
+                Some(Err(e)) => state.set_error(e.to_string()),
+                Some(Ok(patches)) => {
+                    state.pending_patch = Some(patches);
+                    state.input_mode = InputMode::PatchConfirm;
+                }
+            }
+        }
+        AppAction::ConfirmApplyPatch => {
+            state.input_mode = InputMode::Normal;
+            let patches = state.pending_patch.take().unwrap_or_default();
+            if let Err(e) = permissions::check(&state.config.permissions, Capability::FileWrite) {
+                state.set_error(e.to_string());
+                return;
+            }
+            let base = std::env::current_dir().unwrap_or_default();
+            match patch::apply_all(&patches, &base) {
+                Ok(()) => {
+                    let applied: Vec<String> = patches.iter().map(|p| p.path.display().to_string()).collect();
+                    info!("Patched {}", applied.join(", "));
+                    state.set_status(format!("Patched {}", applied.join(", ")));
+                }
+                Err((path, e)) => {
+                    warn!("Failed to apply patch to {:?}: {}", path, e);
+                    state.set_error(format!("{}: {} (no files changed)", path.display(), e));
+                }
+            }
+        }
+        AppAction::CancelApplyPatch => {
+            state.pending_patch = None;
+            state.input_mode = InputMode::Normal;
+        }
+
+        // Generation
+        AppAction::AbortGeneration => {
+            if state.generating() {
+                info!("Generation interrupted");
+                state.abort_generation();
+                state.set_status("Generation interrupted");
+            }
+        }
+
+        // Misc
+        AppAction::ExportMarkdown => match state.export_active_session() {
+            None => state.set_status("No active session to export"),
+            Some(Ok(path)) => {
+                info!("Exported session to {:?}", path);
+                state.set_status(format!("Exported to {}", path.display()));
+            }
+            Some(Err(e)) => {
+                warn!("Export failed: {}", e);
+                state.set_error(format!("Export failed: {}", e));
+            }
+        },
+        AppAction::CopySessionMarkdown => copy_markdown(state, None),
+        AppAction::ExportToVault => match state.export_to_vault() {
+            None if state.config.export.vault.is_none() => {
+                state.set_status("No vault configured (set [export] vault)");
+            }
+            None => state.set_status("No active session to export"),
+            Some(Ok(path)) => {
+                info!("Exported session to vault: {:?}", path);
+                state.set_status(format!("Saved note {}", path.display()));
+            }
+            Some(Err(e)) => {
+                warn!("Vault export failed: {}", e);
+                state.set_error(format!("Vault export failed: {}", e));
+            }
+        },
+        AppAction::ToggleFlag => {
+            match state.active_session_mut().and_then(|s| s.toggle_flag_last_response()) {
+                Some(true) => state.set_status("Response flagged (excluded from datasets)"),
+                Some(false) => state.set_status("Response unflagged"),
+                None => state.set_status("No response to flag"),
+            }
+        }
+        // Message selection
+        AppAction::EnterSelectMode => {
+            let count = state.active_session().map_or(0, |s| s.messages.len());
+            if count == 0 {
+                state.set_status("No messages to select");
+            } else {
+                // Start from the newest message, or the top of the view when scrolled back
+                let idx = if state.chat_scroll == 0 {
+                    count - 1
+                } else {
+                    state.chat_viewport.message_at(state.chat_scroll).unwrap_or(count - 1)
+                };
+                state.input_mode = InputMode::MessageSelect;
+                state.select_message(idx);
+            }
+        }
+        AppAction::ExitSelectMode => {
+            state.select_anchor = None;
+            state.input_mode = InputMode::Normal;
+        }
+        AppAction::ToggleRangeSelect => {
+            state.select_anchor = match state.select_anchor {
+                Some(_) => None,
+                None => Some(state.selected_message),
+            };
+        }
+        AppAction::SelectNext => state.select_message(state.selected_message + 1),
+        AppAction::SelectPrev => state.select_message(state.selected_message.saturating_sub(1)),
+        AppAction::SelectFirst => state.select_message(0),
+        AppAction::SelectLast => state.select_message(usize::MAX),
+        AppAction::CopySelected => {
+            let range = state.selection();
+            let count = range.clone().count();
+            let content = state
+                .active_session()
+                .and_then(|s| s.messages.get(range))
+                .map(|messages| messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>().join("\n\n"))
+                .unwrap_or_default();
+            match state.yank(&content) {
+                Ok(()) if count == 1 => state.set_status("Message copied to clipboard"),
+                Ok(()) => state.set_status(format!("{} messages copied to clipboard", count)),
+                Err(e) => state.set_error(e.to_string()),
+            }
+        }
+        AppAction::CopySelectedMarkdown => {
+            let range = state.selection();
+            copy_markdown(state, Some(range));
+        }
+        AppAction::ExportSelected => match state.export_selection() {
+            None => state.set_status("Nothing selected to export"),
+            Some(Ok(path)) => {
+                info!("Exported messages to {:?}", path);
+                state.set_status(format!("Exported to {}", path.display()));
+            }
+            Some(Err(e)) => {
+                warn!("Export failed: {}", e);
+                state.set_error(format!("Export failed: {}", e));
+            }
+        },
+        AppAction::DeleteSelected => {
+            let range = state.selection();
+            let is_streaming = state
+                .active_session()
+                .and_then(|s| s.messages.get(range.clone()))
+                .is_some_and(|messages| messages.iter().any(|m| m.streaming));
+            if is_streaming {
+                state.set_status("Can't delete a message while it is generating");
+            } else if let Some(session) = state.active_session_mut() {
+                let start = *range.start();
+                let role = session.messages.get(start).map(|m| m.role);
+                match (session.delete_messages(range), role) {
+                    (0, _) | (_, None) => return,
+                    (1, Some(role)) => {
+                        info!("Deleted {} message", role);
+                        state.set_status(format!("Deleted {} message", role));
+                    }
+                    (n, _) => {
+                        info!("Deleted {} messages", n);
+                        state.set_status(format!("Deleted {} messages", n));
+                    }
+                }
+                state.select_anchor = None;
+                if state.active_session().is_some_and(|s| s.messages.is_empty()) {
+                    state.input_mode = InputMode::Normal;
+                } else {
+                    state.select_message(start);
+                }
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after deleting message: {}", e);
+                }
+            }
+        }
+        AppAction::EditSelected => {
+            if let Some(content) = state.selected().map(|m| m.content.clone()) {
+                state.input = content;
+                state.move_cursor_end();
+                state.input_mode = InputMode::Editing;
+            }
+        }
+        AppAction::InspectSelected => {
+            if state.selected().is_some() {
+                state.input_mode = InputMode::MessageInspect;
+            }
+        }
+        AppAction::CloseInspect => state.input_mode = InputMode::MessageSelect,
+        AppAction::ToggleThinking => match state.toggle_selected_thinking() {
+            Some(true) => state.set_status("Reasoning expanded"),
+            Some(false) => state.set_status("Reasoning collapsed"),
+            None => state.set_status("No reasoning in this message"),
+        },
+        AppAction::ForkAtSelected => {
+            if state.streaming {
+                state.set_status("Can't branch while a response is generating");
+            } else if let Some(name) = state.fork_at_selected().map(|s| s.name.clone()) {
+                info!("Forked session: {}", name);
+                state.set_status(format!("Branched into \"{}\"", name));
+                state.select_anchor = None;
+                state.input_mode = InputMode::Normal;
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after forking: {}", e);
+                }
+            }
+        }
+
+        // Code blocks
+        AppAction::CopyCodeBlock => {
+            let mut blocks = state
+                .active_session()
+                .and_then(|s| s.last_assistant_message())
+                .map(|m| code_blocks(&m.content))
+                .unwrap_or_default();
+            match blocks.len() {
+                0 => state.set_status("No code blocks in the last response"),
+                1 => copy_code_block(state, &blocks.remove(0)),
+                n => {
+                    // Preselect the most recent block
+                    state.code_picker = blocks;
+                    state.code_picker_idx = n - 1;
+                    state.input_mode = InputMode::CodePicker;
+                }
+            }
+        }
+        AppAction::NextCodeBlock => {
+            if !state.code_picker.is_empty() {
+                state.code_picker_idx = (state.code_picker_idx + 1) % state.code_picker.len();
+            }
+        }
+        AppAction::PrevCodeBlock => {
+            if !state.code_picker.is_empty() {
+                state.code_picker_idx = state
+                    .code_picker_idx
+                    .checked_sub(1)
+                    .unwrap_or(state.code_picker.len() - 1);
+            }
+        }
+        AppAction::ConfirmCodeBlock => {
+            let idx = state.code_picker_idx;
+            apply_action(AppAction::PickCodeBlock(idx), state);
+        }
+        AppAction::PickCodeBlock(idx) => {
+            if let Some(block) = state.code_picker.get(idx).cloned() {
+                state.code_picker.clear();
+                state.input_mode = InputMode::Normal;
+                copy_code_block(state, &block);
+            }
+        }
+        AppAction::CloseCodePicker => {
+            state.code_picker.clear();
+            state.input_mode = InputMode::Normal;
+        }
+
+        // Yank history
+        AppAction::OpenYanks => {
+            if state.yanks.is_empty() {
+                state.set_status("Nothing copied yet");
+            } else {
+                state.yank_idx = 0;
+                state.input_mode = InputMode::Yanks;
+            }
+        }
+        AppAction::NextYank => {
+            if !state.yanks.is_empty() {
+                state.yank_idx = (state.yank_idx + 1) % state.yanks.len();
+            }
+        }
+        AppAction::PrevYank => {
+            if !state.yanks.is_empty() {
+                state.yank_idx = state.yank_idx.checked_sub(1).unwrap_or(state.yanks.len() - 1);
+            }
+        }
+        AppAction::RecopyYank => {
+            if let Some(text) = state.yanks.get(state.yank_idx).cloned() {
+                state.input_mode = InputMode::Normal;
+                match state.yank(&text) {
+                    Ok(()) => state.set_status("Copied to clipboard"),
+                    Err(e) => state.set_error(e.to_string()),
+                }
+            }
+        }
+        AppAction::InsertYank => {
+            if let Some(text) = state.yanks.get(state.yank_idx).cloned() {
+                state.insert_str(&text);
+                state.input_mode = InputMode::Editing;
+            }
+        }
+        AppAction::DeleteYank => {
+            if state.yanks.remove(state.yank_idx).is_some() {
+                if state.yanks.is_empty() {
+                    state.input_mode = InputMode::Normal;
+                }
+                state.yank_idx = state.yank_idx.min(state.yanks.len().saturating_sub(1));
+            }
+        }
+        AppAction::CloseYanks => state.input_mode = InputMode::Normal,
+
+        // Saved snippets
+        AppAction::OpenSnippets => {
+            state.snippet_idx = state.snippet_idx.min(state.snippets.len().saturating_sub(1));
+            state.input_mode = InputMode::Snippets;
+        }
+        AppAction::NextSnippet => {
+            if !state.snippets.is_empty() {
+                state.snippet_idx = (state.snippet_idx + 1) % state.snippets.len();
+            }
+        }
+        AppAction::PrevSnippet => {
+            if !state.snippets.is_empty() {
+                state.snippet_idx = state.snippet_idx.checked_sub(1).unwrap_or(state.snippets.len() - 1);
+            }
+        }
+        AppAction::InsertSnippet => {
+            if let Some(text) = state.snippets.get(state.snippet_idx).cloned() {
+                state.insert_str(&text);
+                state.input_mode = InputMode::Editing;
+            }
+        }
+        AppAction::SaveInputSnippet => {
+            let text = state.input.clone();
+            if state.add_snippet(&text) {
+                state.set_status("Input saved as a snippet");
+                save_snippets(state);
+            } else {
+                state.set_status("Type something first, then save it as a snippet");
+            }
+        }
+        AppAction::SaveSelectedSnippet => {
+            let range = state.selection();
+            let text = state
+                .active_session()
+                .and_then(|s| s.messages.get(range))
+                .map(|messages| messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>().join("\n\n"))
+                .unwrap_or_default();
+            if state.add_snippet(&text) {
+                state.set_status("Saved as a snippet");
+                save_snippets(state);
+            }
+        }
+        AppAction::DeleteSnippet => {
+            if state.snippet_idx < state.snippets.len() {
+                state.snippets.remove(state.snippet_idx);
+                state.snippet_idx = state.snippet_idx.min(state.snippets.len().saturating_sub(1));
+                save_snippets(state);
+            }
+        }
+        AppAction::CloseSnippets => state.input_mode = InputMode::Normal,
+
+        // System prompt library
+        AppAction::OpenPrompts => {
+            // Start on the prompt the session uses, if it is in the library
+            let current = state.active_session().and_then(|s| s.prompt_name.clone());
+            if let Some(idx) = current.and_then(|name| state.prompts.prompts.iter().position(|p| p.name == name)) {
+                state.prompt_idx = idx;
+            }
+            state.prompt_idx = state.prompt_idx.min(state.prompts.prompts.len().saturating_sub(1));
+            state.input_mode = InputMode::Prompts;
+        }
+        AppAction::NextPrompt => {
+            let count = state.prompts.prompts.len();
+            if count > 0 {
+                state.prompt_idx = (state.prompt_idx + 1) % count;
+            }
+        }
+        AppAction::PrevPrompt => {
+            let count = state.prompts.prompts.len();
+            if count > 0 {
+                state.prompt_idx = state.prompt_idx.checked_sub(1).unwrap_or(count - 1);
+            }
+        }
+        AppAction::UsePrompt => {
+            if let Some(name) = state.use_prompt(state.prompt_idx) {
+                state.input_mode = InputMode::Normal;
+                state.set_status(format!("System prompt: {}", name));
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after setting the system prompt: {}", e);
+                }
+            }
+        }
+        AppAction::ClearPrompt => {
+            if let Some(session) = state.active_session_mut() {
+                session.system_prompt = None;
+                session.prompt_name = None;
+            }
+            state.input_mode = InputMode::Normal;
+            state.set_status("System prompt cleared");
+            if let Err(e) = state.save_sessions() {
+                warn!("Failed to save sessions after clearing the system prompt: {}", e);
+            }
+        }
+        AppAction::ClosePrompts => state.input_mode = InputMode::Normal,
+
+        // Dry-run report
+        AppAction::ScrollDryRun(delta) => {
+            let lines = state.dry_run_report.as_deref().map_or(0, |r| r.lines().count());
+            let scroll = (i32::from(state.dry_run_scroll) + i32::from(delta)).clamp(0, lines.saturating_sub(1) as i32);
+            state.dry_run_scroll = scroll as u16;
+        }
+        AppAction::CopyDryRun => {
+            if let Some(report) = state.dry_run_report.clone() {
+                match state.yank(&report) {
+                    Ok(()) => state.set_status("Dry-run report copied to clipboard"),
+                    Err(e) => state.set_error(e.to_string()),
+                }
+            }
+        }
+        AppAction::CloseDryRun => state.input_mode = InputMode::Editing,
+
+        // Compare mode
+        AppAction::ToggleCompare => {
+            if state.stop_compare() {
+                state.set_status("Compare mode off");
+            } else {
+                state.input_mode = InputMode::ModelSelect;
+                state.set_status("Pick a model to compare with and press c");
+            }
+        }
+        AppAction::CompareWithSelected => {
+            let Some(model) = state.selected_model().map(|m| m.name.clone()) else {
+                return;
+            };
+            if state.generating() {
+                state.set_status("Wait for the response to finish (Ctrl+x stops it)");
+            } else if state.start_compare(&model).is_some() {
+                state.set_status(format!("Comparing {} with {}", state.current_model(), model));
+                state.input_mode = InputMode::Editing;
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after starting compare mode: {}", e);
+                }
+            } else {
+                state.set_status("Pick a model other than the session's own");
             }
         }
-        AppAction::RequestDeleteSession => {
-            // Check if we can delete (not the last session, not streaming)
-            if state.sessions.len() <= 1 {
-                state.set_error("Cannot delete the last remaining session");
-            } else if state.streaming {
-                state.set_error("Cannot delete session while receiving response");
+
+        // Split view
+        AppAction::ToggleSplit => {
+            let was_split = state.split.is_some();
+            if state.toggle_split() {
+                state.set_status("Split view (w to switch panes)");
+            } else if was_split {
+                // Compare mode answers into the pane just closed
+                state.stop_compare();
+                state.set_status("Split view closed");
             } else {
-                state.input_mode = InputMode::DeleteConfirm;
+                state.set_warning("Split view needs two sessions");
             }
         }
-        AppAction::ConfirmDeleteSession => {
-            let session_name = state.active_session()
-                .map(|s| s.name.clone())
-                .unwrap_or_default();
-            
-            state.delete_current_session();
-            info!("Session deleted: {}", session_name);
-            state.set_status(format!("Session deleted: {}", session_name));
-            state.input_mode = InputMode::Normal;
-            
-            // Save sessions after deletion
-            if let Err(e) = persistence::save_sessions(&state.sessions) {
-                warn!("Failed to save sessions after deletion: {}", e);
+        AppAction::SwitchPane => {
+            // Responses stream into the session they were asked in
+            if state.generating() {
+                state.set_status("Wait for the response to finish (Ctrl+x stops it)");
+            } else {
+                state.switch_split_focus();
             }
         }
-        AppAction::CancelDeleteSession => {
-            state.input_mode = InputMode::Normal;
+        AppAction::ScrollSplit(delta) => {
+            if let Some(split) = state.split.as_mut() {
+                let viewport = &split.viewport;
+                let max_scroll = viewport.total_lines.saturating_sub(viewport.visible_lines);
+                split.scroll = split.scroll.saturating_add_signed(isize::from(delta)).min(max_scroll);
+            }
         }
 
-        // Model selection
-        AppAction::OpenModelSelect => {
-            state.input_mode = InputMode::ModelSelect;
-            // Try to select current model in the list
-            if let Some(current) = state.active_session() {
-                if let Some(idx) = state.models.iter().position(|m| m.name == current.model) {
-                    state.selected_model_idx = idx;
-                }
+        // Context inspector
+        AppAction::OpenContext => {
+            // Start at the bottom, where the newest messages are
+            state.context_scroll = state.context_entries().len().saturating_sub(1) as u16;
+            state.input_mode = InputMode::ContextInspect;
+        }
+        AppAction::ScrollContext(delta) => {
+            let rows = state.context_entries().len();
+            let scroll = (i32::from(state.context_scroll) + i32::from(delta)).clamp(0, rows.saturating_sub(1) as i32);
+            state.context_scroll = scroll as u16;
+        }
+        AppAction::CloseContext => state.input_mode = InputMode::Normal,
+
+        // Response cache
+        AppAction::OpenCache => {
+            let pruned = state.cache.prune(state.config.cache.ttl(), chrono::Utc::now());
+            if pruned > 0 {
+                save_cache(state);
             }
+            state.cache_idx = state.cache.entries.len().saturating_sub(1);
+            state.input_mode = InputMode::Cache;
         }
-        AppAction::CloseModelSelect => {
-            state.input_mode = InputMode::Normal;
+        AppAction::NextCacheEntry => {
+            if !state.cache.entries.is_empty() {
+                state.cache_idx = (state.cache_idx + 1) % state.cache.entries.len();
+            }
         }
-        AppAction::NextModel => state.next_model(),
-        AppAction::PrevModel => state.prev_model(),
-        AppAction::ConfirmModel => {
-            if let Some(model) = state.selected_model() {
-                let model_name = model.name.clone();
-                state.set_model(&model_name);
-                state.set_status(format!("Switched to model: {}", model_name));
+        AppAction::PrevCacheEntry => {
+            if !state.cache.entries.is_empty() {
+                state.cache_idx = state.cache_idx.checked_sub(1).unwrap_or(state.cache.entries.len() - 1);
             }
-            state.input_mode = InputMode::Normal;
         }
-        AppAction::SelectModel(idx) => {
-            if idx < state.models.len() {
-                state.selected_model_idx = idx;
+        AppAction::DeleteCacheEntry => {
+            if state.cache_idx < state.cache.entries.len() {
+                state.cache.entries.remove(state.cache_idx);
+                state.cache_idx = state.cache_idx.min(state.cache.entries.len().saturating_sub(1));
+                save_cache(state);
             }
         }
+        AppAction::ClearCache => {
+            let count = state.cache.entries.len();
+            state.cache.entries.clear();
+            state.cache_idx = 0;
+            save_cache(state);
+            state.set_status(format!("Cleared {} cached responses", count));
+        }
+        AppAction::CloseCache => state.input_mode = InputMode::Normal,
 
-        // Input
-        AppAction::EnterEditMode => {
-            state.input_mode = InputMode::Editing;
+        // Anonymized export
+        AppAction::OpenRedactReview => match Redactor::new(&state.config.redact) {
+            Ok(redactor) => {
+                if let Some(session) = state.active_session().filter(|s| !s.messages.is_empty()) {
+                    state.redact_review = Some(RedactReview::new(session, redactor));
+                    state.input_mode = InputMode::RedactReview;
+                } else {
+                    state.set_status("Nothing to export");
+                }
+            }
+            Err(e) => state.set_error(format!("Invalid [redact] rule: {}", e)),
+        },
+        AppAction::NextRedaction | AppAction::PrevRedaction => {
+            if let Some(review) = state.redact_review.as_mut() {
+                review.step(matches!(action, AppAction::NextRedaction));
+            }
         }
-        AppAction::ExitEditMode => {
-            state.input_mode = InputMode::Normal;
+        AppAction::ExportRedacted if state.redact_review.as_ref().is_some_and(|r| r.pending > 0) => {
+            state.set_status("Wait for the model pass to finish before exporting");
         }
-        AppAction::SubmitMessage => {
-            // Don't submit empty messages or while streaming
-            if !state.input.trim().is_empty() && !state.streaming {
-                // Message submission is handled by the main loop
-                // This action just signals intent
+        AppAction::ExportRedacted => match state.export_redacted() {
+            None => {}
+            Some(Ok(path)) => {
+                info!("Exported anonymized session to {:?}", path);
+                state.set_status(format!("Exported to {}", path.display()));
+                state.redact_review = None;
+                state.input_mode = InputMode::Normal;
+            }
+            Some(Err(e)) => {
+                warn!("Export failed: {}", e);
+                state.set_error(format!("Export failed: {}", e));
             }
+        },
+        AppAction::CloseRedactReview => {
+            state.redact_review = None;
+            state.input_mode = InputMode::Normal;
         }
-        AppAction::InsertChar(c) => state.insert_char(c),
-        AppAction::DeleteChar => state.delete_char(),
-        AppAction::DeleteCharForward => state.delete_char_forward(),
-        AppAction::MoveCursorLeft => state.move_cursor_left(),
-        AppAction::MoveCursorRight => state.move_cursor_right(),
-        AppAction::MoveCursorStart => state.move_cursor_start(),
-        AppAction::MoveCursorEnd => state.move_cursor_end(),
-        AppAction::ClearInput => state.clear_input(),
 
-        // Scrolling
-        AppAction::ScrollUp(n) => state.scroll_up(n),
-        AppAction::ScrollDown(n) => state.scroll_down(n),
-        AppAction::ScrollToTop => {
-            // Set to max value to show oldest messages
-            state.chat_scroll = usize::MAX / 2;
+        AppAction::Rate(rating) => {
+            match state.active_session_mut().and_then(|s| s.rate_last_response(rating, None)) {
+                Some(Some(Rating::Up)) => state.set_status("Rated response +"),
+                Some(Some(Rating::Down)) => state.set_status("Rated response - (excluded from datasets)"),
+                Some(None) => state.set_status("Rating cleared"),
+                None => state.set_status("No response to rate"),
+            }
         }
-        AppAction::ScrollToBottom => state.scroll_to_bottom(),
-        AppAction::PageUp => state.scroll_up(10),
-        AppAction::PageDown => state.scroll_down(10),
-
-        // Misc
+        AppAction::OpenInPager
+        | AppAction::OpenInEditor
+        | AppAction::EditPrompts
+        | AppAction::React(_)
+        | AppAction::SubmitMessage
+        | AppAction::ContinueResponse
+        | AppAction::RetryFailed
+        | AppAction::AnonymizeWithModel => {}
         AppAction::ToggleHelp => {
             state.input_mode = if state.input_mode == InputMode::Help {
                 InputMode::Normal
@@ -315,7 +1542,19 @@ pub fn process_action(action: AppAction, state: &mut AppState) {
                 InputMode::Help
             };
         }
-        AppAction::ClearError => state.clear_error(),
+        // Handled by process_action
+        AppAction::Undo | AppAction::Redo => {}
+        AppAction::DismissBanner => {
+            let newest = state.banners().next().map(|n| n.severity);
+            if state.dismiss_banner() && newest == Some(Severity::Notice) {
+                // The only notice is the update one; don't repeat it for this version
+                if let Some(version) = state.update_available.take() {
+                    if let Err(e) = update::dismiss(&version) {
+                        warn!("Failed to record dismissed update: {}", e);
+                    }
+                }
+            }
+        }
         AppAction::Quit => state.should_quit = true,
 
         // Server actions are handled by the main loop
@@ -325,36 +1564,133 @@ pub fn process_action(action: AppAction, state: &mut AppState) {
     }
 }
 
+/// Close the file picker, back to the mode it was opened from
+fn close_picker(state: &mut AppState) {
+    let purpose = state.file_picker.take().map(|p| p.purpose);
+    state.input_mode = match purpose {
+        Some(PickPurpose::ExportDir) => InputMode::Normal,
+        _ => InputMode::Editing,
+    };
+}
+
+/// Act on a path chosen in the file picker
+///
+/// A file that can't be attached leaves the picker open to choose another.
+fn use_picked_path(state: &mut AppState, purpose: PickPurpose, path: PathBuf) {
+    match purpose {
+        PickPurpose::AttachFile => match Attachment::load(&path) {
+            Ok(attachment) => {
+                info!("Attached {:?}", path);
+                state.set_status(format!(
+                    "Attached {} ({} lines); sent with the next message",
+                    attachment.name,
+                    attachment.line_count()
+                ));
+                state.pending_attachments.push(attachment);
+            }
+            Err(e) => {
+                state.set_warning(format!("{}: {}", path.display(), e));
+                return;
+            }
+        },
+        PickPurpose::AttachImage => {
+            // Relative to the working directory where possible, for a shorter prompt
+            let cwd = std::env::current_dir().unwrap_or_default();
+            let shown = path.strip_prefix(&cwd).unwrap_or(&path);
+            state.insert_str(&format!("@{} ", shown.display()));
+        }
+        PickPurpose::ExportDir => match state.export_active_session_to(&path) {
+            None => state.set_status("No active session to export"),
+            Some(Ok(file)) => {
+                info!("Exported session to {:?}", file);
+                state.set_status(format!("Exported to {}", file.display()));
+            }
+            Some(Err(e)) => {
+                warn!("Export failed: {}", e);
+                state.set_error(format!("Export failed: {}", e));
+            }
+        },
+    }
+    close_picker(state);
+}
+
+/// Persist snippets, reporting a failure without losing the in-memory list
+fn save_snippets(state: &mut AppState) {
+    if let Err(e) = state.save_snippets() {
+        warn!("Failed to save snippets: {}", e);
+        state.set_warning(format!("Could not save snippets: {}", e));
+    }
+}
+
+/// Persist the response cache, reporting a failure
+fn save_cache(state: &mut AppState) {
+    if let Err(e) = state.save_cache() {
+        warn!("Failed to save response cache: {}", e);
+        state.set_warning(format!("Could not save the response cache: {}", e));
+    }
+}
+
+/// Copy the active session, or some of its messages, to the clipboard as Markdown
+fn copy_markdown(state: &mut AppState, range: Option<RangeInclusive<usize>>) {
+    let count = range.as_ref().map(|r| r.clone().count());
+    let Some(markdown) = state.session_markdown(range) else {
+        state.set_status("Nothing to copy");
+        return;
+    };
+    match state.yank(&markdown) {
+        Ok(()) => match count {
+            Some(1) => state.set_status("Message copied as Markdown"),
+            Some(n) => state.set_status(format!("{} messages copied as Markdown", n)),
+            None => state.set_status("Session copied as Markdown"),
+        },
+        Err(e) => state.set_error(e.to_string()),
+    }
+}
+
+/// Copy a code block to the clipboard and report it
+fn copy_code_block(state: &mut AppState, block: &CodeBlock) {
+    match state.yank(&block.code) {
+        Ok(()) => {
+            let lines = block.code.lines().count();
+            let lang = if block.lang.is_empty() { "code" } else { &block.lang };
+            state.set_status(format!("Copied {} block ({} lines)", lang, lines));
+        }
+        Err(e) => state.set_error(e.to_string()),
+    }
+}
+
+/// Help rows for keys that aren't in the keymap, by section
+const FIXED_KEYS_HELP: &[(&str, &str, &str)] = &[
+    ("Navigation", "  l / u", "Load / unload model (in picker)"),
+    ("Navigation", "  A-Z / PgUp/PgDn", "Jump by letter / page (in picker)"),
+    ("Chat", "  v (selecting)", "Select a range (y / C / E / d / b act on it)"),
+    ("Chat", "  t (selecting)", "Expand / collapse the message's reasoning"),
+    ("Chat", "  e / s / x", "Follow up: explain more / shorter / example"),
+];
+
 /// Get help text for keybindings
-pub fn get_help_text() -> Vec<(&'static str, &'static str)> {
-    vec![
-        ("General", ""),
-        ("  q / Ctrl+c", "Quit"),
-        ("  ?", "Toggle help"),
-        ("  Ctrl+r", "Refresh models"),
-        ("", ""),
-        ("Navigation", ""),
-        ("  Tab", "Next session"),
-        ("  Shift+Tab", "Previous session"),
-        ("  Ctrl+n", "New session"),
-        ("  Ctrl+w", "Delete session"),
-        ("  m", "Select model"),
-        ("", ""),
-        ("Chat", ""),
-        ("  i / Enter", "Start typing"),
-        ("  Esc", "Stop typing"),
-        ("  Enter", "Send message (while typing)"),
-        ("", ""),
-        ("Scrolling", ""),
-        ("  j/k or ↑/↓", "Scroll up/down"),
-        ("  Ctrl+u/d", "Page up/down"),
-        ("  g / G", "Top / Bottom"),
-        ("", ""),
-        ("Input Editing", ""),
-        ("  Ctrl+a/e", "Start/end of line"),
-        ("  Ctrl+u", "Clear input"),
-        ("  Ctrl+w", "Delete word"),
-    ]
+///
+/// Chat and typing keys come from `keymap`, so rebound keys show as they
+/// are bound.
+pub fn get_help_text(keymap: &Keymap) -> Vec<(String, &'static str)> {
+    let mut help = Vec::new();
+    for section in ["General", "Navigation", "Chat", "Scrolling", "Transcript view", "Input Editing"] {
+        if !help.is_empty() {
+            help.push((String::new(), ""));
+        }
+        help.push((section.to_string(), ""));
+        help.extend(keymap.help_section(section).into_iter().map(|(keys, desc)| (format!("  {}", keys), desc)));
+        help.extend(
+            FIXED_KEYS_HELP
+                .iter()
+                .filter(|(s, _, _)| *s == section)
+                .map(|(_, keys, desc)| (keys.to_string(), *desc)),
+        );
+    }
+    help.push((String::new(), ""));
+    help.push(("Commands".to_string(), ""));
+    help.extend(COMMANDS.iter().map(|(name, desc)| (name.to_string(), *desc)));
+    help
 }
 
 // ============================================================================
@@ -372,23 +1708,55 @@ pub fn handle_mouse_event(
 
     match mouse.kind {
         // Left click
+        MouseEventKind::Down(MouseButton::Left) if state.transcript => None,
         MouseEventKind::Down(MouseButton::Left) => {
             handle_mouse_click(x, y, state, layout)
         }
         
-        // Scroll wheel (anywhere in the window scrolls chat)
+        // Scroll wheel (anywhere in the window scrolls chat, or the split
+        // view's other pane when over it)
         MouseEventKind::ScrollUp => {
             // Only scroll in normal or editing mode, not in popups
             match state.input_mode {
+                InputMode::Normal | InputMode::Editing if contains(layout.split, x, y) => {
+                    Some(AppAction::ScrollSplit(3))
+                }
                 InputMode::Normal | InputMode::Editing => Some(AppAction::ScrollUp(3)),
                 InputMode::ModelSelect => Some(AppAction::PrevModel),
+                InputMode::CodePicker => Some(AppAction::PrevCodeBlock),
+                InputMode::Yanks => Some(AppAction::PrevYank),
+                InputMode::Snippets => Some(AppAction::PrevSnippet),
+                InputMode::Prompts => Some(AppAction::PrevPrompt),
+                InputMode::RedactReview => Some(AppAction::PrevRedaction),
+                InputMode::Cache => Some(AppAction::PrevCacheEntry),
+                InputMode::DryRun => Some(AppAction::ScrollDryRun(-3)),
+                InputMode::ContextInspect => Some(AppAction::ScrollContext(-3)),
+                InputMode::MessageSelect => Some(AppAction::SelectPrev),
+                InputMode::Search => Some(AppAction::PrevSearchResult),
+                InputMode::Palette => Some(AppAction::PrevPaletteEntry),
+                InputMode::FilePicker => Some(AppAction::PrevPickerEntry),
                 _ => None,
             }
         }
         MouseEventKind::ScrollDown => {
             match state.input_mode {
+                InputMode::Normal | InputMode::Editing if contains(layout.split, x, y) => {
+                    Some(AppAction::ScrollSplit(-3))
+                }
                 InputMode::Normal | InputMode::Editing => Some(AppAction::ScrollDown(3)),
                 InputMode::ModelSelect => Some(AppAction::NextModel),
+                InputMode::CodePicker => Some(AppAction::NextCodeBlock),
+                InputMode::Yanks => Some(AppAction::NextYank),
+                InputMode::Snippets => Some(AppAction::NextSnippet),
+                InputMode::Prompts => Some(AppAction::NextPrompt),
+                InputMode::RedactReview => Some(AppAction::NextRedaction),
+                InputMode::Cache => Some(AppAction::NextCacheEntry),
+                InputMode::DryRun => Some(AppAction::ScrollDryRun(3)),
+                InputMode::ContextInspect => Some(AppAction::ScrollContext(3)),
+                InputMode::MessageSelect => Some(AppAction::SelectNext),
+                InputMode::Search => Some(AppAction::NextSearchResult),
+                InputMode::Palette => Some(AppAction::NextPaletteEntry),
+                InputMode::FilePicker => Some(AppAction::NextPickerEntry),
                 _ => None,
             }
         }
@@ -415,6 +1783,54 @@ fn handle_mouse_click(
             // We keep it simple: clicking anywhere cancels
             return Some(AppAction::CancelDeleteSession);
         }
+        InputMode::PatchConfirm => {
+            return Some(AppAction::CancelApplyPatch);
+        }
+        InputMode::Downloads => {
+            return Some(AppAction::CloseDownloads);
+        }
+        InputMode::PullConfirm => {
+            return Some(AppAction::CancelPullMissing);
+        }
+        InputMode::CodePicker => {
+            return Some(AppAction::CloseCodePicker);
+        }
+        InputMode::Yanks => {
+            return Some(AppAction::CloseYanks);
+        }
+        InputMode::Snippets => {
+            return Some(AppAction::CloseSnippets);
+        }
+        InputMode::Prompts => {
+            return Some(AppAction::ClosePrompts);
+        }
+        InputMode::RedactReview => {
+            return Some(AppAction::CloseRedactReview);
+        }
+        InputMode::Cache => {
+            return Some(AppAction::CloseCache);
+        }
+        InputMode::DryRun => {
+            return Some(AppAction::CloseDryRun);
+        }
+        InputMode::ContextInspect => {
+            return Some(AppAction::CloseContext);
+        }
+        InputMode::MessageInspect => {
+            return Some(AppAction::CloseInspect);
+        }
+        InputMode::Rename => {
+            return Some(AppAction::CancelRename);
+        }
+        InputMode::FilePicker => {
+            return Some(AppAction::ClosePicker);
+        }
+        InputMode::Search => {
+            return Some(AppAction::CloseSearch);
+        }
+        InputMode::Palette => {
+            return Some(AppAction::ClosePalette);
+        }
         InputMode::ModelSelect => {
             // Clicking outside the popup closes it
             // The popup is centered, so we'd need popup bounds
@@ -425,6 +1841,10 @@ fn handle_mouse_click(
         _ => {}
     }
 
+    if contains(layout.tabs, x, y) {
+        return tab_at(state, layout.tabs, x).map(AppAction::SelectSession);
+    }
+
     // Check if click is in sidebar (sessions list area at top of sidebar)
     if contains(layout.sidebar, x, y) {
         return handle_sidebar_click(x, y, state, layout);
@@ -439,6 +1859,11 @@ fn handle_mouse_click(
         return None;
     }
     
+    // Clicking the split view's other pane focuses it
+    if contains(layout.split, x, y) {
+        return Some(AppAction::SwitchPane);
+    }
+
     // Check if click is in chat area
     if contains(layout.chat, x, y) {
         // Clicking in chat in normal mode does nothing special for now
@@ -478,7 +1903,7 @@ fn handle_sidebar_click(
     if y >= list_area_y && y < list_area_y + list_area_height {
         let clicked_idx = (y - list_area_y) as usize;
         
-        if clicked_idx < state.sessions.len() {
+        if clicked_idx < state.chat.sessions.len() {
             return Some(AppAction::SelectSession(clicked_idx));
         }
     }
@@ -495,8 +1920,28 @@ fn contains(rect: Rect, x: u16, y: u16) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::Message;
     use crate::config::Config;
 
+    #[test]
+    fn test_frame_pacing() {
+        let mut pacer = FramePacer::new(30);
+        let tick = Duration::from_millis(100);
+        assert!(pacer.due());
+
+        // Changes right after a frame wait out the interval
+        pacer.drawn();
+        assert!(!pacer.due());
+        assert_eq!(pacer.wait(tick), tick);
+        pacer.mark_dirty();
+        assert!(!pacer.due());
+        assert!(pacer.wait(tick) <= pacer.interval());
+
+        pacer.force();
+        assert!(pacer.due());
+        assert_eq!(FramePacer::new(0).interval(), Duration::from_secs(1));
+    }
+
     #[test]
     fn test_normal_mode_quit() {
         let config = Config::default();
@@ -508,6 +1953,91 @@ mod tests {
         assert!(matches!(action, Some(AppAction::Quit)));
     }
 
+    #[test]
+    fn test_quick_reactions() {
+        let mut config = Config::default();
+        config.reactions.0.insert("q".to_string(), "Never sent".to_string());
+        let mut state = AppState::new(config);
+
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert!(matches!(handle_key_event(key('s'), &state), Some(AppAction::React('s'))));
+        // Built-in bindings win
+        assert!(matches!(handle_key_event(key('q'), &state), Some(AppAction::Quit)));
+
+        assert_eq!(state.reaction_prompt('s'), None);
+        let session = state.active_session_mut().unwrap();
+        session.add_user_message("Tell me about ownership");
+        session.start_assistant_response();
+        session.append_to_response("Each value has one owner.");
+        session.finish_response();
+        assert_eq!(state.reaction_prompt('s').as_deref(), Some("Make that shorter."));
+    }
+
+    #[test]
+    fn test_rapid_enter_during_stream_completion() {
+        let mut state = AppState::new(Config::default());
+        state.input_mode = InputMode::Editing;
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        let press = |state: &mut AppState| match handle_key_event(enter, state) {
+            Some(AppAction::SubmitMessage) => state.take_submission(),
+            other => panic!("Enter gave {:?}", other),
+        };
+
+        // The first press sends; sending starts a stream
+        state.input = "first".to_string();
+        assert_eq!(press(&mut state).as_deref(), Some("first"));
+        state.streaming = true;
+        assert_eq!(press(&mut state), None);
+
+        // A draft typed during the stream waits for it instead of being lost
+        state.input = "second".to_string();
+        assert_eq!(press(&mut state), None);
+        assert_eq!(press(&mut state), None);
+        assert_eq!(state.input, "second");
+
+        // Once the stream completes, one press sends it once
+        state.streaming = false;
+        assert_eq!(press(&mut state).as_deref(), Some("second"));
+        assert_eq!(press(&mut state), None);
+    }
+
+    #[test]
+    fn test_transcript_view_is_read_only() {
+        let mut state = AppState::new(Config::default());
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        process_action(handle_key_event(key('o'), &state).unwrap(), &mut state);
+        assert!(state.transcript);
+
+        // Typing keys don't open the input
+        assert!(handle_key_event(key('i'), &state).is_none());
+        assert!(handle_key_event(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &state).is_none());
+        assert!(matches!(handle_key_event(key('j'), &state), Some(AppAction::ScrollDown(1))));
+
+        // Find works, and Esc clears it before leaving
+        process_action(handle_key_event(key('/'), &state).unwrap(), &mut state);
+        process_action(AppAction::FindInsertChar('x'), &mut state);
+        process_action(AppAction::ConfirmFind, &mut state);
+        assert!(state.transcript);
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        process_action(handle_key_event(esc, &state).unwrap(), &mut state);
+        assert!(state.transcript && state.find_query.is_empty());
+        process_action(handle_key_event(esc, &state).unwrap(), &mut state);
+        assert!(!state.transcript);
+    }
+
+    #[test]
+    fn test_cycle_timestamps() {
+        let mut state = AppState::new(Config::default());
+        let key = KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE);
+        let mut cycle = || {
+            process_action(handle_key_event(key, &state).unwrap(), &mut state);
+            state.config.ui.show_timestamps.then_some(state.config.ui.timestamp_style)
+        };
+        assert_eq!(cycle(), Some(TimestampStyle::Relative));
+        assert_eq!(cycle(), None);
+        assert_eq!(cycle(), Some(TimestampStyle::Clock));
+    }
+
     #[test]
     fn test_edit_mode_escape() {
         let config = Config::default();
@@ -531,4 +2061,360 @@ mod tests {
         
         assert!(matches!(action, Some(AppAction::Quit)));
     }
+
+    #[test]
+    fn test_yank_history() {
+        let mut config = Config::default();
+        config.ui.yank_history = 2;
+        let mut state = AppState::new(config);
+        for text in ["one", "two", "one", "three"] {
+            state.remember_yank(text);
+        }
+        assert_eq!(state.yanks, ["three", "one"]);
+
+        process_action(AppAction::OpenYanks, &mut state);
+        assert_eq!(state.input_mode, InputMode::Yanks);
+        process_action(AppAction::NextYank, &mut state);
+        state.input = "say ".to_string();
+        state.move_cursor_end();
+        process_action(AppAction::InsertYank, &mut state);
+        assert_eq!(state.input, "say one");
+        assert_eq!(state.input_mode, InputMode::Editing);
+
+        state.input_mode = InputMode::Yanks;
+        process_action(AppAction::DeleteYank, &mut state);
+        process_action(AppAction::DeleteYank, &mut state);
+        assert!(state.yanks.is_empty());
+        assert_eq!(state.input_mode, InputMode::Normal);
+    }
+
+    #[test]
+    fn test_snippets() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        state.demo = true; // don't touch the real snippets file
+        state.input = "Best,\nAda".to_string();
+        state.cursor_position = state.input.len();
+
+        let key = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        state.input_mode = InputMode::Editing;
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.input_mode, InputMode::Snippets);
+        process_action(AppAction::SaveInputSnippet, &mut state);
+        process_action(AppAction::SaveInputSnippet, &mut state);
+        assert_eq!(state.snippets, ["Best,\nAda"]);
+
+        state.input = "Thanks! ".to_string();
+        state.cursor_position = state.input.len();
+        process_action(AppAction::InsertSnippet, &mut state);
+        assert_eq!(state.input, "Thanks! Best,\nAda");
+        assert_eq!(state.input_mode, InputMode::Editing);
+
+        state.input_mode = InputMode::Snippets;
+        process_action(AppAction::DeleteSnippet, &mut state);
+        assert!(state.snippets.is_empty());
+        process_action(AppAction::InsertSnippet, &mut state);
+        assert_eq!(state.input_mode, InputMode::Snippets);
+    }
+
+    #[test]
+    fn test_alt_number_switches_session() {
+        let mut state = AppState::new(Config::default());
+        state.chat.new_session();
+        let key = KeyEvent::new(KeyCode::Char('1'), KeyModifiers::ALT);
+        let action = handle_key_event(key, &state).unwrap();
+        assert!(matches!(action, AppAction::SelectSession(0)));
+        process_action(action, &mut state);
+        assert_eq!(state.chat.active_session_idx, 0);
+
+        // Out of range does nothing; popups keep their keys
+        process_action(AppAction::SelectSession(8), &mut state);
+        assert_eq!(state.chat.active_session_idx, 0);
+        state.input_mode = InputMode::Help;
+        assert!(!matches!(handle_key_event(key, &state), Some(AppAction::SelectSession(_))));
+    }
+
+    #[test]
+    fn test_prompt_library() {
+        let mut config = Config::default();
+        config.storage.backend = crate::config::StorageBackend::Memory;
+        let mut state = AppState::new(config);
+        state.demo = true;
+
+        let key = KeyEvent::new(KeyCode::Char('I'), KeyModifiers::SHIFT);
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.input_mode, InputMode::Prompts);
+        process_action(AppAction::NextPrompt, &mut state);
+        process_action(AppAction::UsePrompt, &mut state);
+        let editor = state.prompts.prompts[1].clone();
+        let session = state.active_session().unwrap();
+        assert_eq!(session.prompt_name.as_deref(), Some(editor.name.as_str()));
+        assert_eq!(session.system_prompt.as_deref(), Some(editor.text.as_str()));
+        assert_eq!(state.input_mode, InputMode::Normal);
+
+        // Edits reach the session only with propagation on
+        let mut edited = state.prompts.clone();
+        edited.prompts[1].text = "Only fix typos.".to_string();
+        assert_eq!(state.reload_prompts(edited.clone()), 0);
+        assert_eq!(state.active_session().unwrap().system_prompt, Some(editor.text));
+        state.config.prompts.propagate = true;
+        assert_eq!(state.reload_prompts(edited), 1);
+        assert_eq!(state.active_session().unwrap().system_prompt.as_deref(), Some("Only fix typos."));
+
+        process_action(AppAction::ClearPrompt, &mut state);
+        assert_eq!(state.active_session().unwrap().system_prompt, None);
+        assert_eq!(state.active_session().unwrap().prompt_name, None);
+    }
+
+    #[test]
+    fn test_model_select_load_unload() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        state.input_mode = InputMode::ModelSelect;
+
+        let key = KeyEvent::new(KeyCode::Char('l'), KeyModifiers::NONE);
+        assert!(matches!(handle_key_event(key, &state), Some(AppAction::LoadModel)));
+
+        let key = KeyEvent::new(KeyCode::Char('u'), KeyModifiers::NONE);
+        assert!(matches!(handle_key_event(key, &state), Some(AppAction::UnloadModel)));
+    }
+
+    #[test]
+    fn test_record_and_replay_macro() {
+        let mut state = AppState::new(Config::default());
+        state.demo = true; // don't touch the real sessions file
+        let press = |state: &mut AppState, code: KeyCode, modifiers: KeyModifiers| {
+            let key = KeyEvent::new(code, modifiers);
+            state.record_key(key);
+            if let Some(action) = handle_key_event(key, state) {
+                process_action(action, state);
+            }
+        };
+
+        press(&mut state, KeyCode::Char('Q'), KeyModifiers::SHIFT);
+        press(&mut state, KeyCode::Char('a'), KeyModifiers::NONE);
+        assert!(state.recording.is_some());
+        press(&mut state, KeyCode::Char('p'), KeyModifiers::NONE);
+        press(&mut state, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut state, KeyCode::Char('Q'), KeyModifiers::SHIFT);
+        assert!(state.recording.is_none());
+        assert_eq!(state.macros[&'a'].len(), 2);
+
+        press(&mut state, KeyCode::Char('@'), KeyModifiers::NONE);
+        press(&mut state, KeyCode::Char('a'), KeyModifiers::NONE);
+        assert_eq!(state.replay.len(), 2);
+        assert_eq!(state.replay[0].code, KeyCode::Char('p'));
+
+        // @@ repeats the last macro; unknown registers warn
+        state.replay.clear();
+        assert_eq!(state.replay_macro('@'), Ok(2));
+        assert!(state.replay_macro('z').is_err());
+
+        // A macro that replays itself stops, even though the queue never
+        // holds more than one copy of it
+        state.replay.clear();
+        state.macros.insert('b', vec![KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE); 600]);
+        let mut replays = 0;
+        while state.replay_macro('b').is_ok() {
+            replays += 1;
+            state.replay.pop_front();
+        }
+        assert!(replays < 20);
+        assert!(state.replay.is_empty());
+        assert!(!state.stop_replay());
+    }
+
+    #[test]
+    fn test_undo_ui_changes() {
+        let mut state = AppState::new(Config::default());
+        state.demo = true; // don't touch the real sessions file
+        state.chat.models = vec![crate::ollama::ModelInfo::remote("llama3.2"), crate::ollama::ModelInfo::remote("mistral")];
+        process_action(AppAction::NewSession, &mut state);
+        let first = state.chat.sessions[0].id;
+
+        // Switch session, switch model
+        process_action(AppAction::SelectSession(0), &mut state);
+        process_action(AppAction::SelectModel(1), &mut state);
+        process_action(AppAction::ConfirmModel, &mut state);
+
+        // Neither scrolling nor banners are undo steps
+        process_action(AppAction::ScrollUp(1), &mut state);
+        state.set_error("Connection lost");
+        process_action(AppAction::DismissBanner, &mut state);
+
+        process_action(AppAction::Undo, &mut state);
+        assert_eq!(state.banners().count(), 0);
+        assert_eq!(state.current_model(), state.chat.sessions[1].model);
+        assert_ne!(state.current_model(), "mistral");
+        process_action(AppAction::Undo, &mut state);
+        assert_ne!(state.active_session().unwrap().id, first);
+
+        process_action(AppAction::Redo, &mut state);
+        assert_eq!(state.active_session().unwrap().id, first);
+        process_action(AppAction::Redo, &mut state);
+        assert_eq!(state.current_model(), "mistral");
+    }
+
+    #[test]
+    fn test_command_palette() {
+        let mut state = AppState::new(Config::default());
+        let press = |state: &mut AppState, code| {
+            let action = handle_key_event(KeyEvent::new(code, KeyModifiers::NONE), state).unwrap();
+            process_action(action, state);
+        };
+        press(&mut state, KeyCode::Char(':'));
+        assert_eq!(state.input_mode, InputMode::Palette);
+
+        // Words match the name or the description, in any order
+        for c in "width zen".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        assert_eq!(state.palette_entries().len(), 1);
+        press(&mut state, KeyCode::Enter);
+        assert!(state.zen);
+        assert_eq!(state.input_mode, InputMode::Normal);
+
+        // The palette doesn't list itself
+        assert!(crate::keymap::palette("palette").is_empty());
+    }
+
+    #[test]
+    fn test_message_select_mode() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        let session = state.active_session_mut().unwrap();
+        session.messages.push(Message::user("one"));
+        session.messages.push(Message::assistant("two"));
+        session.messages.push(Message::user("three"));
+
+        let key = KeyEvent::new(KeyCode::Char('V'), KeyModifiers::SHIFT);
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.input_mode, InputMode::MessageSelect);
+        assert_eq!(state.selected_message, 2);
+
+        let key = KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE);
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.selected().unwrap().content, "two");
+
+        state.demo = true; // don't touch the real sessions file
+        process_action(AppAction::DeleteSelected, &mut state);
+        assert_eq!(state.active_session().unwrap().messages.len(), 2);
+        assert_eq!(state.selected().unwrap().content, "three");
+
+        process_action(AppAction::EditSelected, &mut state);
+        assert_eq!(state.input_mode, InputMode::Editing);
+        assert_eq!(state.input, "three");
+    }
+
+    #[test]
+    fn test_message_range_delete() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        state.demo = true; // don't touch the real sessions file
+        let session = state.active_session_mut().unwrap();
+        for text in ["one", "two", "three", "four"] {
+            session.messages.push(Message::user(text));
+        }
+        state.input_mode = InputMode::MessageSelect;
+        state.select_message(2);
+
+        let v = KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE);
+        process_action(handle_key_event(v, &state).unwrap(), &mut state);
+        state.select_message(1);
+        assert_eq!(state.selection(), 1..=2);
+
+        // Esc cancels the range before leaving select mode
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert!(matches!(handle_key_event(esc, &state), Some(AppAction::ToggleRangeSelect)));
+
+        process_action(AppAction::DeleteSelected, &mut state);
+        let remaining: Vec<_> = state.active_session().unwrap().messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(remaining, ["one", "four"]);
+        assert_eq!(state.select_anchor, None);
+        assert_eq!(state.selected().unwrap().content, "four");
+    }
+
+    #[test]
+    fn test_code_picker_preselects_latest() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        let reply = "```sh\nls\n```\nthen\n```sh\npwd\n```";
+        state.active_session_mut().unwrap().messages.push(Message::assistant(reply));
+
+        let key = KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE);
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.input_mode, InputMode::CodePicker);
+        assert_eq!(state.code_picker_idx, 1);
+
+        process_action(AppAction::NextCodeBlock, &mut state);
+        assert_eq!(state.code_picker_idx, 0);
+        process_action(AppAction::CloseCodePicker, &mut state);
+        assert_eq!(state.input_mode, InputMode::Normal);
+        assert!(state.code_picker.is_empty());
+    }
+
+    #[test]
+    fn test_mark_keys() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+
+        let key = KeyEvent::new(KeyCode::Char('m'), KeyModifiers::NONE);
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.pending_key, Some(PendingKey::Mark));
+
+        let key = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE);
+        assert!(matches!(handle_key_event(key, &state), Some(AppAction::SetMark('a'))));
+
+        // Any other key cancels
+        let key = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.pending_key, None);
+
+        let key = KeyEvent::new(KeyCode::Char('M'), KeyModifiers::SHIFT);
+        assert!(matches!(handle_key_event(key, &state), Some(AppAction::OpenModelSelect)));
+    }
+
+    #[test]
+    fn test_pull_missing_model() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        state.missing_model = Some("llama9".to_string());
+        state.input_mode = InputMode::PullConfirm;
+
+        let key = KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE);
+        process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        assert_eq!(state.input_mode, InputMode::Normal);
+        assert_eq!(state.downloads.len(), 1);
+        assert_eq!(state.downloads[0].model, "llama9");
+        assert!(state.missing_model.is_none());
+    }
+
+    #[test]
+    fn test_downloads_queue_pull() {
+        let config = Config::default();
+        let mut state = AppState::new(config);
+        process_action(AppAction::OpenDownloads, &mut state);
+        assert_eq!(state.input_mode, InputMode::Downloads);
+
+        // Typing goes to the pull entry, not the chat input
+        for c in "phi3".chars() {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            process_action(handle_key_event(key, &state).unwrap(), &mut state);
+        }
+        assert_eq!(state.pull_input, "phi3");
+        assert!(state.input.is_empty());
+
+        process_action(AppAction::QueuePull, &mut state);
+        process_action(AppAction::PullInsertChar('x'), &mut state);
+        process_action(AppAction::PullDeleteChar, &mut state);
+        process_action(AppAction::QueuePull, &mut state);
+        assert_eq!(state.downloads.len(), 2);
+        assert_eq!(state.downloads[0].model, "phi3");
+        assert_eq!(state.downloads[1].model, POPULAR_MODELS[0]);
+
+        // Duplicates of pending pulls are rejected
+        process_action(AppAction::QueuePull, &mut state);
+        assert_eq!(state.downloads.len(), 2);
+        assert!(state.banners().next().is_some());
+    }
 }
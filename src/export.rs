@@ -0,0 +1,134 @@
+//! Session export
+//!
+//! Renders sessions to text for reading outside the TUI, and to chat-format
+//! JSONL for fine-tuning.
+
+use serde_json::json;
+
+use crate::app::{ChatSession, Message};
+use crate::ollama::Role;
+
+/// Plain-text transcript of a session, as shown in the chat area
+pub fn transcript(session: &ChatSession) -> String {
+    let mut out = format!("{}\nModel: {}\n", session.name, session.model);
+    if let Some(system) = &session.system_prompt {
+        out.push_str(&format!("System prompt: {}\n", system));
+    }
+
+    for message in &session.messages {
+        let role = match message.role {
+            Role::User => "You",
+            Role::Assistant => "Assistant",
+            Role::System => "System",
+            Role::Tool => "Tool",
+        };
+        out.push_str(&format!("\n{} ({}):\n", role, message.timestamp.format("%Y-%m-%d %H:%M")));
+
+        if !message.thinking.is_empty() {
+            for line in message.thinking.trim().lines() {
+                out.push_str(&format!("  ┆ {}\n", line));
+            }
+            out.push('\n');
+        }
+        for line in message.content.lines() {
+            out.push_str(&format!("  {}\n", line));
+        }
+        if message.interrupted {
+            out.push_str("  [interrupted]\n");
+        }
+        for path in &message.images {
+            out.push_str(&format!("  [image: {}]\n", path.display()));
+        }
+    }
+
+    out
+}
+
+/// Chat-format JSONL, one `{"messages": [...]}` line per session
+///
+/// Only complete user/assistant exchanges are kept: unanswered prompts,
+/// interrupted or failed responses, and (with `exclude_flagged`) flagged or
+/// down-rated exchanges are dropped. Sessions with no exchanges left are skipped.
+/// `progress` gets the number of sessions done before each one starts.
+pub fn dataset_jsonl(sessions: &[&ChatSession], exclude_flagged: bool, mut progress: impl FnMut(usize)) -> String {
+    let mut out = String::new();
+    for (done, session) in sessions.iter().enumerate() {
+        progress(done);
+        let exchanges = exchanges(session)
+            .filter(|(_, reply)| usable_reply(reply) && !(exclude_flagged && reply.excluded_from_datasets()))
+            .collect::<Vec<_>>();
+        if exchanges.is_empty() {
+            continue;
+        }
+
+        let mut messages = Vec::with_capacity(exchanges.len() * 2 + 1);
+        if let Some(system) = &session.system_prompt {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        for (prompt, reply) in exchanges {
+            messages.push(json!({ "role": "user", "content": prompt.content }));
+            messages.push(json!({ "role": "assistant", "content": reply.content }));
+        }
+        out.push_str(&json!({ "messages": messages }).to_string());
+        out.push('\n');
+    }
+    out
+}
+
+/// User messages paired with the assistant reply that follows them
+fn exchanges(session: &ChatSession) -> impl Iterator<Item = (&Message, &Message)> {
+    session
+        .messages
+        .windows(2)
+        .filter(|pair| pair[0].role == Role::User && pair[1].role == Role::Assistant)
+        .map(|pair| (&pair[0], &pair[1]))
+}
+
+fn usable_reply(reply: &Message) -> bool {
+    !reply.streaming
+        && !reply.interrupted
+        && !reply.content.trim().is_empty()
+        && !reply.content.starts_with("[Error:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript() {
+        let mut session = ChatSession::new("Rust questions", "llama3.2");
+        session.messages.push(Message::user("What is a lifetime?"));
+        session.messages.push(Message::assistant("A scope.\nMore or less."));
+
+        let text = transcript(&session);
+        assert!(text.starts_with("Rust questions\nModel: llama3.2\n"));
+        assert!(text.contains("  What is a lifetime?\n"));
+        assert!(text.contains("  A scope.\n  More or less.\n"));
+    }
+
+    #[test]
+    fn test_dataset_jsonl() {
+        let mut session = ChatSession::new("Chat", "llama3.2");
+        session.system_prompt = Some("Be brief".to_string());
+        session.messages.push(Message::user("2+2?"));
+        session.messages.push(Message::assistant("4"));
+        session.messages.push(Message::user("Capital of France?"));
+        let mut bad = Message::assistant("Berlin");
+        bad.flagged = true;
+        session.messages.push(bad);
+        session.messages.push(Message::user("unanswered"));
+
+        let jsonl = dataset_jsonl(&[&session], true, |_| {});
+        assert_eq!(
+            jsonl,
+            "{\"messages\":[{\"content\":\"Be brief\",\"role\":\"system\"},\
+             {\"content\":\"2+2?\",\"role\":\"user\"},\
+             {\"content\":\"4\",\"role\":\"assistant\"}]}\n"
+        );
+        assert!(dataset_jsonl(&[&session], false, |_| {}).contains("Berlin"));
+
+        let empty = ChatSession::new("Empty", "llama3.2");
+        assert_eq!(dataset_jsonl(&[&empty], true, |_| {}), "");
+    }
+}
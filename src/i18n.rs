@@ -0,0 +1,114 @@
+//! Interface translations
+//!
+//! English strings in the code are the lookup keys: a locale bundle is a TOML
+//! file mapping them to translations, optionally grouped under tables such as
+//! `[help]` or `[popups]`, which are only there to keep the file readable:
+//!
+//! ```toml
+//! [help]
+//! "Toggle help" = "Mostrar u ocultar la ayuda"
+//! ```
+//!
+//! `ui.language` picks the bundle. A file in `locales/<language>.toml` under
+//! the config directory wins over the bundles shipped with ratatalk, and any
+//! string a bundle leaves out is shown in English.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::LocaleError;
+
+/// Bundles compiled into the binary
+const BUNDLED: &[(&str, &str)] = &[("es", include_str!("../locales/es.toml"))];
+
+/// Translations for one language
+#[derive(Debug, Clone, Default)]
+pub struct Locale {
+    strings: HashMap<String, String>,
+}
+
+impl Locale {
+    /// Load the bundle for `language`; English needs none
+    pub fn load(language: &str) -> Result<Self, LocaleError> {
+        let language = language.trim();
+        if language.is_empty() || language.eq_ignore_ascii_case("en") {
+            return Ok(Self::default());
+        }
+        if let Some(path) = user_bundle_path(language).filter(|p| p.is_file()) {
+            let contents = std::fs::read_to_string(&path).map_err(LocaleError::Read)?;
+            return Self::parse(&contents);
+        }
+        match BUNDLED.iter().find(|(name, _)| name.eq_ignore_ascii_case(language)) {
+            Some((_, contents)) => Self::parse(contents),
+            None => Err(LocaleError::Unknown(language.to_string())),
+        }
+    }
+
+    /// Parse a bundle, flattening its tables
+    pub fn parse(contents: &str) -> Result<Self, LocaleError> {
+        let table: toml::Table = toml::from_str(contents).map_err(LocaleError::Parse)?;
+        let mut strings = HashMap::new();
+        flatten(table, &mut strings);
+        Ok(Self { strings })
+    }
+
+    /// `text` in this language, or unchanged when there is no translation
+    pub fn tr<'a>(&'a self, text: &'a str) -> &'a str {
+        self.strings.get(text).map_or(text, String::as_str)
+    }
+
+    /// Translate a popup title, keeping the padding borders need
+    pub fn title(&self, text: &str) -> String {
+        format!(" {} ", self.tr(text))
+    }
+}
+
+fn flatten(table: toml::Table, strings: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        match value {
+            toml::Value::String(text) => {
+                strings.insert(key, text);
+            }
+            toml::Value::Table(inner) => flatten(inner, strings),
+            _ => {}
+        }
+    }
+}
+
+/// Where a user's own bundle for `language` would live
+fn user_bundle_path(language: &str) -> Option<PathBuf> {
+    let dir = Config::config_dir().ok()?;
+    Some(dir.join("locales").join(format!("{}.toml", language)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_with_fallback() {
+        let locale = Locale::parse(
+            r#"
+            "Quit" = "Salir"
+            [popups]
+            "Delete Session?" = "¿Borrar la sesión?"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(locale.tr("Quit"), "Salir");
+        assert_eq!(locale.title("Delete Session?"), " ¿Borrar la sesión? ");
+        assert_eq!(locale.tr("Toggle help"), "Toggle help");
+
+        assert_eq!(Locale::load("en").unwrap().tr("Quit"), "Quit");
+        assert!(matches!(Locale::load("xx"), Err(LocaleError::Unknown(_))));
+    }
+
+    #[test]
+    fn test_bundled_locales_parse() {
+        for (name, contents) in BUNDLED {
+            let locale = Locale::parse(contents).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_ne!(locale.tr("Toggle help"), "Toggle help", "{} has no help strings", name);
+        }
+    }
+}
@@ -0,0 +1,1085 @@
+//! Key bindings
+//!
+//! Every action reachable from the chat or while typing is declared once in
+//! [`ACTIONS`]: its name, what it does, its default keys and the views it
+//! works in. Key handling, the help popup and the command palette (`:`) all
+//! read the table, so a binding can't do one thing and be documented as
+//! another.
+//! `[keybindings.keys]` rebinds actions by name:
+//!
+//! ```toml
+//! [keybindings.keys]
+//! new_session = "Ctrl+t"
+//! page_down = ["PgDn", "Space"]
+//! ```
+//!
+//! Typed characters and the keys inside popups, pickers and select mode are
+//! fixed; each popup shows its own.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{AppAction, AppState, Rating};
+use crate::config::KeyList;
+use crate::error::KeymapError;
+use crate::picker::PickPurpose;
+
+/// Where an action's keys work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    /// Every mode, before its own keys
+    Any,
+    /// The chat in normal mode
+    Chat,
+    /// The read-only transcript view
+    Transcript,
+    /// Typing a message, where printable keys type
+    Editing,
+}
+
+/// A key and its modifiers, written like `Ctrl+n`, `G`, `Shift+Tab` or `PgDn`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// The key pressed in `event`
+    ///
+    /// Shift is dropped for characters, whose case already shows it, so `G`
+    /// matches however the terminal reports it.
+    pub fn from_event(event: &KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(event.code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code: event.code, modifiers }
+    }
+}
+
+impl FromStr for Key {
+    type Err = KeymapError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeymapError::InvalidKey(text.to_string());
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text.trim();
+        // A lone "+" is the key itself, not a separator
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "del" | "delete" => KeyCode::Delete,
+                "space" => KeyCode::Char(' '),
+                "up" | "↑" => KeyCode::Up,
+                "down" | "↓" => KeyCode::Down,
+                "left" | "←" => KeyCode::Left,
+                "right" | "→" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pgup" | "pageup" => KeyCode::PageUp,
+                "pgdn" | "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(invalid()),
+                },
+            },
+        };
+
+        // Shift is part of a character or of Shift+Tab, as in `from_event`
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            code => code,
+        };
+        if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl Key {
+    /// Whether the key types a character in the input
+    fn is_printable(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Up => f.write_str("↑"),
+            KeyCode::Down => f.write_str("↓"),
+            KeyCode::Left => f.write_str("←"),
+            KeyCode::Right => f.write_str("→"),
+            KeyCode::PageUp => f.write_str("PgUp"),
+            KeyCode::PageDown => f.write_str("PgDn"),
+            KeyCode::BackTab => f.write_str("Shift+Tab"),
+            KeyCode::Delete => f.write_str("Del"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// An action keys can be bound to
+pub struct ActionDef {
+    /// Name used in `[keybindings.keys]`
+    pub name: &'static str,
+    /// Help text, which is also the key for translations
+    pub description: &'static str,
+    /// Help section the action is listed under
+    pub section: &'static str,
+    pub keys: &'static [&'static str],
+    pub views: &'static [View],
+    /// What a key bound to it does, given the position of the pressed key
+    /// among the action's keys; `None` lets the key fall through to the
+    /// actions after this one, which is how Esc stops a generation, clears
+    /// a find or dismisses a banner depending on the state
+    pub action: fn(&AppState, usize) -> Option<AppAction>,
+}
+
+const CHAT: &[View] = &[View::Chat];
+const BOTH: &[View] = &[View::Chat, View::Transcript];
+const EDITING: &[View] = &[View::Editing];
+
+/// Every bindable action, in help order; the first match for a key wins
+pub const ACTIONS: &[ActionDef] = &[
+    // General
+    ActionDef {
+        name: "quit",
+        description: "Quit",
+        section: "General",
+        keys: &["q"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::Quit),
+    },
+    ActionDef {
+        name: "force_quit",
+        description: "Quit from anywhere",
+        section: "General",
+        keys: &["Ctrl+c", "Ctrl+q"],
+        views: &[View::Any],
+        action: |_, _| Some(AppAction::Quit),
+    },
+    ActionDef {
+        name: "help",
+        description: "Toggle help",
+        section: "General",
+        keys: &["?", "F1"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::ToggleHelp),
+    },
+    ActionDef {
+        name: "palette",
+        description: "Command palette: run any action by name",
+        section: "General",
+        keys: &[":"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenPalette),
+    },
+    ActionDef {
+        name: "refresh_models",
+        description: "Refresh models",
+        section: "General",
+        keys: &["Ctrl+r"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::RefreshModels),
+    },
+    ActionDef {
+        name: "stop",
+        description: "Stop generating and edit prompt",
+        section: "General",
+        keys: &["Ctrl+x", "Esc"],
+        views: CHAT,
+        action: |state, _| state.generating().then_some(AppAction::AbortGeneration),
+    },
+    ActionDef {
+        name: "cancel_find",
+        description: "Clear the find",
+        section: "General",
+        keys: &["Esc"],
+        views: BOTH,
+        action: |state, _| (!state.find_query.is_empty()).then_some(AppAction::CancelFind),
+    },
+    ActionDef {
+        name: "dismiss",
+        description: "Dismiss newest warning/error",
+        section: "General",
+        keys: &["Esc"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::DismissBanner),
+    },
+    ActionDef {
+        name: "undo",
+        description: "Undo a session or model switch",
+        section: "General",
+        keys: &["u"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::Undo),
+    },
+    ActionDef {
+        name: "redo",
+        description: "Redo what was undone",
+        section: "General",
+        keys: &["U"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::Redo),
+    },
+    // Navigation
+    ActionDef {
+        name: "next_session",
+        description: "Next session",
+        section: "Navigation",
+        keys: &["Tab"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::NextSession),
+    },
+    ActionDef {
+        name: "prev_session",
+        description: "Previous session",
+        section: "Navigation",
+        keys: &["Shift+Tab"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::PrevSession),
+    },
+    ActionDef {
+        name: "new_session",
+        description: "New session",
+        section: "Navigation",
+        keys: &["Ctrl+n"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::NewSession),
+    },
+    ActionDef {
+        name: "delete_session",
+        description: "Delete session",
+        section: "Navigation",
+        keys: &["Ctrl+w"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::RequestDeleteSession),
+    },
+    ActionDef {
+        name: "split",
+        description: "Split view: next session beside this one",
+        section: "Navigation",
+        keys: &["|"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::ToggleSplit),
+    },
+    ActionDef {
+        name: "switch_pane",
+        description: "Focus the other pane of the split view",
+        section: "Navigation",
+        keys: &["w"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::SwitchPane),
+    },
+    ActionDef {
+        name: "rename_session",
+        description: "Rename session",
+        section: "Navigation",
+        keys: &["r"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::RequestRename),
+    },
+    ActionDef {
+        name: "pin_session",
+        description: "Pin/unpin session",
+        section: "Navigation",
+        keys: &["p"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::TogglePin),
+    },
+    ActionDef {
+        name: "go_to_session",
+        description: "Switch to session 1-9",
+        section: "Navigation",
+        // The nth key picks the nth session
+        keys: &["Alt+1", "Alt+2", "Alt+3", "Alt+4", "Alt+5", "Alt+6", "Alt+7", "Alt+8", "Alt+9"],
+        views: &[View::Chat, View::Editing],
+        action: |_, nth| Some(AppAction::SelectSession(nth)),
+    },
+    ActionDef {
+        name: "search",
+        description: "Search all sessions",
+        section: "Navigation",
+        keys: &["/"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenSearch),
+    },
+    ActionDef {
+        name: "select_model",
+        description: "Select model",
+        section: "Navigation",
+        keys: &["M"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenModelSelect),
+    },
+    ActionDef {
+        name: "downloads",
+        description: "Download models",
+        section: "Navigation",
+        keys: &["D"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenDownloads),
+    },
+    // Chat
+    ActionDef {
+        name: "type",
+        description: "Start typing",
+        section: "Chat",
+        keys: &["i", "Enter"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::EnterEditMode),
+    },
+    ActionDef {
+        name: "compare",
+        description: "Compare mode: send each prompt to a second model too",
+        section: "Chat",
+        keys: &["K"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::ToggleCompare),
+    },
+    ActionDef {
+        name: "continue",
+        description: "Continue a cut-off response",
+        section: "Chat",
+        keys: &["c"],
+        views: CHAT,
+        action: |state, _| (!state.streaming).then_some(AppAction::ContinueResponse),
+    },
+    ActionDef {
+        name: "retry",
+        description: "Retry a failed message",
+        section: "Chat",
+        keys: &["R"],
+        views: CHAT,
+        action: |state, _| (!state.streaming).then_some(AppAction::RetryFailed),
+    },
+    ActionDef {
+        name: "apply_patch",
+        description: "Apply diff from last response",
+        section: "Chat",
+        keys: &["P"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::RequestApplyPatch),
+    },
+    ActionDef {
+        name: "transcript_view",
+        description: "Read-only transcript view (q to leave)",
+        section: "Chat",
+        keys: &["o"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenTranscript),
+    },
+    ActionDef {
+        name: "zen",
+        description: "Zen mode: only the chat, at a readable width",
+        section: "Chat",
+        keys: &["z"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::ToggleZen),
+    },
+    ActionDef {
+        name: "pager",
+        description: "Open transcript in $PAGER",
+        section: "Chat",
+        keys: &["v"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenInPager),
+    },
+    ActionDef {
+        name: "anonymized_export",
+        description: "Anonymized export: review redactions, m model pass, Enter export",
+        section: "Chat",
+        keys: &["A"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenRedactReview),
+    },
+    ActionDef {
+        name: "export_markdown",
+        description: "Export session to Markdown",
+        section: "Chat",
+        keys: &["E"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::ExportMarkdown),
+    },
+    ActionDef {
+        name: "export_vault",
+        description: "Save session as a note in the vault",
+        section: "Chat",
+        keys: &["O"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::ExportToVault),
+    },
+    ActionDef {
+        name: "export_to_folder",
+        description: "Export session to a chosen folder",
+        section: "Chat",
+        keys: &["W"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenFilePicker(PickPurpose::ExportDir)),
+    },
+    ActionDef {
+        name: "copy_session",
+        description: "Copy session as Markdown (C in select mode: selected message)",
+        section: "Chat",
+        keys: &["C"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::CopySessionMarkdown),
+    },
+    ActionDef {
+        name: "select_messages",
+        description: "Select messages (j/k move, y copy, d delete, e edit, b branch, s snippet, Enter inspect)",
+        section: "Chat",
+        keys: &["V"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::EnterSelectMode),
+    },
+    ActionDef {
+        name: "copy_code_block",
+        description: "Copy code block from last response",
+        section: "Chat",
+        keys: &["y"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::CopyCodeBlock),
+    },
+    ActionDef {
+        name: "yank_history",
+        description: "Yank history (Enter copy, Tab insert, d delete)",
+        section: "Chat",
+        keys: &["Y"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenYanks),
+    },
+    ActionDef {
+        name: "snippets",
+        description: "Snippets (Enter insert, a save input, d delete)",
+        section: "Chat",
+        keys: &["S"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenSnippets),
+    },
+    ActionDef {
+        name: "system_prompts",
+        description: "System prompt library (Enter use, c clear, e edit)",
+        section: "Chat",
+        keys: &["I"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenPrompts),
+    },
+    ActionDef {
+        name: "context",
+        description: "Context inspector: what the next request will send",
+        section: "Chat",
+        keys: &["B"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::OpenContext),
+    },
+    ActionDef {
+        name: "flag",
+        description: "Flag/unflag last response for datasets",
+        section: "Chat",
+        keys: &["X"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::ToggleFlag),
+    },
+    ActionDef {
+        name: "rate_up",
+        description: "Rate last response up",
+        section: "Chat",
+        keys: &["+"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::Rate(Rating::Up)),
+    },
+    ActionDef {
+        name: "rate_down",
+        description: "Rate last response down",
+        section: "Chat",
+        keys: &["-"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::Rate(Rating::Down)),
+    },
+    ActionDef {
+        name: "toggle_tools",
+        description: "Expand/collapse agent tool calls",
+        section: "Chat",
+        keys: &["T"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::ToggleToolOutput),
+    },
+    ActionDef {
+        name: "timestamps",
+        description: "Timestamps: clock / relative / hidden",
+        section: "Chat",
+        keys: &["t"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::CycleTimestamps),
+    },
+    ActionDef {
+        name: "theme",
+        description: "Next color theme",
+        section: "Chat",
+        keys: &["L"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::CycleTheme),
+    },
+    // Scrolling
+    ActionDef {
+        name: "scroll_up",
+        description: "Scroll up",
+        section: "Scrolling",
+        keys: &["k", "Up"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::ScrollUp(1)),
+    },
+    ActionDef {
+        name: "scroll_down",
+        description: "Scroll down",
+        section: "Scrolling",
+        keys: &["j", "Down"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::ScrollDown(1)),
+    },
+    ActionDef {
+        name: "page_up",
+        description: "Page up",
+        section: "Scrolling",
+        keys: &["Ctrl+u", "PgUp"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::PageUp),
+    },
+    ActionDef {
+        name: "page_down",
+        description: "Page down",
+        section: "Scrolling",
+        keys: &["Ctrl+d", "PgDn"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::PageDown),
+    },
+    ActionDef {
+        name: "top",
+        description: "Top",
+        section: "Scrolling",
+        keys: &["g", "Home"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::ScrollToTop),
+    },
+    ActionDef {
+        name: "bottom",
+        description: "Bottom",
+        section: "Scrolling",
+        keys: &["G", "End"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::ScrollToBottom),
+    },
+    ActionDef {
+        name: "set_mark",
+        description: "Set mark at current message (then a letter)",
+        section: "Scrolling",
+        keys: &["m"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::BeginMark),
+    },
+    ActionDef {
+        name: "jump_to_mark",
+        description: "Jump to mark (then a letter)",
+        section: "Scrolling",
+        keys: &["'"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::BeginJump),
+    },
+    ActionDef {
+        name: "record_macro",
+        description: "Record a macro of keys (then a letter; again to stop)",
+        section: "Scrolling",
+        keys: &["Q"],
+        views: CHAT,
+        action: |state, _| {
+            Some(if state.recording.is_some() { AppAction::StopRecording } else { AppAction::BeginRecord })
+        },
+    },
+    ActionDef {
+        name: "replay_macro",
+        description: "Replay a macro (then a letter, or @ for the last one)",
+        section: "Scrolling",
+        keys: &["@"],
+        views: CHAT,
+        action: |_, _| Some(AppAction::BeginReplay),
+    },
+    ActionDef {
+        name: "find",
+        description: "Find in chat (n / N: next / previous)",
+        section: "Scrolling",
+        keys: &["Ctrl+f"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::OpenFind),
+    },
+    ActionDef {
+        name: "find_next",
+        description: "Next find match",
+        section: "Scrolling",
+        keys: &["n"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::FindNext),
+    },
+    ActionDef {
+        name: "find_prev",
+        description: "Previous find match",
+        section: "Scrolling",
+        keys: &["N"],
+        views: BOTH,
+        action: |_, _| Some(AppAction::FindPrev),
+    },
+    // Transcript view
+    ActionDef {
+        name: "view_page_up",
+        description: "Page up",
+        section: "Transcript view",
+        keys: &["b"],
+        views: &[View::Transcript],
+        action: |_, _| Some(AppAction::PageUp),
+    },
+    ActionDef {
+        name: "view_page_down",
+        description: "Page down",
+        section: "Transcript view",
+        keys: &["Space"],
+        views: &[View::Transcript],
+        action: |_, _| Some(AppAction::PageDown),
+    },
+    ActionDef {
+        name: "view_find",
+        description: "Find in the transcript",
+        section: "Transcript view",
+        keys: &["/"],
+        views: &[View::Transcript],
+        action: |_, _| Some(AppAction::OpenFind),
+    },
+    ActionDef {
+        name: "close_view",
+        description: "Leave the transcript view",
+        section: "Transcript view",
+        keys: &["q", "o", "Esc"],
+        views: &[View::Transcript],
+        action: |_, _| Some(AppAction::CloseTranscript),
+    },
+    // Input editing
+    ActionDef {
+        name: "send_message",
+        description: "Send message",
+        section: "Input Editing",
+        keys: &["Enter"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::SubmitMessage),
+    },
+    ActionDef {
+        name: "newline",
+        description: "New line",
+        section: "Input Editing",
+        // Ctrl+j for terminals that don't report Shift+Enter
+        keys: &["Alt+Enter", "Shift+Enter", "Ctrl+j"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::InsertNewline),
+    },
+    ActionDef {
+        name: "leave_input",
+        description: "Stop typing",
+        section: "Input Editing",
+        keys: &["Esc"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::ExitEditMode),
+    },
+    ActionDef {
+        name: "stop_from_input",
+        description: "Stop generating and edit prompt",
+        section: "Input Editing",
+        keys: &["Ctrl+x"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::AbortGeneration),
+    },
+    ActionDef {
+        name: "line_up",
+        description: "Previous line",
+        section: "Input Editing",
+        keys: &["Up"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorUp),
+    },
+    ActionDef {
+        name: "line_down",
+        description: "Next line",
+        section: "Input Editing",
+        keys: &["Down"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorDown),
+    },
+    ActionDef {
+        name: "cursor_left",
+        description: "Back one character",
+        section: "Input Editing",
+        keys: &["Left", "Ctrl+b"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorLeft),
+    },
+    ActionDef {
+        name: "cursor_right",
+        description: "Forward one character",
+        section: "Input Editing",
+        keys: &["Right", "Ctrl+f"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorRight),
+    },
+    ActionDef {
+        name: "word_left",
+        description: "Back one word",
+        section: "Input Editing",
+        keys: &["Alt+b", "Ctrl+Left"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorWordLeft),
+    },
+    ActionDef {
+        name: "word_right",
+        description: "Forward one word",
+        section: "Input Editing",
+        keys: &["Alt+f", "Ctrl+Right"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorWordRight),
+    },
+    ActionDef {
+        name: "line_start",
+        description: "Start of line",
+        section: "Input Editing",
+        keys: &["Ctrl+a", "Home"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorStart),
+    },
+    ActionDef {
+        name: "line_end",
+        description: "End of line",
+        section: "Input Editing",
+        keys: &["End"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::MoveCursorEnd),
+    },
+    ActionDef {
+        name: "delete_back",
+        description: "Delete previous character",
+        section: "Input Editing",
+        keys: &["Backspace", "Ctrl+h"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::DeleteChar),
+    },
+    ActionDef {
+        name: "delete_forward",
+        description: "Delete next character",
+        section: "Input Editing",
+        keys: &["Del"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::DeleteCharForward),
+    },
+    ActionDef {
+        name: "delete_word_back",
+        description: "Delete previous word",
+        section: "Input Editing",
+        keys: &["Ctrl+w", "Alt+Backspace"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::DeleteWordBack),
+    },
+    ActionDef {
+        name: "delete_word_forward",
+        description: "Delete next word",
+        section: "Input Editing",
+        keys: &["Alt+d"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::DeleteWordForward),
+    },
+    ActionDef {
+        name: "clear_input",
+        description: "Clear input",
+        section: "Input Editing",
+        keys: &["Ctrl+u"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::ClearInput),
+    },
+    ActionDef {
+        name: "external_editor",
+        description: "Edit input in $EDITOR",
+        section: "Input Editing",
+        keys: &["Ctrl+e"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::OpenInEditor),
+    },
+    ActionDef {
+        name: "attach_file",
+        description: "Attach a text file to the next message",
+        section: "Input Editing",
+        keys: &["Ctrl+o"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::OpenFilePicker(PickPurpose::AttachFile)),
+    },
+    ActionDef {
+        name: "attach_image",
+        description: "Pick an image to attach",
+        section: "Input Editing",
+        keys: &["Ctrl+p"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::OpenFilePicker(PickPurpose::AttachImage)),
+    },
+    ActionDef {
+        name: "input_snippets",
+        description: "Snippets",
+        section: "Input Editing",
+        keys: &["Ctrl+s"],
+        views: EDITING,
+        action: |_, _| Some(AppAction::OpenSnippets),
+    },
+];
+
+/// Actions the palette doesn't offer: itself, and one whose key picks what
+/// it does
+const NOT_IN_PALETTE: &[&str] = &["palette", "go_to_session"];
+
+/// Chat actions whose name or description holds every word of `query`, in
+/// help order
+pub fn palette(query: &str) -> Vec<&'static ActionDef> {
+    let query = query.to_lowercase();
+    ACTIONS
+        .iter()
+        .filter(|def| def.views.contains(&View::Chat) && !NOT_IN_PALETTE.contains(&def.name))
+        .filter(|def| {
+            let text = format!("{} {}", def.name, def.description).to_lowercase();
+            query.split_whitespace().all(|word| text.contains(word))
+        })
+        .collect()
+}
+
+/// Whether keys of actions in `a` and in `b` can be pressed in the same view
+fn views_overlap(a: &[View], b: &[View]) -> bool {
+    a.contains(&View::Any) || b.contains(&View::Any) || a.iter().any(|view| b.contains(view))
+}
+
+/// The keys bound to each action in [`ACTIONS`]
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    /// Keys of each action, in the order of [`ACTIONS`]
+    keys: Vec<Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let keys = ACTIONS
+            .iter()
+            .map(|def| def.keys.iter().map(|k| k.parse().expect("default key binding")).collect())
+            .collect();
+        Self { keys }
+    }
+}
+
+impl Keymap {
+    /// The default bindings with `overrides` applied, by action name
+    pub fn new(overrides: &BTreeMap<String, KeyList>) -> Result<Self, KeymapError> {
+        let mut keymap = Self::default();
+        for (name, keys) in overrides {
+            let idx = ACTIONS
+                .iter()
+                .position(|def| def.name == name)
+                .ok_or_else(|| KeymapError::UnknownAction(name.clone()))?;
+            keymap.keys[idx] = keys.as_slice().iter().map(|k| k.parse()).collect::<Result<_, _>>()?;
+        }
+        keymap.check()?;
+        Ok(keymap)
+    }
+
+    /// Refuse keys that would shadow another action or typing
+    ///
+    /// Two actions may share a key in the same view only when both have it
+    /// by default, like Esc, since their order is what decides between them.
+    fn check(&self) -> Result<(), KeymapError> {
+        let defaults = Self::default();
+        for (idx, (def, keys)) in ACTIONS.iter().zip(&self.keys).enumerate() {
+            let typing = def.views.contains(&View::Any) || def.views.contains(&View::Editing);
+            for key in keys {
+                if typing && key.is_printable() {
+                    return Err(KeymapError::Printable { key: key.to_string(), action: def.name });
+                }
+                let clash = ACTIONS[..idx]
+                    .iter()
+                    .zip(&self.keys)
+                    .zip(&defaults.keys)
+                    .find(|((other, other_keys), other_defaults)| {
+                        views_overlap(def.views, other.views)
+                            && other_keys.contains(key)
+                            && !(other_defaults.contains(key) && defaults.keys[idx].contains(key))
+                    });
+                if let Some(((other, _), _)) = clash {
+                    return Err(KeymapError::Clash { key: key.to_string(), first: other.name, second: def.name });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The action `key` triggers in `view`, if any
+    pub fn action(&self, key: &KeyEvent, view: View, state: &AppState) -> Option<AppAction> {
+        let key = Key::from_event(key);
+        ACTIONS
+            .iter()
+            .zip(&self.keys)
+            .filter(|(def, _)| def.views.contains(&view))
+            .filter_map(|(def, keys)| Some((def, keys.iter().position(|k| *k == key)?)))
+            .find_map(|(def, nth)| (def.action)(state, nth))
+    }
+
+    /// Keys bound to the action named `name`
+    pub fn keys_for(&self, name: &str) -> &[Key] {
+        ACTIONS.iter().position(|def| def.name == name).map_or(&[], |idx| &self.keys[idx])
+    }
+
+    /// Help rows for one section: the keys, joined, and the description
+    ///
+    /// A long run of keys, like `go_to_session`'s, shows as its first and last.
+    pub fn help_section(&self, section: &str) -> Vec<(String, &'static str)> {
+        ACTIONS
+            .iter()
+            .zip(&self.keys)
+            .filter(|(def, keys)| def.section == section && !keys.is_empty())
+            .map(|(def, keys)| {
+                let shown = match keys.as_slice() {
+                    [first, .., last] if keys.len() > 3 => format!("{}…{}", first, last),
+                    _ => keys.iter().map(Key::to_string).collect::<Vec<_>>().join(" / "),
+                };
+                (shown, def.description)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::config::Config;
+
+    #[test]
+    fn test_parse_and_display_keys() {
+        for (text, shown) in [
+            ("Ctrl+n", "Ctrl+n"),
+            ("ctrl+N", "Ctrl+N"),
+            ("Shift+g", "G"),
+            ("shift+tab", "Shift+Tab"),
+            ("pagedown", "PgDn"),
+            ("space", "Space"),
+            ("+", "+"),
+            ("Alt++", "Alt++"),
+            ("F1", "F1"),
+        ] {
+            assert_eq!(text.parse::<Key>().unwrap().to_string(), shown, "{}", text);
+        }
+        assert!("Hyper+x".parse::<Key>().is_err());
+        assert!("F13".parse::<Key>().is_err());
+
+        // Terminals report G with or without Shift
+        let g = Key::from_event(&KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT));
+        assert_eq!(g, "G".parse().unwrap());
+    }
+
+    #[test]
+    fn test_overrides_and_fallthrough() {
+        let mut state = AppState::new(Config::default());
+        let overrides: BTreeMap<String, KeyList> = toml::from_str(r#"new_session = ["Ctrl+t", "F2"]"#).unwrap();
+        let keymap = Keymap::new(&overrides).unwrap();
+        let f2 = KeyEvent::new(KeyCode::F(2), KeyModifiers::NONE);
+        assert!(matches!(keymap.action(&f2, View::Chat, &state), Some(AppAction::NewSession)));
+        let ctrl_n = KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert!(keymap.action(&ctrl_n, View::Chat, &state).is_none());
+        assert_eq!(keymap.help_section("Navigation")[2], ("Ctrl+t / F2".to_string(), "New session"));
+        assert_eq!(keymap.keys_for("select_model")[0].to_string(), "M");
+
+        // Esc stops a generation, then clears a find, then dismisses
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert!(matches!(keymap.action(&esc, View::Chat, &state), Some(AppAction::DismissBanner)));
+        state.find_query = "x".to_string();
+        assert!(matches!(keymap.action(&esc, View::Chat, &state), Some(AppAction::CancelFind)));
+        state.streaming = true;
+        assert!(matches!(keymap.action(&esc, View::Chat, &state), Some(AppAction::AbortGeneration)));
+
+        // The nth key of go_to_session picks the nth session, while typing too
+        let alt_3 = KeyEvent::new(KeyCode::Char('3'), KeyModifiers::ALT);
+        assert!(matches!(keymap.action(&alt_3, View::Editing, &state), Some(AppAction::SelectSession(2))));
+        let rows = keymap.help_section("Navigation");
+        assert!(rows.iter().any(|(keys, desc)| keys == "Alt+1…Alt+9" && *desc == "Switch to session 1-9"));
+
+        let unknown = BTreeMap::from([("launch_rockets".to_string(), KeyList::One("x".to_string()))]);
+        assert!(matches!(Keymap::new(&unknown), Err(KeymapError::UnknownAction(_))));
+    }
+
+    #[test]
+    fn test_clashing_and_printable_keys_are_refused() {
+        let bind = |name: &str, key: &str| BTreeMap::from([(name.to_string(), KeyList::One(key.to_string()))]);
+        // z is zen mode's
+        assert!(matches!(Keymap::new(&bind("new_session", "z")), Err(KeymapError::Clash { .. })));
+        // force_quit works while typing
+        assert!(matches!(Keymap::new(&bind("force_quit", "x")), Err(KeymapError::Printable { .. })));
+        assert!(matches!(Keymap::new(&bind("send_message", "s")), Err(KeymapError::Printable { .. })));
+        assert!(Keymap::new(&bind("send_message", "Ctrl+s")).is_err());
+        assert!(Keymap::new(&bind("send_message", "Ctrl+Enter")).is_ok());
+        // q is free in the transcript view once close_view doesn't use it
+        let overrides = BTreeMap::from([
+            ("close_view".to_string(), KeyList::One("Esc".to_string())),
+            ("view_find".to_string(), KeyList::One("q".to_string())),
+        ]);
+        assert!(Keymap::new(&overrides).is_ok());
+    }
+
+    #[test]
+    fn test_default_keys_do_not_clash() {
+        let keymap = Keymap::default();
+        for view in [View::Chat, View::Transcript, View::Editing] {
+            let mut seen = HashMap::new();
+            for ((def, keys), idx) in ACTIONS.iter().zip(&keymap.keys).zip(0..) {
+                if !def.views.contains(&view) {
+                    continue;
+                }
+                for key in keys {
+                    // Esc is shared on purpose: each action checks the state
+                    if let Some(other) = seen.insert(*key, idx).filter(|_| key.code != KeyCode::Esc) {
+                        panic!("{} is bound to both {} and {}", key, ACTIONS[other].name, def.name);
+                    }
+                }
+            }
+        }
+    }
+}
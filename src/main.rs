@@ -3,34 +3,95 @@
 //! A TUI-based chat interface for interacting with locally-running Ollama LLMs.
 
 mod app;
+mod cache;
+mod clipboard;
+mod commands;
 mod config;
+mod control;
+mod credentials;
+mod demo;
+mod dryrun;
 mod error;
 mod events;
+mod export;
+mod i18n;
+mod keymap;
+mod middleware;
+mod mirror;
+mod notify;
 mod ollama;
+mod patch;
+mod permissions;
 mod persistence;
+mod picker;
+mod prompts;
+mod providers;
+mod redact;
+mod retention;
+mod search;
+mod storage;
+mod store;
+mod theme;
+mod tools;
 mod ui;
+mod update;
 
 use anyhow::{Context, Result};
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste, EnableFocusChange,
+        EnableMouseCapture, Event, KeyCode, KeyEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
+use std::collections::VecDeque;
 use std::io::{self, Stdout};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
 use tokio_stream::StreamExt;
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
-use app::{AppEvent, AppState, InputMode, ResponseStats};
-use config::Config;
-use events::{handle_key_event, handle_mouse_event, process_action, EventHandler};
-use ollama::{ChatRequest, OllamaClient};
-use ui::{render_help_popup, render_layout, render_model_popup, render_delete_confirm_popup, AppLayout};
+use app::{
+    extract_image_refs, AppAction, AppEvent, AppState, ChatSession, ChatViewport, DownloadStatus, InputMode, Message, ResponseStats,
+};
+use cache::{CacheEntry, ResponseCache};
+use commands::{DatasetScope, FollowUp, Outcome};
+use config::{AgentConfig, Config, UpdateConfig};
+use credentials::AuthCommand;
+use demo::DemoScript;
+use error::OllamaError;
+use events::{handle_key_event, handle_mouse_event, process_action, EventHandler, FramePacer};
+use mirror::Mirror;
+use ollama::{ChatMessage, ChatRequest, FunctionCall, ToolCall};
+use prompts::PromptLibrary;
+use providers::Providers;
+use tools::Tool;
+use ui::{
+    render_help_popup, render_layout, render_model_popup, render_delete_confirm_popup,
+    render_code_picker_popup, render_downloads_popup, render_message_inspect_popup, render_patch_confirm_popup,
+    render_pull_confirm_popup, render_rename_popup, render_file_picker_popup, render_search_popup, render_palette_popup, render_snippets_popup, render_prompts_popup, render_yanks_popup, render_redact_review_popup, render_cache_popup, render_context_popup, render_dry_run_popup, AppLayout, GraphicsProtocol, ImagePlacement, ImageRenderer,
+};
 
 /// Terminal type alias
 type Term = Terminal<CrosstermBackend<Stdout>>;
 
+/// Banner shown while the Ollama server is unreachable
+const DISCONNECTED: &str = "Cannot connect to Ollama server";
+
+/// Sent to the model when an agent run has used up its tool calls
+const AGENT_BUDGET_NOTE: &str = "The tool budget for this turn is used up. \
+Answer the user's question now with the information you have.";
+
+/// Sent, but not shown, to have a cut-off response carry on
+const CONTINUE_PROMPT: &str = "Your last message was cut off. Continue exactly where it stopped, \
+mid-word if need be, without repeating anything or adding a preamble.";
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging to file (avoid disturbing TUI)
@@ -38,15 +99,30 @@ async fn main() -> Result<()> {
     
     info!("Starting ratatalk...");
 
-    // Load configuration
-    let config = Config::load().context("Failed to load configuration")?;
-    info!("Configuration loaded from {:?}", Config::config_path());
+    // A demo script replaces config and saved sessions entirely
+    let Args { demo, dry_run, auth, self_update, test_control, mirror } = parse_args()?;
+    let config = if demo.is_some() {
+        info!("Running in demo mode");
+        Config::default()
+    } else {
+        let config = Config::load().context("Failed to load configuration")?;
+        info!("Configuration loaded from {:?}", Config::config_path());
+        config
+    };
+
+    // `ratatalk auth ...` manages stored keys and exits without the TUI
+    if let Some(command) = auth {
+        return credentials::run(&command, &config).map_err(Into::into);
+    }
+    if self_update {
+        return update::self_update(config.update.channel).await.map_err(Into::into);
+    }
 
     // Initialize terminal
     let mut terminal = setup_terminal()?;
     
     // Run the application
-    let result = run_app(&mut terminal, config).await;
+    let result = run_app(&mut terminal, config, demo, dry_run, test_control, mirror).await;
     
     // Restore terminal
     restore_terminal(&mut terminal)?;
@@ -61,6 +137,99 @@ async fn main() -> Result<()> {
     result
 }
 
+/// Command-line arguments
+struct Args {
+    demo: Option<DemoScript>,
+    /// Describe chat requests instead of sending them
+    dry_run: bool,
+    /// `auth` subcommand to run instead of the TUI
+    auth: Option<AuthCommand>,
+    /// Replace this binary with the latest release instead of running the TUI
+    self_update: bool,
+    /// Loopback address for the test control socket (hidden flag)
+    test_control: Option<SocketAddr>,
+    /// Loopback address to serve the read-only WebSocket mirror on
+    mirror: Option<SocketAddr>,
+}
+
+/// Parse command-line arguments
+fn parse_args() -> Result<Args> {
+    let mut parsed = Args {
+        demo: None,
+        dry_run: false,
+        auth: None,
+        self_update: false,
+        test_control: None,
+        mirror: None,
+    };
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("auth") {
+        let rest: Vec<String> = args.skip(1).collect();
+        parsed.auth = Some(AuthCommand::parse(&rest).map_err(anyhow::Error::msg)?);
+        return Ok(parsed);
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--demo" => {
+                let path = args.next().context("--demo requires a script path")?;
+                let script = DemoScript::load(Path::new(&path))
+                    .with_context(|| format!("Failed to load demo script {}", path))?;
+                parsed.demo = Some(script);
+            }
+            "--dry-run" => parsed.dry_run = true,
+            "--mirror" => {
+                let addr = args.next().context("--mirror requires an address, e.g. 127.0.0.1:7879")?;
+                parsed.mirror = Some(addr.parse().with_context(|| format!("Invalid address: {}", addr))?);
+            }
+            "self-update" => parsed.self_update = true,
+            // Undocumented: drive the TUI over a socket, for end-to-end tests
+            "--test-control" => {
+                let addr = args.next().context("--test-control requires an address, e.g. 127.0.0.1:7878")?;
+                parsed.test_control = Some(addr.parse().with_context(|| format!("Invalid address: {}", addr))?);
+            }
+            other => anyhow::bail!(
+                "Unknown argument: {} (usage: ratatalk [--demo <script.toml>] [--dry-run] [--mirror <addr>] | ratatalk auth ... | ratatalk self-update)",
+                other
+            ),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Prune old sessions per `[retention]`, archiving them first
+fn apply_retention(state: &mut AppState) {
+    match retention::prune(&mut state.chat.sessions, &state.config, chrono::Utc::now()) {
+        Ok(report) => {
+            if report.pruned > 0 {
+                info!("Pruned {} sessions", report.pruned);
+                let archived = match &report.archive_dir {
+                    Some(dir) => format!(" (archived to {})", dir.display()),
+                    None => String::new(),
+                };
+                state.set_status(format!("Pruned {} old sessions{}", report.pruned, archived));
+                if let Err(e) = state.save_sessions() {
+                    warn!("Failed to save sessions after pruning: {}", e);
+                }
+            }
+            if report.failed > 0 {
+                state.set_warning(format!("Could not archive {} old sessions; kept them", report.failed));
+            }
+        }
+        Err(e) => {
+            warn!("Retention pass failed: {}", e);
+            state.set_error(format!("Retention failed: {}", e));
+        }
+    }
+}
+
+/// Where to find the newest intact backup, for integrity errors
+fn backup_hint() -> String {
+    match persistence::latest_intact_backup() {
+        Some(path) => format!(" (latest intact backup: {})", path.display()),
+        None => " (no intact backup found)".to_string(),
+    }
+}
+
 /// Initialize logging to a file
 fn init_logging() -> Result<()> {
     use tracing_subscriber::{fmt, prelude::*, EnvFilter};
@@ -90,7 +259,7 @@ fn init_logging() -> Result<()> {
 fn setup_terminal() -> Result<Term> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste, EnableFocusChange)?;
     let backend = CrosstermBackend::new(stdout);
     let terminal = Terminal::new(backend)?;
     Ok(terminal)
@@ -102,98 +271,330 @@ fn restore_terminal(terminal: &mut Term) -> Result<()> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        DisableFocusChange
     )?;
     terminal.show_cursor()?;
     Ok(())
 }
 
 /// Main application loop
-async fn run_app(terminal: &mut Term, config: Config) -> Result<()> {
+async fn run_app(
+    terminal: &mut Term,
+    config: Config,
+    demo: Option<DemoScript>,
+    dry_run: bool,
+    test_control: Option<SocketAddr>,
+    mirror: Option<SocketAddr>,
+) -> Result<()> {
     // Create application state
     let mut state = AppState::new(config.clone());
+    state.dry_run = dry_run;
+    match i18n::Locale::load(&config.ui.language) {
+        Ok(locale) => state.locale = locale,
+        Err(e) => {
+            warn!("Failed to load locale: {}", e);
+            state.set_warning(format!("{}; using English", e));
+        }
+    }
+    match theme::Theme::load(&config.ui.theme) {
+        Ok(theme) => state.theme = theme,
+        Err(e) => {
+            warn!("Failed to load theme: {}", e);
+            state.set_warning(format!("{}; using the default theme", e));
+        }
+    }
+    match keymap::Keymap::new(&config.keybindings.keys) {
+        Ok(keymap) => state.keymap = keymap,
+        Err(e) => {
+            warn!("Failed to apply key bindings: {}", e);
+            state.set_warning(format!("{}; using the default keys", e));
+        }
+    }
     
     // Load saved sessions
-    match persistence::load_sessions() {
-        Ok(sessions) if !sessions.is_empty() => {
-            info!("Loaded {} sessions from disk", sessions.len());
-            state.sessions = sessions;
+    let saved = if demo.is_some() { Ok(Default::default()) } else { state.chat.storage().load() };
+    match saved {
+        Ok(report) if !report.sessions.is_empty() => {
+            info!("Loaded {} sessions from disk", report.sessions.len());
+            state.chat.sessions = report.sessions;
+            state.sort_pinned();
+            apply_retention(&mut state);
+            state.trim_memory();
+            if !report.mismatched.is_empty() {
+                warn!("Sessions failed integrity check: {:?}", report.mismatched);
+                let kept = report.quarantined.map(|path| format!("; file as loaded kept at {}", path.display()));
+                state.set_error(format!(
+                    "Integrity check failed for {}{}{}",
+                    report.mismatched.join(", "),
+                    kept.unwrap_or_default(),
+                    backup_hint()
+                ));
+            }
         }
         Ok(_) => {
             info!("No saved sessions found, starting fresh");
         }
         Err(e) => {
             warn!("Failed to load sessions: {}", e);
-            state.set_status("Could not load saved sessions");
+            state.set_error(format!("Could not load saved sessions: {}{}", e, backup_hint()));
+        }
+    }
+    
+    // Load saved snippets, cached responses and the prompt library
+    if demo.is_none() {
+        match persistence::load_cache() {
+            Ok(entries) => state.cache.entries = entries,
+            Err(e) => warn!("Failed to load response cache: {}", e),
+        }
+        match persistence::load_snippets() {
+            Ok(snippets) => state.snippets = snippets,
+            Err(e) => {
+                warn!("Failed to load snippets: {}", e);
+                state.set_warning(format!("Could not load snippets: {}", e));
+            }
+        }
+        // The library may have been edited since the sessions were saved
+        match PromptLibrary::load() {
+            Ok(library) => {
+                if state.reload_prompts(library) > 0 {
+                    if let Err(e) = state.save_sessions() {
+                        warn!("Failed to save sessions after updating system prompts: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Failed to load prompt library: {}", e);
+                state.set_warning(format!("Could not load the prompt library: {}", e));
+            }
         }
     }
     
-    // Create Ollama client
-    let client = OllamaClient::new(&config.server.host, config.server.timeout_secs)
-        .context("Failed to create Ollama client")?;
+    // Create provider clients (Ollama plus any configured hosted providers)
+    let client = Providers::from_config(&config)
+        .context("Failed to create provider clients")?;
     
     // Create event channels
     let (event_tx, mut event_rx) = mpsc::channel::<AppEvent>(100);
     
-    // Spawn task to load models
-    {
-        let client = client.clone();
-        let tx = event_tx.clone();
-        tokio::spawn(async move {
-            match client.list_models().await {
-                Ok(models) => {
-                    let _ = tx.send(AppEvent::ModelsLoaded(models)).await;
-                }
-                Err(e) => {
-                    let _ = tx.send(AppEvent::ModelsError(e.to_string())).await;
-                }
-            }
-        });
+    // Demo mode plays a script instead of talking to a server
+    if let Some(script) = demo {
+        state.demo = true;
+        state.chat.sessions = vec![script.session()];
+        state.server_connected = true;
+        state.server_latency = Some(std::time::Duration::from_millis(12));
+        demo::spawn(script, event_tx.clone());
+    } else {
+        // Spawn task to load models
+        spawn_model_refresh(&mut state, &client, &event_tx);
+        
+        // Check server connectivity, now and then periodically
+        spawn_health_monitor(client.clone(), event_tx.clone(), config.server.health_check_secs);
+
+        if config.update.check {
+            spawn_update_check(config.update.clone(), event_tx.clone());
+        }
     }
     
-    // Check server connectivity
-    {
-        let client = client.clone();
-        let tx = event_tx.clone();
-        tokio::spawn(async move {
-            let connected = client.health_check().await.unwrap_or(false);
-            let _ = tx.send(AppEvent::ServerStatus(connected)).await;
-        });
-    }
+    // Background worker that pulls queued models one at a time
+    let pull_tx = spawn_pull_worker(client.clone(), event_tx.clone());
+    
+    // Inline image previews, if the terminal supports a graphics protocol
+    let mut image_renderer = GraphicsProtocol::detect(config.ui.image_protocol).map(|protocol| {
+        info!("Image previews enabled via {:?}", protocol);
+        ImageRenderer::new(protocol)
+    });
+    state.image_previews = image_renderer.is_some();
     
     // Event handler
     let event_handler = EventHandler::new(config.ui.tick_rate_ms);
+    let mut pacer = FramePacer::new(config.ui.max_fps);
+    
+    // Test control: keys injected by a client, and the reply owed once
+    // they have been handled
+    let (control_tx, mut control_rx) = mpsc::channel::<control::Request>(16);
+    if let Some(addr) = test_control {
+        let token = std::env::var(control::TOKEN_VAR)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .with_context(|| format!("--test-control needs a secret in {}", control::TOKEN_VAR))?;
+        control::listen(addr, token, control_tx)
+            .await
+            .with_context(|| format!("Failed to open test control socket on {}", addr))?;
+    }
+    let mut injected: VecDeque<Event> = VecDeque::new();
+    // Terminal events that arrived while a macro was replaying
+    let mut typed: VecDeque<Event> = VecDeque::new();
+    let mut control_reply: Option<tokio::sync::oneshot::Sender<serde_json::Value>> = None;
+    
+    // Read-only mirror for a second screen
+    let mirror = match mirror {
+        Some(addr) => {
+            let (mirror, url) = Mirror::listen(addr)
+                .await
+                .with_context(|| format!("Failed to open the mirror on {}", addr))?;
+            state.set_status(format!("Mirroring on {}", url));
+            Some(mirror)
+        }
+        None => None,
+    };
+    
+    // When the current generation started, for the completion webhook
+    let mut generation_started: Option<Instant> = None;
     
     // Main loop
     loop {
-        // Render
-        terminal.draw(|frame| {
-            render_layout(frame, &state);
-            render_model_popup(frame, &state);
-            render_help_popup(frame, &state);
-            render_delete_confirm_popup(frame, &state);
-        })?;
+        if state.expire_banners() {
+            pacer.mark_dirty();
+        }
+        // A broken preview is tried again once its file changes
+        if let Some(renderer) = image_renderer.as_mut() {
+            if renderer.retry_changed() {
+                state.broken_images = renderer.failed().cloned().collect();
+                pacer.mark_dirty();
+            }
+        }
+        spawn_context_lookup(&mut state, &client, &event_tx);
+        match (state.streaming, generation_started) {
+            (true, None) => generation_started = Some(Instant::now()),
+            (false, Some(_)) => generation_started = None,
+            _ => {}
+        }
+        
+        // Render, at most max_fps times a second
+        if pacer.due() {
+            let (mut placements, viewport, split_viewport) = draw(terminal, &state)?;
+            pacer.drawn();
+            state.chat_viewport = viewport;
+            if let Some(split) = state.split.as_mut() {
+                split.viewport = split_viewport;
+            }
+            if let Some(mirror) = &mirror {
+                mirror.publish(&state);
+            }
+            if state.apply_reveal() || state.apply_find_jump() {
+                // Redraw at once with the search result or find match in view
+                pacer.force();
+                continue;
+            }
+            if let Some(renderer) = image_renderer.as_mut() {
+                // Images are hidden while a popup covers the chat
+                if !matches!(state.input_mode, InputMode::Normal | InputMode::Editing | InputMode::MessageSelect) {
+                    placements.clear();
+                }
+                if renderer.needs_repaint(&placements) {
+                    terminal.clear()?;
+                    draw(terminal, &state)?;
+                }
+                renderer.draw(terminal.backend_mut(), placements)?;
+                // Failed previews get a note in the next frame
+                if renderer.failed().len() != state.broken_images.len() {
+                    state.broken_images = renderer.failed().cloned().collect();
+                    pacer.mark_dirty();
+                }
+            }
+        }
         
         // Compute current layout for mouse hit-testing
         let size = terminal.size()?;
         let area = ratatui::layout::Rect::new(0, 0, size.width, size.height);
-        let current_layout = AppLayout::new(area, state.config.ui.sidebar_width);
+        let current_layout = AppLayout::new(area, &state);
         
-        // Handle terminal events (non-blocking with timeout)
-        if let Some(event) = event_handler.poll()? {
+        // Handle terminal events, replayed macro keys and injected ones
+        // first. Wait no longer than the next owed frame, or a tick when the
+        // screen is up to date; a frame while streaming, since tokens don't
+        // wake the poll.
+        let idle = if state.streaming { pacer.interval() } else { event_handler.tick_rate() };
+        // Input keeps being read during a replay: Esc stops it, anything
+        // else waits until it is done
+        if !state.replay.is_empty() {
+            match event_handler.poll(Duration::ZERO)? {
+                Some(Event::Key(key)) if key.code == KeyCode::Esc && key.kind == KeyEventKind::Press => {
+                    state.stop_replay();
+                    state.set_status("Macro stopped");
+                }
+                Some(event) => typed.push_back(event),
+                None => {}
+            }
+        }
+        let replayed = state.replay.pop_front();
+        let event = match replayed {
+            Some(key) => Some(Event::Key(key)),
+            None if !typed.is_empty() => typed.pop_front(),
+            None if !injected.is_empty() => injected.pop_front(),
+            None => event_handler.poll(pacer.wait(idle))?,
+        };
+        // Events always redraw; a tick (no event before the timeout) only
+        // while something animates
+        let redraw = match &event {
+            Some(_) => true,
+            None => state.tick(),
+        };
+        if redraw {
+            pacer.mark_dirty();
+        }
+        if let Some(event) = event {
             match event {
                 Event::Key(key) => {
-                    // Special handling for submit in editing mode
-                    if state.input_mode == InputMode::Editing 
-                        && key.code == KeyCode::Enter 
-                        && !state.input.trim().is_empty()
-                        && !state.streaming
-                    {
-                        // Submit message - stay in editing mode for continuous chat
-                        let input = state.take_input();
-                        submit_message(&mut state, &client, &event_tx, input).await;
-                    } else if let Some(action) = handle_key_event(key, &state) {
-                        process_action(action, &mut state);
+                    if replayed.is_none() {
+                        state.record_key(key);
+                    }
+                    // Actions that need the terminal or the clients run here;
+                    // the rest go through process_action. A palette pick is
+                    // dispatched as if its key had been pressed.
+                    let action = match handle_key_event(key, &state) {
+                        Some(AppAction::RunPaletteEntry) => state.take_palette_action(),
+                        action => action,
+                    };
+                    match action {
+                        Some(AppAction::SubmitMessage) => {
+                            // Stay in editing mode for continuous chat
+                            if let Some(input) = state.take_submission() {
+                                submit_message(&mut state, &client, &event_tx, input).await;
+                            }
+                        }
+                        Some(AppAction::OpenInPager) => {
+                            if let Err(e) = open_in_pager(terminal, &state) {
+                                warn!("Pager failed: {:#}", e);
+                                state.set_error(format!("Pager failed: {}", e));
+                            }
+                        }
+                        Some(AppAction::OpenInEditor) => {
+                            if let Err(e) = open_in_editor(terminal, &mut state) {
+                                warn!("Editor failed: {:#}", e);
+                                state.set_error(format!("Editor failed: {}", e));
+                            }
+                        }
+                        Some(AppAction::EditPrompts) => {
+                            if let Err(e) = edit_prompts(terminal, &mut state) {
+                                warn!("Editing the prompt library failed: {:#}", e);
+                                state.set_error(format!("Prompt library: {}", e));
+                            }
+                        }
+                        Some(AppAction::ContinueResponse) => continue_response(&mut state, &client, &event_tx).await,
+                        Some(AppAction::RetryFailed) => {
+                            match state.active_session_mut().and_then(|s| s.take_failed_turn()) {
+                                Some(question) => {
+                                    // Attachments picked since stay for the next message
+                                    let picked =
+                                        std::mem::replace(&mut state.pending_attachments, question.attachments);
+                                    submit_message(&mut state, &client, &event_tx, question.content).await;
+                                    state.pending_attachments = picked;
+                                }
+                                None => state.set_status("The last message didn't fail"),
+                            }
+                        }
+                        Some(AppAction::React(reaction)) => {
+                            if let Some(prompt) = state.reaction_prompt(reaction) {
+                                submit_message(&mut state, &client, &event_tx, prompt).await;
+                            }
+                        }
+                        Some(action) => {
+                            spawn_action_task(&action, &mut state, &client, &event_tx, &pull_tx);
+                            process_action(action, &mut state);
+                        }
+                        None => {}
                     }
                 }
                 Event::Mouse(mouse) if state.config.ui.mouse_support => {
@@ -202,6 +603,11 @@ async fn run_app(terminal: &mut Term, config: Config) -> Result<()> {
                         process_action(action, &mut state);
                     }
                 }
+                // Pasted text arrives whole, so its newlines don't submit
+                Event::Paste(text) => process_action(AppAction::Paste(text), &mut state),
+                // Bells only ring while the terminal is in the background
+                Event::FocusGained => state.terminal_focused = true,
+                Event::FocusLost => state.terminal_focused = false,
                 Event::Resize(_, _) => {
                     // Terminal will be redrawn on next iteration
                 }
@@ -211,15 +617,20 @@ async fn run_app(terminal: &mut Term, config: Config) -> Result<()> {
         
         // Handle async events (non-blocking)
         while let Ok(event) = event_rx.try_recv() {
+            pacer.mark_dirty();
             match event {
                 AppEvent::ModelsLoaded(models) => {
                     info!("Loaded {} models", models.len());
-                    state.models = models;
+                    // Also settles a reload still running when the list came from elsewhere
+                    if state.cancel_model_refresh() {
+                        state.set_status(format!("Loaded {} models", models.len()));
+                    }
+                    state.chat.models = models;
                     state.loading = false;
-                    if !state.models.is_empty() {
+                    if !state.chat.models.is_empty() {
                         // Find current model in list
                         let current = state.current_model().to_string();
-                        if let Some(idx) = state.models.iter().position(|m| m.name == current) {
+                        if let Some(idx) = state.chat.models.iter().position(|m| m.name == current) {
                             state.selected_model_idx = idx;
                         }
                     }
@@ -227,47 +638,139 @@ async fn run_app(terminal: &mut Term, config: Config) -> Result<()> {
                 AppEvent::ModelsError(err) => {
                     warn!("Failed to load models: {}", err);
                     state.set_error(format!("Failed to load models: {}", err));
+                    state.cancel_model_refresh();
                     state.loading = false;
                 }
-                AppEvent::StreamChunk(content) => {
-                    if let Some(session) = state.active_session_mut() {
-                        session.append_to_response(&content);
-                    }
-                    // Auto-scroll to bottom during streaming
-                    state.scroll_to_bottom();
+                AppEvent::Stream { session_id, event } => {
+                    handle_stream_event(&mut state, &client, &event_tx, &mut generation_started, session_id, *event);
                 }
-                AppEvent::StreamComplete(stats) => {
-                    info!("Stream complete: {} tokens at {:.1} tok/s", 
-                        stats.tokens, stats.tokens_per_second);
-                    if let Some(session) = state.active_session_mut() {
-                        session.finish_response();
+                // Demo playback streams untagged into the session it submitted to
+                event @ (AppEvent::StreamChunk(_) | AppEvent::StreamThinking(_) | AppEvent::StreamComplete(_)) => {
+                    if let Some(session_id) = state.active_session().map(|s| s.id) {
+                        handle_stream_event(&mut state, &client, &event_tx, &mut generation_started, session_id, event);
                     }
-                    state.streaming = false;
-                    state.last_response_stats = Some(stats);
-                    
-                    // Auto-save after response
-                    if let Err(e) = persistence::save_sessions(&state.sessions) {
-                        warn!("Failed to save sessions: {}", e);
+                }
+                AppEvent::Compare { session_id, event } => handle_compare_event(&mut state, session_id, *event),
+                AppEvent::Latency(latency) => state.server_latency = Some(latency),
+                AppEvent::ContextWindow { model, tokens } => {
+                    state.chat.set_context_window(model, tokens);
+                    pacer.mark_dirty();
+                }
+                AppEvent::SessionTitled { session_id, title } => {
+                    // The user may have renamed it while the title was generating
+                    let session = state.chat.sessions.iter_mut().find(|s| s.id == session_id);
+                    if let Some(session) = session.filter(|s| s.has_default_name()) {
+                        info!("Titled session: {}", title);
+                        session.rename(&title);
+                        if let Err(e) = state.save_sessions() {
+                            warn!("Failed to save sessions after titling: {}", e);
+                        }
                     }
                 }
-                AppEvent::StreamError(err) => {
-                    error!("Stream error: {}", err);
-                    if let Some(session) = state.active_session_mut() {
-                        session.finish_response();
-                        // Append error to message
-                        if let Some(msg) = session.messages.last_mut() {
-                            if msg.content.is_empty() {
-                                msg.content = format!("[Error: {}]", err);
-                            }
+                AppEvent::NotifyFailed(err) => state.set_warning(format!("Notification failed: {}", err)),
+                AppEvent::UpdateAvailable(version) if state.update_available.as_ref() != Some(&version) => {
+                    info!("ratatalk {} is available", version);
+                    state.set_notice(format!("ratatalk {} is available (run `ratatalk self-update`)", version));
+                    state.update_available = Some(version);
+                }
+                AppEvent::Anonymized { review_id, idx, text } => {
+                    let Some(review) = state.redact_review.as_mut().filter(|r| r.id == review_id) else {
+                        continue;
+                    };
+                    review.pending = review.pending.saturating_sub(1);
+                    match text {
+                        Some(text) => review.apply_anonymized(idx, &text),
+                        None => review.failed += 1,
+                    }
+                    if review.pending == 0 {
+                        match review.failed {
+                            0 => state.set_status("Model pass done, review the changes"),
+                            n => state.set_warning(format!(
+                                "Model pass failed on {} messages; they keep the rule-based version",
+                                n
+                            )),
                         }
                     }
-                    state.streaming = false;
-                    state.set_error(err);
                 }
                 AppEvent::ServerStatus(connected) => {
                     state.server_connected = connected;
                     if !connected {
-                        state.set_error("Cannot connect to Ollama server");
+                        state.server_latency = None;
+                        state.set_error(DISCONNECTED);
+                    }
+                }
+                AppEvent::ServerReconnected => {
+                    info!("Reconnected to Ollama server");
+                    state.server_connected = true;
+                    state.dismiss_banner_text(DISCONNECTED);
+                    state.set_status("Reconnected to Ollama server");
+                }
+                AppEvent::DemoTyped(text) => {
+                    state.input_mode = InputMode::Editing;
+                    state.input = text;
+                    state.move_cursor_end();
+                }
+                AppEvent::DemoSubmit(at) => {
+                    let content = state.take_input();
+                    if let Some(session) = state.active_session_mut() {
+                        session.add_user_message(content);
+                        session.start_assistant_response();
+                        for message in session.messages.iter_mut().rev().take(2) {
+                            message.timestamp = at;
+                        }
+                        session.updated_at = at;
+                    }
+                    state.streaming = true;
+                    state.scroll_to_bottom();
+                }
+                AppEvent::ModelLoaded(model) => {
+                    info!("Model loaded: {}", model);
+                    state.set_status(format!("Model loaded: {}", model));
+                }
+                AppEvent::ModelUnloaded(model) => {
+                    info!("Model unloaded: {}", model);
+                    state.set_status(format!("Model unloaded: {}", model));
+                }
+                AppEvent::ModelCommandError(err) => {
+                    warn!("Model command failed: {}", err);
+                    state.set_error(err);
+                }
+                AppEvent::PullProgress { model, status, completed, total } => {
+                    if let Some(download) = state.download_mut(&model) {
+                        download.status = DownloadStatus::Active;
+                        download.detail = status;
+                        download.completed = completed;
+                        download.total = total;
+                    }
+                }
+                AppEvent::Progress { id, done, total } => state.update_progress(&id, done, total),
+                AppEvent::DatasetExported(result) => {
+                    state.finish_progress(EXPORT_PROGRESS);
+                    match result {
+                        Ok(Some((path, count))) => {
+                            info!("Exported {} conversations to {:?}", count, path);
+                            state.set_status(format!("Exported {} conversations to {}", count, path.display()));
+                        }
+                        Ok(None) => state.set_status("No complete exchanges to export"),
+                        Err(e) => state.set_error(format!("Dataset export failed: {}", e)),
+                    }
+                }
+                AppEvent::PullFinished { model, error } => {
+                    state.finish_progress(&format!("pull {}", model));
+                    let status = match error {
+                        None => {
+                            info!("Pulled model: {}", model);
+                            state.set_status(format!("Downloaded {}", model));
+                            DownloadStatus::Done
+                        }
+                        Some(err) => {
+                            warn!("Failed to pull {}: {}", model, err);
+                            state.set_error(format!("Failed to download {}: {}", model, err));
+                            DownloadStatus::Failed(err)
+                        }
+                    };
+                    if let Some(download) = state.download_mut(&model) {
+                        download.status = status;
                     }
                 }
                 AppEvent::Quit => {
@@ -277,10 +780,29 @@ async fn run_app(terminal: &mut Term, config: Config) -> Result<()> {
             }
         }
         
+        // Test control: answer once injected keys are handled, then take the
+        // next command
+        if injected.is_empty() {
+            if let Some(reply) = control_reply.take() {
+                let _ = reply.send(serde_json::json!({ "ok": true }));
+            }
+            match control_rx.try_recv() {
+                Ok(control::Request::Events(events, reply)) => {
+                    injected.extend(events);
+                    control_reply = Some(reply);
+                }
+                Ok(control::Request::Snapshot(reply)) => {
+                    let _ = reply.send(control::snapshot(&state));
+                }
+                Err(_) => {}
+            }
+        }
+        
         // Check for quit
         if state.should_quit {
+            state.cancel_model_refresh();
             // Save sessions before quitting
-            if let Err(e) = persistence::save_sessions(&state.sessions) {
+            if let Err(e) = state.save_sessions() {
                 warn!("Failed to save sessions on exit: {}", e);
             }
             break;
@@ -290,114 +812,1269 @@ async fn run_app(terminal: &mut Term, config: Config) -> Result<()> {
     Ok(())
 }
 
-/// Submit a user message and start streaming response
-async fn submit_message(
+/// Start background work for actions that need the network
+///
+/// Called before `process_action`, which updates the synchronous state.
+fn spawn_action_task(
+    action: &AppAction,
     state: &mut AppState,
-    client: &OllamaClient,
+    client: &Providers,
     event_tx: &mpsc::Sender<AppEvent>,
-    content: String,
+    pull_tx: &mpsc::UnboundedSender<String>,
 ) {
-    let content = content.trim().to_string();
-    if content.is_empty() {
+    match action {
+        AppAction::RefreshModels if !state.demo => spawn_model_refresh(state, client, event_tx),
+        AppAction::AnonymizeWithModel => spawn_anonymize(state, client, event_tx),
+        AppAction::QueuePull | AppAction::ConfirmPullMissing => {
+            let model = match action {
+                AppAction::QueuePull => state.pull_target(),
+                _ => state.missing_model.clone(),
+            };
+            if let Some(model) = model.filter(|m| !state.is_pull_pending(m)) {
+                let _ = pull_tx.send(model);
+            }
+        }
+        AppAction::LoadModel | AppAction::UnloadModel => {
+            let Some(model) = state.selected_model().map(|m| m.name.clone()) else {
+                return;
+            };
+            let tx = event_tx.clone();
+            let Some(ollama) = client.ollama_for(&model).cloned() else {
+                let _ = tx.try_send(AppEvent::ModelCommandError(format!(
+                    "{} is not an Ollama model",
+                    model
+                )));
+                return;
+            };
+            let load = matches!(action, AppAction::LoadModel);
+            tokio::spawn(async move {
+                let result = if load {
+                    ollama.load_model(&model).await
+                } else {
+                    ollama.unload_model(&model).await
+                };
+                let event = match result {
+                    Ok(()) if load => AppEvent::ModelLoaded(model),
+                    Ok(()) => AppEvent::ModelUnloaded(model),
+                    Err(e) => AppEvent::ModelCommandError(format!("{}: {}", model, e)),
+                };
+                let _ = tx.send(event).await;
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Reload the model list in the background
+///
+/// A reload already in flight is cancelled, so repeated refreshes never
+/// race each other.
+fn spawn_model_refresh(state: &mut AppState, client: &Providers, tx: &mpsc::Sender<AppEvent>) {
+    let client = client.clone();
+    let tx = tx.clone();
+    let task = tokio::spawn(async move {
+        let event = match client.list_models().await {
+            Ok(models) => AppEvent::ModelsLoaded(models),
+            Err(e) => AppEvent::ModelsError(e.to_string()),
+        };
+        let _ = tx.send(event).await;
+    });
+    if state.begin_model_refresh(task.abort_handle()) {
+        info!("Restarted model refresh");
+    }
+}
+
+/// Run the model-assisted pass over the anonymized export under review
+///
+/// Messages go to the model one at a time. The pass never uses a remote
+/// model, since that would send the unreviewed transcript off the machine.
+fn spawn_anonymize(state: &mut AppState, client: &Providers, tx: &mpsc::Sender<AppEvent>) {
+    let demo = state.demo;
+    let Some(review) = state.redact_review.as_ref() else {
+        return;
+    };
+    let model = state.config.redact.model.clone().unwrap_or_else(|| review.before.model.clone());
+    if demo {
+        state.set_status("The model pass is not available in demo mode");
         return;
     }
-    
-    // Add user message
-    if let Some(session) = state.active_session_mut() {
-        session.add_user_message(&content);
-        session.start_assistant_response();
+    if review.pending > 0 {
+        state.set_status("The model pass is already running");
+        return;
     }
-    
-    state.streaming = true;
-    state.scroll_to_bottom();
-    
-    // Get messages for API call
-    let messages = state
-        .active_session()
-        .map(|s| s.to_chat_messages())
-        .unwrap_or_default();
-    
-    let model = state.current_model().to_string();
-    
-    // Build request with options from config
-    let mut request = ChatRequest::new(model, messages);
-    
-    // Apply generation options from config
-    let opts = ollama::GenerationOptions {
-        temperature: Some(state.config.model.temperature),
-        top_k: Some(state.config.model.top_k),
-        top_p: Some(state.config.model.top_p),
-        num_predict: if state.config.model.max_tokens > 0 {
-            Some(state.config.model.max_tokens as i32)
-        } else {
-            None
-        },
-        num_ctx: if state.config.model.num_ctx > 0 {
-            Some(state.config.model.num_ctx)
-        } else {
-            None
-        },
+    if state.config.is_remote_model(&model) {
+        state.set_warning(format!("Not sending the transcript to remote model {}", model));
+        return;
+    }
+
+    let jobs: Vec<(usize, String)> = review
+        .after
+        .messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| !m.content.trim().is_empty())
+        .map(|(idx, m)| (idx, m.content.clone()))
+        .collect();
+    let review_id = review.id;
+    // Dry run: nothing leaves the machine, so only say what would be sent
+    if state.dry_run {
+        state.set_status(format!("Dry run: the model pass would send {} messages to {}", jobs.len(), model));
+        return;
+    }
+    state.set_status(format!("Anonymizing {} messages with {}...", jobs.len(), model));
+    if let Some(review) = state.redact_review.as_mut() {
+        review.pending = jobs.len();
+        review.failed = 0;
+    }
+
+    let client = client.clone();
+    let tx = tx.clone();
+    let options = ollama::GenerationOptions {
+        temperature: Some(0.0),
         ..Default::default()
     };
-    request = request.with_options(opts);
-    
-    // Spawn streaming task
+    tokio::spawn(async move {
+        for (idx, text) in jobs {
+            let request = ChatRequest::new(model.clone(), redact::anonymize_request(&text)).with_options(options.clone());
+            let mut raw = String::new();
+            let result = match client.chat_stream(request).await {
+                Ok(mut stream) => loop {
+                    match stream.next().await {
+                        Some(Ok(chunk)) => raw.push_str(chunk.content().unwrap_or_default()),
+                        Some(Err(e)) => break Err(e),
+                        None => break Ok(raw),
+                    }
+                },
+                Err(e) => Err(e),
+            };
+            let text = result
+                .inspect_err(|e| warn!("Anonymizing message {} failed: {}", idx, e))
+                .ok()
+                .filter(|text| !text.trim().is_empty());
+            if tx.send(AppEvent::Anonymized { review_id, idx, text }).await.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Ask the session's model for a title in the background
+///
+/// Failures are only logged; the session keeps its generated name.
+fn spawn_auto_title(client: &Providers, tx: &mpsc::Sender<AppEvent>, session: &app::ChatSession) {
     let client = client.clone();
-    let tx = event_tx.clone();
+    let tx = tx.clone();
+    let session_id = session.id;
+    let options = ollama::GenerationOptions {
+        temperature: Some(0.2),
+        ..Default::default()
+    };
+    let request = ChatRequest::new(session.model.clone(), session.title_request()).with_options(options);
     
     tokio::spawn(async move {
+        let mut raw = String::new();
         match client.chat_stream(request).await {
             Ok(mut stream) => {
-                let mut total_tokens = 0u32;
-                let mut tokens_per_sec = 0.0;
-                let mut total_duration = 0u64;
-                
                 while let Some(result) = stream.next().await {
                     match result {
-                        Ok(chunk) => {
-                            // Check for errors in the chunk
-                            if let Some(error) = chunk.error {
-                                let _ = tx.send(AppEvent::StreamError(error)).await;
-                                return;
-                            }
-                            
-                            // Send content if present
-                            if let Some(content) = chunk.content() {
-                                if !content.is_empty() {
-                                    let _ = tx.send(AppEvent::StreamChunk(content.to_string())).await;
-                                }
-                            }
-                            
-                            // Capture final stats
-                            if chunk.done {
-                                if let Some(count) = chunk.eval_count {
-                                    total_tokens = count;
-                                }
-                                if let Some(tps) = chunk.tokens_per_second() {
-                                    tokens_per_sec = tps;
-                                }
-                                if let Some(duration) = chunk.total_duration {
-                                    total_duration = duration / 1_000_000; // ns to ms
-                                }
-                            }
-                        }
+                        Ok(chunk) => raw.push_str(chunk.content().unwrap_or_default()),
                         Err(e) => {
-                            let _ = tx.send(AppEvent::StreamError(e.to_string())).await;
+                            warn!("Auto-title failed: {}", e);
                             return;
                         }
                     }
                 }
-                
-                // Send completion
-                let _ = tx.send(AppEvent::StreamComplete(ResponseStats {
-                    tokens: total_tokens,
-                    tokens_per_second: tokens_per_sec,
-                    total_duration_ms: total_duration,
-                })).await;
             }
             Err(e) => {
-                let _ = tx.send(AppEvent::StreamError(e.to_string())).await;
+                warn!("Auto-title failed: {}", e);
+                return;
+            }
+        }
+        match app::clean_title(&raw) {
+            Some(title) => {
+                let _ = tx.send(AppEvent::SessionTitled { session_id, title }).await;
+            }
+            None => warn!("Auto-title: no usable title in {:?}", raw),
+        }
+    });
+}
+
+/// Look for a newer release in the background, once per interval
+///
+/// Results are cached on disk, so restarting doesn't check again early.
+/// Failures are only logged; the check must never get in the way.
+fn spawn_update_check(config: UpdateConfig, tx: mpsc::Sender<AppEvent>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_hours.max(1) * 3600));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match update::check(&config).await {
+                Ok(Some(version)) => {
+                    if tx.send(AppEvent::UpdateAvailable(version.to_string())).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => info!("No update for ratatalk {}", update::CURRENT),
+                Err(e) => warn!("Update check failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Announce a finished generation: ring the bell (with a desktop
+/// notification if enabled) per `[notify] bell`, and ping the completion
+/// webhook if it ran long enough
+fn notify_completion(
+    state: &AppState,
+    tx: &mpsc::Sender<AppEvent>,
+    started: Option<Instant>,
+    outcome: notify::Outcome,
+) {
+    let config = &state.config.notify;
+    let elapsed = started.map(|s| s.elapsed()).unwrap_or_default();
+    let alert = notify::alert_wanted(config, state.terminal_focused);
+    let webhook = started.is_some() && notify::wanted(config, elapsed);
+    if state.demo || !(alert || webhook) {
+        return;
+    }
+    let completion = notify::Completion {
+        session: state.active_session().map(|s| s.name.clone()).unwrap_or_default(),
+        model: state.current_model().to_string(),
+        elapsed,
+        outcome,
+    };
+    if alert {
+        if let Err(e) = notify::ring_bell() {
+            warn!("Failed to ring the bell: {}", e);
+        }
+        if config.desktop {
+            let summary = completion.summary();
+            let tx = tx.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = notify::desktop("ratatalk", &summary) {
+                    warn!("Desktop notification failed: {}", e);
+                    let _ = tx.blocking_send(AppEvent::NotifyFailed(e.to_string()));
+                }
+            });
+        }
+    }
+    if !webhook {
+        return;
+    }
+    let body = notify::payload(config, &completion);
+    let url = config.webhook_url.clone().unwrap_or_default();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        if let Err(e) = notify::send(&url, &body).await {
+            warn!("Completion webhook failed: {}", e);
+            let _ = tx.send(AppEvent::NotifyFailed(e.to_string())).await;
+        }
+    });
+}
+
+/// Spawn the connectivity monitor
+///
+/// Reports the initial status, then only changes. On reconnect the model
+/// list is fetched again, since the server may have restarted with a
+/// different set of models.
+fn spawn_health_monitor(client: Providers, tx: mpsc::Sender<AppEvent>, interval_secs: u64) {
+    tokio::spawn(async move {
+        let mut last: Option<bool> = None;
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        
+        loop {
+            ticker.tick().await;
+            let started = std::time::Instant::now();
+            let connected = client.health_check().await.unwrap_or(false);
+            if connected && tx.send(AppEvent::Latency(started.elapsed())).await.is_err() {
+                break;
+            }
+            
+            if last != Some(connected) {
+                let event = if last == Some(false) {
+                    AppEvent::ServerReconnected
+                } else {
+                    AppEvent::ServerStatus(connected)
+                };
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+                if last == Some(false) {
+                    if let Ok(models) = client.list_models().await {
+                        let _ = tx.send(AppEvent::ModelsLoaded(models)).await;
+                    }
+                }
+                last = Some(connected);
+            }
+            
+            if interval_secs == 0 {
+                break;
             }
         }
     });
 }
+
+/// Spawn the download worker, returning the queue to send model names to
+///
+/// Pulls run sequentially; the model list is refreshed after each success.
+fn spawn_pull_worker(client: Providers, tx: mpsc::Sender<AppEvent>) -> mpsc::UnboundedSender<String> {
+    let (pull_tx, mut pull_rx) = mpsc::unbounded_channel::<String>();
+    
+    tokio::spawn(async move {
+        while let Some(model) = pull_rx.recv().await {
+            let error = pull_model(client.ollama(), &model, &tx).await.err();
+            let succeeded = error.is_none();
+            let _ = tx.send(AppEvent::PullFinished { model, error }).await;
+            
+            if succeeded {
+                if let Ok(models) = client.list_models().await {
+                    let _ = tx.send(AppEvent::ModelsLoaded(models)).await;
+                }
+            }
+        }
+    });
+    
+    pull_tx
+}
+
+/// Pull one model, forwarding progress updates
+async fn pull_model(
+    ollama: &ollama::OllamaClient,
+    model: &str,
+    tx: &mpsc::Sender<AppEvent>,
+) -> Result<(), String> {
+    let stream = ollama.pull_model(model).await.map_err(|e| e.to_string())?;
+    let mut stream = std::pin::pin!(stream);
+    let mut succeeded = false;
+    
+    while let Some(result) = stream.next().await {
+        let progress = result.map_err(|e| e.to_string())?;
+        if let Some(err) = progress.error {
+            return Err(err);
+        }
+        succeeded = progress.status == "success";
+        let (done, total) = (progress.completed.unwrap_or(0), progress.total.unwrap_or(0));
+        let _ = tx.send(AppEvent::Progress { id: format!("pull {}", model), done, total }).await;
+        let _ = tx.send(AppEvent::PullProgress {
+            model: model.to_string(),
+            status: progress.status,
+            completed: progress.completed.unwrap_or(0),
+            total: progress.total.unwrap_or(0),
+        }).await;
+    }
+    
+    if succeeded {
+        Ok(())
+    } else {
+        Err("download ended unexpectedly".to_string())
+    }
+}
+
+/// Act on a completed response according to the command that requested it
+fn run_follow_up(state: &mut AppState, follow_up: FollowUp) {
+    match follow_up {
+        FollowUp::CopyResponse => {
+            let reply = state
+                .active_session()
+                .and_then(|s| s.last_assistant_message())
+                .map(|m| commands::unfence(&m.content).to_string())
+                .unwrap_or_default();
+            match state.yank(&reply) {
+                Ok(()) => state.set_status("Response copied to clipboard"),
+                Err(e) => state.set_error(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Show the current session's transcript in `$PAGER`, suspending the TUI
+fn open_in_pager(terminal: &mut Term, state: &AppState) -> Result<()> {
+    let Some(session) = state.active_session() else {
+        return Ok(());
+    };
+    // A fresh file no other user can have planted; it is removed on drop
+    let file = tempfile::Builder::new()
+        .prefix("ratatalk-transcript-")
+        .suffix(".txt")
+        .tempfile()
+        .context("Failed to create transcript file")?;
+    std::fs::write(file.path(), export::transcript(session)).context("Failed to write transcript")?;
+    
+    let pager = std::env::var("PAGER").unwrap_or_default();
+    let (program, args) = command_line(&pager, "less")?;
+    
+    restore_terminal(terminal)?;
+    let status = std::process::Command::new(&program).args(args).arg(file.path()).status();
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste, EnableFocusChange)?;
+    terminal.clear()?;
+    
+    status.with_context(|| format!("Failed to run {}", program))?;
+    Ok(())
+}
+
+/// Edit the input buffer in `$VISUAL` / `$EDITOR`, suspending the TUI
+///
+/// The edited text replaces the input; a failed editor leaves it unchanged.
+fn open_in_editor(terminal: &mut Term, state: &mut AppState) -> Result<()> {
+    // A fresh file no other user can have planted; it is removed on drop
+    let file = tempfile::Builder::new()
+        .prefix("ratatalk-input-")
+        .suffix(".md")
+        .tempfile()
+        .context("Failed to create input file")?;
+    std::fs::write(file.path(), &state.input).context("Failed to write input")?;
+
+    let status = run_editor(terminal, file.path());
+    let edited = std::fs::read_to_string(file.path());
+    status?;
+    let edited = edited.context("Failed to read edited input")?;
+    // Editors add a final newline; a trailing one would only pad the message
+    state.set_input(edited.trim_end_matches(['\n', '\r']).to_string());
+    Ok(())
+}
+
+/// Edit the system prompt library in `$VISUAL` / `$EDITOR`, then reload it
+///
+/// The starter prompts are written out first when there is no library yet.
+/// If the edited file doesn't parse, the error says why and the library in
+/// use stays as it was.
+fn edit_prompts(terminal: &mut Term, state: &mut AppState) -> Result<()> {
+    let path = PromptLibrary::path()?;
+    if !path.exists() {
+        state.prompts.save()?;
+    }
+    run_editor(terminal, &path)?;
+
+    let changed = state.reload_prompts(PromptLibrary::load()?);
+    if changed > 0 {
+        state.save_sessions()?;
+        state.set_status(format!("Prompt library saved; updated {} sessions", changed));
+    } else {
+        state.set_status("Prompt library saved");
+    }
+    Ok(())
+}
+
+/// Open `path` in `$VISUAL` / `$EDITOR` and wait for it, suspending the TUI
+fn run_editor(terminal: &mut Term, path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_default();
+    let (program, args) = command_line(&editor, "vi")?;
+
+    restore_terminal(terminal)?;
+    let status = std::process::Command::new(&program).args(args).arg(path).status();
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste, EnableFocusChange)?;
+    terminal.clear()?;
+
+    let status = status.with_context(|| format!("Failed to run {}", program))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+/// Program and arguments of a command such as `$EDITOR`, split the way a
+/// shell would, so quoted paths with spaces stay whole; `default` when empty
+fn command_line(command: &str, default: &str) -> Result<(String, Vec<String>)> {
+    let mut words = shell_words::split(command).with_context(|| format!("Can't parse command {:?}", command))?;
+    if words.is_empty() {
+        return Ok((default.to_string(), Vec::new()));
+    }
+    let program = words.remove(0);
+    Ok((program, words))
+}
+
+/// Draw one frame, returning image placements and line layouts for the chat
+/// area and the split view's other pane
+fn draw(terminal: &mut Term, state: &AppState) -> Result<(Vec<ImagePlacement>, ChatViewport, ChatViewport)> {
+    let mut chat = Default::default();
+    terminal.draw(|frame| {
+        chat = render_layout(frame, state);
+        render_model_popup(frame, state);
+        render_help_popup(frame, state);
+        render_delete_confirm_popup(frame, state);
+        render_rename_popup(frame, state);
+        render_file_picker_popup(frame, state);
+        render_search_popup(frame, state);
+        render_palette_popup(frame, state);
+        render_patch_confirm_popup(frame, state);
+        render_downloads_popup(frame, state);
+        render_pull_confirm_popup(frame, state);
+        render_code_picker_popup(frame, state);
+        render_yanks_popup(frame, state);
+        render_snippets_popup(frame, state);
+        render_prompts_popup(frame, state);
+        render_redact_review_popup(frame, state);
+        render_cache_popup(frame, state);
+        render_dry_run_popup(frame, state);
+        render_context_popup(frame, state);
+        render_message_inspect_popup(frame, state);
+    })?;
+    Ok(chat)
+}
+
+/// Submit a user message and start streaming response
+async fn submit_message(
+    state: &mut AppState,
+    client: &Providers,
+    event_tx: &mpsc::Sender<AppEvent>,
+    content: String,
+) {
+    let content = content.trim().to_string();
+    if content.is_empty() {
+        return;
+    }
+    let prompt = content.clone();
+    let mut agent = false;
+    
+    // Expand slash commands; on failure keep the input for editing
+    let content = match commands::expand(&content) {
+        Ok(Some(Outcome::Send(expanded))) => {
+            state.pending_follow_up = expanded.follow_up;
+            agent = expanded.agent;
+            expanded.content
+        }
+        Ok(Some(Outcome::TogglePostProcessor(processor))) => {
+            let enabled = state.toggle_post_processor(processor);
+            let verb = if enabled { "enabled" } else { "disabled" };
+            state.set_status(format!("Post-processor {} {}", processor.name(), verb));
+            return;
+        }
+        Ok(Some(Outcome::ToggleTag(tag))) => {
+            if let Some(session) = state.active_session_mut() {
+                let verb = if session.toggle_tag(&tag) { "Tagged" } else { "Untagged" };
+                state.set_status(format!("{} #{}", verb, tag));
+            }
+            return;
+        }
+        Ok(Some(Outcome::ExportDataset(scope))) => {
+            spawn_dataset_export(state, &scope, event_tx);
+            return;
+        }
+        Ok(Some(Outcome::Rate(rating, note))) => {
+            match state.active_session_mut().and_then(|s| s.rate_last_response(rating, note)) {
+                Some(Some(_)) => state.set_status("Feedback saved"),
+                Some(None) => state.set_status("Rating cleared"),
+                None => state.set_status("No response to rate"),
+            }
+            return;
+        }
+        Ok(Some(Outcome::ShowCache)) => {
+            process_action(AppAction::OpenCache, state);
+            return;
+        }
+        Ok(Some(Outcome::ShowPostProcessors)) => {
+            let names: Vec<&str> = state.post_processors().iter().map(|p| p.name()).collect();
+            if names.is_empty() {
+                state.set_status("No post-processors enabled");
+            } else {
+                state.set_status(format!("Post-processors: {}", names.join(", ")));
+            }
+            return;
+        }
+        Ok(None) => content,
+        Err(e) => {
+            state.set_error(e.to_string());
+            state.input = content;
+            state.move_cursor_end();
+            return;
+        }
+    };
+    
+    // Tool calls are only wired up for Ollama's chat API
+    if agent && client.ollama_for(state.current_model()).is_none() {
+        state.set_warning("Agent mode needs an Ollama model");
+        state.input = prompt;
+        state.move_cursor_end();
+        return;
+    }
+    
+    // Add user message, attaching any `@path` image references and the
+    // files picked with Ctrl+o
+    let images = extract_image_refs(&content);
+    let attachments = std::mem::take(&mut state.pending_attachments);
+    if let Some(session) = state.active_session_mut() {
+        session.add_user_message_with_images(&content, images);
+        if let Some(message) = session.messages.last_mut() {
+            message.attachments = attachments;
+        }
+        session.start_assistant_response();
+    }
+    
+    state.streaming = true;
+    state.last_prompt = Some(prompt);
+    state.scroll_to_bottom();
+    
+    let model = state.current_model().to_string();
+    
+    let messages = request_history(state, &model);
+    
+    // Build request with options from config
+    let mut request = ChatRequest::new(model, messages);
+    
+    // Apply generation options from config and the session, and note them
+    // on the response
+    request = request
+        .with_options(state.generation_options())
+        .with_think(state.config.model.think);
+    if let Some(reply) = state.active_session_mut().and_then(|s| s.messages.last_mut()) {
+        reply.options = request.options.clone();
+    }
+    
+    // Dry run: describe the request, then undo the exchange
+    if state.dry_run {
+        let (provider, _) = client.route(&request.model);
+        let rounds = agent.then_some(state.config.agent.max_steps);
+        let request = if agent {
+            let tools = permissions::permitted_tools(&state.config.permissions, &state.config.agent.tools);
+            request.with_tools(tools.iter().map(|tool| tool.spec()).collect())
+        } else {
+            request
+        };
+        state.dry_run_report = Some(dryrun::report(&request, provider.name(), rounds));
+        state.dry_run_scroll = 0;
+        let mut attachments = Vec::new();
+        if let Some(session) = state.active_session_mut() {
+            let kept = session.messages.len().saturating_sub(2);
+            if let Some(user) = session.messages.get_mut(kept) {
+                attachments = std::mem::take(&mut user.attachments);
+            }
+            session.messages.truncate(kept);
+        }
+        state.pending_attachments = attachments;
+        state.streaming = false;
+        state.input = state.last_prompt.take().unwrap_or_default();
+        state.move_cursor_end();
+        state.input_mode = InputMode::DryRun;
+        return;
+    }
+    
+    if agent && state.compare.is_some() {
+        state.set_warning("Compare mode leaves agent prompts to the session's own model");
+    } else if state.compare.is_some() {
+        spawn_compare(state, client, event_tx);
+    }
+    
+    // Identical requests are answered from the cache, when it's enabled
+    state.cache_pending = None;
+    if state.config.cache.enabled && !agent && !state.demo {
+        let key = ResponseCache::key(&request);
+        let now = chrono::Utc::now();
+        if let Some(hit) = state.cache.lookup(&key, state.config.cache.ttl(), now).cloned() {
+            answer_from_cache(state, hit);
+            return;
+        }
+        state.cache_pending = Some(CacheEntry {
+            key,
+            model: request.model.clone(),
+            prompt: state.last_prompt.clone().unwrap_or_default(),
+            content: String::new(),
+            thinking: String::new(),
+            created: now,
+            hits: 0,
+        });
+    }
+    
+    // Spawn streaming task; its events are tagged with the session
+    let client = client.clone();
+    let Some(session_id) = state.active_session().map(|s| s.id) else {
+        return;
+    };
+    let tx = tagged_sender(event_tx, move |event| AppEvent::Stream { session_id, event });
+    
+    let task = if agent {
+        let mut agent = state.config.agent.clone();
+        agent.tools = permissions::permitted_tools(&state.config.permissions, &agent.tools);
+        if agent.tools.len() < state.config.agent.tools.len() {
+            state.set_warning("File tools are disabled; set [permissions] allow_file_read = true to offer them");
+        }
+        tokio::spawn(run_agent(client, request, agent, tx))
+    } else {
+        tokio::spawn(async move {
+            if let Some(round) = stream_round(&client, request, &tx).await {
+                let _ = tx.send(AppEvent::StreamComplete(round.stats)).await;
+            }
+        })
+    };
+    state.stream_abort = Some(task.abort_handle());
+}
+
+/// Label of the dataset export's progress gauge
+const EXPORT_PROGRESS: &str = "export";
+
+/// Write the sessions in `scope` as a JSONL dataset in the export directory,
+/// off the UI thread, reporting progress per session
+fn spawn_dataset_export(state: &mut AppState, scope: &DatasetScope, event_tx: &mpsc::Sender<AppEvent>) {
+    if state.progress.iter().any(|p| p.id == EXPORT_PROGRESS) {
+        state.set_status("An export is already running");
+        return;
+    }
+    let (sessions, dir) = match state.dataset_sessions(scope).and_then(|s| Ok((s, state.export_dir()?))) {
+        Ok(found) => found,
+        Err(e) => {
+            state.set_error(format!("Dataset export failed: {}", e));
+            return;
+        }
+    };
+    let exclude_flagged = state.config.export.dataset_exclude_flagged;
+    let total = sessions.len() as u64;
+    state.update_progress(EXPORT_PROGRESS, 0, total);
+    
+    let tx = event_tx.clone();
+    tokio::task::spawn_blocking(move || {
+        let sessions: Vec<&ChatSession> = sessions.iter().collect();
+        let jsonl = export::dataset_jsonl(&sessions, exclude_flagged, |done| {
+            let id = EXPORT_PROGRESS.to_string();
+            let _ = tx.blocking_send(AppEvent::Progress { id, done: done as u64, total });
+        });
+        let result = if jsonl.is_empty() {
+            Ok(None)
+        } else {
+            persistence::export_dataset_to_file(&jsonl, &dir)
+                .map(|path| Some((path, jsonl.lines().count())))
+                .map_err(|e| e.to_string())
+        };
+        let _ = tx.blocking_send(AppEvent::DatasetExported(result));
+    });
+}
+
+/// Compare mode: send the prompt just asked in the active session to the
+/// second model too, streaming its answer into the compare session
+fn spawn_compare(state: &mut AppState, client: &Providers, event_tx: &mpsc::Sender<AppEvent>) {
+    let Some(question) = state
+        .active_session()
+        .and_then(|s| s.messages.len().checked_sub(2).map(|idx| s.messages[idx].clone()))
+    else {
+        return;
+    };
+    let options = state.generation_options();
+    let think = state.config.model.think;
+    let Some(compare) = state.compare.as_ref() else {
+        return;
+    };
+    let (session_id, model) = (compare.session, compare.model.clone());
+    // Only prompts asked in the session being compared go to both models
+    if state.chat.active_session().map(|s| s.id) != Some(compare.source) {
+        return;
+    }
+    let Some(session) = state.chat.session_mut(session_id) else {
+        // The compare session was deleted
+        state.compare = None;
+        return;
+    };
+    session.messages.push(Message { id: Uuid::new_v4(), ..question });
+    session.start_assistant_response();
+    let Some(session) = state.chat.sessions.iter().find(|s| s.id == session_id) else {
+        return;
+    };
+    let (messages, not_sent) = state.request_messages(session, &model);
+    let request = ChatRequest::new(model, messages)
+        .with_options(options)
+        .with_think(think);
+    if let Some(session) = state.chat.session_mut(session_id) {
+        session.not_sent = not_sent;
+        if let Some(reply) = session.messages.last_mut() {
+            reply.options = request.options.clone();
+        }
+    }
+    if let Some(split) = state.split.as_mut().filter(|split| split.other == session_id) {
+        split.scroll = 0;
+    }
+    
+    // Its events are tagged with the session on their way to the main loop
+    let round_tx = tagged_sender(event_tx, move |event| AppEvent::Compare { session_id, event });
+    let client = client.clone();
+    let task = tokio::spawn(async move {
+        if let Some(round) = stream_round(&client, request, &round_tx).await {
+            let _ = round_tx.send(AppEvent::StreamComplete(round.stats)).await;
+        }
+    });
+    if let Some(compare) = state.compare.as_mut() {
+        compare.abort = Some(task.abort_handle());
+    }
+}
+
+/// Apply a stream event of a response to the session it was asked in
+///
+/// Switching sessions or undoing mid-stream doesn't redirect the tokens.
+/// Events for a session deleted since are dropped, though its end still
+/// settles the stream.
+fn handle_stream_event(
+    state: &mut AppState,
+    client: &Providers,
+    event_tx: &mpsc::Sender<AppEvent>,
+    generation_started: &mut Option<Instant>,
+    session_id: Uuid,
+    event: AppEvent,
+) {
+    let is_active = state.active_session().is_some_and(|s| s.id == session_id);
+    match event {
+        AppEvent::StreamChunk(content) => {
+            if let Some(session) = state.chat.session_mut(session_id) {
+                session.append_to_response(&content);
+            }
+        }
+        AppEvent::StreamThinking(thinking) => {
+            if let Some(session) = state.chat.session_mut(session_id) {
+                session.append_thinking_to_response(&thinking);
+            }
+        }
+        AppEvent::ToolCalled { name, arguments } => {
+            if let Some(session) = state.chat.session_mut(session_id) {
+                session.add_tool_step(name, arguments);
+            }
+        }
+        AppEvent::ToolResult(result) => {
+            if let Some(session) = state.chat.session_mut(session_id) {
+                session.finish_tool_step(result);
+            }
+        }
+        AppEvent::StreamComplete(stats) => {
+            info!("Stream complete: {} tokens at {:.1} tok/s", 
+                stats.tokens, stats.tokens_per_second);
+            state.streaming = false;
+            let processors = state.post_processors();
+            let Some(session) = state.chat.session_mut(session_id) else {
+                state.cache_pending = None;
+                state.pending_follow_up = None;
+                *generation_started = None;
+                return;
+            };
+            session.finish_response();
+            session.record_stats(&stats);
+            // Cache the answer as the model gave it, before post-processing
+            store_in_cache(state, session_id);
+            if let Some(session) = state.chat.session_mut(session_id) {
+                session.post_process_response(&processors);
+            }
+            if let Some(compare) = state.compare.as_mut() {
+                compare.stats.insert(session_id, stats.clone());
+            }
+            let outcome = notify::Outcome::Completed { tokens: stats.tokens };
+            notify_completion(state, event_tx, generation_started.take(), outcome);
+            state.last_response_stats = Some(stats);
+            
+            if state.config.model.auto_title && !state.demo {
+                let session = state.chat.sessions.iter().find(|s| s.id == session_id);
+                if let Some(session) = session.filter(|s| s.wants_auto_title()) {
+                    spawn_auto_title(client, event_tx, session);
+                }
+            }
+            
+            // A follow-up and the vault note belong to the session on screen
+            if is_active {
+                if let Some(follow_up) = state.pending_follow_up.take() {
+                    run_follow_up(state, follow_up);
+                }
+                if state.config.export.vault_auto_export {
+                    if let Some(Err(e)) = state.export_to_vault() {
+                        warn!("Failed to auto-export to vault: {}", e);
+                    }
+                }
+            } else {
+                state.pending_follow_up = None;
+            }
+            
+            // Auto-save after response
+            match state.save_sessions() {
+                Ok(()) => state.trim_memory(),
+                Err(e) => warn!("Failed to save sessions: {}", e),
+            }
+            return;
+        }
+        AppEvent::StreamError(err) => {
+            error!("Stream error: {}", err);
+            if let Some(session) = state.chat.session_mut(session_id) {
+                session.finish_response();
+                // Append error to message
+                if let Some(msg) = session.messages.last_mut() {
+                    if msg.content.is_empty() {
+                        msg.content = format!("[Error: {}]", err);
+                        msg.touch();
+                    }
+                }
+            }
+            state.streaming = false;
+            state.pending_follow_up = None;
+            state.cache_pending = None;
+            let outcome = notify::Outcome::Failed(err.clone());
+            notify_completion(state, event_tx, generation_started.take(), outcome);
+            state.set_error(err);
+            return;
+        }
+        AppEvent::ModelMissing(model) => {
+            warn!("Model not installed: {}", model);
+            if let Some(session) = state.chat.session_mut(session_id) {
+                session.finish_response();
+                if let Some(msg) = session.messages.last_mut() {
+                    if msg.content.is_empty() {
+                        msg.content = format!("[Error: model {} is not installed]", model);
+                        msg.touch();
+                    }
+                }
+            }
+            state.streaming = false;
+            state.pending_follow_up = None;
+            state.cache_pending = None;
+            state.missing_model = Some(model);
+            state.input_mode = InputMode::PullConfirm;
+            return;
+        }
+        _ => return,
+    }
+    // Auto-scroll to bottom during streaming
+    if is_active {
+        state.scroll_to_bottom();
+    }
+}
+
+/// A sender whose events reach the main loop wrapped by `tag`, e.g. with
+/// the session they are for
+fn tagged_sender(event_tx: &mpsc::Sender<AppEvent>, tag: impl Fn(Box<AppEvent>) -> AppEvent + Send + 'static) -> mpsc::Sender<AppEvent> {
+    let (round_tx, mut round_rx) = mpsc::channel(100);
+    let tx = event_tx.clone();
+    tokio::spawn(async move {
+        while let Some(event) = round_rx.recv().await {
+            if tx.send(tag(Box::new(event))).await.is_err() {
+                return;
+            }
+        }
+    });
+    round_tx
+}
+
+/// Apply a stream event of the second model to its session
+fn handle_compare_event(state: &mut AppState, session_id: Uuid, event: AppEvent) {
+    let processors = state.post_processors();
+    let Some(session) = state.chat.session_mut(session_id) else {
+        return;
+    };
+    let failure = match event {
+        AppEvent::StreamChunk(content) => {
+            session.append_to_response(&content);
+            return;
+        }
+        AppEvent::StreamThinking(thinking) => {
+            session.append_thinking_to_response(&thinking);
+            return;
+        }
+        AppEvent::StreamComplete(stats) => {
+            info!("Compare stream complete: {} tokens at {:.1} tok/s", stats.tokens, stats.tokens_per_second);
+            session.finish_response();
+            session.record_stats(&stats);
+            session.post_process_response(&processors);
+            if let Some(compare) = state.compare.as_mut().filter(|c| c.session == session_id) {
+                compare.abort = None;
+                compare.stats.insert(session_id, stats);
+            }
+            if let Err(e) = state.save_sessions() {
+                warn!("Failed to save sessions: {}", e);
+            }
+            return;
+        }
+        AppEvent::StreamError(err) => err,
+        AppEvent::ModelMissing(model) => format!("model {} is not installed", model),
+        _ => return,
+    };
+    error!("Compare stream error: {}", failure);
+    session.finish_response();
+    if let Some(msg) = session.messages.last_mut().filter(|m| m.content.is_empty()) {
+        msg.content = format!("[Error: {}]", failure);
+        msg.touch();
+    }
+    let model = session.model.clone();
+    if let Some(compare) = state.compare.as_mut().filter(|c| c.session == session_id) {
+        compare.abort = None;
+    }
+    state.set_error(format!("{}: {}", model, failure));
+}
+
+/// Learn the current model's context window in the background, once
+///
+/// Not needed while `num_ctx` sets the window. Until the answer arrives
+/// requests go out untrimmed.
+fn spawn_context_lookup(state: &mut AppState, client: &Providers, tx: &mpsc::Sender<AppEvent>) {
+    let model = state.current_model();
+    if state.dry_run || state.demo || state.chat.context_window_asked(model) {
+        return;
+    }
+    if state.generation_options().num_ctx.is_some_and(|n| n > 0) {
+        return;
+    }
+    let Some(ollama) = client.ollama_for(model).cloned() else {
+        return;
+    };
+    let model = model.to_string();
+    // Marks the lookup as started
+    state.chat.set_context_window(model.clone(), 0);
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let tokens = match ollama.show_model(&model).await {
+            Ok(show) => show.context_length().unwrap_or(0),
+            Err(e) => {
+                warn!("Could not read the context window of {}: {}", model, e);
+                0
+            }
+        };
+        let _ = tx.send(AppEvent::ContextWindow { model, tokens }).await;
+    });
+}
+
+/// Messages to send `model`: the session's history, leaving out the oldest
+/// messages that don't fit the context window
+///
+/// Remote models get the guardrail ahead of everything else.
+fn request_history(state: &mut AppState, model: &str) -> Vec<ChatMessage> {
+    let Some(session) = state.active_session() else {
+        return state.config.guardrail_for(model).map(ChatMessage::system).into_iter().collect();
+    };
+    let (messages, not_sent) = state.request_messages(session, model);
+    if let Some(session) = state.active_session_mut() {
+        session.not_sent = not_sent;
+    }
+    if not_sent > 0 {
+        info!("Left {} older messages out to fit the context window", not_sent);
+    }
+    messages
+}
+
+/// Ask the model to carry on from a response that was cut off, streaming
+/// the rest onto the same message
+///
+/// The request is the conversation so far plus a "continue" turn, which is
+/// sent but never added to the session.
+async fn continue_response(state: &mut AppState, client: &Providers, event_tx: &mpsc::Sender<AppEvent>) {
+    if state.streaming {
+        return;
+    }
+    if !state.active_session_mut().is_some_and(|s| s.resume_response()) {
+        state.set_status("The last response wasn't cut off");
+        return;
+    }
+    
+    let model = state.current_model().to_string();
+    let mut messages = request_history(state, &model);
+    messages.push(ChatMessage::user(CONTINUE_PROMPT));
+    let request = ChatRequest::new(model, messages)
+        .with_options(state.generation_options())
+        .with_think(state.config.model.think);
+    
+    // Dry run: describe the request, leaving the response as it was
+    if state.dry_run {
+        let (provider, _) = client.route(&request.model);
+        state.dry_run_report = Some(dryrun::report(&request, provider.name(), None));
+        state.dry_run_scroll = 0;
+        if let Some(session) = state.active_session_mut() {
+            session.finish_response();
+            session.mark_truncated();
+        }
+        state.input_mode = InputMode::DryRun;
+        return;
+    }
+    
+    state.streaming = true;
+    state.cache_pending = None;
+    state.scroll_to_bottom();
+    let client = client.clone();
+    let Some(session_id) = state.active_session().map(|s| s.id) else {
+        return;
+    };
+    let tx = tagged_sender(event_tx, move |event| AppEvent::Stream { session_id, event });
+    let task = tokio::spawn(async move {
+        if let Some(round) = stream_round(&client, request, &tx).await {
+            let _ = tx.send(AppEvent::StreamComplete(round.stats)).await;
+        }
+    });
+    state.stream_abort = Some(task.abort_handle());
+}
+
+/// Complete the pending response with a cached answer
+fn answer_from_cache(state: &mut AppState, hit: CacheEntry) {
+    info!("Answered from cache: {}", hit.key);
+    let processors = state.post_processors();
+    if let Some(session) = state.active_session_mut() {
+        session.append_thinking_to_response(&hit.thinking);
+        session.append_to_response(&hit.content);
+        if let Some(msg) = session.messages.last_mut() {
+            msg.cached = true;
+        }
+        session.finish_response();
+        session.post_process_response(&processors);
+    }
+    state.streaming = false;
+    state.last_response_stats = None;
+    state.set_status(format!("Answered from cache (used {} times)", hit.hits));
+    if let Some(follow_up) = state.pending_follow_up.take() {
+        run_follow_up(state, follow_up);
+    }
+    if let Err(e) = state.save_cache() {
+        warn!("Failed to save response cache: {}", e);
+    }
+    if let Err(e) = state.save_sessions() {
+        warn!("Failed to save sessions: {}", e);
+    }
+}
+
+/// Remember the response that just completed in `session_id` for
+/// identical requests
+fn store_in_cache(state: &mut AppState, session_id: Uuid) {
+    let Some(mut entry) = state.cache_pending.take() else {
+        return;
+    };
+    let session = state.chat.sessions.iter().find(|s| s.id == session_id);
+    let Some(message) = session.and_then(|s| s.messages.last()) else {
+        return;
+    };
+    if message.content.trim().is_empty() || message.interrupted {
+        return;
+    }
+    entry.content = message.content.clone();
+    entry.thinking = message.thinking.clone();
+    entry.created = chrono::Utc::now();
+    state.cache.insert(entry, state.config.cache.max_entries);
+    if let Err(e) = state.save_cache() {
+        warn!("Failed to save response cache: {}", e);
+    }
+}
+
+/// One streamed response: its stats, text and any tool calls requested
+struct Round {
+    stats: ResponseStats,
+    content: String,
+    tool_calls: Vec<ToolCall>,
+}
+
+/// Stream a request, forwarding chunks to the UI
+///
+/// Returns `None` after reporting an error.
+async fn stream_round(client: &Providers, request: ChatRequest, tx: &mpsc::Sender<AppEvent>) -> Option<Round> {
+    let model = request.model.clone();
+    let started = Instant::now();
+    let mut stream = match client.chat_stream(request).await {
+        Ok(stream) => stream,
+        Err(OllamaError::ModelNotFound { model }) => {
+            let _ = tx.send(AppEvent::ModelMissing(model)).await;
+            return None;
+        }
+        Err(e) => {
+            let _ = tx.send(AppEvent::StreamError(e.with_suggestion())).await;
+            return None;
+        }
+    };
+    
+    let mut round = Round {
+        stats: ResponseStats::default(),
+        content: String::new(),
+        tool_calls: Vec::new(),
+    };
+    while let Some(result) = stream.next().await {
+        let chunk = match result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                let _ = tx.send(AppEvent::StreamError(e.with_suggestion())).await;
+                return None;
+            }
+        };
+        
+        // Check for errors in the chunk
+        if let Some(error) = chunk.error {
+            // Anything unrecognized is shown as the server worded it
+            let error = match ollama::error_message(&model, &error) {
+                Some(e) => e.with_suggestion(),
+                None => error,
+            };
+            let _ = tx.send(AppEvent::StreamError(error)).await;
+            return None;
+        }
+        
+        // Reasoning counts as the first token as much as the answer does
+        let has_token = chunk.thinking().is_some_and(|t| !t.is_empty()) || chunk.content().is_some_and(|c| !c.is_empty());
+        if has_token && round.stats.first_token_ms.is_none() {
+            round.stats.first_token_ms = Some(started.elapsed().as_millis() as u64);
+        }
+
+        // Reasoning arrives separately from the answer
+        if let Some(thinking) = chunk.thinking() {
+            if !thinking.is_empty() {
+                let _ = tx.send(AppEvent::StreamThinking(thinking.to_string())).await;
+            }
+        }
+        
+        // Send content if present
+        if let Some(content) = chunk.content() {
+            if !content.is_empty() {
+                round.content.push_str(content);
+                let _ = tx.send(AppEvent::StreamChunk(content.to_string())).await;
+            }
+        }
+        round.tool_calls.extend_from_slice(chunk.tool_calls());
+        if chunk.done_reason.is_some() {
+            round.stats.truncated = chunk.truncated();
+        }
+        
+        // Capture stats (hosted providers report usage on a chunk before
+        // the final one)
+        if let Some(count) = chunk.eval_count {
+            round.stats.tokens = count;
+        }
+        if let Some(count) = chunk.prompt_eval_count {
+            round.stats.prompt_tokens = count;
+        }
+        if chunk.done {
+            if let Some(tps) = chunk.tokens_per_second() {
+                round.stats.tokens_per_second = tps;
+            }
+            if let Some(duration) = chunk.total_duration {
+                round.stats.total_duration_ms = duration / 1_000_000; // ns to ms
+            }
+        }
+    }
+    Some(round)
+}
+
+/// Run an agent turn: let the model call tools for up to `max_steps` rounds,
+/// then stream its final answer
+///
+/// Each call is reported to the UI as it starts and finishes. Once the budget
+/// is spent the model is asked to answer without tools.
+async fn run_agent(client: Providers, mut request: ChatRequest, agent: AgentConfig, tx: mpsc::Sender<AppEvent>) {
+    let base = std::env::current_dir().unwrap_or_default();
+    request = request.with_tools(agent.tools.iter().map(|tool| tool.spec()).collect());
+    
+    for _ in 0..agent.max_steps {
+        let Some(round) = stream_round(&client, request.clone(), &tx).await else {
+            return;
+        };
+        if round.tool_calls.is_empty() {
+            let _ = tx.send(AppEvent::StreamComplete(round.stats)).await;
+            return;
+        }
+        
+        request.messages.push(ChatMessage {
+            tool_calls: Some(round.tool_calls.clone()),
+            ..ChatMessage::assistant(round.content)
+        });
+        for call in round.tool_calls {
+            let FunctionCall { name, arguments } = call.function;
+            let _ = tx.send(AppEvent::ToolCalled { name: name.clone(), arguments: arguments.clone() }).await;
+            let result = match Tool::from_name(&name).filter(|tool| agent.tools.contains(tool)) {
+                Some(tool) => tool.run(&arguments, &base),
+                None => format!("error: no tool named {}", name),
+            };
+            let _ = tx.send(AppEvent::ToolResult(result.clone())).await;
+            request.messages.push(ChatMessage::tool(result));
+        }
+    }
+    
+    // Budget spent: withhold the tools so the model has to answer
+    request.tools = None;
+    request.messages.push(ChatMessage::system(AGENT_BUDGET_NOTE));
+    if let Some(round) = stream_round(&client, request, &tx).await {
+        let _ = tx.send(AppEvent::StreamComplete(round.stats)).await;
+    }
+}
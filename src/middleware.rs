@@ -0,0 +1,192 @@
+//! Message processing pipeline
+//!
+//! Post-processors run on each assistant message once its response has
+//! finished streaming. The set comes from `[middleware] post_processors` and
+//! can be toggled per session with `/post <name>`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::Message;
+
+/// A stage applied to completed assistant messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostProcessor {
+    /// Remove `<think>` blocks and separately streamed reasoning
+    StripThinking,
+    /// Trim trailing whitespace from lines and the message
+    TrimWhitespace,
+    /// Collapse runs of blank lines outside code blocks
+    CollapseBlankLines,
+    /// Drop paragraphs repeating an earlier one verbatim
+    DedupeParagraphs,
+}
+
+impl PostProcessor {
+    /// All processors, in the order they run
+    pub const ALL: [PostProcessor; 4] = [
+        PostProcessor::StripThinking,
+        PostProcessor::TrimWhitespace,
+        PostProcessor::CollapseBlankLines,
+        PostProcessor::DedupeParagraphs,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PostProcessor::StripThinking => "strip_thinking",
+            PostProcessor::TrimWhitespace => "trim_whitespace",
+            PostProcessor::CollapseBlankLines => "collapse_blank_lines",
+            PostProcessor::DedupeParagraphs => "dedupe_paragraphs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    /// Apply this stage to message text
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            PostProcessor::StripThinking => strip_think_tags(text),
+            PostProcessor::TrimWhitespace => text
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string(),
+            PostProcessor::CollapseBlankLines => collapse_blank_lines(text),
+            PostProcessor::DedupeParagraphs => dedupe_paragraphs(text),
+        }
+    }
+}
+
+/// Run the enabled processors over a finished message
+pub fn post_process(processors: &[PostProcessor], message: &mut Message) {
+    // Run in canonical order regardless of how the list was written
+    for processor in PostProcessor::ALL.into_iter().filter(|p| processors.contains(p)) {
+        if processor == PostProcessor::StripThinking {
+            message.thinking.clear();
+        }
+        message.content = processor.apply(&message.content);
+    }
+    message.touch();
+}
+
+/// Remove `<think>...</think>` blocks, including an unterminated trailing one
+fn strip_think_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("<think>") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find("</think>") {
+            Some(end) => rest = &rest[start + end + "</think>".len()..],
+            None => rest = "",
+        }
+    }
+    out.push_str(rest);
+    out.trim_start().to_string()
+}
+
+/// Whether each line is inside (or delimits) a code fence
+fn fenced_lines(lines: &[&str]) -> Vec<bool> {
+    let mut in_fence = false;
+    lines
+        .iter()
+        .map(|line| {
+            let is_fence = line.trim_start().starts_with("```");
+            if is_fence {
+                in_fence = !in_fence;
+            }
+            in_fence || is_fence
+        })
+        .collect()
+}
+
+fn collapse_blank_lines(text: &str) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let fenced = fenced_lines(&lines);
+    let mut out: Vec<&str> = Vec::with_capacity(lines.len());
+    for (line, in_fence) in lines.iter().zip(fenced) {
+        let blank = line.trim().is_empty();
+        let prev_blank = out.last().is_some_and(|l| l.trim().is_empty());
+        if blank && prev_blank && !in_fence {
+            continue;
+        }
+        out.push(line);
+    }
+    out.join("\n")
+}
+
+fn dedupe_paragraphs(text: &str) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let fenced = fenced_lines(&lines);
+
+    // Split into paragraphs at blank lines outside code blocks
+    let mut paragraphs: Vec<(Vec<&str>, bool)> = vec![(Vec::new(), false)];
+    for (line, in_fence) in lines.iter().zip(fenced) {
+        if !in_fence && line.trim().is_empty() {
+            paragraphs.push((Vec::new(), false));
+        } else if let Some((current, has_code)) = paragraphs.last_mut() {
+            current.push(line);
+            *has_code |= in_fence;
+        }
+    }
+
+    let mut seen: Vec<String> = Vec::new();
+    let mut kept: Vec<String> = Vec::new();
+    for (paragraph, has_code) in paragraphs {
+        let joined = paragraph.join("\n");
+        let key = joined.trim().to_string();
+        if !key.is_empty() && !has_code {
+            if seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+        }
+        kept.push(joined);
+    }
+    kept.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_thinking() {
+        let text = "<think>\nhmm\n</think>\n\nThe answer is 4.";
+        assert_eq!(PostProcessor::StripThinking.apply(text), "The answer is 4.");
+        assert_eq!(PostProcessor::StripThinking.apply("<think>still going"), "");
+    }
+
+    #[test]
+    fn test_collapse_blank_lines_keeps_code() {
+        let text = "a\n\n\n\nb\n```\nx\n\n\ny\n```";
+        assert_eq!(
+            PostProcessor::CollapseBlankLines.apply(text),
+            "a\n\nb\n```\nx\n\n\ny\n```"
+        );
+    }
+
+    #[test]
+    fn test_dedupe_paragraphs() {
+        let text = "Hello there.\n\nSome detail.\n\nHello there.\n\n```\n}\n```\n\n```\n}\n```";
+        assert_eq!(
+            PostProcessor::DedupeParagraphs.apply(text),
+            "Hello there.\n\nSome detail.\n\n```\n}\n```\n\n```\n}\n```"
+        );
+    }
+
+    #[test]
+    fn test_post_process_clears_thinking() {
+        let mut message = Message::assistant("  answer  \n");
+        message.thinking = "reasoning".to_string();
+        post_process(
+            &[PostProcessor::TrimWhitespace, PostProcessor::StripThinking],
+            &mut message,
+        );
+        assert_eq!(message.content, "answer");
+        assert!(message.thinking.is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+//! Read-only UI mirror
+//!
+//! With `--mirror <addr>`, ratatalk serves a WebSocket on a loopback address
+//! and pushes what the UI shows to every client that connects: the session,
+//! its messages as they stream, the input draft and the mode. Clients can
+//! only watch; anything they send is ignored. `assets/mirror.html` is a
+//! small web view for it, handy for showing a live demo on a second screen,
+//! and any WebSocket client works from another terminal:
+//!
+//! ```text
+//! websocat 'ws://127.0.0.1:7879/?token=…'
+//! ```
+//!
+//! Every run picks a random token, shown in the status bar with the URL, and
+//! a handshake without it is refused. So are handshakes from web pages: a
+//! browser sends the page's `Origin`, and only pages opened from a file
+//! (origin `null`, like `mirror.html`) or clients that send none get in.
+//!
+//! Each message is a whole JSON state with `"type": "state"`. A new one is
+//! built at most once a frame, only when a cheap fingerprint of what it
+//! shows changed, and a slow client skips straight to the newest state
+//! instead of falling behind.
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::app::AppState;
+
+/// Publishes the UI state to connected mirror clients
+pub struct Mirror {
+    tx: Arc<watch::Sender<Value>>,
+    /// Fingerprint of the last state sent
+    sent: Cell<Option<u64>>,
+}
+
+impl Mirror {
+    /// Serve mirror clients on `addr`, which must be a loopback address
+    ///
+    /// Returns the mirror and the URL clients connect to, token included.
+    pub async fn listen(addr: SocketAddr) -> std::io::Result<(Self, String)> {
+        if !addr.ip().is_loopback() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("--mirror only binds to loopback addresses, not {}", addr.ip()),
+            ));
+        }
+        let listener = TcpListener::bind(addr).await?;
+        let token: Arc<str> = Uuid::new_v4().simple().to_string().into();
+        let url = format!("ws://{}/?token={}", listener.local_addr()?, token);
+        info!("Mirror listening on {}", url);
+
+        let tx = Arc::new(watch::Sender::new(Value::Null));
+        let clients = tx.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                info!("Mirror client connected from {}", peer);
+                tokio::spawn(serve(stream, clients.subscribe(), token.clone()));
+            }
+        });
+        Ok((Self { tx, sent: Cell::new(None) }, url))
+    }
+
+    /// Send the current state to clients, if it changed since the last one
+    pub fn publish(&self, state: &AppState) {
+        if self.tx.receiver_count() == 0 {
+            // A client connecting later gets a fresh state
+            self.sent.set(None);
+            return;
+        }
+        let key = fingerprint(state);
+        if self.sent.replace(Some(key)) == Some(key) {
+            return;
+        }
+        self.tx.send_replace(snapshot(state));
+    }
+}
+
+/// Changes whenever the snapshot would; messages count by their revision
+/// rather than their text
+fn fingerprint(state: &AppState) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let session = state.active_session();
+    session.map(|s| (s.id, &s.name)).hash(&mut hasher);
+    for message in session.iter().flat_map(|s| &s.messages) {
+        (message.id, message.revision, message.streaming, &message.model).hash(&mut hasher);
+    }
+    (state.current_model(), state.input_mode, state.streaming, &state.input).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// What a mirror client sees
+pub fn snapshot(state: &AppState) -> Value {
+    let messages: Vec<Value> = state
+        .active_session()
+        .map(|s| {
+            s.messages
+                .iter()
+                .map(|m| {
+                    json!({
+                        "role": m.role,
+                        "content": m.content,
+                        "thinking": m.thinking,
+                        "streaming": m.streaming,
+                        "model": m.model,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    json!({
+        "type": "state",
+        "session": state.active_session().map(|s| s.name.as_str()),
+        "model": state.current_model(),
+        "mode": format!("{:?}", state.input_mode),
+        "streaming": state.streaming,
+        "input": state.input,
+        "messages": messages,
+    })
+}
+
+/// Why a handshake is refused, if it is: a web page's origin or a missing
+/// or wrong token
+fn refusal(request: &Request, token: &str) -> Option<&'static str> {
+    let origin = request.headers().get("origin").map(|o| o.as_bytes());
+    if origin.is_some_and(|o| o != b"null") {
+        return Some("web pages can't attach to the mirror");
+    }
+    let given = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="));
+    (given != Some(token)).then_some("missing or wrong token")
+}
+
+/// Stream states to one client until it disconnects
+async fn serve(stream: TcpStream, mut updates: watch::Receiver<Value>, token: Arc<str>) {
+    // The error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let check = |request: &Request, response: Response| match refusal(request, &token) {
+        Some(reason) => {
+            warn!("Mirror client refused: {}", reason);
+            let mut refused = ErrorResponse::new(Some(reason.to_string()));
+            *refused.status_mut() = StatusCode::FORBIDDEN;
+            Err(refused)
+        }
+        None => Ok(response),
+    };
+    let ws = match tokio_tungstenite::accept_hdr_async(stream, check).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            warn!("Mirror handshake failed: {}", e);
+            return;
+        }
+    };
+    let (mut write, mut read) = ws.split();
+    let first = updates.borrow_and_update().to_string();
+    if write.send(Message::Text(first)).await.is_err() {
+        return;
+    }
+    loop {
+        tokio::select! {
+            changed = updates.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                let text = updates.borrow_and_update().to_string();
+                if write.send(Message::Text(text)).await.is_err() {
+                    return;
+                }
+            }
+            // Read-only: client messages are dropped, but reading keeps
+            // pings answered and notices the close
+            incoming = read.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn test_client_receives_state() {
+        let (mirror, url) = Mirror::listen("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let (without_token, _) = url.split_once('?').unwrap();
+        assert!(tokio_tungstenite::connect_async(without_token).await.is_err());
+        let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+        while mirror.tx.receiver_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let mut state = AppState::new(Config::default());
+        state.input = "hello".to_string();
+        mirror.publish(&state);
+
+        // The state from before the publish may arrive first
+        loop {
+            let Some(Ok(Message::Text(text))) = ws.next().await else {
+                panic!("mirror closed the connection");
+            };
+            let value: Value = serde_json::from_str(&text).unwrap();
+            if value["input"] == "hello" {
+                assert_eq!(value["type"], "state");
+                break;
+            }
+        }
+
+        assert!(Mirror::listen("0.0.0.0:0".parse().unwrap()).await.is_err());
+    }
+
+    #[test]
+    fn test_refuses_web_pages_and_wrong_tokens() {
+        let request = |uri: &str, origin: Option<&str>| {
+            let mut builder = Request::builder().uri(uri);
+            if let Some(origin) = origin {
+                builder = builder.header("Origin", origin);
+            }
+            builder.body(()).unwrap()
+        };
+        assert_eq!(refusal(&request("/?token=abc", None), "abc"), None);
+        assert_eq!(refusal(&request("/?token=abc", Some("null")), "abc"), None);
+        assert!(refusal(&request("/?token=abc", Some("https://evil.example")), "abc").is_some());
+        assert!(refusal(&request("/?token=abd", None), "abc").is_some());
+        assert!(refusal(&request("/", None), "abc").is_some());
+    }
+}
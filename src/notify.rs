@@ -0,0 +1,182 @@
+//! Completion notifications
+//!
+//! With `[notify] webhook_url` set, a generation that ran for at least
+//! `min_secs` POSTs a JSON payload when it finishes or fails, so a long run
+//! can be left alone until a phone buzzes. The payload carries a `text`
+//! summary, which Slack-compatible incoming webhooks display as is, and the
+//! details as separate fields. With `topic` set it also has the `topic`,
+//! `title` and `message` fields of ntfy's JSON publishing, for posting to the
+//! root of an ntfy server.
+//!
+//! Closer to home, a finished response rings the terminal bell while the
+//! terminal is unfocused (or always, or never, per `bell`), and with
+//! `desktop = true` also pops up a desktop notification.
+
+use std::io::Write;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::config::{AlertWhen, NotifyConfig};
+use crate::error::NotifyError;
+
+/// How a generation ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Finished, having generated this many tokens
+    Completed { tokens: u32 },
+    /// Failed with this error
+    Failed(String),
+}
+
+/// A finished generation worth reporting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub session: String,
+    pub model: String,
+    pub elapsed: Duration,
+    pub outcome: Outcome,
+}
+
+impl Completion {
+    /// One-line summary, e.g. `llama3:70b finished in 20m 4s (3120 tokens) in "Refactor"`
+    pub fn summary(&self) -> String {
+        let elapsed = format_elapsed(self.elapsed);
+        match &self.outcome {
+            Outcome::Completed { tokens } => {
+                format!("{} finished in {} ({} tokens) in \"{}\"", self.model, elapsed, tokens, self.session)
+            }
+            Outcome::Failed(error) => {
+                format!("{} failed after {} in \"{}\": {}", self.model, elapsed, self.session, error)
+            }
+        }
+    }
+}
+
+/// Whether a generation that ran for `elapsed` should trigger the webhook
+pub fn wanted(config: &NotifyConfig, elapsed: Duration) -> bool {
+    config.webhook_url.as_deref().is_some_and(|url| !url.trim().is_empty())
+        && elapsed >= Duration::from_secs(config.min_secs)
+}
+
+/// Whether a finished response should ring the bell, given whether the
+/// terminal has focus
+pub fn alert_wanted(config: &NotifyConfig, focused: bool) -> bool {
+    match config.bell {
+        AlertWhen::Never => false,
+        AlertWhen::Unfocused => !focused,
+        AlertWhen::Always => true,
+    }
+}
+
+/// Ring the terminal bell
+pub fn ring_bell() -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()
+}
+
+/// Show `body` as a desktop notification
+///
+/// This blocks until the platform's notification service has taken it, so
+/// call it off the async runtime.
+pub fn desktop(title: &str, body: &str) -> Result<(), NotifyError> {
+    notify_rust::Notification::new()
+        .appname("ratatalk")
+        .summary(title)
+        .body(body)
+        .show()?;
+    Ok(())
+}
+
+/// JSON body sent for `completion`
+pub fn payload(config: &NotifyConfig, completion: &Completion) -> Value {
+    let summary = completion.summary();
+    let (event, tokens, error) = match &completion.outcome {
+        Outcome::Completed { tokens } => ("completed", Some(*tokens), None),
+        Outcome::Failed(error) => ("failed", None, Some(error.as_str())),
+    };
+    let mut body = json!({
+        "text": summary,
+        "event": event,
+        "session": completion.session,
+        "model": completion.model,
+        "elapsed_secs": completion.elapsed.as_secs(),
+        "tokens": tokens,
+        "error": error,
+    });
+    if let Some(topic) = config.topic.as_deref() {
+        body["topic"] = json!(topic);
+        body["title"] = json!(format!("ratatalk: generation {}", event));
+        body["message"] = json!(summary);
+    }
+    body
+}
+
+/// POST `body` to `url`
+pub async fn send(url: &str, body: &Value) -> Result<(), NotifyError> {
+    let response = reqwest::Client::builder()
+        .user_agent(concat!("ratatalk/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(15))
+        .build()?
+        .post(url)
+        .json(body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(NotifyError::Status(response.status()));
+    }
+    Ok(())
+}
+
+/// `1h 2m`, `20m 4s` or `42s`
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let mut config = NotifyConfig { webhook_url: Some("https://example.com/hook".into()), ..Default::default() };
+        let completion = Completion {
+            session: "Refactor".to_string(),
+            model: "llama3:70b".to_string(),
+            elapsed: Duration::from_secs(1204),
+            outcome: Outcome::Completed { tokens: 3120 },
+        };
+        assert!(wanted(&config, completion.elapsed));
+        assert!(!wanted(&config, Duration::from_secs(5)));
+
+        let body = payload(&config, &completion);
+        assert_eq!(body["text"], "llama3:70b finished in 20m 4s (3120 tokens) in \"Refactor\"");
+        assert_eq!(body["event"], "completed");
+        assert!(body.get("topic").is_none());
+
+        config.topic = Some("ratatalk".to_string());
+        let failed = Completion { outcome: Outcome::Failed("connection reset".into()), ..completion };
+        let body = payload(&config, &failed);
+        assert_eq!(body["event"], "failed");
+        assert_eq!(body["error"], "connection reset");
+        assert_eq!(body["topic"], "ratatalk");
+        assert_eq!(body["message"], body["text"]);
+    }
+
+    #[test]
+    fn test_alert_wanted() {
+        let mut config = NotifyConfig::default();
+        assert!(alert_wanted(&config, false));
+        assert!(!alert_wanted(&config, true));
+        config.bell = AlertWhen::Always;
+        assert!(alert_wanted(&config, true));
+        config.bell = AlertWhen::Never;
+        assert!(!alert_wanted(&config, false));
+    }
+}
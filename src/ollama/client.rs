@@ -4,30 +4,50 @@
 
 use crate::error::OllamaError;
 use futures::Stream;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use std::pin::Pin;
 use std::time::Duration;
 use tokio_stream::StreamExt;
 
+use super::response_lines;
 use super::types::*;
 
+/// Scheme prefix selecting a Unix domain socket, e.g. `unix:///run/ollama.sock`
+const UNIX_SCHEME: &str = "unix://";
+
+/// Placeholder HTTP origin used for requests sent over a Unix socket
+const UNIX_BASE_URL: &str = "http://localhost";
+
+/// Upper bound for a single model download
+const PULL_TIMEOUT_SECS: u64 = 6 * 60 * 60;
+
 /// Ollama API client
 #[derive(Debug, Clone)]
 pub struct OllamaClient {
     client: Client,
     base_url: String,
+    /// Configured host as written by the user (for error messages)
+    host: String,
 }
 
 impl OllamaClient {
     /// Create a new Ollama client
-    pub fn new(base_url: impl Into<String>, timeout_secs: u64) -> Result<Self, OllamaError> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(timeout_secs))
-            .build()?;
+    ///
+    /// `host` is either an HTTP(S) URL or `unix://<path>` to connect through
+    /// a Unix domain socket.
+    pub fn new(host: impl Into<String>, timeout_secs: u64) -> Result<Self, OllamaError> {
+        let host = host.into();
+        let builder = Client::builder().timeout(Duration::from_secs(timeout_secs));
+
+        let (builder, base_url) = match host.strip_prefix(UNIX_SCHEME) {
+            Some(path) => (unix_socket(builder, path, &host)?, UNIX_BASE_URL.to_string()),
+            None => (builder, host.trim_end_matches('/').to_string()),
+        };
 
         Ok(Self {
-            client,
-            base_url: base_url.into(),
+            client: builder.build()?,
+            base_url,
+            host,
         })
     }
 
@@ -56,7 +76,7 @@ impl OllamaClient {
             .await
             .map_err(|e| {
                 if e.is_connect() {
-                    OllamaError::ConnectionFailed { url: self.base_url.clone() }
+                    OllamaError::ConnectionFailed { url: self.host.clone() }
                 } else {
                     OllamaError::Request(e)
                 }
@@ -72,6 +92,22 @@ impl OllamaClient {
         Ok(body.models)
     }
 
+    /// Show details of a model
+    pub async fn show_model(&self, model: &str) -> Result<ShowResponse, OllamaError> {
+        let url = format!("{}/api/show", self.base_url);
+        let request = ShowRequest { model: model.to_string() };
+        let response = self.client.post(&url).json(&request).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(OllamaError::ModelNotFound { model: model.to_string() });
+        }
+        if !response.status().is_success() {
+            return Err(OllamaError::ApiError {
+                message: format!("Failed to show model: HTTP {}", response.status()),
+            });
+        }
+        Ok(response.json().await?)
+    }
+
     /// Send a chat request and return a stream of response chunks
     pub async fn chat_stream(
         &self,
@@ -86,7 +122,7 @@ impl OllamaClient {
             .await
             .map_err(|e| {
                 if e.is_connect() {
-                    OllamaError::ConnectionFailed { url: self.base_url.clone() }
+                    OllamaError::ConnectionFailed { url: self.host.clone() }
                 } else {
                     OllamaError::Request(e)
                 }
@@ -95,9 +131,7 @@ impl OllamaClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(OllamaError::ApiError {
-                message: format!("Chat request failed: HTTP {} - {}", status, body),
-            });
+            return Err(chat_error(&request.model, status, &body));
         }
 
         // Convert the response body into a stream of chunks
@@ -125,6 +159,7 @@ impl OllamaClient {
                             prompt_eval_duration: None,
                             eval_count: None,
                             eval_duration: None,
+                            done_reason: None,
                             error: None,
                         });
                     }
@@ -138,7 +173,6 @@ impl OllamaClient {
     }
 
     /// Send a chat request and get the full response (non-streaming)
-    #[allow(dead_code)]
     pub async fn chat(
         &self,
         request: ChatRequest,
@@ -157,7 +191,7 @@ impl OllamaClient {
             .await
             .map_err(|e| {
                 if e.is_connect() {
-                    OllamaError::ConnectionFailed { url: self.base_url.clone() }
+                    OllamaError::ConnectionFailed { url: self.host.clone() }
                 } else {
                     OllamaError::Request(e)
                 }
@@ -166,20 +200,72 @@ impl OllamaClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(OllamaError::ApiError {
-                message: format!("Chat request failed: HTTP {} - {}", status, body),
-            });
+            return Err(chat_error(&non_streaming.model, status, &body));
         }
 
         let chunk: ChatResponseChunk = response.json().await?;
         
-        if let Some(error) = &chunk.error {
+        if let Some(error) = chunk.error.clone() {
+            return Err(error_message(&non_streaming.model, &error)
+                .unwrap_or(OllamaError::ApiError { message: error }));
+        }
+
+        Ok(chunk)
+    }
+
+    /// Pull (download) a model, streaming progress updates
+    pub async fn pull_model(
+        &self,
+        model: &str,
+    ) -> Result<impl Stream<Item = Result<PullProgress, OllamaError>>, OllamaError> {
+        let url = format!("{}/api/pull", self.base_url);
+        let request = PullRequest {
+            model: model.to_string(),
+            stream: true,
+        };
+
+        // Pulls can take far longer than the request timeout
+        let response = self.client
+            .post(&url)
+            .timeout(Duration::from_secs(PULL_TIMEOUT_SECS))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_connect() {
+                    OllamaError::ConnectionFailed { url: self.host.clone() }
+                } else {
+                    OllamaError::Request(e)
+                }
+            })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
             return Err(OllamaError::ApiError {
-                message: error.clone(),
+                message: format!("Pull failed: HTTP {} - {}", status, body),
             });
         }
 
-        Ok(chunk)
+        let stream = response_lines(response)
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(|line| line.and_then(|l| serde_json::from_str::<PullProgress>(&l).map_err(OllamaError::from)));
+
+        Ok(stream)
+    }
+
+    /// Load a model into memory by sending an empty chat request
+    pub async fn load_model(&self, model: &str) -> Result<(), OllamaError> {
+        self.chat(ChatRequest::new(model, Vec::new())).await.map(|_| ())
+    }
+
+    /// Unload a model from memory immediately (`keep_alive: 0`)
+    pub async fn unload_model(&self, model: &str) -> Result<(), OllamaError> {
+        let request = ChatRequest {
+            keep_alive: Some("0".to_string()),
+            ..ChatRequest::new(model, Vec::new())
+        };
+        self.chat(request).await.map(|_| ())
     }
 
     /// Get the base URL
@@ -189,13 +275,66 @@ impl OllamaClient {
     }
 }
 
+/// Map a failed chat response to an error
+///
+/// Ollama answers with `{"error": "..."}`; the status and that message
+/// together tell a missing model, a model too big for memory and a full
+/// request queue apart from other failures.
+fn chat_error(model: &str, status: StatusCode, body: &str) -> OllamaError {
+    let message = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v["error"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.trim().to_string());
+    match status {
+        StatusCode::NOT_FOUND if message.contains("not found") => {
+            OllamaError::ModelNotFound { model: model.to_string() }
+        }
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => OllamaError::Busy { message },
+        _ => error_message(model, &message).unwrap_or_else(|| OllamaError::ApiError {
+            message: format!("Chat request failed: HTTP {} - {}", status, message),
+        }),
+    }
+}
+
+/// Classify an error message from a response or a streamed chunk
+///
+/// `None` when it is nothing more specific than an API error.
+pub fn error_message(model: &str, message: &str) -> Option<OllamaError> {
+    let lower = message.to_lowercase();
+    let message = message.to_string();
+    if lower.contains("out of memory") || lower.contains("requires more system memory") {
+        Some(OllamaError::OutOfMemory { model: model.to_string(), message })
+    } else if lower.contains("server busy") {
+        Some(OllamaError::Busy { message })
+    } else {
+        None
+    }
+}
+
+/// Route all connections through a Unix domain socket
+#[cfg(unix)]
+fn unix_socket(
+    builder: reqwest::ClientBuilder,
+    path: &str,
+    _host: &str,
+) -> Result<reqwest::ClientBuilder, OllamaError> {
+    Ok(builder.unix_socket(std::path::PathBuf::from(path)))
+}
+
+#[cfg(not(unix))]
+fn unix_socket(
+    _builder: reqwest::ClientBuilder,
+    _path: &str,
+    host: &str,
+) -> Result<reqwest::ClientBuilder, OllamaError> {
+    Err(OllamaError::UnsupportedHost { host: host.to_string() })
+}
+
 /// Boxed stream type for easier handling
-#[allow(dead_code)]
 pub type ChatStream = Pin<Box<dyn Stream<Item = Result<ChatResponseChunk, OllamaError>> + Send>>;
 
 impl OllamaClient {
     /// Send a chat request and return a boxed stream (easier to store/pass around)
-    #[allow(dead_code)]
     pub async fn chat_stream_boxed(
         &self,
         request: ChatRequest,
@@ -221,4 +360,51 @@ mod tests {
         assert!(client.is_ok());
         assert_eq!(client.unwrap().base_url(), "http://127.0.0.1:11434");
     }
+
+    #[test]
+    fn test_missing_model_error() {
+        let body = r#"{"error":"model \"llama9\" not found, try pulling it first"}"#;
+        assert!(matches!(
+            chat_error("llama9", StatusCode::NOT_FOUND, body),
+            OllamaError::ModelNotFound { model } if model == "llama9"
+        ));
+        assert!(matches!(
+            chat_error("llama9", StatusCode::INTERNAL_SERVER_ERROR, "boom"),
+            OllamaError::ApiError { .. }
+        ));
+    }
+
+    #[test]
+    fn test_error_taxonomy() {
+        let body = r#"{"error":"model requires more system memory (38.2 GiB) than is available (15.4 GiB)"}"#;
+        let error = chat_error("llama3:70b", StatusCode::INTERNAL_SERVER_ERROR, body);
+        assert!(matches!(&error, OllamaError::OutOfMemory { model, .. } if model == "llama3:70b"));
+        assert!(error.with_suggestion().contains("smaller or more quantized model"));
+
+        let body = r#"{"error":"llama runner process has terminated: CUDA error: out of memory"}"#;
+        assert!(matches!(
+            chat_error("qwen2.5:32b", StatusCode::INTERNAL_SERVER_ERROR, body),
+            OllamaError::OutOfMemory { .. }
+        ));
+
+        let body = r#"{"error":"server busy, please try again.  maximum pending requests exceeded"}"#;
+        let error = chat_error("llama3.2", StatusCode::SERVICE_UNAVAILABLE, body);
+        assert!(matches!(&error, OllamaError::Busy { message } if message.starts_with("server busy")));
+        assert!(matches!(chat_error("llama3.2", StatusCode::TOO_MANY_REQUESTS, ""), OllamaError::Busy { .. }));
+
+        // A 404 that is not about the model stays generic, with the message unwrapped
+        let error = chat_error("llama3.2", StatusCode::NOT_FOUND, r#"{"error":"page missing"}"#);
+        assert_eq!(error.to_string(), "API error: Chat request failed: HTTP 404 Not Found - page missing");
+        assert_eq!(error.suggestion(), None);
+
+        assert!(error_message("llama3.2", "unexpected EOF").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_host() {
+        let client = OllamaClient::new("unix:///run/ollama/ollama.sock", 30).unwrap();
+        assert_eq!(client.base_url(), UNIX_BASE_URL);
+        assert_eq!(client.host, "unix:///run/ollama/ollama.sock");
+    }
 }
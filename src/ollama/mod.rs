@@ -3,7 +3,9 @@
 //! HTTP client and types for the Ollama API.
 
 mod client;
+mod stream;
 mod types;
 
-pub use client::OllamaClient;
+pub use client::{error_message, ChatStream, OllamaClient};
+pub(crate) use stream::response_lines;
 pub use types::*;
@@ -0,0 +1,66 @@
+//! Line-oriented response streaming
+//!
+//! Ollama streams newline-delimited JSON and hosted APIs stream SSE; in both
+//! cases a record may straddle network chunks, so bytes are buffered until a
+//! complete line is available.
+
+use futures::stream::{self, Stream, StreamExt};
+
+use crate::error::OllamaError;
+
+/// Split a streaming response body into lines (without line terminators)
+pub(crate) fn response_lines(response: reqwest::Response) -> impl Stream<Item = Result<String, OllamaError>> + Send {
+    lines(response.bytes_stream())
+}
+
+/// Split a stream of byte chunks into lines
+///
+/// Lines are decoded only once complete, so a multi-byte character split
+/// across chunks survives intact.
+fn lines<S, B, E>(chunks: S) -> impl Stream<Item = Result<String, OllamaError>> + Send
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]>,
+    OllamaError: From<E>,
+{
+    let state = (chunks.boxed(), Vec::new(), false);
+
+    stream::unfold(state, |(mut bytes, mut buffer, mut finished)| async move {
+        loop {
+            if let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string();
+                return Some((Ok(line), (bytes, buffer, finished)));
+            }
+
+            if finished {
+                return None;
+            }
+
+            match bytes.next().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(chunk.as_ref()),
+                Some(Err(e)) => return Some((Err(OllamaError::from(e)), (bytes, buffer, true))),
+                None => {
+                    // Flush a trailing line without newline
+                    finished = true;
+                    if !buffer.is_empty() {
+                        buffer.push(b'\n');
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lines_split_across_chunks() {
+        // "é" is split between the first two chunks
+        let chunks: Vec<Result<&[u8], OllamaError>> = vec![Ok(b"caf\xc3"), Ok(b"\xa9\r\nsecond"), Ok(b" line")];
+        let lines: Vec<String> = lines(stream::iter(chunks)).map(Result::unwrap).collect().await;
+        assert_eq!(lines, ["café", "second line"]);
+    }
+}
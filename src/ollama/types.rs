@@ -25,6 +25,21 @@ pub struct ModelInfo {
     pub details: Option<ModelDetails>,
 }
 
+impl ModelInfo {
+    /// Create an entry for a model served by a hosted provider
+    pub fn remote(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            model: name.clone(),
+            name,
+            modified_at: None,
+            size: 0,
+            digest: String::new(),
+            details: None,
+        }
+    }
+}
+
 /// Detailed model information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelDetails {
@@ -48,6 +63,31 @@ pub struct ListModelsResponse {
     pub models: Vec<ModelInfo>,
 }
 
+/// Request to /api/show
+#[derive(Debug, Clone, Serialize)]
+pub struct ShowRequest {
+    pub model: String,
+}
+
+/// Response from /api/show (only the fields ratatalk reads)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ShowResponse {
+    /// Architecture metadata, keyed like `llama.context_length`
+    #[serde(default)]
+    pub model_info: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ShowResponse {
+    /// Context window the model was trained with
+    pub fn context_length(&self) -> Option<u32> {
+        self.model_info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .and_then(|n| u32::try_from(n).ok())
+    }
+}
+
 // ============================================================================
 // Chat Types
 // ============================================================================
@@ -59,6 +99,8 @@ pub enum Role {
     System,
     User,
     Assistant,
+    /// Result of a tool call, sent back to the model
+    Tool,
 }
 
 impl std::fmt::Display for Role {
@@ -67,6 +109,7 @@ impl std::fmt::Display for Role {
             Role::System => write!(f, "system"),
             Role::User => write!(f, "user"),
             Role::Assistant => write!(f, "assistant"),
+            Role::Tool => write!(f, "tool"),
         }
     }
 }
@@ -78,6 +121,12 @@ pub struct ChatMessage {
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub images: Option<Vec<String>>,
+    /// Reasoning streamed separately by thinking models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<String>,
+    /// Functions the model asked to call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
 }
 
 impl ChatMessage {
@@ -86,6 +135,8 @@ impl ChatMessage {
             role: Role::System,
             content: content.into(),
             images: None,
+            thinking: None,
+            tool_calls: None,
         }
     }
 
@@ -95,6 +146,8 @@ impl ChatMessage {
             role: Role::User,
             content: content.into(),
             images: None,
+            thinking: None,
+            tool_calls: None,
         }
     }
 
@@ -104,8 +157,51 @@ impl ChatMessage {
             role: Role::Assistant,
             content: content.into(),
             images: None,
+            thinking: None,
+            tool_calls: None,
         }
     }
+
+    /// The result of a tool call
+    pub fn tool(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::Tool,
+            content: content.into(),
+            images: None,
+            thinking: None,
+            tool_calls: None,
+        }
+    }
+}
+
+/// A function call requested by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub function: FunctionCall,
+}
+
+/// Name and arguments of a requested function call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+/// A function offered to the model in a chat request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolSpec {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionSpec,
+}
+
+/// Description of an offered function; `parameters` is a JSON schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
 }
 
 /// Options for model generation
@@ -134,6 +230,118 @@ pub struct GenerationOptions {
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub repeat_penalty: Option<f32>,
+    
+    /// Tokens to look back for repetition (0 = disabled, -1 = num_ctx)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_last_n: Option<i32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub penalize_newline: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typical_p: Option<f32>,
+    
+    /// Tail free sampling (1.0 = disabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfs_z: Option<f32>,
+    
+    /// Mirostat sampling (0 = disabled, 1 = Mirostat, 2 = Mirostat 2.0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat: Option<u8>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat_eta: Option<f32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat_tau: Option<f32>,
+    
+    /// Tokens from the prompt to keep when the context is full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_keep: Option<i32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_batch: Option<u32>,
+    
+    /// Layers to offload to the GPU (0 = CPU only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_gpu: Option<i32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub main_gpu: Option<u32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_thread: Option<u32>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numa: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low_vram: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_mmap: Option<bool>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_mlock: Option<bool>,
+}
+
+impl GenerationOptions {
+    /// Every option that is set, as `name=value` pairs
+    pub fn describe(&self) -> String {
+        let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(self) else {
+            return String::new();
+        };
+        // The options hold f32s, so print floats back at that precision
+        fields
+            .iter()
+            .map(|(name, value)| match value.as_f64().filter(|_| value.is_f64()) {
+                Some(float) => format!("{}={}", name, float as f32),
+                None => format!("{}={}", name, value),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Short form of the sampling settings, e.g. `temp 0.7 · seed 42`
+    pub fn badge(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(temperature) = self.temperature {
+            parts.push(format!("temp {}", temperature));
+        }
+        if let Some(seed) = self.seed {
+            parts.push(format!("seed {}", seed));
+        }
+        parts.join(" · ")
+    }
+
+    /// Overlay the options set in `overrides` on top of these
+    pub fn merged(mut self, overrides: &GenerationOptions) -> Self {
+        macro_rules! overlay {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if overrides.$field.is_some() {
+                        self.$field = overrides.$field.clone();
+                    }
+                )*
+            };
+        }
+        overlay!(
+            temperature, top_k, top_p, num_predict, num_ctx, stop, seed, repeat_penalty,
+            repeat_last_n, presence_penalty, frequency_penalty, penalize_newline, min_p,
+            typical_p, tfs_z, mirostat, mirostat_eta, mirostat_tau, num_keep, num_batch,
+            num_gpu, main_gpu, num_thread, numa, low_vram, use_mmap, use_mlock,
+        );
+        self
+    }
 }
 
 /// Request body for /api/chat
@@ -147,6 +355,12 @@ pub struct ChatRequest {
     pub options: Option<GenerationOptions>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub keep_alive: Option<String>,
+    /// Enable or disable the reasoning phase of thinking models
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+    /// Functions the model may call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolSpec>>,
 }
 
 fn default_true() -> bool {
@@ -161,6 +375,8 @@ impl ChatRequest {
             stream: true,
             options: None,
             keep_alive: None,
+            think: None,
+            tools: None,
         }
     }
 
@@ -169,6 +385,16 @@ impl ChatRequest {
         self
     }
 
+    pub fn with_think(mut self, think: Option<bool>) -> Self {
+        self.think = think;
+        self
+    }
+
+    pub fn with_tools(mut self, tools: Vec<ToolSpec>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
     #[allow(dead_code)]
     pub fn with_stream(mut self, stream: bool) -> Self {
         self.stream = stream;
@@ -177,7 +403,7 @@ impl ChatRequest {
 }
 
 /// Streamed response chunk from /api/chat
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatResponseChunk {
     pub model: String,
     pub created_at: Option<DateTime<Utc>>,
@@ -197,6 +423,10 @@ pub struct ChatResponseChunk {
     pub eval_count: Option<u32>,
     #[serde(default)]
     pub eval_duration: Option<u64>,
+    /// Why generation stopped: `stop`, `length`, or a hosted provider's
+    /// equivalent
+    #[serde(default)]
+    pub done_reason: Option<String>,
     
     // Error field
     #[serde(default)]
@@ -209,6 +439,25 @@ impl ChatResponseChunk {
         self.message.as_ref().map(|m| m.content.as_str())
     }
 
+    /// Get the tool calls requested in this chunk
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        self.message.as_ref().and_then(|m| m.tool_calls.as_deref()).unwrap_or_default()
+    }
+
+    /// Get the reasoning from this chunk if present
+    pub fn thinking(&self) -> Option<&str> {
+        self.message.as_ref().and_then(|m| m.thinking.as_deref())
+    }
+
+    /// Whether generation was cut off by the token limit or a stop sequence
+    /// rather than ending on its own
+    ///
+    /// Ollama and OpenAI-compatible servers report a matched stop sequence
+    /// as a plain `stop`, so only Anthropic's can be told apart.
+    pub fn truncated(&self) -> bool {
+        matches!(self.done_reason.as_deref(), Some("length" | "max_tokens" | "stop_sequence"))
+    }
+
     /// Check if this chunk contains an error
     #[allow(dead_code)]
     pub fn is_error(&self) -> bool {
@@ -226,6 +475,33 @@ impl ChatResponseChunk {
     }
 }
 
+// ============================================================================
+// Pull Types
+// ============================================================================
+
+/// Request body for /api/pull
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub model: String,
+    #[serde(default = "default_true")]
+    pub stream: bool,
+}
+
+/// Progress update streamed from /api/pull
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub digest: Option<String>,
+    #[serde(default)]
+    pub total: Option<u64>,
+    #[serde(default)]
+    pub completed: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
 // ============================================================================
 // Health/Status Types
 // ============================================================================
@@ -299,6 +575,49 @@ mod tests {
         assert!(json.contains("\"stream\":true"));
     }
 
+    #[test]
+    fn test_thinking_chunk_parsing() {
+        let json = r#"{"model":"deepseek-r1","message":{"role":"assistant","content":"","thinking":"Let me see"},"done":false}"#;
+        let chunk: ChatResponseChunk = serde_json::from_str(json).unwrap();
+        assert_eq!(chunk.thinking(), Some("Let me see"));
+        assert_eq!(chunk.content(), Some(""));
+
+        let request = ChatRequest::new("deepseek-r1", vec![]).with_think(Some(true));
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["think"], true);
+        assert!(serde_json::to_value(ChatRequest::new("m", vec![])).unwrap().get("think").is_none());
+    }
+
+    #[test]
+    fn test_options_merge() {
+        let base = GenerationOptions {
+            temperature: Some(0.7),
+            mirostat: Some(2),
+            ..Default::default()
+        };
+        let session = GenerationOptions {
+            temperature: Some(0.1),
+            num_gpu: Some(0),
+            ..Default::default()
+        };
+        let merged = base.merged(&session);
+        assert_eq!(merged.temperature, Some(0.1));
+        assert_eq!(merged.mirostat, Some(2));
+        assert_eq!(merged.num_gpu, Some(0));
+
+        let json = serde_json::to_value(&merged).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_pull_progress_parsing() {
+        let json = r#"{"status":"pulling 6a0746a1ec1a","digest":"sha256:6a07","total":4661211808,"completed":1202364416}"#;
+        let progress: PullProgress = serde_json::from_str(json).unwrap();
+        assert_eq!(progress.total, Some(4661211808));
+        assert_eq!(progress.completed, Some(1202364416));
+        assert!(progress.error.is_none());
+    }
+
     #[test]
     fn test_response_chunk_parsing() {
         let json = r#"{"model":"llama3.2","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Hello"},"done":false}"#;
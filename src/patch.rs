@@ -0,0 +1,492 @@
+//! Unified diff parsing and application
+//!
+//! Used to detect diffs in assistant responses, color them in the chat, and
+//! apply them to files in the current working directory.
+
+use std::path::{Component, Path, PathBuf};
+
+use tracing::warn;
+
+use crate::error::PatchError;
+
+/// A single line within a hunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Add(String),
+    Remove(String),
+}
+
+/// A hunk of changes (`@@ -a,b +c,d @@`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// 1-based line number in the original file
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+/// All hunks targeting one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    pub path: PathBuf,
+    /// True when the original is `/dev/null`
+    pub new_file: bool,
+    /// True when the new file is `/dev/null`: the file is removed
+    pub deleted: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Kind of a diff line, used for coloring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Header,
+    HunkHeader,
+    Add,
+    Remove,
+    Context,
+}
+
+/// Classify each line of a message: `Some(kind)` for lines inside a diff
+///
+/// Diffs are recognized inside ```diff / ```patch fences and as bare
+/// `--- ` / `+++ ` header pairs.
+pub fn classify_lines(text: &str) -> Vec<Option<DiffLineKind>> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut kinds = Vec::with_capacity(lines.len());
+    let mut in_fence = false;
+    let mut in_bare = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            let lang = trimmed.trim_start_matches('`').trim();
+            in_fence = !in_fence && (lang == "diff" || lang == "patch");
+            in_bare = false;
+            kinds.push(None);
+            continue;
+        }
+
+        if !in_fence && !in_bare {
+            let next_is_new = lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "));
+            in_bare = line.starts_with("--- ") && next_is_new;
+        } else if in_bare && line.trim().is_empty() {
+            in_bare = false;
+        }
+
+        kinds.push((in_fence || in_bare).then(|| kind_of(line)));
+    }
+
+    kinds
+}
+
+fn kind_of(line: &str) -> DiffLineKind {
+    if line.starts_with("+++ ") || line.starts_with("--- ") || line.starts_with("diff ") {
+        DiffLineKind::Header
+    } else if line.starts_with("@@") {
+        DiffLineKind::HunkHeader
+    } else if line.starts_with('+') {
+        DiffLineKind::Add
+    } else if line.starts_with('-') {
+        DiffLineKind::Remove
+    } else {
+        DiffLineKind::Context
+    }
+}
+
+/// Extract the diff text from a message, if it contains one
+pub fn extract_diff(text: &str) -> Option<String> {
+    let diff: Vec<&str> = text
+        .split('\n')
+        .zip(classify_lines(text))
+        .filter_map(|(line, kind)| kind.map(|_| line))
+        .collect();
+
+    diff.iter().any(|l| l.starts_with("@@")).then(|| diff.join("\n"))
+}
+
+/// Parse a unified diff into per-file patches
+///
+/// Hunk bodies are read by the line counts in their `@@` header, so a
+/// removed line that itself starts with `-- ` isn't taken for a file header.
+/// Lines past the counts are still read as long as they look like hunk
+/// lines, since models often get the counts wrong.
+pub fn parse(diff: &str) -> Result<Vec<FilePatch>, PatchError> {
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut old_path: Option<String> = None;
+    // Old and new lines still expected in the current hunk
+    let (mut old_left, mut new_left) = (0usize, 0usize);
+
+    for line in diff.lines() {
+        let in_hunk = old_left > 0 || new_left > 0;
+        if in_hunk && !line.starts_with("@@") {
+            let hunk = patches.last_mut().and_then(|p| p.hunks.last_mut());
+            if let Some((hunk, hunk_line)) = hunk.zip(parse_hunk_line(line)) {
+                match hunk_line {
+                    HunkLine::Add(_) => new_left = new_left.saturating_sub(1),
+                    HunkLine::Remove(_) => old_left = old_left.saturating_sub(1),
+                    HunkLine::Context(_) => {
+                        old_left = old_left.saturating_sub(1);
+                        new_left = new_left.saturating_sub(1);
+                    }
+                }
+                hunk.lines.push(hunk_line);
+            }
+        } else if let Some(rest) = line.strip_prefix("--- ") {
+            old_path = Some(header_path(rest));
+        } else if let Some(rest) = line.strip_prefix("+++ ") {
+            let new_path = header_path(rest);
+            let new_file = old_path.as_deref() == Some("/dev/null");
+            let deleted = new_path == "/dev/null";
+            // A deletion names its file in the `---` header
+            let path = match old_path.as_deref() {
+                _ if !deleted => new_path,
+                Some(old) if !new_file => old.to_string(),
+                _ => return Err(PatchError::Parse("deletion without a file to delete".to_string())),
+            };
+            patches.push(FilePatch {
+                path: safe_path(&path)?,
+                new_file,
+                deleted,
+                hunks: Vec::new(),
+            });
+        } else if line.starts_with("@@") {
+            let patch = patches
+                .last_mut()
+                .ok_or_else(|| PatchError::Parse("hunk before file header".to_string()))?;
+            let (old_start, old_len, new_len) = parse_hunk_header(line)?;
+            (old_left, new_left) = (old_len, new_len);
+            patch.hunks.push(Hunk { old_start, lines: Vec::new() });
+        } else if let Some(hunk) = patches.last_mut().and_then(|p| p.hunks.last_mut()) {
+            hunk.lines.extend(parse_hunk_line(line));
+        }
+    }
+
+    if patches.iter().all(|p| p.hunks.is_empty()) {
+        return Err(PatchError::NoDiff);
+    }
+    Ok(patches)
+}
+
+/// A line of a hunk body; `None` for anything else (e.g. "\ No newline")
+fn parse_hunk_line(line: &str) -> Option<HunkLine> {
+    match line.chars().next() {
+        Some('+') => Some(HunkLine::Add(line[1..].to_string())),
+        Some('-') => Some(HunkLine::Remove(line[1..].to_string())),
+        Some(' ') => Some(HunkLine::Context(line[1..].to_string())),
+        // Models often drop the leading space on blank context lines
+        None => Some(HunkLine::Context(String::new())),
+        _ => None,
+    }
+}
+
+/// Path from a `---`/`+++` header, without `a/`/`b/` prefixes or timestamps
+fn header_path(rest: &str) -> String {
+    let path = rest.split('\t').next().unwrap_or(rest).trim();
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// Only relative paths inside the working directory may be patched
+///
+/// This only looks at the text of the path; [`check_inside`] follows
+/// symlinks once the working directory is known.
+fn safe_path(path: &str) -> Result<PathBuf, PatchError> {
+    let path = PathBuf::from(path);
+    let escapes = path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if escapes || path.as_os_str().is_empty() {
+        return Err(PatchError::UnsafePath(path));
+    }
+    Ok(path)
+}
+
+/// Refuse `path` unless it stays inside `base` with symlinks followed
+///
+/// The deepest part of `path` that exists is canonicalized, so a symlinked
+/// file or directory pointing out of `base` is caught before anything is
+/// created under it.
+fn check_inside(base: &Path, path: &Path) -> Result<(), PatchError> {
+    let unsafe_path = || PatchError::UnsafePath(path.to_path_buf());
+    let base = base.canonicalize().map_err(PatchError::Read)?;
+    let existing = path
+        .ancestors()
+        .find(|p| p.symlink_metadata().is_ok())
+        .ok_or_else(unsafe_path)?;
+    // A dangling symlink doesn't canonicalize and is refused with the rest
+    let resolved = existing.canonicalize().map_err(|_| unsafe_path())?;
+    if !resolved.starts_with(&base) {
+        return Err(unsafe_path());
+    }
+    Ok(())
+}
+
+/// Old start line and the old and new line counts of a `@@` header
+fn parse_hunk_header(line: &str) -> Result<(usize, usize, usize), PatchError> {
+    let bad = || PatchError::Parse(format!("bad hunk header: {}", line));
+    // A missing count means one line
+    let range = |prefix: char| -> Option<(usize, usize)> {
+        let range = line.split_whitespace().find_map(|part| part.strip_prefix(prefix))?;
+        let (start, len) = range.split_once(',').unwrap_or((range, "1"));
+        Some((start.parse().ok()?, len.parse().ok()?))
+    };
+    let (old_start, old_len) = range('-').ok_or_else(bad)?;
+    // Models sometimes leave out the new range; read such hunks leniently
+    let new_len = range('+').map_or(0, |(_, len)| len);
+    Ok((old_start, old_len, new_len))
+}
+
+/// Apply hunks to file contents
+pub fn apply_to_str(original: &str, hunks: &[Hunk]) -> Result<String, PatchError> {
+    let mut lines: Vec<String> = original.lines().map(String::from).collect();
+    // Offset between original and patched line numbers from earlier hunks
+    let mut offset: isize = 0;
+
+    for hunk in hunks {
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect();
+        let new: Vec<String> = hunk
+            .lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Add(s) => Some(s.clone()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect();
+
+        // A hunk with no old lines (`-5,0`) inserts after line `old_start`
+        let index = if old.is_empty() { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (index as isize + offset).max(0) as usize;
+        let start = find_hunk(&lines, &old, expected).ok_or(PatchError::HunkMismatch {
+            line: hunk.old_start,
+        })?;
+
+        lines.splice(start..start + old.len(), new.iter().cloned());
+        offset += new.len() as isize - old.len() as isize;
+    }
+
+    let mut result = lines.join("\n");
+    if original.ends_with('\n') || original.is_empty() {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// Find where `old` matches, searching outward from the expected position
+fn find_hunk(lines: &[String], old: &[&str], expected: usize) -> Option<usize> {
+    let matches_at = |start: usize| {
+        start + old.len() <= lines.len()
+            && lines[start..start + old.len()].iter().zip(old).all(|(a, b)| a.trim_end() == b.trim_end())
+    };
+
+    (0..=lines.len()).find_map(|delta| {
+        if matches_at(expected + delta) {
+            Some(expected + delta)
+        } else if delta <= expected && matches_at(expected - delta) {
+            Some(expected - delta)
+        } else {
+            None
+        }
+    })
+}
+
+/// Apply file patches relative to `base`, all or none
+///
+/// Every hunk is checked before any file is written, and files already
+/// written are restored if a later write fails. A deletion removes its file
+/// once its hunks match it. Several patches to one file apply in turn, each
+/// to what the ones before it produced. The error names the file it
+/// happened on.
+pub fn apply_all(patches: &[FilePatch], base: &Path) -> Result<(), (PathBuf, PatchError)> {
+    // Per file: the patch that first named it, its full path, the original
+    // contents and the planned ones, `None` for no file
+    let mut planned: Vec<(&FilePatch, PathBuf, Option<String>, Option<String>)> = Vec::new();
+    for patch in patches {
+        let fail = |e| (patch.path.clone(), e);
+        let idx = match planned.iter().position(|(first, ..)| first.path == patch.path) {
+            Some(idx) => idx,
+            None => {
+                let path = base.join(&patch.path);
+                check_inside(base, &path).map_err(fail)?;
+                let original = if patch.new_file {
+                    None
+                } else {
+                    Some(std::fs::read_to_string(&path).map_err(|e| fail(PatchError::Read(e)))?)
+                };
+                planned.push((patch, path, original.clone(), original));
+                planned.len() - 1
+            }
+        };
+        let current = &mut planned[idx].3;
+        if current.is_none() && !patch.new_file {
+            let gone = std::io::Error::from(std::io::ErrorKind::NotFound);
+            return Err(fail(PatchError::Read(gone)));
+        }
+        let patched = apply_to_str(current.as_deref().unwrap_or_default(), &patch.hunks).map_err(fail)?;
+        *current = (!patch.deleted).then_some(patched);
+    }
+
+    for (i, (patch, path, original, patched)) in planned.iter().enumerate() {
+        let written = match patched {
+            Some(patched) => write(path, patched),
+            // Created and deleted again by the same diff
+            None if original.is_none() => Ok(()),
+            None => std::fs::remove_file(path),
+        };
+        if let Err(e) = written {
+            for (_, path, original, _) in planned[..i].iter().rev() {
+                let restored = match original {
+                    Some(original) => std::fs::write(path, original),
+                    None => std::fs::remove_file(path),
+                };
+                if let Err(e) = restored {
+                    warn!("Failed to restore {:?}: {}", path, e);
+                }
+            }
+            return Err((patch.path.clone(), PatchError::Write(e)));
+        }
+    }
+    Ok(())
+}
+
+fn write(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@\n fn main() {\n-    old();\n+    new();\n }";
+
+    #[test]
+    fn test_classify_fenced_diff() {
+        let text = "Here:\n```diff\n-a\n+b\n```\nDone";
+        let kinds = classify_lines(text);
+        assert_eq!(kinds[0], None);
+        assert_eq!(kinds[2], Some(DiffLineKind::Remove));
+        assert_eq!(kinds[3], Some(DiffLineKind::Add));
+        assert_eq!(kinds[5], None);
+    }
+
+    #[test]
+    fn test_parse_and_apply() {
+        let patches = parse(DIFF).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].path, PathBuf::from("src/lib.rs"));
+
+        let original = "fn main() {\n    old();\n}\n";
+        let patched = apply_to_str(original, &patches[0].hunks).unwrap();
+        assert_eq!(patched, "fn main() {\n    new();\n}\n");
+    }
+
+    #[test]
+    fn test_pure_insert_hunk() {
+        let diff = "--- a/x\n+++ b/x\n@@ -2,0 +3,2 @@\n+three\n+four";
+        let patches = parse(diff).unwrap();
+        let patched = apply_to_str("one\ntwo\nfive\n", &patches[0].hunks).unwrap();
+        assert_eq!(patched, "one\ntwo\nthree\nfour\nfive\n");
+
+        let diff = "--- a/x\n+++ b/x\n@@ -0,0 +1 @@\n+zero";
+        let patched = apply_to_str("one\n", &parse(diff).unwrap()[0].hunks).unwrap();
+        assert_eq!(patched, "zero\none\n");
+    }
+
+    #[test]
+    fn test_apply_all_deletes_files() {
+        let dir = std::env::temp_dir().join(format!("ratatalk-patch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("old.txt"), "bye\n").unwrap();
+        let patches = parse("--- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye").unwrap();
+        assert_eq!(patches[0].path, PathBuf::from("old.txt"));
+        assert!(patches[0].deleted);
+
+        apply_all(&patches, &dir).unwrap();
+        assert!(!dir.join("old.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_all_chains_patches_to_one_file() {
+        let dir = std::env::temp_dir().join(format!("ratatalk-patch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+        let diff = "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-one\n+uno\n--- a/a.txt\n+++ b/a.txt\n@@ -2 +2 @@\n-two\n+dos";
+
+        apply_all(&parse(diff).unwrap(), &dir).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("a.txt")).unwrap(), "uno\ndos\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_mismatch() {
+        let patches = parse(DIFF).unwrap();
+        let result = apply_to_str("something else\n", &patches[0].hunks);
+        assert!(matches!(result, Err(PatchError::HunkMismatch { .. })));
+    }
+
+    #[test]
+    fn test_rejects_escaping_paths() {
+        let diff = "--- a/../etc/passwd\n+++ b/../etc/passwd\n@@ -1 +1 @@\n-x\n+y";
+        assert!(matches!(parse(diff), Err(PatchError::UnsafePath(_))));
+    }
+
+    #[test]
+    fn test_removed_line_that_looks_like_a_header() {
+        let diff = "--- a/q.sql\n+++ b/q.sql\n@@ -1,2 +1,1 @@\n--- drop this comment\n select 1;";
+        let patches = parse(diff).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].hunks[0].lines[0], HunkLine::Remove("-- drop this comment".to_string()));
+        let patched = apply_to_str("-- drop this comment\nselect 1;\n", &patches[0].hunks).unwrap();
+        assert_eq!(patched, "select 1;\n");
+    }
+
+    #[test]
+    fn test_apply_all_writes_nothing_on_a_bad_hunk() {
+        let dir = std::env::temp_dir().join(format!("ratatalk-patch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "two\n").unwrap();
+        let diff = "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-one\n+uno\n--- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-three\n+tres";
+        let patches = parse(diff).unwrap();
+
+        let (path, e) = apply_all(&patches, &dir).unwrap_err();
+        assert_eq!(path, PathBuf::from("b.txt"));
+        assert!(matches!(e, PatchError::HunkMismatch { .. }));
+        assert_eq!(std::fs::read_to_string(dir.join("a.txt")).unwrap(), "one\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_all_refuses_symlinks_out_of_the_tree() {
+        let root = std::env::temp_dir().join(format!("ratatalk-patch-{}", uuid::Uuid::new_v4()));
+        let (dir, outside) = (root.join("work"), root.join("outside"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("a.txt"), "one\n").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("link")).unwrap();
+
+        for diff in [
+            "--- a/link/a.txt\n+++ b/link/a.txt\n@@ -1 +1 @@\n-one\n+uno",
+            "--- /dev/null\n+++ b/link/new/b.txt\n@@ -0,0 +1 @@\n+dos",
+        ] {
+            let (_, e) = apply_all(&parse(diff).unwrap(), &dir).unwrap_err();
+            assert!(matches!(e, PatchError::UnsafePath(_)), "{}", diff);
+        }
+        assert_eq!(std::fs::read_to_string(outside.join("a.txt")).unwrap(), "one\n");
+        assert!(!outside.join("new").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
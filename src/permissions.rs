@@ -0,0 +1,97 @@
+//! Capability gate for risky features
+//!
+//! Features that run commands, reach the network on the model's behalf or
+//! touch the filesystem ask here before doing so. Each capability is off
+//! unless `[permissions]` enables it, and nothing else can turn it on.
+
+use crate::config::PermissionsConfig;
+use crate::error::PermissionError;
+use crate::tools::Tool;
+
+/// Something a feature needs permission to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Run shell commands (no feature needs this yet)
+    #[allow(dead_code)]
+    Shell,
+    /// Let tools make network requests (no tool needs this yet)
+    #[allow(dead_code)]
+    NetworkTools,
+    /// Let tools read files and list directories
+    FileRead,
+    /// Write files, e.g. when applying a patch from a response
+    FileWrite,
+}
+
+impl Capability {
+    /// What the capability covers, for messages
+    pub fn describe(self) -> &'static str {
+        match self {
+            Capability::Shell => "Shell commands",
+            Capability::NetworkTools => "Network tools",
+            Capability::FileRead => "File access from tools",
+            Capability::FileWrite => "File writes",
+        }
+    }
+
+    /// Config key that enables the capability
+    pub fn key(self) -> &'static str {
+        match self {
+            Capability::Shell => "allow_shell",
+            Capability::NetworkTools => "allow_network_tools",
+            Capability::FileRead => "allow_file_read",
+            Capability::FileWrite => "allow_file_write",
+        }
+    }
+}
+
+/// Whether `config` enables `capability`
+pub fn allows(config: &PermissionsConfig, capability: Capability) -> bool {
+    match capability {
+        Capability::Shell => config.allow_shell,
+        Capability::NetworkTools => config.allow_network_tools,
+        Capability::FileRead => config.allow_file_read,
+        Capability::FileWrite => config.allow_file_write,
+    }
+}
+
+/// Fail unless `config` enables `capability`
+pub fn check(config: &PermissionsConfig, capability: Capability) -> Result<(), PermissionError> {
+    if allows(config, capability) {
+        Ok(())
+    } else {
+        Err(PermissionError::Denied(capability))
+    }
+}
+
+/// The subset of `tools` that `config` permits
+pub fn permitted_tools(config: &PermissionsConfig, tools: &[Tool]) -> Vec<Tool> {
+    tools
+        .iter()
+        .copied()
+        .filter(|tool| tool.capability().is_none_or(|c| allows(config, c)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_everything_denied_by_default() {
+        let config = PermissionsConfig::default();
+        for capability in [
+            Capability::Shell,
+            Capability::NetworkTools,
+            Capability::FileRead,
+            Capability::FileWrite,
+        ] {
+            assert!(check(&config, capability).is_err());
+        }
+        assert_eq!(permitted_tools(&config, &Tool::ALL), [Tool::CurrentTime]);
+
+        let config = PermissionsConfig { allow_file_read: true, ..Default::default() };
+        assert_eq!(permitted_tools(&config, &Tool::ALL), Tool::ALL);
+        assert!(check(&config, Capability::FileWrite).is_err());
+    }
+}
@@ -1,11 +1,23 @@
 //! Persistence layer
 //!
 //! Handles saving and loading chat sessions to disk.
+//!
+//! Each stored session carries a content hash. Sessions are verified at
+//! startup; a file that verifies is copied into `backups/`, so there is an
+//! intact copy to point to when a later load finds corruption.
 
-use crate::app::ChatSession;
+use crate::app::{ChatSession, Message, Rating};
+use crate::cache::CacheEntry;
 use crate::error::PersistenceError;
+use crate::ollama::GenerationOptions;
+use crate::update::UpdateCheck;
 use directories::ProjectDirs;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Number of verified backups kept
+const BACKUPS_KEPT: usize = 5;
 
 /// Get the data directory path
 pub fn data_dir() -> Result<PathBuf, PersistenceError> {
@@ -21,15 +33,22 @@ pub fn sessions_path() -> Result<PathBuf, PersistenceError> {
     Ok(dir.join("sessions.json"))
 }
 
+/// Get the backups directory path
+pub fn backups_dir() -> Result<PathBuf, PersistenceError> {
+    Ok(data_dir()?.join("backups"))
+}
+
 /// Load all sessions from disk
 pub fn load_sessions() -> Result<Vec<ChatSession>, PersistenceError> {
-    let path = sessions_path()?;
-    
+    read_sessions(&sessions_path()?)
+}
+
+fn read_sessions(path: &Path) -> Result<Vec<ChatSession>, PersistenceError> {
     if !path.exists() {
         return Ok(Vec::new());
     }
 
-    let contents = std::fs::read_to_string(&path)
+    let contents = std::fs::read_to_string(path)
         .map_err(PersistenceError::Read)?;
     
     // Handle empty file
@@ -43,25 +62,159 @@ pub fn load_sessions() -> Result<Vec<ChatSession>, PersistenceError> {
     Ok(sessions)
 }
 
+/// Sessions loaded at startup, with any that failed verification
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub sessions: Vec<ChatSession>,
+    /// Names of sessions whose content doesn't match their checksum
+    pub mismatched: Vec<String>,
+    /// Copy of the file as loaded, kept when some sessions mismatched
+    pub quarantined: Option<PathBuf>,
+}
+
+/// Load sessions and verify their checksums
+///
+/// A file that verifies is backed up. One that can't be parsed is moved
+/// aside so the next save doesn't overwrite it, and one with mismatched
+/// sessions is copied aside, as the next save checksums them afresh.
+pub fn load_sessions_checked() -> Result<LoadReport, PersistenceError> {
+    let path = sessions_path()?;
+    let sessions = match read_sessions(&path) {
+        Err(PersistenceError::Parse(source)) => {
+            let moved = path.with_extension(format!("json.corrupt-{}", timestamp()));
+            std::fs::rename(&path, &moved).map_err(PersistenceError::Write)?;
+            return Err(PersistenceError::Corrupt { moved, source });
+        }
+        result => result?,
+    };
+
+    let mismatched: Vec<String> = sessions
+        .iter()
+        .filter(|s| !s.verify())
+        .map(|s| s.name.clone())
+        .collect();
+    let mut quarantined = None;
+    if !mismatched.is_empty() {
+        let copy = path.with_extension(format!("json.mismatch-{}", timestamp()));
+        std::fs::copy(&path, &copy).map_err(PersistenceError::Write)?;
+        quarantined = Some(copy);
+    } else if !sessions.is_empty() {
+        backup(&path)?;
+    }
+    Ok(LoadReport { sessions, mismatched, quarantined })
+}
+
+/// Copy a verified sessions file into the backups, dropping the oldest
+fn backup(path: &Path) -> Result<(), PersistenceError> {
+    let dir = backups_dir()?;
+    std::fs::create_dir_all(&dir).map_err(PersistenceError::CreateDir)?;
+    std::fs::copy(path, dir.join(format!("sessions-{}.json", timestamp())))
+        .map_err(PersistenceError::Write)?;
+
+    let backups = list_backups(&dir);
+    for old in backups.iter().skip(BACKUPS_KEPT) {
+        if let Err(e) = std::fs::remove_file(old) {
+            tracing::warn!("Failed to remove old backup {:?}: {}", old, e);
+        }
+    }
+    Ok(())
+}
+
+/// Backup files, newest first
+fn list_backups(dir: &Path) -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    backups.retain(|p| p.extension().is_some_and(|e| e == "json"));
+    // Timestamped names sort chronologically
+    backups.sort_by(|a, b| b.cmp(a));
+    backups
+}
+
+/// The newest backup whose sessions all verify
+pub fn latest_intact_backup() -> Option<PathBuf> {
+    let dir = backups_dir().ok()?;
+    list_backups(&dir).into_iter().find(|path| {
+        read_sessions(path).is_ok_and(|sessions| sessions.iter().all(ChatSession::verify))
+    })
+}
+
+fn timestamp() -> String {
+    chrono::Local::now().format("%Y%m%d-%H%M%S").to_string()
+}
+
+/// A session as written to disk, with its checksum
+#[derive(Serialize)]
+struct StoredSession<'a> {
+    #[serde(flatten)]
+    session: &'a ChatSession,
+    checksum: String,
+}
+
 /// Save all sessions to disk
 pub fn save_sessions(sessions: &[ChatSession]) -> Result<(), PersistenceError> {
-    let path = sessions_path()?;
-    
+    write_sessions(&sessions_path()?, sessions)
+}
+
+/// Write sessions to `path`
+///
+/// Sessions whose messages were unloaded get them back from the file being
+/// replaced, so they are written out whole.
+fn write_sessions(path: &Path, sessions: &[ChatSession]) -> Result<(), PersistenceError> {
     // Ensure directory exists
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)
             .map_err(PersistenceError::CreateDir)?;
     }
 
-    let contents = serde_json::to_string_pretty(sessions)
+    let mut on_disk = if sessions.iter().any(|s| s.unloaded) { read_sessions(path)? } else { Vec::new() };
+    let sessions = sessions
+        .iter()
+        .map(|session| {
+            if !session.unloaded {
+                return Ok(Cow::Borrowed(session));
+            }
+            let stored = on_disk
+                .iter_mut()
+                .find(|s| s.id == session.id)
+                .ok_or_else(|| PersistenceError::SessionNotFound { id: session.id.to_string() })?;
+            let mut whole = session.clone();
+            whole.messages = std::mem::take(&mut stored.messages);
+            Ok(Cow::Owned(whole))
+        })
+        .collect::<Result<Vec<_>, PersistenceError>>()?;
+    drop(on_disk);
+
+    let stored: Vec<StoredSession> = sessions
+        .iter()
+        .map(|session| StoredSession { session, checksum: session.content_hash() })
+        .collect();
+    let contents = serde_json::to_string_pretty(&stored)
         .map_err(PersistenceError::Serialize)?;
     
-    std::fs::write(&path, contents)
+    // Write then rename, so an interrupted save can't truncate the file
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, contents)
+        .map_err(PersistenceError::Write)?;
+    std::fs::rename(&tmp, path)
         .map_err(PersistenceError::Write)?;
     
     Ok(())
 }
 
+/// Read the saved messages of one session, to load it back after unloading
+pub fn load_messages(session_id: &uuid::Uuid) -> Result<Vec<Message>, PersistenceError> {
+    read_messages(&sessions_path()?, session_id)
+}
+
+fn read_messages(path: &Path, session_id: &uuid::Uuid) -> Result<Vec<Message>, PersistenceError> {
+    read_sessions(path)?
+        .into_iter()
+        .find(|s| &s.id == session_id)
+        .map(|s| s.messages)
+        .ok_or_else(|| PersistenceError::SessionNotFound { id: session_id.to_string() })
+}
+
 /// Save a single session (merge with existing)
 #[allow(dead_code)]
 pub fn save_session(session: &ChatSession) -> Result<(), PersistenceError> {
@@ -85,19 +238,113 @@ pub fn delete_session(session_id: &uuid::Uuid) -> Result<(), PersistenceError> {
     save_sessions(&sessions)
 }
 
+/// Get the saved snippets file path
+pub fn snippets_path() -> Result<PathBuf, PersistenceError> {
+    Ok(data_dir()?.join("snippets.json"))
+}
+
+/// Load saved snippets, or none if the file doesn't exist yet
+pub fn load_snippets() -> Result<Vec<String>, PersistenceError> {
+    let path = snippets_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path).map_err(PersistenceError::Read)?;
+    serde_json::from_str(&contents).map_err(PersistenceError::Parse)
+}
+
+/// Save all snippets to disk
+pub fn save_snippets(snippets: &[String]) -> Result<(), PersistenceError> {
+    let path = snippets_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(PersistenceError::CreateDir)?;
+    }
+    let contents = serde_json::to_string_pretty(snippets).map_err(PersistenceError::Serialize)?;
+    std::fs::write(&path, contents).map_err(PersistenceError::Write)
+}
+
+/// Get the response cache file path
+pub fn cache_path() -> Result<PathBuf, PersistenceError> {
+    Ok(data_dir()?.join("cache.json"))
+}
+
+/// Load cached responses, or none if the file doesn't exist yet
+pub fn load_cache() -> Result<Vec<CacheEntry>, PersistenceError> {
+    let path = cache_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path).map_err(PersistenceError::Read)?;
+    serde_json::from_str(&contents).map_err(PersistenceError::Parse)
+}
+
+/// Save cached responses to disk
+pub fn save_cache(entries: &[CacheEntry]) -> Result<(), PersistenceError> {
+    let path = cache_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(PersistenceError::CreateDir)?;
+    }
+    let contents = serde_json::to_string(entries).map_err(PersistenceError::Serialize)?;
+    std::fs::write(&path, contents).map_err(PersistenceError::Write)
+}
+
+/// Get the update check cache file path
+pub fn update_check_path() -> Result<PathBuf, PersistenceError> {
+    Ok(data_dir()?.join("update_check.json"))
+}
+
+/// Load the last update check, or none if there hasn't been one
+pub fn load_update_check() -> Result<Option<UpdateCheck>, PersistenceError> {
+    let path = update_check_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path).map_err(PersistenceError::Read)?;
+    serde_json::from_str(&contents).map(Some).map_err(PersistenceError::Parse)
+}
+
+/// Save the last update check
+pub fn save_update_check(check: &UpdateCheck) -> Result<(), PersistenceError> {
+    let path = update_check_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(PersistenceError::CreateDir)?;
+    }
+    let contents = serde_json::to_string_pretty(check).map_err(PersistenceError::Serialize)?;
+    std::fs::write(&path, contents).map_err(PersistenceError::Write)
+}
+
+/// What to include in a Markdown export
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownOptions {
+    /// Start with YAML front matter (model, dates, tags, stats)
+    pub front_matter: bool,
+    /// Generation settings to record in the front matter, with the system prompt
+    pub settings: Option<GenerationOptions>,
+    /// List tags as `[[wiki-links]]` under the title (for Obsidian vaults)
+    pub wiki_links: bool,
+}
+
 /// Export a session to Markdown
-#[allow(dead_code)]
-pub fn export_session_to_markdown(session: &ChatSession) -> String {
+pub fn export_session_to_markdown(session: &ChatSession, options: &MarkdownOptions) -> String {
     use crate::ollama::Role;
     
     let mut md = String::new();
     
+    if options.front_matter {
+        md.push_str(&front_matter(session, options.settings.as_ref()));
+    }
+    
     // Header
     md.push_str(&format!("# {}\n\n", session.name));
     md.push_str(&format!("**Model:** {}\n", session.model));
     md.push_str(&format!("**Created:** {}\n", session.created_at.format("%Y-%m-%d %H:%M")));
     md.push_str(&format!("**Updated:** {}\n\n", session.updated_at.format("%Y-%m-%d %H:%M")));
     
+    if options.wiki_links && !session.tags.is_empty() {
+        let links: Vec<String> = session.tags.iter().map(|t| format!("[[{}]]", t)).collect();
+        md.push_str(&format!("**Tags:** {}\n\n", links.join(" ")));
+    }
+    
     // System prompt if present
     if let Some(system) = &session.system_prompt {
         md.push_str("## System Prompt\n\n");
@@ -113,10 +360,14 @@ pub fn export_session_to_markdown(session: &ChatSession) -> String {
             Role::User => "**You**",
             Role::Assistant => "**Assistant**",
             Role::System => "**System**",
+            Role::Tool => "**Tool**",
         };
         
         let timestamp = message.timestamp.format("%H:%M").to_string();
-        md.push_str(&format!("{} ({})\n\n", role_name, timestamp));
+        match message.model.as_deref().filter(|m| *m != session.model) {
+            Some(model) => md.push_str(&format!("{} · {} ({})\n\n", role_name, model, timestamp)),
+            None => md.push_str(&format!("{} ({})\n\n", role_name, timestamp)),
+        }
         md.push_str(&message.content);
         md.push_str("\n\n---\n\n");
     }
@@ -124,11 +375,106 @@ pub fn export_session_to_markdown(session: &ChatSession) -> String {
     md
 }
 
-/// Export a session to a Markdown file
-#[allow(dead_code)]
-pub fn export_session_to_file(session: &ChatSession, path: &PathBuf) -> Result<(), PersistenceError> {
-    let md = export_session_to_markdown(session);
-    std::fs::write(path, md).map_err(PersistenceError::Write)
+/// YAML front matter block for a session
+fn front_matter(session: &ChatSession, settings: Option<&GenerationOptions>) -> String {
+    // JSON strings are valid YAML scalars and handle all escaping
+    let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+    let words: usize = session.messages.iter().map(|m| m.content.split_whitespace().count()).sum();
+    let tags: Vec<String> = session.tags.iter().map(|t| quote(t)).collect();
+
+    let mut yaml = String::from("---\n");
+    yaml.push_str(&format!("title: {}\n", quote(&session.name)));
+    yaml.push_str(&format!("model: {}\n", quote(&session.model)));
+    yaml.push_str(&format!("created: {}\n", session.created_at.to_rfc3339()));
+    yaml.push_str(&format!("updated: {}\n", session.updated_at.to_rfc3339()));
+    yaml.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    yaml.push_str(&format!("messages: {}\n", session.messages.len()));
+    yaml.push_str(&format!("words: {}\n", words));
+    let count_rated = |rating| session.messages.iter().filter(|m| m.rating() == Some(rating)).count();
+    let (up, down) = (count_rated(Rating::Up), count_rated(Rating::Down));
+    if up + down > 0 {
+        yaml.push_str(&format!("rated_up: {}\nrated_down: {}\n", up, down));
+    }
+
+    if let Some(settings) = settings {
+        if let Some(system) = &session.system_prompt {
+            yaml.push_str(&format!("system_prompt: {}\n", quote(system)));
+        }
+        // Options serialize as a flat map of the values that are set
+        if let Ok(serde_json::Value::Object(map)) = serde_json::to_value(settings) {
+            if !map.is_empty() {
+                yaml.push_str("settings:\n");
+                for (key, value) in map {
+                    yaml.push_str(&format!("  {}: {}\n", key, value));
+                }
+            }
+        }
+    }
+
+    yaml.push_str("---\n\n");
+    yaml
+}
+
+/// Default directory for exported files
+pub fn exports_dir() -> Result<PathBuf, PersistenceError> {
+    Ok(data_dir()?.join("exports"))
+}
+
+/// Deterministic export file name: creation date, a slug of the name, and a
+/// short id so same-day sessions with the same name don't collide
+///
+/// A name with nothing to slug, e.g. only emoji, leaves the full session id.
+pub fn export_file_name(session: &ChatSession) -> String {
+    format!("{}.md", file_stem(session))
+}
+
+fn file_stem(session: &ChatSession) -> String {
+    let slug: String = session
+        .name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let id = session.id.simple().to_string();
+    let date = session.created_at.format("%Y-%m-%d");
+    if slug.is_empty() {
+        return format!("{}-{}", date, id);
+    }
+    format!("{}-{}-{}", date, slug, &id[..8])
+}
+
+/// Write a JSONL dataset to a timestamped file in `dir`, returning its path
+pub fn export_dataset_to_file(jsonl: &str, dir: &Path) -> Result<PathBuf, PersistenceError> {
+    std::fs::create_dir_all(dir).map_err(PersistenceError::CreateDir)?;
+    let path = dir.join(format!("dataset-{}.jsonl", timestamp()));
+    std::fs::write(&path, jsonl).map_err(PersistenceError::Write)?;
+    Ok(path)
+}
+
+/// Write a session as JSON (the on-disk session format) into `dir`
+pub fn export_session_json(session: &ChatSession, dir: &Path) -> Result<PathBuf, PersistenceError> {
+    std::fs::create_dir_all(dir).map_err(PersistenceError::CreateDir)?;
+    let path = dir.join(format!("{}.json", file_stem(session)));
+    let contents = serde_json::to_string_pretty(session).map_err(PersistenceError::Serialize)?;
+    std::fs::write(&path, contents).map_err(PersistenceError::Write)?;
+    Ok(path)
+}
+
+/// Export a session to a Markdown file in `dir`, returning its path
+pub fn export_session_to_file(
+    session: &ChatSession,
+    dir: &Path,
+    options: &MarkdownOptions,
+) -> Result<PathBuf, PersistenceError> {
+    std::fs::create_dir_all(dir).map_err(PersistenceError::CreateDir)?;
+    let path = dir.join(export_file_name(session));
+    let md = export_session_to_markdown(session, options);
+    std::fs::write(&path, md).map_err(PersistenceError::Write)?;
+    Ok(path)
 }
 
 // ============================================================================
@@ -176,7 +522,28 @@ CREATE TABLE IF NOT EXISTS models (
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::app::Message;
+
+    #[test]
+    fn test_save_keeps_unloaded_messages() {
+        let dir = std::env::temp_dir().join(format!("ratatalk-test-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("sessions.json");
+        let mut session = ChatSession::new("Long", "llama3.2");
+        session.messages.push(Message::user("Hello!"));
+        let mut sessions = vec![session, ChatSession::new("Other", "llama3.2")];
+        write_sessions(&path, &sessions).unwrap();
+
+        sessions[0].messages.clear();
+        sessions[0].unloaded = true;
+        sessions[0].rename("Renamed");
+        write_sessions(&path, &sessions).unwrap();
+
+        let stored = read_sessions(&path).unwrap();
+        assert_eq!(stored[0].name, "Renamed");
+        assert!(stored[0].verify());
+        let messages = read_messages(&path, &sessions[0].id).unwrap();
+        assert_eq!(messages[0].content, "Hello!");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 
     #[test]
     fn test_export_markdown() {
@@ -184,7 +551,7 @@ mod tests {
         session.messages.push(Message::user("Hello!"));
         session.messages.push(Message::assistant("Hi there!"));
         
-        let md = export_session_to_markdown(&session);
+        let md = export_session_to_markdown(&session, &MarkdownOptions::default());
         
         assert!(md.contains("# Test Chat"));
         assert!(md.contains("llama3.2"));
@@ -192,6 +559,73 @@ mod tests {
         assert!(md.contains("Hi there!"));
     }
 
+    #[test]
+    fn test_export_front_matter() {
+        let mut session = ChatSession::new("Test: \"quoted\"", "llama3.2");
+        session.tags = vec!["rust".to_string()];
+        session.system_prompt = Some("Be brief".to_string());
+        session.messages.push(Message::user("Hello there!"));
+
+        let options = MarkdownOptions {
+            front_matter: true,
+            settings: Some(GenerationOptions {
+                temperature: Some(0.5),
+                ..Default::default()
+            }),
+            wiki_links: false,
+        };
+        let md = export_session_to_markdown(&session, &options);
+
+        assert!(md.starts_with("---\ntitle: \"Test: \\\"quoted\\\"\"\nmodel: \"llama3.2\"\n"));
+        assert!(md.contains("tags: [\"rust\"]\n"));
+        assert!(md.contains("words: 2\n"));
+        assert!(md.contains("system_prompt: \"Be brief\"\n"));
+        assert!(md.contains("settings:\n  temperature: 0.5\n---\n\n# "));
+        assert!(!md.contains("[[rust]]"));
+
+        let name = export_file_name(&session);
+        assert!(name[10..].starts_with("-test-quoted-"));
+        assert_eq!(name, export_file_name(&session));
+
+        session.name = "🚀 !!".to_string();
+        assert_eq!(export_file_name(&session)[10..], format!("-{}.md", session.id.simple()));
+    }
+
+    #[test]
+    fn test_checksum_roundtrip() {
+        let mut session = ChatSession::new("Test Chat", "llama3.2");
+        session.messages.push(Message::user("Hello!"));
+        let stored = StoredSession { session: &session, checksum: session.content_hash() };
+        let json = serde_json::to_string(&stored).unwrap();
+
+        let loaded: ChatSession = serde_json::from_str(&json).unwrap();
+        assert!(loaded.checksum.is_some());
+        assert!(loaded.verify());
+
+        // Simulate corruption of the stored content
+        let tampered: ChatSession = serde_json::from_str(&json.replace("Hello!", "Hellp!")).unwrap();
+        assert!(!tampered.verify());
+
+        // Sessions saved before checksums existed load as intact
+        let legacy: ChatSession = serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+        assert!(legacy.checksum.is_none() && legacy.verify());
+
+        // Moving text across a field boundary changes the hash
+        let mut shifted = session.clone();
+        shifted.name = "Test Cha".to_string();
+        shifted.model = "tllama3.2".to_string();
+        assert_ne!(shifted.content_hash(), session.content_hash());
+    }
+
+    #[test]
+    fn test_export_wiki_links() {
+        let mut session = ChatSession::new("Notes", "llama3.2");
+        session.tags = vec!["rust".to_string(), "async io".to_string()];
+        let options = MarkdownOptions { wiki_links: true, ..Default::default() };
+        let md = export_session_to_markdown(&session, &options);
+        assert!(md.contains("**Tags:** [[rust]] [[async io]]\n"));
+    }
+
     #[test]
     fn test_sessions_serialization() {
         let session = ChatSession::new("Test", "llama3.2");
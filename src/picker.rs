@@ -0,0 +1,237 @@
+//! File picker
+//!
+//! A directory browser with a fuzzy filter, shared by everything that asks
+//! for a path: attaching text files or images and choosing an export folder.
+//! Typing filters the current directory; Enter opens a directory or picks a
+//! file, and Backspace on an empty filter goes up a level.
+
+use std::path::{Path, PathBuf};
+
+use crate::app::pop_grapheme;
+use crate::search::fuzzy_match;
+
+/// What the picked path is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickPurpose {
+    /// A text file to send with the next message
+    AttachFile,
+    /// An image to reference as `@path` in the input
+    AttachImage,
+    /// A folder to export the session into
+    ExportDir,
+}
+
+impl PickPurpose {
+    pub fn title(self) -> &'static str {
+        match self {
+            PickPurpose::AttachFile => "Attach File",
+            PickPurpose::AttachImage => "Attach Image",
+            PickPurpose::ExportDir => "Export To Folder",
+        }
+    }
+
+    /// Whether the purpose picks a directory rather than a file
+    pub fn wants_dir(self) -> bool {
+        self == PickPurpose::ExportDir
+    }
+}
+
+/// A listed directory entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PickerEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// What Enter did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pick {
+    /// Moved into another directory
+    Opened,
+    /// Chose this path
+    Chosen(PathBuf),
+    /// Nothing selectable
+    Nothing,
+}
+
+/// Label of the entry that chooses the current directory
+pub const THIS_DIR: &str = "./ (this folder)";
+
+/// Browser state of the file picker popup
+#[derive(Debug, Clone)]
+pub struct FilePicker {
+    pub purpose: PickPurpose,
+    pub dir: PathBuf,
+    entries: Vec<PickerEntry>,
+    pub query: String,
+    pub selected: usize,
+    /// Why the directory couldn't be listed, if it couldn't
+    pub error: Option<String>,
+}
+
+impl FilePicker {
+    /// Open the picker in `dir`
+    pub fn new(purpose: PickPurpose, dir: PathBuf) -> Self {
+        let mut picker = Self {
+            purpose,
+            dir,
+            entries: Vec::new(),
+            query: String::new(),
+            selected: 0,
+            error: None,
+        };
+        picker.read_dir();
+        picker
+    }
+
+    /// List the current directory: folders first, then files, by name
+    ///
+    /// Only files the purpose can use are listed; hidden entries are left
+    /// out unless the filter starts with a dot.
+    fn read_dir(&mut self) {
+        self.entries.clear();
+        self.error = None;
+        let read = match std::fs::read_dir(&self.dir) {
+            Ok(read) => read,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            }
+        };
+        for entry in read.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_dir = entry.path().is_dir();
+            if is_dir || self.accepts_file(Path::new(&name)) {
+                self.entries.push(PickerEntry { name, is_dir });
+            }
+        }
+        self.entries
+            .sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+    }
+
+    fn accepts_file(&self, path: &Path) -> bool {
+        match self.purpose {
+            PickPurpose::AttachFile => true,
+            PickPurpose::AttachImage => crate::app::is_image_path(path),
+            PickPurpose::ExportDir => false,
+        }
+    }
+
+    /// Entries matching the filter, best first
+    ///
+    /// Without a filter, folder pickers lead with an entry for the current
+    /// folder itself.
+    pub fn visible(&self) -> Vec<PickerEntry> {
+        let show_hidden = self.query.starts_with('.');
+        let listed = self.entries.iter().filter(|e| show_hidden || !e.name.starts_with('.'));
+        if self.query.is_empty() {
+            let this_dir = self.purpose.wants_dir().then(|| PickerEntry { name: THIS_DIR.to_string(), is_dir: true });
+            return this_dir.into_iter().chain(listed.cloned()).collect();
+        }
+        let mut scored: Vec<(i64, &PickerEntry)> = listed
+            .filter_map(|e| fuzzy_match(&self.query, &e.name).map(|(score, _)| (score, e)))
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, e)| e.clone()).collect()
+    }
+
+    pub fn push_query(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    /// Delete a filter character, or go up a directory when there is none
+    pub fn backspace(&mut self) {
+        if !pop_grapheme(&mut self.query) {
+            self.parent();
+        }
+        self.selected = 0;
+    }
+
+    pub fn next(&mut self) {
+        let len = self.visible().len();
+        if len > 0 {
+            self.selected = (self.selected + 1) % len;
+        }
+    }
+
+    pub fn prev(&mut self) {
+        let len = self.visible().len();
+        if len > 0 {
+            self.selected = (self.selected + len - 1) % len;
+        }
+    }
+
+    /// Go to the parent directory, keeping the one we left selected
+    pub fn parent(&mut self) {
+        let Some(parent) = self.dir.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let left = self.dir.file_name().map(|n| n.to_string_lossy().into_owned());
+        self.dir = parent;
+        self.query.clear();
+        self.read_dir();
+        self.selected = left
+            .and_then(|name| self.visible().iter().position(|e| e.name == name))
+            .unwrap_or(0);
+    }
+
+    /// Open the selected directory or choose the selected file
+    pub fn enter(&mut self) -> Pick {
+        let Some(entry) = self.visible().get(self.selected).cloned() else {
+            return Pick::Nothing;
+        };
+        if entry.name == THIS_DIR && self.purpose.wants_dir() {
+            return Pick::Chosen(self.dir.clone());
+        }
+        let path = self.dir.join(&entry.name);
+        if entry.is_dir {
+            self.dir = path;
+            self.query.clear();
+            self.selected = 0;
+            self.read_dir();
+            Pick::Opened
+        } else {
+            Pick::Chosen(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browse_filter_and_pick() {
+        let root = std::env::temp_dir().join(format!("ratatalk-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        for file in ["README.md", "src/main.rs", "src/model.rs", "logo.png"] {
+            std::fs::write(root.join(file), "x").unwrap();
+        }
+
+        let mut picker = FilePicker::new(PickPurpose::AttachFile, root.clone());
+        let names = |p: &FilePicker| p.visible().into_iter().map(|e| e.name).collect::<Vec<_>>();
+        assert_eq!(names(&picker), ["src", "logo.png", "README.md"]);
+
+        // Into src, then filter and pick
+        assert_eq!(picker.enter(), Pick::Opened);
+        "mrs".chars().for_each(|c| picker.push_query(c));
+        assert_eq!(names(&picker), ["main.rs", "model.rs"]);
+        assert_eq!(picker.enter(), Pick::Chosen(root.join("src/main.rs")));
+
+        // Backspace through the filter, then up a level onto "src"
+        (0..4).for_each(|_| picker.backspace());
+        assert_eq!(picker.dir, root);
+        assert_eq!(picker.visible()[picker.selected].name, "src");
+
+        let images = FilePicker::new(PickPurpose::AttachImage, root.clone());
+        assert_eq!(names(&images), ["src", "logo.png"]);
+
+        let mut folders = FilePicker::new(PickPurpose::ExportDir, root.clone());
+        assert_eq!(names(&folders), [THIS_DIR, "src"]);
+        assert_eq!(folders.enter(), Pick::Chosen(root.clone()));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
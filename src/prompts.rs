@@ -0,0 +1,163 @@
+//! System prompt library
+//!
+//! Named system prompts shared by every session, kept in `prompts.toml` in
+//! the config directory:
+//!
+//! ```toml
+//! [[prompt]]
+//! name = "Translator"
+//! text = "Translate everything I write into English."
+//! ```
+//!
+//! Picking a prompt for a session copies its text and remembers its name.
+//! With `[prompts] propagate = true`, editing the library rewrites the system
+//! prompt of every session that uses an edited name; otherwise sessions keep
+//! the text they were given. Until the file exists, the library holds a few
+//! starter prompts.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::ChatSession;
+use crate::config::Config;
+use crate::error::PromptError;
+
+/// Prompts in the library before the user has one of their own
+const STARTERS: &[(&str, &str)] = &[
+    (
+        "Coding assistant",
+        "You are an expert programmer. Answer with working code and a short explanation. \
+         Point out bugs, edge cases and simpler alternatives when you see them.",
+    ),
+    (
+        "Editor",
+        "You are a careful copy editor. Fix grammar, spelling and awkward phrasing while \
+         keeping the author's voice. Reply with the edited text, then a brief list of changes.",
+    ),
+    (
+        "Translator",
+        "You are a translator. Translate everything I write into English, keeping the tone \
+         and formatting. If the text is already English, translate it into Spanish.",
+    ),
+];
+
+/// A named system prompt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemPrompt {
+    pub name: String,
+    pub text: String,
+}
+
+/// The prompts in `prompts.toml`, in file order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptLibrary {
+    #[serde(default, rename = "prompt")]
+    pub prompts: Vec<SystemPrompt>,
+}
+
+impl PromptLibrary {
+    /// The starter prompts
+    pub fn starter() -> Self {
+        let prompts = STARTERS
+            .iter()
+            .map(|(name, text)| SystemPrompt { name: name.to_string(), text: text.to_string() })
+            .collect();
+        Self { prompts }
+    }
+
+    /// Where the library is kept
+    pub fn path() -> Result<PathBuf, PromptError> {
+        Ok(Config::config_dir()?.join("prompts.toml"))
+    }
+
+    /// Load the library, or the starter prompts if there is no file yet
+    pub fn load() -> Result<Self, PromptError> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::starter());
+        }
+        let contents = std::fs::read_to_string(&path).map_err(PromptError::Read)?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, PromptError> {
+        toml::from_str(contents).map_err(PromptError::Parse)
+    }
+
+    /// Write the library, creating the config directory if needed
+    pub fn save(&self) -> Result<(), PromptError> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(PromptError::Write)?;
+        }
+        let contents = toml::to_string_pretty(self).map_err(PromptError::Serialize)?;
+        std::fs::write(&path, contents).map_err(PromptError::Write)
+    }
+
+    /// The prompt called `name`
+    pub fn get(&self, name: &str) -> Option<&SystemPrompt> {
+        self.prompts.iter().find(|p| p.name == name)
+    }
+
+    /// Give every session using a library prompt its current text; returns
+    /// how many changed
+    ///
+    /// Sessions whose prompt is no longer in the library keep their text.
+    pub fn propagate(&self, sessions: &mut [ChatSession]) -> usize {
+        let mut changed = 0;
+        for session in sessions {
+            let Some(prompt) = session.prompt_name.as_deref().and_then(|name| self.get(name)) else {
+                continue;
+            };
+            if session.system_prompt.as_deref() != Some(prompt.text.as_str()) {
+                session.system_prompt = Some(prompt.text.clone());
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_propagate() {
+        let library = PromptLibrary::parse(
+            r#"
+            [[prompt]]
+            name = "Translator"
+            text = "Translate into French."
+
+            [[prompt]]
+            name = "Editor"
+            text = """
+            Fix my grammar.
+            """
+            "#,
+        )
+        .unwrap();
+        assert_eq!(library.prompts.len(), 2);
+        assert_eq!(library.get("Editor").unwrap().text.trim(), "Fix my grammar.");
+        assert_eq!(PromptLibrary::parse(&toml::to_string_pretty(&library).unwrap()).unwrap(), library);
+
+        let mut sessions = vec![
+            ChatSession::new("a", "llama3.2"),
+            ChatSession::new("b", "llama3.2"),
+            ChatSession::new("c", "llama3.2"),
+        ];
+        sessions[0].prompt_name = Some("Translator".to_string());
+        sessions[0].system_prompt = Some("Translate into German.".to_string());
+        sessions[1].system_prompt = Some("Be brief".to_string());
+        sessions[2].prompt_name = Some("Removed".to_string());
+        sessions[2].system_prompt = Some("Kept".to_string());
+
+        assert_eq!(library.propagate(&mut sessions), 1);
+        assert_eq!(sessions[0].system_prompt.as_deref(), Some("Translate into French."));
+        assert_eq!(sessions[1].system_prompt.as_deref(), Some("Be brief"));
+        assert_eq!(sessions[2].system_prompt.as_deref(), Some("Kept"));
+        assert_eq!(library.propagate(&mut sessions), 0);
+    }
+}
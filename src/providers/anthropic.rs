@@ -0,0 +1,229 @@
+//! Anthropic chat provider
+//!
+//! Talks to the Messages API (`/v1/messages`) with SSE streaming.
+
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::HostedProviderConfig;
+use crate::error::OllamaError;
+use crate::ollama::{ChatMessage, ChatRequest, ChatResponseChunk, ChatStream, ModelInfo, Role};
+
+use super::{message_json, sse_data, Provider};
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com/v1";
+const API_VERSION: &str = "2023-06-01";
+
+/// The Messages API requires `max_tokens`; used when the config leaves it unlimited
+const DEFAULT_MAX_TOKENS: i32 = 4096;
+
+/// Anthropic API client
+#[derive(Debug, Clone)]
+pub struct AnthropicClient {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    models: Vec<String>,
+}
+
+impl AnthropicClient {
+    pub fn new(cfg: &HostedProviderConfig, api_key: String, timeout_secs: u64) -> Result<Self, OllamaError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: cfg.base_url.clone().unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            api_key,
+            models: cfg.models.clone(),
+        })
+    }
+
+    fn authed(&self, builder: RequestBuilder) -> RequestBuilder {
+        builder
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        if !self.models.is_empty() {
+            return Ok(self.models.iter().map(ModelInfo::remote).collect());
+        }
+
+        #[derive(Deserialize)]
+        struct ModelList {
+            data: Vec<ModelEntry>,
+        }
+        #[derive(Deserialize)]
+        struct ModelEntry {
+            id: String,
+        }
+
+        let response = self
+            .authed(self.client.get(format!("{}/models", self.base_url)))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(OllamaError::ApiError {
+                message: format!("Failed to list Anthropic models: HTTP {}", response.status()),
+            });
+        }
+
+        let list: ModelList = response.json().await?;
+        Ok(list.data.into_iter().map(|m| ModelInfo::remote(m.id)).collect())
+    }
+
+    async fn stream(&self, request: ChatRequest) -> Result<ChatStream, OllamaError> {
+        let model = request.model.clone();
+        let body = build_body(&request);
+
+        let response = self
+            .authed(self.client.post(format!("{}/messages", self.base_url)))
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OllamaError::ApiError {
+                message: format!("Anthropic request failed: HTTP {} - {}", status, body),
+            });
+        }
+
+        let stream = sse_data(response).map(move |data| data.and_then(|d| parse_event(&model, &d)));
+        Ok(Box::pin(stream))
+    }
+}
+
+impl Provider for AnthropicClient {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<ModelInfo>, OllamaError>> {
+        Box::pin(self.fetch_models())
+    }
+
+    fn chat_stream(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatStream, OllamaError>> {
+        Box::pin(self.stream(request))
+    }
+}
+
+/// Build the Messages API body; system messages move to the top-level field
+fn build_body(request: &ChatRequest) -> Value {
+    let system: Vec<&str> = request
+        .messages
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| m.content.as_str())
+        .collect();
+
+    let messages: Vec<Value> = request
+        .messages
+        .iter()
+        .filter(|m| m.role != Role::System)
+        .map(message_json)
+        .collect();
+
+    let max_tokens = request
+        .options
+        .as_ref()
+        .and_then(|o| o.num_predict)
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_MAX_TOKENS);
+
+    let mut body = json!({
+        "model": request.model,
+        "messages": messages,
+        "max_tokens": max_tokens,
+        "stream": true,
+    });
+
+    if !system.is_empty() {
+        body["system"] = json!(system.join("\n\n"));
+    }
+
+    if let Some(opts) = &request.options {
+        if let Some(t) = opts.temperature {
+            // Anthropic accepts 0.0 - 1.0
+            body["temperature"] = json!(t.min(1.0));
+        }
+        if let Some(p) = opts.top_p {
+            body["top_p"] = json!(p);
+        }
+        if let Some(k) = opts.top_k {
+            body["top_k"] = json!(k);
+        }
+        if let Some(stop) = &opts.stop {
+            body["stop_sequences"] = json!(stop);
+        }
+    }
+
+    body
+}
+
+/// Convert one SSE payload into a normalized chunk
+fn parse_event(model: &str, data: &str) -> Result<ChatResponseChunk, OllamaError> {
+    let event: Value = serde_json::from_str(data)?;
+    let mut chunk = ChatResponseChunk {
+        model: model.to_string(),
+        ..Default::default()
+    };
+
+    match event["type"].as_str().unwrap_or_default() {
+        "content_block_delta" => {
+            chunk.message = Some(ChatMessage {
+                role: Role::Assistant,
+                content: event["delta"]["text"].as_str().unwrap_or_default().to_string(),
+                images: None,
+                thinking: event["delta"]["thinking"].as_str().map(String::from),
+                tool_calls: None,
+            });
+        }
+        "message_start" => {
+            chunk.prompt_eval_count = event["message"]["usage"]["input_tokens"].as_u64().map(|n| n as u32);
+        }
+        "message_delta" => {
+            chunk.eval_count = event["usage"]["output_tokens"].as_u64().map(|n| n as u32);
+            chunk.done_reason = event["delta"]["stop_reason"].as_str().map(String::from);
+        }
+        "message_stop" => chunk.done = true,
+        "error" => {
+            chunk.error = Some(event["error"]["message"].as_str().unwrap_or("unknown error").to_string());
+        }
+        _ => {}
+    }
+
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_prompt_moves_to_top_level() {
+        let request = ChatRequest::new(
+            "claude-3-5-haiku-latest",
+            vec![ChatMessage::system("Be brief"), ChatMessage::user("Hi")],
+        );
+        let body = build_body(&request);
+        assert_eq!(body["system"], "Be brief");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn test_parse_text_delta() {
+        let data = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#;
+        let chunk = parse_event("claude", data).unwrap();
+        assert_eq!(chunk.content(), Some("Hello"));
+    }
+}
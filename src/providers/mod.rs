@@ -0,0 +1,251 @@
+//! Chat providers
+//!
+//! A common `Provider` trait implemented by the local Ollama client and the
+//! hosted OpenAI / Anthropic clients, plus a registry that routes a session's
+//! model name to the provider that serves it.
+//!
+//! Hosted models are addressed as `<provider>/<model>` (e.g. `openai/gpt-4o`),
+//! so a single session list can mix local and hosted models.
+
+mod anthropic;
+mod openai;
+
+pub use anthropic::AnthropicClient;
+pub use openai::OpenAiClient;
+
+use futures::future::BoxFuture;
+use futures::stream::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config::{Config, HostedProviderConfig};
+use crate::credentials;
+use crate::error::OllamaError;
+use crate::ollama::{response_lines, ChatMessage, ChatRequest, ChatStream, ModelInfo, OllamaClient, Role};
+
+/// A backend capable of listing models and streaming chat completions
+pub trait Provider: Send + Sync + std::fmt::Debug {
+    /// Short provider name, also used as the model prefix for hosted providers
+    fn name(&self) -> &'static str;
+
+    /// List the models offered by this provider (unprefixed names)
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<ModelInfo>, OllamaError>>;
+
+    /// Send a chat request and return a stream of normalized response chunks
+    fn chat_stream(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatStream, OllamaError>>;
+}
+
+impl Provider for OllamaClient {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<ModelInfo>, OllamaError>> {
+        Box::pin(OllamaClient::list_models(self))
+    }
+
+    fn chat_stream(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatStream, OllamaError>> {
+        Box::pin(self.chat_stream_boxed(request))
+    }
+}
+
+/// Registry of configured providers
+///
+/// Ollama is always present; hosted providers are added when configured and
+/// an API key can be resolved.
+#[derive(Debug, Clone)]
+pub struct Providers {
+    ollama: OllamaClient,
+    hosted: Vec<Arc<dyn Provider>>,
+}
+
+impl Providers {
+    /// The local Ollama client
+    pub fn ollama(&self) -> &OllamaClient {
+        &self.ollama
+    }
+
+    /// Build the registry from configuration
+    pub fn from_config(config: &Config) -> Result<Self, OllamaError> {
+        let ollama = OllamaClient::new(&config.server.host, config.server.timeout_secs)?;
+        let mut hosted: Vec<Arc<dyn Provider>> = Vec::new();
+
+        if let Some(cfg) = &config.providers.openai {
+            match resolve_api_key(cfg, "openai") {
+                Some(key) => hosted.push(Arc::new(OpenAiClient::new(cfg, key, config.server.timeout_secs)?)),
+                None => warn!("OpenAI provider configured but no API key found"),
+            }
+        }
+
+        if let Some(cfg) = &config.providers.anthropic {
+            match resolve_api_key(cfg, "anthropic") {
+                Some(key) => hosted.push(Arc::new(AnthropicClient::new(cfg, key, config.server.timeout_secs)?)),
+                None => warn!("Anthropic provider configured but no API key found"),
+            }
+        }
+
+        Ok(Self { ollama, hosted })
+    }
+
+    /// The local Ollama client, if it serves `model`
+    ///
+    /// Used for Ollama-only operations such as loading and unloading models.
+    pub fn ollama_for(&self, model: &str) -> Option<&OllamaClient> {
+        let (provider, _) = self.route(model);
+        (provider.name() == "ollama").then_some(&self.ollama)
+    }
+
+    /// Check local server connectivity (hosted providers are assumed reachable)
+    pub async fn health_check(&self) -> Result<bool, OllamaError> {
+        self.ollama.health_check().await
+    }
+
+    /// List models from every provider, prefixing hosted model names
+    ///
+    /// Failures of individual hosted providers are logged and skipped; an
+    /// error is only returned if no provider produced any models.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        let mut models = Vec::new();
+        let mut first_error = None;
+
+        match self.ollama.list_models().await {
+            Ok(local) => models.extend(local),
+            Err(e) => first_error = Some(e),
+        }
+
+        for provider in &self.hosted {
+            match provider.list_models().await {
+                Ok(list) => models.extend(list.into_iter().map(|mut m| {
+                    m.name = format!("{}/{}", provider.name(), m.name);
+                    m
+                })),
+                Err(e) => {
+                    warn!("Failed to list {} models: {}", provider.name(), e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) if models.is_empty() => Err(e),
+            _ => Ok(models),
+        }
+    }
+
+    /// Route a chat request to the provider serving its model
+    pub async fn chat_stream(&self, mut request: ChatRequest) -> Result<ChatStream, OllamaError> {
+        let (provider, model) = self.route(&request.model);
+        let model = model.to_string();
+        request.model = model;
+        provider.chat_stream(request).await
+    }
+
+    /// Resolve a (possibly prefixed) model name to its provider and bare name
+    pub fn route<'m>(&self, model: &'m str) -> (&dyn Provider, &'m str) {
+        if let Some((prefix, rest)) = model.split_once('/') {
+            if let Some(provider) = self.hosted.iter().find(|p| p.name() == prefix) {
+                return (provider.as_ref(), rest);
+            }
+        }
+        (&self.ollama, model)
+    }
+}
+
+/// Resolve an API key from config, the system keychain or an environment variable
+fn resolve_api_key(cfg: &HostedProviderConfig, provider: &str) -> Option<String> {
+    if let Some(key) = cfg.api_key.clone().filter(|k| !k.is_empty()) {
+        return Some(key);
+    }
+    match credentials::get(provider) {
+        Ok(Some(key)) => return Some(key),
+        Ok(None) => {}
+        Err(e) => warn!("Could not read the {} API key from the keychain: {}", provider, e),
+    }
+    let var = credentials::env_var(provider).ok()?;
+    std::env::var(var).ok().filter(|k| !k.is_empty())
+}
+
+/// A message as plain text for the hosted APIs
+///
+/// Tool calls replayed from an agent run are in Ollama's format, which
+/// neither API accepts, so they are written out as text: the call as an
+/// assistant turn and its result as a user turn.
+fn message_json(msg: &ChatMessage) -> Value {
+    if msg.role == Role::Tool {
+        return json!({ "role": "user", "content": format!("Tool result:\n{}", msg.content) });
+    }
+    let calls: Vec<String> = msg
+        .tool_calls
+        .iter()
+        .flatten()
+        .map(|call| format!("Called {}({})", call.function.name, call.function.arguments))
+        .collect();
+    let content = [msg.content.as_str()]
+        .into_iter()
+        .chain(calls.iter().map(String::as_str))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    json!({ "role": msg.role.to_string(), "content": content })
+}
+
+/// Split a Server-Sent Events response body into `data:` payloads
+pub(crate) fn sse_data(response: reqwest::Response) -> impl Stream<Item = Result<String, OllamaError>> + Send {
+    response_lines(response).filter_map(|line| async move {
+        match line {
+            Ok(line) => line.strip_prefix("data:").map(|data| Ok(data.trim_start().to_string())),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_defaults_to_ollama() {
+        let providers = Providers::from_config(&Config::default()).unwrap();
+        let (provider, model) = providers.route("llama3.2:latest");
+        assert_eq!(provider.name(), "ollama");
+        assert_eq!(model, "llama3.2:latest");
+
+        // Unknown prefixes stay with Ollama (e.g. hf.co/... models)
+        let (provider, model) = providers.route("hf.co/org/model");
+        assert_eq!(provider.name(), "ollama");
+        assert_eq!(model, "hf.co/org/model");
+    }
+
+    #[test]
+    fn test_route_hosted_prefix() {
+        let mut config = Config::default();
+        config.providers.openai = Some(HostedProviderConfig {
+            api_key: Some("sk-test".to_string()),
+            ..Default::default()
+        });
+        let providers = Providers::from_config(&config).unwrap();
+        let (provider, model) = providers.route("openai/gpt-4o-mini");
+        assert_eq!(provider.name(), "openai");
+        assert_eq!(model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn test_tool_messages_become_text() {
+        let call = ChatMessage {
+            tool_calls: Some(vec![crate::ollama::ToolCall {
+                function: crate::ollama::FunctionCall {
+                    name: "current_time".to_string(),
+                    arguments: json!({}),
+                },
+            }]),
+            ..ChatMessage::assistant("")
+        };
+        assert_eq!(message_json(&call), json!({ "role": "assistant", "content": "Called current_time({})" }));
+        assert_eq!(
+            message_json(&ChatMessage::tool("12:00")),
+            json!({ "role": "user", "content": "Tool result:\n12:00" })
+        );
+        assert_eq!(message_json(&ChatMessage::user("Hi")), json!({ "role": "user", "content": "Hi" }));
+    }
+}
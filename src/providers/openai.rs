@@ -0,0 +1,208 @@
+//! OpenAI-compatible chat provider
+//!
+//! Talks to `/v1/chat/completions` with SSE streaming. Works with OpenAI and
+//! any OpenAI-compatible endpoint via `base_url`.
+
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::HostedProviderConfig;
+use crate::error::OllamaError;
+use crate::ollama::{ChatMessage, ChatRequest, ChatResponseChunk, ChatStream, ModelInfo, Role};
+
+use super::{message_json, sse_data, Provider};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// OpenAI API client
+#[derive(Debug, Clone)]
+pub struct OpenAiClient {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    models: Vec<String>,
+}
+
+impl OpenAiClient {
+    pub fn new(cfg: &HostedProviderConfig, api_key: String, timeout_secs: u64) -> Result<Self, OllamaError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: cfg.base_url.clone().unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            api_key,
+            models: cfg.models.clone(),
+        })
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        if !self.models.is_empty() {
+            return Ok(self.models.iter().map(ModelInfo::remote).collect());
+        }
+
+        #[derive(Deserialize)]
+        struct ModelList {
+            data: Vec<ModelEntry>,
+        }
+        #[derive(Deserialize)]
+        struct ModelEntry {
+            id: String,
+        }
+
+        let response = self.client
+            .get(format!("{}/models", self.base_url))
+            .bearer_auth(&self.api_key)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(OllamaError::ApiError {
+                message: format!("Failed to list OpenAI models: HTTP {}", response.status()),
+            });
+        }
+
+        let list: ModelList = response.json().await?;
+        Ok(list.data.into_iter().map(|m| ModelInfo::remote(m.id)).collect())
+    }
+
+    async fn stream(&self, request: ChatRequest) -> Result<ChatStream, OllamaError> {
+        let model = request.model.clone();
+        let body = build_body(&request);
+
+        let response = self.client
+            .post(format!("{}/chat/completions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OllamaError::ApiError {
+                message: format!("OpenAI request failed: HTTP {} - {}", status, body),
+            });
+        }
+
+        let stream = sse_data(response).map(move |data| data.and_then(|d| parse_event(&model, &d)));
+        Ok(Box::pin(stream))
+    }
+}
+
+impl Provider for OpenAiClient {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<ModelInfo>, OllamaError>> {
+        Box::pin(self.fetch_models())
+    }
+
+    fn chat_stream(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatStream, OllamaError>> {
+        Box::pin(self.stream(request))
+    }
+}
+
+/// Build the OpenAI request body from a provider-neutral request
+fn build_body(request: &ChatRequest) -> Value {
+    let messages: Vec<Value> = request.messages.iter().map(message_json).collect();
+
+    let mut body = json!({
+        "model": request.model,
+        "messages": messages,
+        "stream": true,
+        "stream_options": { "include_usage": true },
+    });
+
+    if let Some(opts) = &request.options {
+        if let Some(t) = opts.temperature {
+            body["temperature"] = json!(t);
+        }
+        if let Some(p) = opts.top_p {
+            body["top_p"] = json!(p);
+        }
+        if let Some(n) = opts.num_predict.filter(|n| *n > 0) {
+            body["max_tokens"] = json!(n);
+        }
+        if let Some(stop) = &opts.stop {
+            body["stop"] = json!(stop);
+        }
+        if let Some(seed) = opts.seed {
+            body["seed"] = json!(seed);
+        }
+        if let Some(p) = opts.presence_penalty {
+            body["presence_penalty"] = json!(p);
+        }
+        if let Some(p) = opts.frequency_penalty {
+            body["frequency_penalty"] = json!(p);
+        }
+    }
+
+    body
+}
+
+/// Convert one SSE payload into a normalized chunk
+fn parse_event(model: &str, data: &str) -> Result<ChatResponseChunk, OllamaError> {
+    if data == "[DONE]" {
+        return Ok(ChatResponseChunk {
+            model: model.to_string(),
+            done: true,
+            ..Default::default()
+        });
+    }
+
+    let event: Value = serde_json::from_str(data)?;
+
+    if let Some(error) = event.get("error") {
+        return Ok(ChatResponseChunk {
+            model: model.to_string(),
+            error: Some(error["message"].as_str().unwrap_or("unknown error").to_string()),
+            ..Default::default()
+        });
+    }
+
+    let content = event["choices"][0]["delta"]["content"].as_str().unwrap_or_default();
+    let usage = &event["usage"];
+
+    Ok(ChatResponseChunk {
+        model: model.to_string(),
+        message: Some(ChatMessage {
+            role: Role::Assistant,
+            content: content.to_string(),
+            images: None,
+            // Reasoning models behind OpenAI-compatible servers (e.g. DeepSeek)
+            thinking: event["choices"][0]["delta"]["reasoning_content"].as_str().map(String::from),
+            tool_calls: None,
+        }),
+        prompt_eval_count: usage["prompt_tokens"].as_u64().map(|n| n as u32),
+        eval_count: usage["completion_tokens"].as_u64().map(|n| n as u32),
+        done_reason: event["choices"][0]["finish_reason"].as_str().map(String::from),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delta_and_done() {
+        let chunk = parse_event("gpt-4o", r#"{"choices":[{"delta":{"content":"Hi"}}]}"#).unwrap();
+        assert_eq!(chunk.content(), Some("Hi"));
+        assert!(!chunk.done);
+
+        let done = parse_event("gpt-4o", "[DONE]").unwrap();
+        assert!(done.done);
+
+        let cut = parse_event("gpt-4o", r#"{"choices":[{"delta":{},"finish_reason":"length"}]}"#).unwrap();
+        assert!(cut.truncated());
+        let stopped = parse_event("gpt-4o", r#"{"choices":[{"delta":{},"finish_reason":"stop"}]}"#).unwrap();
+        assert!(!stopped.truncated());
+    }
+}
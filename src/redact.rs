@@ -0,0 +1,157 @@
+//! Anonymization for shareable exports
+//!
+//! Rewrites a copy of a session before it is exported. Regex rules from
+//! `[redact]` always run; an optional model pass can catch what they miss
+//! and its output goes through the rules again.
+
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::app::ChatSession;
+use crate::config::RedactConfig;
+use crate::ollama::ChatMessage;
+
+/// Instructions for the model-assisted pass
+const ANONYMIZE_PROMPT: &str = "Rewrite the user's text so it identifies no one. Replace names of people \
+with [name], organizations with [org], places with [place], and any other identifying detail with \
+[redacted]. Change nothing else: keep the wording, formatting and code exactly as they are. Reply with \
+the rewritten text only.";
+
+/// Compiled redaction rules
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Redactor {
+    /// Compile the rules in `config`, failing on the first invalid pattern
+    pub fn new(config: &RedactConfig) -> Result<Self, regex::Error> {
+        let mut rules = Vec::new();
+        if config.builtin {
+            rules.push((Regex::new(r"[\w.%+-]+@[\w-]+(\.[\w-]+)*\.[a-zA-Z]{2,}")?, "[email]".to_string()));
+            rules.push((Regex::new(r"(/home/|/Users/|(?i:[a-z]:\\Users\\))[^/\\\s]+")?, "${1}[user]".to_string()));
+        }
+        for name in config.names.iter().filter(|n| !n.trim().is_empty()) {
+            let pattern = format!(r"(?i)\b{}\b", regex::escape(name.trim()));
+            rules.push((Regex::new(&pattern)?, "[name]".to_string()));
+        }
+        for rule in &config.rules {
+            rules.push((Regex::new(&rule.pattern)?, rule.replacement.clone()));
+        }
+        Ok(Self { rules })
+    }
+
+    /// Apply every rule to `text`, in order
+    pub fn redact(&self, text: &str) -> String {
+        self.rules.iter().fold(text.to_string(), |text, (regex, replacement)| {
+            regex.replace_all(&text, replacement.as_str()).into_owned()
+        })
+    }
+
+    /// Copy of `session` with every text it exports redacted
+    ///
+    /// Besides message contents this covers the name, tags and system
+    /// prompt, thinking, tool calls and attachments, and image paths.
+    pub fn redact_session(&self, session: &ChatSession) -> ChatSession {
+        let mut copy = session.clone();
+        copy.name = self.redact(&session.name);
+        copy.system_prompt = session.system_prompt.as_deref().map(|p| self.redact(p));
+        copy.tags = session.tags.iter().map(|t| self.redact(t)).collect();
+        for message in &mut copy.messages {
+            message.content = self.redact(&message.content);
+            message.thinking = self.redact(&message.thinking);
+            for step in &mut message.tool_steps {
+                self.redact_json(&mut step.arguments);
+                step.result = step.result.as_deref().map(|r| self.redact(r));
+            }
+            for attachment in &mut message.attachments {
+                attachment.name = self.redact(&attachment.name);
+                attachment.content = self.redact(&attachment.content);
+            }
+            for image in &mut message.images {
+                *image = PathBuf::from(self.redact(&image.to_string_lossy()));
+            }
+            message.touch();
+        }
+        copy
+    }
+
+    /// Redact every string in a JSON value, in place
+    fn redact_json(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => *s = self.redact(s),
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|v| self.redact_json(v)),
+            serde_json::Value::Object(fields) => fields.values_mut().for_each(|v| self.redact_json(v)),
+            _ => {}
+        }
+    }
+}
+
+/// Chat messages asking a model to anonymize `text`
+pub fn anonymize_request(text: &str) -> Vec<ChatMessage> {
+    vec![ChatMessage::system(ANONYMIZE_PROMPT), ChatMessage::user(text)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactRule;
+
+    #[test]
+    fn test_builtin_and_configured_rules() {
+        let config = RedactConfig {
+            names: vec!["Ada Lovelace".to_string()],
+            rules: vec![RedactRule {
+                pattern: r"ticket-(\d+)".to_string(),
+                replacement: "ticket-#".to_string(),
+            }],
+            ..Default::default()
+        };
+        let redactor = Redactor::new(&config).unwrap();
+
+        assert_eq!(
+            redactor.redact("Mail ada.l@example.co.uk about /home/ada/notes.txt"),
+            "Mail [email] about /home/[user]/notes.txt"
+        );
+        assert_eq!(redactor.redact(r"C:\Users\ada\src"), r"C:\Users\[user]\src");
+        assert_eq!(redactor.redact("ADA LOVELACE filed ticket-42"), "[name] filed ticket-#");
+        assert_eq!(redactor.redact("nothing here"), "nothing here");
+    }
+
+    #[test]
+    fn test_invalid_rule_is_an_error() {
+        let config = RedactConfig {
+            rules: vec![RedactRule { pattern: "(".to_string(), replacement: String::new() }],
+            ..Default::default()
+        };
+        assert!(Redactor::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_redact_session_covers_everything_exported() {
+        let config = RedactConfig { names: vec!["Ada".to_string()], ..Default::default() };
+        let redactor = Redactor::new(&config).unwrap();
+        let mut session = ChatSession::new("Ada's notes", "llama3.2");
+        session.system_prompt = Some("You help Ada".to_string());
+        session.tags = vec!["ada".to_string()];
+        let mut message = crate::app::Message::assistant("Hi Ada");
+        message.thinking = "Ada asked".to_string();
+        message.images.push(PathBuf::from("/tmp/ada.png"));
+        message.tool_steps.push(crate::app::ToolStep {
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({ "path": ["ada.txt"] }),
+            result: Some("Ada wrote this".to_string()),
+        });
+        session.messages.push(message);
+
+        let copy = redactor.redact_session(&session);
+        assert_eq!(copy.system_prompt.as_deref(), Some("You help [name]"));
+        assert_eq!(copy.tags, ["[name]"]);
+        let message = &copy.messages[0];
+        assert_eq!(message.thinking, "[name] asked");
+        assert_eq!(message.images[0], PathBuf::from("/tmp/[name].png"));
+        assert_eq!(message.tool_steps[0].arguments, serde_json::json!({ "path": ["[name].txt"] }));
+        assert_eq!(message.tool_steps[0].result.as_deref(), Some("[name] wrote this"));
+    }
+}
@@ -0,0 +1,157 @@
+//! Session retention
+//!
+//! At startup, sessions past the configured age or count are pruned. Unless
+//! `archive = false`, each one is exported to the archive first; a session
+//! whose export fails is kept.
+
+use chrono::{DateTime, Duration, Utc};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use crate::app::ChatSession;
+use crate::config::{ArchiveFormat, Config, RetentionConfig};
+use crate::error::PersistenceError;
+use crate::persistence::{self, MarkdownOptions};
+
+/// Outcome of a pruning pass
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    /// Sessions removed
+    pub pruned: usize,
+    /// Sessions kept because archiving them failed
+    pub failed: usize,
+    /// Where archived sessions were written
+    pub archive_dir: Option<PathBuf>,
+}
+
+/// Indices of sessions the policy would prune, in ascending order
+///
+/// The most recently updated session is always kept, as are pinned ones.
+pub fn expired(sessions: &[ChatSession], policy: &RetentionConfig, now: DateTime<Utc>) -> Vec<usize> {
+    let mut by_recency: Vec<usize> = (0..sessions.len()).collect();
+    by_recency.sort_by(|a, b| sessions[*b].updated_at.cmp(&sessions[*a].updated_at));
+
+    let cutoff = policy.max_age_days.map(|days| now - Duration::days(i64::from(days)));
+    let keep = policy.max_sessions.unwrap_or(usize::MAX).max(1);
+
+    let mut expired: Vec<usize> = by_recency
+        .into_iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, idx)| !sessions[*idx].pinned)
+        .filter(|(rank, idx)| *rank >= keep || cutoff.is_some_and(|c| sessions[*idx].updated_at < c))
+        .map(|(_, idx)| idx)
+        .collect();
+    expired.sort_unstable();
+    expired
+}
+
+/// Apply the retention policy, archiving sessions before removing them
+pub fn prune(sessions: &mut Vec<ChatSession>, config: &Config, now: DateTime<Utc>) -> Result<PruneReport, PersistenceError> {
+    let policy = &config.retention;
+    let expired = expired(sessions, policy, now);
+    if expired.is_empty() {
+        return Ok(PruneReport::default());
+    }
+
+    let archive_dir = if policy.archive {
+        Some(match policy.archive_dir() {
+            Some(dir) => dir,
+            None => persistence::data_dir()?.join("archive"),
+        })
+    } else {
+        None
+    };
+    let options = MarkdownOptions {
+        front_matter: config.export.front_matter,
+        ..Default::default()
+    };
+
+    let mut report = PruneReport { archive_dir: archive_dir.clone(), ..Default::default() };
+    let mut remove = vec![false; sessions.len()];
+    for idx in expired {
+        let session = &sessions[idx];
+        if let Some(dir) = &archive_dir {
+            let result = match policy.archive_format {
+                ArchiveFormat::Markdown => persistence::export_session_to_file(session, dir, &options),
+                ArchiveFormat::Json => persistence::export_session_json(session, dir),
+            };
+            match result {
+                Ok(path) => info!("Archived session {:?} to {:?}", session.name, path),
+                Err(e) => {
+                    warn!("Failed to archive session {:?}, keeping it: {}", session.name, e);
+                    report.failed += 1;
+                    continue;
+                }
+            }
+        }
+        remove[idx] = true;
+        report.pruned += 1;
+    }
+
+    let mut flags = remove.into_iter();
+    sessions.retain(|_| !flags.next().unwrap_or(false));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_aged(name: &str, now: DateTime<Utc>, days: i64) -> ChatSession {
+        let mut session = ChatSession::new(name, "llama3.2");
+        session.updated_at = now - Duration::days(days);
+        session
+    }
+
+    #[test]
+    fn test_expired_by_age_and_count() {
+        let now = Utc::now();
+        let sessions = vec![
+            session_aged("old", now, 100),
+            session_aged("new", now, 0),
+            session_aged("mid", now, 10),
+        ];
+
+        let off = RetentionConfig::default();
+        assert!(expired(&sessions, &off, now).is_empty());
+
+        let by_age = RetentionConfig { max_age_days: Some(30), ..Default::default() };
+        assert_eq!(expired(&sessions, &by_age, now), vec![0]);
+
+        let by_count = RetentionConfig { max_sessions: Some(1), ..Default::default() };
+        assert_eq!(expired(&sessions, &by_count, now), vec![0, 2]);
+
+        let mut with_pin = sessions.clone();
+        with_pin[0].pinned = true;
+        assert_eq!(expired(&with_pin, &by_count, now), vec![2]);
+
+        // The newest session survives even when everything is too old
+        let strict = RetentionConfig { max_age_days: Some(0), max_sessions: Some(0), ..Default::default() };
+        assert_eq!(expired(&sessions[..1], &strict, now), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_prune_archives_first() {
+        let now = Utc::now();
+        let dir = std::env::temp_dir().join(format!("ratatalk-archive-{}", uuid::Uuid::new_v4()));
+        let config = Config {
+            retention: RetentionConfig {
+                max_sessions: Some(1),
+                archive_format: ArchiveFormat::Json,
+                archive_dir: Some(dir.clone()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sessions = vec![session_aged("old", now, 5), session_aged("new", now, 0)];
+
+        let report = prune(&mut sessions, &config, now).unwrap();
+        assert_eq!((report.pruned, report.failed), (1, 0));
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].name, "new");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+//! Fuzzy search across sessions
+//!
+//! Matches a query against session names and message contents. Query
+//! characters must appear in order; tighter matches, matches at word starts,
+//! and session names rank higher.
+
+use crate::app::ChatSession;
+
+/// Most results kept for a query
+const MAX_RESULTS: usize = 50;
+
+/// Characters of context kept in a snippet
+const SNIPPET_CHARS: usize = 70;
+
+/// A search result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub session_idx: usize,
+    /// Matching message, or `None` when the session name matched
+    pub message_idx: Option<usize>,
+    pub score: i64,
+    /// Text around the match, on one line
+    pub snippet: String,
+}
+
+/// Score a fuzzy match of `query` in `text`, with the char offset it starts at
+///
+/// Every start position of the first query character is tried and the
+/// tightest match kept. In long text, matches spread over more than a few
+/// characters per query character are rejected as noise.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, usize)> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()).collect();
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let first = *query.first()?;
+    let max_span = if text.len() > 80 { query.len() * 3 + 4 } else { usize::MAX };
+
+    let mut best: Option<(i64, usize)> = None;
+    for start in (0..text.len()).filter(|&i| text[i] == first) {
+        let mut pos = start;
+        let mut score = 0i64;
+        let mut matched = 0;
+        let mut prev: Option<usize> = None;
+        // Give up on a start as soon as its match is too spread out, so long
+        // messages don't cost a scan to the end per start
+        while matched < query.len() && pos < text.len() && pos - start < max_span {
+            if text[pos] == query[matched] {
+                score += 10;
+                if prev == Some(pos.wrapping_sub(1)) {
+                    score += 15;
+                }
+                if pos == 0 || !text[pos - 1].is_alphanumeric() {
+                    score += 10;
+                }
+                prev = Some(pos);
+                matched += 1;
+            }
+            pos += 1;
+        }
+        let span = pos - start;
+        if matched < query.len() || span > max_span {
+            continue;
+        }
+        score -= (span - query.len()) as i64;
+        if best.is_none_or(|(s, _)| score > s) {
+            best = Some((score, start));
+        }
+    }
+    best
+}
+
+/// Search session names and message contents, best matches first
+pub fn search(sessions: &[ChatSession], query: &str) -> Vec<SearchHit> {
+    if query.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut hits = Vec::new();
+    for (session_idx, session) in sessions.iter().enumerate() {
+        if let Some((score, _)) = fuzzy_match(query, &session.name) {
+            hits.push(SearchHit {
+                session_idx,
+                message_idx: None,
+                // Name matches outrank content matches of the same quality
+                score: score + 20,
+                snippet: format!("{} messages", session.messages.len()),
+            });
+        }
+        for (message_idx, message) in session.messages.iter().enumerate() {
+            if let Some((score, start)) = fuzzy_match(query, &message.content) {
+                hits.push(SearchHit {
+                    session_idx,
+                    message_idx: Some(message_idx),
+                    score,
+                    snippet: snippet(&message.content, start),
+                });
+            }
+        }
+    }
+    // Stable, so equal scores keep session and message order
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+    hits.truncate(MAX_RESULTS);
+    hits
+}
+
+/// One line of text around the char offset `start`
+fn snippet(text: &str, start: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let from = start.saturating_sub(SNIPPET_CHARS / 4);
+    let to = (from + SNIPPET_CHARS).min(chars.len());
+    let body: String = chars[from..to]
+        .iter()
+        .map(|&c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    let prefix = if from > 0 { "…" } else { "" };
+    let suffix = if to < chars.len() { "…" } else { "" };
+    format!("{}{}{}", prefix, body, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match_prefers_tight_matches() {
+        let (tight, start) = fuzzy_match("borrow", "the Borrow checker").unwrap();
+        assert_eq!(start, 4);
+        let (loose, _) = fuzzy_match("borrow", "b o r r o w").unwrap();
+        assert!(tight > loose);
+        assert!(fuzzy_match("xyz", "borrow").is_none());
+        assert!(fuzzy_match("", "anything").is_none());
+
+        // Scattered letters in long text don't count
+        let long = format!("b{}orrow", " padding".repeat(20));
+        assert!(fuzzy_match("borrow", &long).is_none());
+    }
+
+    #[test]
+    fn test_search_ranks_names_and_messages() {
+        let mut rust = ChatSession::new("Rust lifetimes", "llama3.2");
+        rust.add_user_message("What does the borrow checker do?");
+        let mut other = ChatSession::new("Groceries", "llama3.2");
+        other.add_user_message("Buy bread and butter");
+
+        let hits = search(&[rust, other], "borrow");
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].session_idx, hits[0].message_idx), (0, Some(0)));
+        assert_eq!(hits[0].snippet, "What does the borrow checker do?");
+
+        let hits = search(&[ChatSession::new("Groceries", "llama3.2")], "groc");
+        assert_eq!(hits[0].message_idx, None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Session storage backends
+//!
+//! Sessions are loaded and saved through [`SessionStore`], so the backend
+//! can change without touching its callers. `[storage] backend` picks one:
+//!
+//! - `json` (default): `sessions.json` in the data directory, with the
+//!   checksums and backups of [`persistence`]
+//! - `memory`: nothing touches the disk and sessions last until ratatalk
+//!   exits, for tests and throwaway runs
+//!
+//! A store always gets every session on save. Sessions unloaded to stay
+//! under the memory cap arrive without their messages, and the store keeps
+//! the ones it has for them.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use uuid::Uuid;
+
+use crate::app::{ChatSession, Message};
+use crate::config::{StorageBackend, StorageConfig};
+use crate::error::PersistenceError;
+use crate::persistence::{self, LoadReport};
+use crate::search::{self, SearchHit};
+
+/// Loads and saves chat sessions
+pub trait SessionStore: Debug + Send + Sync {
+    /// Every stored session, with the names of any that fail verification
+    ///
+    /// Meant for startup: the JSON store also backs up a file that verifies
+    /// and moves aside one that doesn't parse.
+    fn load(&self) -> Result<LoadReport, PersistenceError>;
+
+    /// Every stored session, read without backing up or moving anything
+    fn read(&self) -> Result<Vec<ChatSession>, PersistenceError>;
+
+    /// Replace the stored sessions with `sessions`
+    fn save(&self, sessions: &[ChatSession]) -> Result<(), PersistenceError>;
+
+    /// Remove one session
+    #[allow(dead_code)]
+    fn delete(&self, id: &Uuid) -> Result<(), PersistenceError>;
+
+    /// The messages of one session, to load a large session on its own
+    fn load_messages(&self, id: &Uuid) -> Result<Vec<Message>, PersistenceError>;
+
+    /// Search the stored sessions; hits index into them in stored order
+    #[allow(dead_code)]
+    fn search(&self, query: &str) -> Result<Vec<SearchHit>, PersistenceError> {
+        Ok(search::search(&self.read()?, query))
+    }
+}
+
+/// The configured backend
+pub fn open(config: &StorageConfig) -> Arc<dyn SessionStore> {
+    match config.backend {
+        StorageBackend::Json => Arc::new(JsonStore),
+        StorageBackend::Memory => Arc::new(MemoryStore::default()),
+    }
+}
+
+/// Sessions in `sessions.json`
+#[derive(Debug)]
+pub struct JsonStore;
+
+impl SessionStore for JsonStore {
+    fn load(&self) -> Result<LoadReport, PersistenceError> {
+        persistence::load_sessions_checked()
+    }
+
+    fn read(&self) -> Result<Vec<ChatSession>, PersistenceError> {
+        persistence::load_sessions()
+    }
+
+    fn save(&self, sessions: &[ChatSession]) -> Result<(), PersistenceError> {
+        persistence::save_sessions(sessions)
+    }
+
+    fn delete(&self, id: &Uuid) -> Result<(), PersistenceError> {
+        persistence::delete_session(id)
+    }
+
+    fn load_messages(&self, id: &Uuid) -> Result<Vec<Message>, PersistenceError> {
+        persistence::load_messages(id)
+    }
+}
+
+/// Sessions kept in memory only
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    sessions: Mutex<Vec<ChatSession>>,
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self) -> Result<LoadReport, PersistenceError> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Ok(LoadReport { sessions, ..Default::default() })
+    }
+
+    fn read(&self) -> Result<Vec<ChatSession>, PersistenceError> {
+        Ok(self.sessions.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    fn save(&self, sessions: &[ChatSession]) -> Result<(), PersistenceError> {
+        let mut stored = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let mut saved = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            let mut copy = session.clone();
+            if session.unloaded {
+                let old = stored
+                    .iter_mut()
+                    .find(|s| s.id == session.id)
+                    .ok_or_else(|| PersistenceError::SessionNotFound { id: session.id.to_string() })?;
+                copy.messages = std::mem::take(&mut old.messages);
+                copy.unloaded = false;
+            }
+            saved.push(copy);
+        }
+        *stored = saved;
+        Ok(())
+    }
+
+    fn delete(&self, id: &Uuid) -> Result<(), PersistenceError> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).retain(|s| &s.id != id);
+        Ok(())
+    }
+
+    fn load_messages(&self, id: &Uuid) -> Result<Vec<Message>, PersistenceError> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|s| &s.id == id)
+            .map(|s| s.messages.clone())
+            .ok_or_else(|| PersistenceError::SessionNotFound { id: id.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store() {
+        let store = open(&StorageConfig { backend: StorageBackend::Memory });
+        let mut sessions = vec![ChatSession::new("Rust", "llama3.2"), ChatSession::new("Go", "llama3.2")];
+        sessions[0].messages.push(Message::user("borrow checker"));
+        store.save(&sessions).unwrap();
+
+        // An unloaded session keeps its stored messages
+        sessions[0].messages.clear();
+        sessions[0].unloaded = true;
+        store.save(&sessions).unwrap();
+        assert_eq!(store.load_messages(&sessions[0].id).unwrap()[0].content, "borrow checker");
+        assert_eq!(store.search("borrow").unwrap()[0].session_idx, 0);
+
+        store.delete(&sessions[1].id).unwrap();
+        let report = store.load().unwrap();
+        assert_eq!(report.sessions.len(), 1);
+        assert!(!report.sessions[0].unloaded);
+        assert!(store.load_messages(&sessions[1].id).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Chat store
+//!
+//! The domain half of the application state: the sessions, which one is
+//! active, and the models the server offers with their context windows. It
+//! knows nothing about modes, scrolling or the input box, so code without a
+//! UI (the control socket, retention, unit tests) can work with sessions on
+//! their own. [`AppState`](crate::app::AppState) holds a store and adds the
+//! interface state around it.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::app::{ChatSession, Message};
+use crate::error::PersistenceError;
+use crate::ollama::ModelInfo;
+use crate::storage::SessionStore;
+
+/// Messages of sessions by id
+type StoredMessages = HashMap<Uuid, Vec<Message>>;
+
+/// Sessions and models
+#[derive(Debug, Clone)]
+pub struct ChatStore {
+    /// All chat sessions, pinned ones first
+    pub sessions: Vec<ChatSession>,
+
+    /// Index of the active session
+    pub active_session_idx: usize,
+
+    /// Models offered by the server and the hosted providers
+    pub models: Vec<ModelInfo>,
+
+    /// Context windows reported by the server, by model (0 = unknown)
+    model_contexts: HashMap<String, u32>,
+
+    /// Where sessions are loaded from and saved to
+    storage: Arc<dyn SessionStore>,
+
+    /// Model for new sessions when there is no active one to copy
+    default_model: String,
+
+    /// Messages of the unloaded sessions, read from storage on first need
+    /// and kept until sessions are unloaded again, so searching and
+    /// switching sessions don't re-read the file on every key
+    stored: Arc<Mutex<Option<StoredMessages>>>,
+}
+
+impl ChatStore {
+    /// A store with one empty session on `default_model`, saving to `storage`
+    pub fn new(default_model: impl Into<String>, storage: Arc<dyn SessionStore>) -> Self {
+        let default_model = default_model.into();
+        Self {
+            sessions: vec![ChatSession::with_default_name(&default_model)],
+            active_session_idx: 0,
+            models: Vec::new(),
+            model_contexts: HashMap::new(),
+            storage,
+            default_model,
+            stored: Arc::default(),
+        }
+    }
+
+    /// Save all sessions
+    pub fn save(&self) -> Result<(), PersistenceError> {
+        self.storage.save(&self.sessions)
+    }
+
+    pub fn active_session(&self) -> Option<&ChatSession> {
+        self.sessions.get(self.active_session_idx)
+    }
+
+    /// The active session to change, its messages read back first if they
+    /// were unloaded; `None` if they can't be
+    pub fn active_session_mut(&mut self) -> Option<&mut ChatSession> {
+        self.load(self.active_session_idx);
+        self.sessions.get_mut(self.active_session_idx).filter(|s| !s.unloaded)
+    }
+
+    /// Model of the active session, or the default
+    pub fn current_model(&self) -> &str {
+        self.active_session().map_or(&self.default_model, |s| s.model.as_str())
+    }
+
+    /// Set the model of the active session
+    pub fn set_model(&mut self, model: impl Into<String>) {
+        if let Some(session) = self.active_session_mut() {
+            session.model = model.into();
+        }
+    }
+
+    /// Add a session on the current model and make it active
+    pub fn new_session(&mut self) -> &ChatSession {
+        let session = ChatSession::with_default_name(self.current_model());
+        self.sessions.push(session);
+        self.active_session_idx = self.sessions.len() - 1;
+        &self.sessions[self.active_session_idx]
+    }
+
+    /// Add a session right after the active one and make it active
+    pub fn insert_after_active(&mut self, session: ChatSession) -> &ChatSession {
+        let idx = (self.active_session_idx + 1).min(self.sessions.len());
+        self.sessions.insert(idx, session);
+        self.active_session_idx = idx;
+        self.sort_pinned();
+        &self.sessions[self.active_session_idx]
+    }
+
+    /// Make the session at `idx` active; returns whether it changed
+    ///
+    /// Unloaded messages are read back from storage.
+    pub fn activate(&mut self, idx: usize) -> bool {
+        let changed = idx < self.sessions.len() && idx != self.active_session_idx;
+        if changed {
+            self.active_session_idx = idx;
+            self.load_active();
+        }
+        changed
+    }
+
+    /// Read the active session's messages back if they were unloaded
+    fn load_active(&mut self) {
+        self.load(self.active_session_idx);
+    }
+
+    /// Read the messages of the session at `idx` back if they were unloaded
+    ///
+    /// On failure the session stays unloaded, and the mutators refuse it
+    /// rather than let a save drop what is added to it.
+    pub fn load(&mut self, idx: usize) {
+        let Some(id) = self.sessions.get(idx).filter(|s| s.unloaded).map(|s| s.id) else {
+            return;
+        };
+        let cached = self.with_stored(|stored| stored.remove(&id)).ok().flatten();
+        let messages = match cached {
+            Some(messages) => Ok(messages),
+            None => self.storage.load_messages(&id),
+        };
+        let session = &mut self.sessions[idx];
+        match messages {
+            Ok(messages) => {
+                session.messages = messages;
+                session.unloaded = false;
+            }
+            Err(e) => warn!("Failed to load messages of {:?}: {}", session.name, e),
+        }
+    }
+
+    /// Run `f` on the stored messages of the unloaded sessions, reading
+    /// them from storage if they aren't cached
+    fn with_stored<T>(&self, f: impl FnOnce(&mut StoredMessages) -> T) -> Result<T, PersistenceError> {
+        let mut stored = self.stored.lock().unwrap_or_else(|e| e.into_inner());
+        let stored = match stored.as_mut() {
+            Some(stored) => stored,
+            None => {
+                let unloaded: Vec<Uuid> = self.sessions.iter().filter(|s| s.unloaded).map(|s| s.id).collect();
+                let on_disk = self.storage.read()?;
+                stored.insert(
+                    on_disk
+                        .into_iter()
+                        .filter(|s| unloaded.contains(&s.id))
+                        .map(|s| (s.id, s.messages))
+                        .collect(),
+                )
+            }
+        };
+        Ok(f(stored))
+    }
+
+    /// The session with `id`, its messages read back first if they were
+    /// unloaded
+    pub fn session_mut(&mut self, id: Uuid) -> Option<&mut ChatSession> {
+        let idx = self.sessions.iter().position(|s| s.id == id)?;
+        self.load(idx);
+        self.sessions.get_mut(idx).filter(|s| !s.unloaded)
+    }
+
+    /// Index of the session after (`step` 1) or before (`step` -1) the
+    /// active one, wrapping around
+    pub fn neighbour(&self, step: isize) -> Option<usize> {
+        let count = self.sessions.len();
+        (count > 0).then(|| (self.active_session_idx as isize + step).rem_euclid(count as isize) as usize)
+    }
+
+    /// Delete the active session, unless it is the only one
+    pub fn delete_active_session(&mut self) -> bool {
+        if self.sessions.len() <= 1 {
+            return false;
+        }
+        self.sessions.remove(self.active_session_idx);
+        self.active_session_idx = self.active_session_idx.min(self.sessions.len() - 1);
+        self.load_active();
+        true
+    }
+
+    /// Pin or unpin the active session; returns whether it is now pinned
+    pub fn toggle_pin(&mut self) -> Option<bool> {
+        let session = self.active_session_mut()?;
+        session.pinned = !session.pinned;
+        let pinned = session.pinned;
+        self.sort_pinned();
+        Some(pinned)
+    }
+
+    /// Move pinned sessions to the top, keeping the order within each group
+    ///
+    /// The active session stays active.
+    pub fn sort_pinned(&mut self) {
+        let active = self.active_session().map(|s| s.id);
+        self.sessions.sort_by_key(|s| !s.pinned);
+        if let Some(idx) = active.and_then(|id| self.sessions.iter().position(|s| s.id == id)) {
+            self.active_session_idx = idx;
+        }
+    }
+
+    /// Approximate bytes of message content held in memory
+    pub fn memory_usage(&self) -> usize {
+        self.sessions.iter().map(ChatSession::memory_size).sum()
+    }
+
+    /// Unload inactive sessions not in `keep`, least recently updated
+    /// first, until the loaded messages take at most `max_bytes`; returns
+    /// how many were
+    ///
+    /// Only call right after saving: the messages are read back from storage.
+    ///
+    /// Sessions still streaming are kept, since their answer isn't stored
+    /// yet.
+    pub fn unload_over(&mut self, max_bytes: usize, keep: &[Uuid]) -> usize {
+        // Reading stored messages again is cheaper than holding them
+        *self.stored.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        let mut usage = self.memory_usage();
+        let mut candidates: Vec<usize> = (0..self.sessions.len())
+            .filter(|&idx| idx != self.active_session_idx && !self.sessions[idx].messages.is_empty())
+            .filter(|&idx| !keep.contains(&self.sessions[idx].id) && !self.sessions[idx].is_streaming())
+            .collect();
+        candidates.sort_by_key(|&idx| self.sessions[idx].updated_at);
+
+        let mut unloaded = 0;
+        for idx in candidates {
+            if usage <= max_bytes {
+                break;
+            }
+            let session = &mut self.sessions[idx];
+            usage -= session.memory_size();
+            session.messages = Vec::new();
+            session.unloaded = true;
+            unloaded += 1;
+        }
+        unloaded
+    }
+
+    /// All sessions with their messages, reading unloaded ones from storage
+    pub fn loaded_sessions(&self) -> Result<Cow<'_, [ChatSession]>, PersistenceError> {
+        if !self.sessions.iter().any(|s| s.unloaded) {
+            return Ok(Cow::Borrowed(&self.sessions));
+        }
+        let sessions = self.with_stored(|stored| {
+            self.sessions
+                .iter()
+                .map(|session| {
+                    let mut whole = session.clone();
+                    if let Some(messages) = stored.get(&session.id).filter(|_| session.unloaded) {
+                        whole.messages = messages.clone();
+                        whole.unloaded = false;
+                    }
+                    whole
+                })
+                .collect()
+        })?;
+        Ok(Cow::Owned(sessions))
+    }
+
+    /// Context window the server reported for `model`, if known
+    pub fn context_window(&self, model: &str) -> Option<u32> {
+        self.model_contexts.get(model).copied().filter(|&n| n > 0)
+    }
+
+    /// Whether the context window of `model` was asked for already
+    pub fn context_window_asked(&self, model: &str) -> bool {
+        self.model_contexts.contains_key(model)
+    }
+
+    /// Note the context window of `model`, 0 while unknown
+    pub fn set_context_window(&mut self, model: impl Into<String>, tokens: u32) {
+        self.model_contexts.insert(model.into(), tokens);
+    }
+
+    /// Where sessions are loaded from and saved to
+    pub fn storage(&self) -> &dyn SessionStore {
+        self.storage.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Message;
+    use crate::config::{StorageBackend, StorageConfig};
+    use crate::storage;
+
+    #[test]
+    fn test_sessions_without_ui() {
+        let mut store = ChatStore::new("llama3.2", storage::open(&StorageConfig { backend: StorageBackend::Memory }));
+        store.set_model("mistral");
+        assert_eq!(store.new_session().model, "mistral");
+        assert_eq!(store.active_session_idx, 1);
+
+        assert_eq!(store.neighbour(1), Some(0));
+        assert_eq!(store.neighbour(-1), Some(0));
+        assert!(store.activate(0));
+        assert_eq!(store.neighbour(-1), Some(1));
+
+        // Pinning moves the active session up and keeps it active
+        assert_eq!(store.toggle_pin(), Some(true));
+        assert_eq!(store.active_session_idx, 0);
+        assert!(store.active_session().unwrap().pinned);
+
+        assert!(store.delete_active_session());
+        assert!(!store.delete_active_session());
+        assert_eq!(store.sessions.len(), 1);
+
+        store.set_context_window("mistral", 0);
+        assert_eq!(store.context_window("mistral"), None);
+    }
+
+    #[test]
+    fn test_unload_over() {
+        let mut store = ChatStore::new("llama3.2", storage::open(&StorageConfig { backend: StorageBackend::Memory }));
+        store.sessions = ["old", "newer", "active"].iter().map(|n| ChatSession::new(*n, "llama3.2")).collect();
+        for (i, session) in store.sessions.iter_mut().enumerate() {
+            session.messages.push(Message::user("x".repeat(1000)));
+            session.updated_at += chrono::Duration::seconds(i as i64);
+        }
+        store.active_session_idx = 2;
+        store.save().unwrap();
+        let per_session = store.sessions[0].memory_size();
+        assert_eq!(store.memory_usage(), 3 * per_session);
+
+        // The least recently updated inactive session goes first
+        assert_eq!(store.unload_over(2 * per_session, &[]), 1);
+        assert!(store.sessions[0].unloaded && store.sessions[0].messages.is_empty());
+        assert!(!store.sessions[1].unloaded);
+
+        // The active session is never unloaded
+        assert_eq!(store.unload_over(0, &[]), 1);
+        assert_eq!(store.memory_usage(), per_session);
+
+        // Opening a session reads its messages back
+        assert!(store.activate(0));
+        assert!(!store.sessions[0].unloaded);
+        assert_eq!(store.memory_usage(), 2 * per_session);
+        assert_eq!(store.loaded_sessions().unwrap()[1].messages.len(), 1);
+
+        // Changing an unloaded session reads it back first
+        let id = store.sessions[1].id;
+        store.session_mut(id).unwrap().messages.push(Message::user("more"));
+        assert_eq!(store.sessions[1].messages.len(), 2);
+
+        // A session that is still streaming keeps its messages
+        store.sessions[1].messages.push(Message::assistant_streaming());
+        assert_eq!(store.unload_over(0, &[]), 1);
+        assert!(!store.sessions[1].unloaded && store.sessions[2].unloaded);
+    }
+}
@@ -0,0 +1,386 @@
+//! Color themes
+//!
+//! Every color the interface draws with comes from a [`Theme`]. `ui.theme`
+//! names the one to use: one of the [`PRESETS`] or a file in
+//! `themes/<name>.toml` under the config directory, which wins over a preset
+//! of the same name. A theme file sets colors by name, index or hex, and
+//! anything it leaves out comes from its `base` preset, `dark` by default:
+//!
+//! ```toml
+//! base = "light"
+//! border_focused = "magenta"
+//! user = "#89b4fa"
+//! status_bg = "236"
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use ratatui::style::Color;
+use ratatui::style::Color::{
+    Black, Blue, DarkGray, Gray, Indexed, LightBlue, LightCyan, LightGreen, LightRed, LightYellow, Magenta, Red, Reset, Rgb, White,
+};
+
+use crate::config::Config;
+use crate::error::ThemeError;
+
+/// Colors for every part of the interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Theme {
+    /// Unfocused pane borders
+    pub border: Color,
+    /// Border of the focused pane
+    pub border_focused: Color,
+    /// Border of the input while typing
+    pub border_active: Color,
+
+    /// User message labels
+    pub user: Color,
+    /// Assistant message labels and streamed text
+    pub assistant: Color,
+    /// System and tool message labels
+    pub system: Color,
+
+    pub error: Color,
+    pub warning: Color,
+    pub success: Color,
+    pub info: Color,
+
+    /// Selected list entries
+    pub selected: Color,
+    /// Key hints, headings and the current find match
+    pub highlight: Color,
+    /// Text drawn on top of find matches
+    pub match_fg: Color,
+
+    pub status_bg: Color,
+    pub status_fg: Color,
+
+    /// Background of popups
+    pub popup_bg: Color,
+    /// Border of popups
+    pub popup_border: Color,
+}
+
+/// Themes shipped with ratatalk, in the order the theme key cycles through
+pub const PRESETS: &[(&str, Theme)] = &[
+    ("dark", Theme::DEFAULT),
+    ("light", LIGHT),
+    ("solarized", SOLARIZED),
+    ("gruvbox", GRUVBOX),
+    ("high-contrast", HIGH_CONTRAST),
+    ("monochrome", MONOCHROME),
+];
+
+/// Dark ANSI colors and a gray status bar, readable on a white background
+const LIGHT: Theme = Theme {
+    border: Indexed(248),
+    border_focused: Blue,
+    border_active: Indexed(28),
+    user: Blue,
+    assistant: Indexed(28),
+    system: Indexed(130),
+    error: Red,
+    warning: Indexed(130),
+    success: Indexed(28),
+    info: Blue,
+    selected: Magenta,
+    highlight: Blue,
+    match_fg: White,
+    status_bg: Indexed(252),
+    status_fg: Black,
+    popup_bg: Reset,
+    popup_border: Blue,
+};
+
+/// Solarized dark
+const SOLARIZED: Theme = Theme {
+    border: Rgb(0x58, 0x6e, 0x75),
+    border_focused: Rgb(0x26, 0x8b, 0xd2),
+    border_active: Rgb(0x85, 0x99, 0x00),
+    user: Rgb(0x26, 0x8b, 0xd2),
+    assistant: Rgb(0x85, 0x99, 0x00),
+    system: Rgb(0xb5, 0x89, 0x00),
+    error: Rgb(0xdc, 0x32, 0x2f),
+    warning: Rgb(0xcb, 0x4b, 0x16),
+    success: Rgb(0x85, 0x99, 0x00),
+    info: Rgb(0x6c, 0x71, 0xc4),
+    selected: Rgb(0xb5, 0x89, 0x00),
+    highlight: Rgb(0x2a, 0xa1, 0x98),
+    match_fg: Rgb(0x00, 0x2b, 0x36),
+    status_bg: Rgb(0x07, 0x36, 0x42),
+    status_fg: Rgb(0x93, 0xa1, 0xa1),
+    popup_bg: Rgb(0x00, 0x2b, 0x36),
+    popup_border: Rgb(0x26, 0x8b, 0xd2),
+};
+
+/// Gruvbox dark
+const GRUVBOX: Theme = Theme {
+    border: Rgb(0x66, 0x5c, 0x54),
+    border_focused: Rgb(0x8e, 0xc0, 0x7c),
+    border_active: Rgb(0xb8, 0xbb, 0x26),
+    user: Rgb(0x83, 0xa5, 0x98),
+    assistant: Rgb(0xb8, 0xbb, 0x26),
+    system: Rgb(0xfa, 0xbd, 0x2f),
+    error: Rgb(0xfb, 0x49, 0x34),
+    warning: Rgb(0xfe, 0x80, 0x19),
+    success: Rgb(0xb8, 0xbb, 0x26),
+    info: Rgb(0x83, 0xa5, 0x98),
+    selected: Rgb(0xfa, 0xbd, 0x2f),
+    highlight: Rgb(0x8e, 0xc0, 0x7c),
+    match_fg: Rgb(0x28, 0x28, 0x28),
+    status_bg: Rgb(0x3c, 0x38, 0x36),
+    status_fg: Rgb(0xeb, 0xdb, 0xb2),
+    popup_bg: Rgb(0x28, 0x28, 0x28),
+    popup_border: Rgb(0x8e, 0xc0, 0x7c),
+};
+
+/// Bright colors on black, white borders and an inverted status bar
+const HIGH_CONTRAST: Theme = Theme {
+    border: White,
+    border_focused: LightYellow,
+    border_active: LightGreen,
+    user: LightCyan,
+    assistant: LightGreen,
+    system: LightYellow,
+    error: LightRed,
+    warning: LightYellow,
+    success: LightGreen,
+    info: LightBlue,
+    selected: LightYellow,
+    highlight: LightCyan,
+    match_fg: Black,
+    status_bg: White,
+    status_fg: Black,
+    popup_bg: Black,
+    popup_border: LightYellow,
+};
+
+/// Grays only, for terminals or eyes that do without hue
+const MONOCHROME: Theme = Theme {
+    border: DarkGray,
+    border_focused: White,
+    border_active: White,
+    user: White,
+    assistant: Gray,
+    system: Gray,
+    error: White,
+    warning: White,
+    success: Gray,
+    info: Gray,
+    selected: White,
+    highlight: White,
+    match_fg: Black,
+    status_bg: Gray,
+    status_fg: Black,
+    popup_bg: Reset,
+    popup_border: White,
+};
+
+impl Theme {
+    /// The `dark` preset, which leaves the background to the terminal
+    pub const DEFAULT: Theme = Theme {
+        border: Color::DarkGray,
+        border_focused: Color::Cyan,
+        border_active: Color::Green,
+        user: Color::Cyan,
+        assistant: Color::Green,
+        system: Color::Yellow,
+        error: Color::Red,
+        warning: Color::Yellow,
+        success: Color::Green,
+        info: Color::Blue,
+        selected: Color::Yellow,
+        highlight: Color::Cyan,
+        match_fg: Color::Black,
+        status_bg: Color::DarkGray,
+        status_fg: Color::White,
+        popup_bg: Color::Reset,
+        popup_border: Color::Cyan,
+    };
+
+    /// Load the theme called `name`; `default` is `dark`
+    pub fn load(name: &str) -> Result<Self, ThemeError> {
+        Self::load_in(themes_dir().as_deref(), name)
+    }
+
+    /// Load the theme called `name`, looking for the user's own in `dir`
+    fn load_in(dir: Option<&Path>, name: &str) -> Result<Self, ThemeError> {
+        let name = name.trim();
+        if name.is_empty() || name.eq_ignore_ascii_case("default") {
+            return Ok(Self::DEFAULT);
+        }
+        if let Some(path) = dir.and_then(|dir| theme_path(dir, name)).filter(|p| p.is_file()) {
+            let contents = std::fs::read_to_string(&path).map_err(ThemeError::Read)?;
+            return Self::parse(&contents);
+        }
+        Self::preset(name).ok_or_else(|| ThemeError::Unknown(name.to_string()))
+    }
+
+    /// The preset called `name`
+    fn preset(name: &str) -> Option<Self> {
+        PRESETS.iter().find(|(preset, _)| preset.eq_ignore_ascii_case(name)).map(|(_, theme)| *theme)
+    }
+
+    /// Parse a theme file on top of its base preset
+    pub fn parse(contents: &str) -> Result<Self, ThemeError> {
+        let mut table: toml::Table = toml::from_str(contents).map_err(ThemeError::Parse)?;
+        let mut theme = match table.remove("base") {
+            Some(toml::Value::String(base)) => Self::preset(&base).ok_or(ThemeError::Unknown(base))?,
+            Some(other) => return Err(ThemeError::Unknown(other.to_string())),
+            None => Self::DEFAULT,
+        };
+        for (key, value) in table {
+            let slot = theme.slot(&key).ok_or_else(|| ThemeError::UnknownKey(key.clone()))?;
+            let color = match &value {
+                toml::Value::String(text) => Color::from_str(text).ok(),
+                toml::Value::Integer(index) => u8::try_from(*index).ok().map(Color::Indexed),
+                _ => None,
+            };
+            *slot = color.ok_or_else(|| ThemeError::Color { key, value: value.to_string() })?;
+        }
+        Ok(theme)
+    }
+
+    /// The color a theme file key sets
+    fn slot(&mut self, key: &str) -> Option<&mut Color> {
+        Some(match key {
+            "border" => &mut self.border,
+            "border_focused" => &mut self.border_focused,
+            "border_active" => &mut self.border_active,
+            "user" => &mut self.user,
+            "assistant" => &mut self.assistant,
+            "system" => &mut self.system,
+            "error" => &mut self.error,
+            "warning" => &mut self.warning,
+            "success" => &mut self.success,
+            "info" => &mut self.info,
+            "selected" => &mut self.selected,
+            "highlight" => &mut self.highlight,
+            "match_fg" => &mut self.match_fg,
+            "status_bg" => &mut self.status_bg,
+            "status_fg" => &mut self.status_fg,
+            "popup_bg" => &mut self.popup_bg,
+            "popup_border" => &mut self.popup_border,
+            _ => return None,
+        })
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Every theme name: the presets, then the user's own files
+pub fn names() -> Vec<String> {
+    names_in(themes_dir().as_deref())
+}
+
+/// Every theme name, with the user's own files in `dir`
+fn names_in(dir: Option<&Path>) -> Vec<String> {
+    let mut names: Vec<String> = PRESETS.iter().map(|(name, _)| name.to_string()).collect();
+    let mut own: Vec<String> = dir
+        .and_then(|dir| std::fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let stem = path.file_stem()?.to_str()?.to_string();
+            let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+            (is_toml && !names.contains(&stem)).then_some(stem)
+        })
+        .collect();
+    own.sort();
+    names.append(&mut own);
+    names
+}
+
+/// The theme after `current` in [`names`], wrapping around
+pub fn next_name(names: &[String], current: &str) -> String {
+    let idx = names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(current.trim()))
+        .map_or(0, |idx| (idx + 1) % names.len());
+    names.get(idx).cloned().unwrap_or_default()
+}
+
+/// Directory of the user's own themes
+fn themes_dir() -> Option<PathBuf> {
+    Config::config_dir().ok().map(|dir| dir.join("themes"))
+}
+
+/// Where a user's theme called `name` would live in `dir`
+///
+/// `None` unless `name` is a plain file name, so `ui.theme` can't point
+/// at a file outside `dir`.
+fn theme_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut components = Path::new(name).components();
+    let plain = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    (plain && !name.contains(['/', '\\'])).then(|| dir.join(format!("{}.toml", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = Theme::parse(
+            r##"
+            border_focused = "light magenta"
+            user = "#89b4fa"
+            status_bg = 236
+            "##,
+        )
+        .unwrap();
+        assert_eq!(theme.border_focused, Color::LightMagenta);
+        assert_eq!(theme.user, Color::Rgb(0x89, 0xb4, 0xfa));
+        assert_eq!(theme.status_bg, Color::Indexed(236));
+        assert_eq!(theme.assistant, Theme::DEFAULT.assistant);
+
+        assert!(matches!(Theme::parse("usr = \"red\""), Err(ThemeError::UnknownKey(_))));
+        assert!(matches!(Theme::parse("user = \"reddish\""), Err(ThemeError::Color { .. })));
+        assert_eq!(Theme::load_in(None, "default").unwrap(), Theme::DEFAULT);
+        assert!(matches!(Theme::load_in(None, "no-such-theme"), Err(ThemeError::Unknown(_))));
+    }
+
+    #[test]
+    fn test_user_themes() {
+        let dir = std::env::temp_dir().join(format!("ratatalk-themes-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("ocean.toml"), "user = \"blue\"").unwrap();
+        std::fs::write(dir.join("light.toml"), "base = \"light\"\nuser = \"red\"").unwrap();
+        std::fs::write(dir.join("nested").join("deep.toml"), "user = \"green\"").unwrap();
+
+        assert_eq!(Theme::load_in(Some(&dir), "ocean").unwrap().user, Color::Blue);
+        // A file wins over the preset of the same name
+        assert_eq!(Theme::load_in(Some(&dir), "light").unwrap().user, Color::Red);
+        // Names can't reach outside the directory
+        for name in ["nested/deep", "../ocean", "..", "nested\\deep"] {
+            assert!(matches!(Theme::load_in(Some(&dir), name), Err(ThemeError::Unknown(_))), "{}", name);
+        }
+        assert_eq!(names_in(Some(&dir)).last().map(String::as_str), Some("ocean"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_presets() {
+        for (name, theme) in PRESETS {
+            assert_eq!(Theme::load_in(None, name).unwrap(), *theme);
+        }
+        assert_eq!(Theme::load_in(None, "High-Contrast").unwrap(), HIGH_CONTRAST);
+
+        let theme = Theme::parse("base = \"light\"\nuser = \"magenta\"").unwrap();
+        assert_eq!(theme.user, Color::Magenta);
+        assert_eq!(theme.border, LIGHT.border);
+        assert!(matches!(Theme::parse("base = \"sepia\""), Err(ThemeError::Unknown(_))));
+
+        let names: Vec<String> = PRESETS.iter().map(|(name, _)| name.to_string()).collect();
+        assert_eq!(next_name(&names, "dark"), "light");
+        assert_eq!(next_name(&names, "monochrome"), "dark");
+        assert_eq!(next_name(&names, "default"), "dark");
+    }
+}
@@ -0,0 +1,195 @@
+//! Tools for agent mode
+//!
+//! Functions the model may call during an `/agent` run. The set comes from
+//! `[agent] tools`. File tools only see paths below the working directory,
+//! and failures are returned to the model as text so it can recover.
+
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::ollama::{FunctionSpec, ToolSpec};
+use crate::permissions::Capability;
+
+/// Most bytes of a file returned by `read_file`
+const MAX_READ_BYTES: usize = 16 * 1024;
+
+/// Most entries returned by `list_files`
+const MAX_LIST_ENTRIES: usize = 200;
+
+/// A function offered to the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tool {
+    /// Local date and time
+    CurrentTime,
+    /// Entries of a directory
+    ListFiles,
+    /// Contents of a text file
+    ReadFile,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 3] = [Tool::CurrentTime, Tool::ListFiles, Tool::ReadFile];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::CurrentTime => "current_time",
+            Tool::ListFiles => "list_files",
+            Tool::ReadFile => "read_file",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// Permission the tool needs, if any
+    pub fn capability(self) -> Option<Capability> {
+        match self {
+            Tool::CurrentTime => None,
+            Tool::ListFiles | Tool::ReadFile => Some(Capability::FileRead),
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Tool::CurrentTime => "Get the current local date and time",
+            Tool::ListFiles => "List the files in a directory relative to the working directory",
+            Tool::ReadFile => "Read a text file relative to the working directory",
+        }
+    }
+
+    fn parameters(self) -> Value {
+        let path = |description: &str| {
+            json!({ "path": { "type": "string", "description": description } })
+        };
+        match self {
+            Tool::CurrentTime => json!({ "type": "object", "properties": {} }),
+            Tool::ListFiles => json!({
+                "type": "object",
+                "properties": path("Directory to list, defaults to \".\""),
+            }),
+            Tool::ReadFile => json!({
+                "type": "object",
+                "properties": path("File to read"),
+                "required": ["path"],
+            }),
+        }
+    }
+
+    /// Description sent to the model in the chat request
+    pub fn spec(self) -> ToolSpec {
+        ToolSpec {
+            kind: "function".to_string(),
+            function: FunctionSpec {
+                name: self.name().to_string(),
+                description: self.description().to_string(),
+                parameters: self.parameters(),
+            },
+        }
+    }
+
+    /// Run the tool with the model's arguments, resolving paths against `base`
+    pub fn run(self, args: &Value, base: &Path) -> String {
+        let path = args["path"].as_str();
+        let result = match self {
+            Tool::CurrentTime => Ok(chrono::Local::now().format("%A %Y-%m-%d %H:%M:%S %Z").to_string()),
+            Tool::ListFiles => resolve(base, path.unwrap_or(".")).and_then(|dir| list_files(&dir)),
+            Tool::ReadFile => path
+                .ok_or_else(|| "missing argument: path".to_string())
+                .and_then(|path| resolve(base, path))
+                .and_then(|file| read_file(&file)),
+        };
+        result.unwrap_or_else(|e| format!("error: {}", e))
+    }
+}
+
+/// Join a relative path onto `base`, refusing anything that could leave it
+///
+/// The result is canonicalized, so a symlink inside `base` pointing out of
+/// it is refused too.
+fn resolve(base: &Path, path: &str) -> Result<PathBuf, String> {
+    const OUTSIDE: &str = "path must be relative and stay inside the working directory";
+    let path = Path::new(path);
+    if !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(OUTSIDE.to_string());
+    }
+    let base = base.canonicalize().map_err(|e| e.to_string())?;
+    let resolved = base.join(path).canonicalize().map_err(|e| e.to_string())?;
+    if !resolved.starts_with(&base) {
+        return Err(OUTSIDE.to_string());
+    }
+    Ok(resolved)
+}
+
+fn list_files(dir: &Path) -> Result<String, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| e.to_string())?;
+    let mut names: Vec<String> = entries
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.path().is_dir() {
+                format!("{}/", name)
+            } else {
+                name
+            }
+        })
+        .collect();
+    names.sort();
+    let total = names.len();
+    names.truncate(MAX_LIST_ENTRIES);
+    if total > MAX_LIST_ENTRIES {
+        names.push(format!("… {} more", total - MAX_LIST_ENTRIES));
+    }
+    Ok(names.join("\n"))
+}
+
+fn read_file(file: &Path) -> Result<String, String> {
+    let bytes = std::fs::read(file).map_err(|e| e.to_string())?;
+    let mut text = String::from_utf8(bytes).map_err(|_| "not a text file".to_string())?;
+    if text.len() > MAX_READ_BYTES {
+        let mut end = MAX_READ_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n… (truncated)");
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_tools_stay_in_base() {
+        let base = std::env::temp_dir().join(format!("ratatalk-tools-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("src")).unwrap();
+        std::fs::write(base.join("notes.txt"), "hello").unwrap();
+
+        assert_eq!(Tool::ReadFile.run(&json!({ "path": "notes.txt" }), &base), "hello");
+        assert_eq!(Tool::ListFiles.run(&json!({}), &base), "notes.txt\nsrc/");
+        assert!(Tool::ReadFile.run(&json!({ "path": "../secret" }), &base).starts_with("error:"));
+        assert!(Tool::ReadFile.run(&json!({ "path": "/etc/passwd" }), &base).starts_with("error:"));
+        assert_eq!(Tool::ReadFile.run(&json!({}), &base), "error: missing argument: path");
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/etc", base.join("etc")).unwrap();
+            assert!(Tool::ListFiles.run(&json!({ "path": "etc" }), &base).starts_with("error:"));
+        }
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_tool_names_round_trip() {
+        for tool in Tool::ALL {
+            assert_eq!(Tool::from_name(tool.name()), Some(tool));
+            assert_eq!(tool.spec().function.name, tool.name());
+        }
+        assert_eq!(Tool::from_name("rm_rf"), None);
+    }
+}
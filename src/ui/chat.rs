@@ -10,42 +10,120 @@ use ratatui::{
     Frame,
 };
 
-use crate::app::{AppState, InputMode, Message};
-use crate::ollama::Role;
+use std::borrow::Cow;
+use std::collections::{hash_map::DefaultHasher, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use super::{colors, styles};
+use chrono::{DateTime, Local, Utc};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::{AppState, ChatSession, ChatViewport, InputMode, Message, Rating, StreamLayout};
+use crate::config::TimestampStyle;
+use crate::ollama::{GenerationOptions, Role};
+use crate::patch::{classify_lines, DiffLineKind};
+use crate::theme::Theme;
+
+use super::graphics::ImagePlacement;
+use super::styles;
 
 /// Render the chat history area
-pub fn render_chat(frame: &mut Frame, state: &AppState, area: Rect) {
-    let is_focused = state.input_mode == InputMode::Normal;
+///
+/// Returns the screen areas reserved for fully visible image previews, and
+/// the line layout of the messages.
+pub fn render_chat(frame: &mut Frame, state: &AppState, area: Rect) -> (Vec<ImagePlacement>, ChatViewport) {
+    let pane = Pane {
+        session: state.active_session(),
+        scroll: state.chat_scroll,
+        previous: &state.chat_viewport,
+        focused: true,
+    };
+    render_pane(frame, state, area, pane)
+}
+
+/// Render the split view's other pane; returns its line layout
+///
+/// It shows no find matches, selection or image previews: those follow the
+/// focused pane.
+pub fn render_split_pane(frame: &mut Frame, state: &AppState, area: Rect) -> ChatViewport {
+    let Some(split) = &state.split else {
+        return ChatViewport::default();
+    };
+    let pane = Pane {
+        session: state.split_session(),
+        scroll: split.scroll,
+        previous: &split.viewport,
+        focused: false,
+    };
+    render_pane(frame, state, area, pane).1
+}
+
+/// A session shown in the chat area
+struct Pane<'a> {
+    session: Option<&'a ChatSession>,
+    /// Scroll offset, 0 showing the most recent messages
+    scroll: usize,
+    /// Line layout from the last render
+    previous: &'a ChatViewport,
+    /// Whether input goes to this pane
+    focused: bool,
+}
+
+fn render_pane(frame: &mut Frame, state: &AppState, area: Rect, pane: Pane) -> (Vec<ImagePlacement>, ChatViewport) {
+    let is_focused = pane.focused && matches!(state.input_mode, InputMode::Normal | InputMode::MessageSelect);
     
     let border_style = if is_focused {
-        styles::border_focused()
+        styles::border_focused(&state.theme)
     } else {
-        styles::border_normal()
+        styles::border_normal(&state.theme)
     };
 
-    let title = if state.streaming {
-        " Chat (streaming...) "
+    let streaming = if pane.focused {
+        state.streaming
     } else {
-        " Chat "
+        pane.session.is_some_and(ChatSession::is_streaming)
     };
+    let title = match (state.transcript, streaming) {
+        (true, _) => "Transcript (read-only)",
+        (false, true) => "Chat (streaming...)",
+        (false, false) => "Chat",
+    };
+    let mut title = vec![Span::raw(state.locale.title(title))];
+    // Side by side, the panes name their session, or when comparing, the
+    // model and its speed
+    match (pane.session.filter(|_| state.split.is_some()), &state.compare) {
+        (Some(session), Some(compare)) => {
+            title.push(Span::styled(format!("{} ", session.model), styles::highlight(&state.theme)));
+            if let Some(stats) = compare.stats.get(&session.id).filter(|_| !streaming) {
+                let speed = format!("{} tokens, {:.1} tok/s ", stats.tokens, stats.tokens_per_second);
+                title.push(Span::styled(speed, styles::dim()));
+            }
+        }
+        (Some(session), None) => title.push(Span::styled(format!("{} ", session.name), styles::dim())),
+        (None, _) => {}
+    }
+    let model = pane.session.map_or(state.current_model(), |session| session.model.as_str());
+    if state.config.is_remote_model(model) {
+        title.push(Span::styled("☁ remote ", styles::remote(&state.theme)));
+    }
 
     let block = Block::default()
-        .title(title)
+        .title(Line::from(title))
         .borders(Borders::ALL)
         .border_style(border_style);
 
     let inner_area = block.inner(area);
-    frame.render_widget(block, area);
 
-    // Get messages from active session
-    let messages = state
-        .active_session()
+    let messages = pane
+        .session
         .map(|s| &s.messages[..])
         .unwrap_or(&[]);
 
     if messages.is_empty() {
+        frame.render_widget(block, area);
         // Show placeholder text
         let placeholder = Paragraph::new(vec![
             Line::from(""),
@@ -55,184 +133,1151 @@ pub fn render_chat(frame: &mut Frame, state: &AppState, area: Rect) {
             )),
             Line::from(""),
             Line::from(Span::styled(
-                "Press '?' for help, 'm' to select model.",
+                "Press '?' for help, 'M' to select model.",
                 styles::dim(),
             )),
         ]);
         frame.render_widget(placeholder, inner_area);
-        return;
+        return (Vec::new(), ChatViewport::default());
     }
 
     // Build text lines from messages
-    let lines = build_chat_lines(messages, inner_area.width.saturating_sub(2) as usize);
-    
-    // Calculate scroll
-    let total_lines = lines.len();
-    let visible_lines = inner_area.height as usize;
-    
+    let ui = &state.config.ui;
+    let options = LineOptions {
+        image_rows: (pane.focused && state.image_previews).then_some(ui.image_preview_rows),
+        broken_images: Some(&state.broken_images),
+        expand_tools: state.tools_expanded,
+        show_settings: ui.show_settings,
+        timestamps: ui.show_timestamps.then_some(ui.timestamp_style),
+        now: Utc::now(),
+        break_cjk: ui.wrap_cjk,
+        spinner: state.spinner(),
+        theme: state.theme,
+    };
+    let session_model = pane.session.map_or_else(|| state.current_model(), |s| s.model.as_str());
+    let max_width = inner_area.width.saturating_sub(2) as usize;
+
+    // Lay out the chat from cached message heights, then build lines only
+    // for the messages on screen
+    let previous = pane.previous;
+    let (stream, tail) = match messages.iter().rposition(|m| m.streaming) {
+        Some(idx) => {
+            let (layout, tail) = streamed_content(&messages[idx], &previous.stream, max_width, options);
+            (layout, Some((idx, tail)))
+        }
+        None => (StreamLayout::default(), None),
+    };
+    let streamed = tail.as_ref().map(|(idx, tail)| Streamed { idx: *idx, settled: &stream.chunks, tail });
+    let heights = message_heights(messages, &previous.heights, session_model, max_width, options, streamed);
+    let mut message_starts = Vec::with_capacity(heights.len());
+    let mut total_lines = 0;
+    for (idx, (_, height)) in heights.iter().enumerate() {
+        total_lines += usize::from(idx > 0);
+        message_starts.push(total_lines);
+        total_lines += height;
+    }
+
     // scroll_offset of 0 means show most recent (bottom)
     // We need to calculate the starting line
+    let visible_lines = inner_area.height as usize;
     let max_scroll = total_lines.saturating_sub(visible_lines);
-    let effective_scroll = state.chat_scroll.min(max_scroll);
+    let effective_scroll = pane.scroll.min(max_scroll);
     
     // Show from (total - visible - scroll) to (total - scroll)
     let start_line = total_lines.saturating_sub(visible_lines + effective_scroll);
+
+    // Where the view is, and how many lines up, so it's clear whether the
+    // newest lines are on screen
+    let block = match scroll_position(start_line, max_scroll) {
+        Some(position) if effective_scroll > 0 => {
+            let text = format!(" {} ↑{} ", position, effective_scroll);
+            block.title(Line::from(Span::styled(text, styles::highlight(&state.theme))).right_aligned())
+        }
+        Some(position) => block.title(Line::from(Span::styled(format!(" {} ", position), styles::dim())).right_aligned()),
+        None => block,
+    };
+    frame.render_widget(block, area);
+
+    // Find matches are counted over the whole chat, so a find builds it all
+    let find_query = if pane.focused { state.find_query.as_str() } else { "" };
+    let shown = if find_query.trim().is_empty() {
+        let first = message_starts.partition_point(|&start| start <= start_line).saturating_sub(1);
+        first..message_starts.partition_point(|&start| start < start_line + visible_lines)
+    } else {
+        0..messages.len()
+    };
+    let region_start = match shown.start {
+        0 => 0,
+        first => message_starts[first] - 1,
+    };
+    let (mut lines, images, _) = build_chat_lines(messages, shown.clone(), session_model, max_width, options, streamed);
+
+    // The separator above the first message sent marks where older ones were left out
+    let not_sent = pane.session.map_or(0, |s| s.not_sent);
+    if let Some(&start) = message_starts.get(not_sent).filter(|_| not_sent > 0) {
+        if let Some(line) = (start - 1).checked_sub(region_start).and_then(|i| lines.get_mut(i)) {
+            let marker = format!("┄┄ {} older messages not sent (context window) ┄┄", not_sent);
+            *line = Line::from(Span::styled(marker, styles::dim()));
+        }
+    }
+    
+    let headers: Vec<usize> = message_starts[shown.clone()].iter().map(|start| start - region_start).collect();
+    let find_matches = highlight_matches(&mut lines, &headers, find_query, state.find_idx, &state.theme);
+    // The stream layout joins once the lines borrowing it are drawn
+    let mut viewport = ChatViewport {
+        message_starts,
+        total_lines,
+        visible_lines,
+        find_matches,
+        heights,
+        stream: StreamLayout::default(),
+    };
+
+    let framed = match state.input_mode {
+        _ if !pane.focused => None,
+        InputMode::MessageSelect => Some(state.selection()),
+        InputMode::MessageInspect => Some(state.selected_message..=state.selected_message),
+        _ => None,
+    };
+    for idx in framed.into_iter().flatten().filter(|idx| shown.contains(idx)) {
+        if let Some(range) = viewport.message_lines(idx) {
+            frame_message(&mut lines[range.start - region_start..range.end - region_start], &state.theme);
+        }
+    }
+
+    // Only place images whose reserved rows are entirely on screen
+    let placements = options
+        .image_rows
+        .map(|rows| {
+            images
+                .into_iter()
+                .map(|(line, path)| (region_start + line, path))
+                .filter(|(line, _)| *line >= start_line && line + rows as usize <= start_line + visible_lines)
+                .map(|(line, path)| ImagePlacement {
+                    path,
+                    area: Rect {
+                        x: inner_area.x + 2,
+                        y: inner_area.y + (line - start_line) as u16,
+                        width: inner_area.width.saturating_sub(2),
+                        height: rows,
+                    },
+                })
+                .collect()
+        })
+        .unwrap_or_default();
     
     let visible_text: Vec<Line> = lines
         .into_iter()
-        .skip(start_line)
+        .skip(start_line - region_start)
         .take(visible_lines)
         .collect();
 
     let paragraph = Paragraph::new(visible_text);
     frame.render_widget(paragraph, inner_area);
+    viewport.stream = stream;
 
-    // Show scroll indicator if needed
-    if max_scroll > 0 {
-        let scroll_indicator = if effective_scroll > 0 {
-            format!("↑{}", effective_scroll)
-        } else {
-            String::new()
-        };
-        
-        if !scroll_indicator.is_empty() {
-            let indicator_area = Rect {
-                x: area.x + area.width - scroll_indicator.len() as u16 - 2,
-                y: area.y,
-                width: scroll_indicator.len() as u16 + 1,
-                height: 1,
-            };
-            let indicator = Paragraph::new(scroll_indicator).style(styles::dim());
-            frame.render_widget(indicator, indicator_area);
-        }
+    (placements, viewport)
+}
+
+/// How the chat draws its messages
+#[derive(Debug, Clone, Copy, Default)]
+struct LineOptions<'a> {
+    /// Blank rows to reserve below each image label
+    image_rows: Option<u16>,
+    /// Images whose preview failed, shown as a one-line note instead
+    broken_images: Option<&'a HashSet<PathBuf>>,
+    /// Show agent tool results in full
+    expand_tools: bool,
+    /// Show the temperature and seed in response headers
+    show_settings: bool,
+    /// How headers show when a message was sent, if at all
+    timestamps: Option<TimestampStyle>,
+    /// The time relative timestamps count back from
+    now: DateTime<Utc>,
+    /// Let CJK text break between characters
+    break_cjk: bool,
+    /// Frame of the streaming spinner
+    spinner: char,
+    theme: Theme,
+}
+
+/// Content lines of the message being streamed, laid out already: those
+/// settled in earlier frames and the tail laid out in this one
+#[derive(Debug, Clone, Copy)]
+struct Streamed<'a> {
+    /// Index of the message
+    idx: usize,
+    settled: &'a [Arc<[Line<'static>]>],
+    tail: &'a [Line<'a>],
+}
+
+impl<'a> Streamed<'a> {
+    fn len(&self) -> usize {
+        self.settled.iter().map(|chunk| chunk.len()).sum::<usize>() + self.tail.len()
+    }
+
+    /// The lines, borrowing their text
+    fn lines(self) -> impl Iterator<Item = Line<'a>> {
+        self.settled.iter().flat_map(|chunk| chunk.iter()).chain(self.tail).map(borrow_line)
     }
 }
 
-/// Build text lines from messages with proper formatting
-fn build_chat_lines(messages: &[Message], max_width: usize) -> Vec<Line<'static>> {
+/// Build text lines for a range of messages
+///
+/// Lines start at the first message in `range`, or at the separator above
+/// it when there are messages before it, and the first line of each message
+/// is returned relative to that. With `image_rows` set, blank rows are
+/// reserved below each image label and the first reserved line index is
+/// returned alongside the image path. The content of the message being
+/// streamed comes laid out already as `streamed`.
+///
+/// Lines borrow message text wherever it is shown unchanged, so a frame
+/// copies little more than the wrapped lines that join collapsed spaces.
+fn build_chat_lines<'a>(
+    messages: &'a [Message],
+    range: Range<usize>,
+    session_model: &str,
+    max_width: usize,
+    options: LineOptions,
+    streamed: Option<Streamed<'a>>,
+) -> (Vec<Line<'a>>, Vec<(usize, PathBuf)>, Vec<usize>) {
     let mut lines = Vec::new();
+    let mut images = Vec::new();
+    let mut message_starts = Vec::with_capacity(range.len());
 
-    for (idx, message) in messages.iter().enumerate() {
+    for idx in range {
         // Add separator between messages (except first)
         if idx > 0 {
             lines.push(Line::from(""));
         }
+        message_starts.push(lines.len());
+        let is_last = idx + 1 == messages.len();
+        let content = streamed.filter(|streamed| streamed.idx == idx);
+        let (message_lines, message_images) =
+            message_lines(&messages[idx], is_last, session_model, max_width, options, content);
+        images.extend(message_images.into_iter().map(|(line, path)| (lines.len() + line, path)));
+        lines.extend(message_lines);
+    }
 
-        // Role indicator and styling
-        let (role_prefix, role_style, content_style) = match message.role {
-            Role::User => (
-                "You",
-                Style::default().fg(colors::USER_MSG).add_modifier(Modifier::BOLD),
-                Style::default().fg(colors::USER_MSG),
-            ),
-            Role::Assistant => (
-                "Assistant",
-                Style::default().fg(colors::ASSISTANT_MSG).add_modifier(Modifier::BOLD),
-                if message.streaming {
-                    styles::streaming()
-                } else {
-                    Style::default().fg(colors::ASSISTANT_MSG)
-                },
-            ),
-            Role::System => (
-                "System",
-                Style::default().fg(colors::SYSTEM_MSG).add_modifier(Modifier::BOLD),
-                Style::default().fg(colors::SYSTEM_MSG),
-            ),
+    (lines, images, message_starts)
+}
+
+/// Lines of one message, with its image rows as in [`build_chat_lines`]
+///
+/// Agent tool calls show one line each unless `expand_tools` is set.
+/// Responses from a model other than `session_model` name it in their
+/// header, and with `show_settings` their temperature and seed. The last
+/// message offers a retry when it is an error. Content lines laid out
+/// already are passed as `content`.
+fn message_lines<'a>(
+    message: &'a Message,
+    is_last: bool,
+    session_model: &str,
+    max_width: usize,
+    options: LineOptions,
+    content: Option<Streamed<'a>>,
+) -> (Vec<Line<'a>>, Vec<(usize, PathBuf)>) {
+    let mut lines = Vec::new();
+    let mut images = Vec::new();
+
+    // Role indicator and styling
+    let (role_prefix, role_style, content_style) = role_styles(message, &options.theme);
+
+    // Header line with role and optional timestamp
+    let timestamp = match options.timestamps {
+        Some(TimestampStyle::Clock) => format!(" {}", message.timestamp.with_timezone(&Local).format("%H:%M")),
+        Some(TimestampStyle::Relative) => format!(" {}", relative_time(message.timestamp, options.now)),
+        None => String::new(),
+    };
+    lines.push(Line::from(vec![
+        Span::styled(role_prefix, role_style),
+        Span::styled(timestamp, styles::dim()),
+        match message.model.as_deref() {
+            Some(model) if model != session_model => Span::styled(format!(" · {}", model), styles::dim()),
+            _ => Span::raw(""),
+        },
+        match message.options.as_ref().map(GenerationOptions::badge) {
+            Some(badge) if options.show_settings && !badge.is_empty() => {
+                Span::styled(format!(" · {}", badge), styles::dim())
+            }
+            _ => Span::raw(""),
+        },
+        if message.streaming {
+            Span::styled(format!(" {}", options.spinner), styles::streaming(&options.theme))
+        } else if message.flagged {
+            Span::styled(" ⚑ flagged", styles::error(&options.theme))
+        } else {
+            Span::raw("")
+        },
+        if message.cached {
+            Span::styled(" · cached", styles::dim())
+        } else {
+            Span::raw("")
+        },
+        match message.rating() {
+            Some(Rating::Up) => Span::styled(" [+]", Style::default().fg(options.theme.success)),
+            Some(Rating::Down) => Span::styled(" [-]", styles::error(&options.theme)),
+            None => Span::raw(""),
+        },
+    ]));
+
+    // Reasoning from thinking models, set apart from the answer and
+    // collapsed to one line unless expanded
+    let (thinking, answer) = message.reasoning();
+    let thinking = thinking.trim();
+    if !thinking.is_empty() {
+        let thinking_style = styles::dim().add_modifier(Modifier::ITALIC);
+        let summary = if message.thinking_expanded {
+            "┆ ▾ Thinking".to_string()
+        } else {
+            let count = thinking.lines().count();
+            format!("┆ ▸ Thinking ({} line{}, t in select mode expands)", count, if count == 1 { "" } else { "s" })
         };
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled(summary, thinking_style.add_modifier(Modifier::BOLD)),
+        ]));
+        for source_line in thinking.split('\n').filter(|_| message.thinking_expanded) {
+            for thinking_line in wrap_text(source_line, max_width.saturating_sub(2), options.break_cjk) {
+                lines.push(Line::from(vec![
+                    Span::raw("  "),
+                    Span::styled("┆ ", thinking_style),
+                    Span::styled(thinking_line, thinking_style),
+                ]));
+            }
+        }
+        if !answer.is_empty() {
+            lines.push(Line::from(""));
+        }
+    }
 
-        // Header line with role and optional timestamp
-        let timestamp = message.timestamp.format("%H:%M").to_string();
+    // Tool calls made by the agent before answering
+    for step in &message.tool_steps {
+        let status = match &step.result {
+            Some(result) if result.starts_with("error:") => Span::styled(" ✗", styles::error(&options.theme)),
+            Some(result) => Span::styled(format!(" ✓ {} lines", result.lines().count()), styles::dim()),
+            None if message.streaming => Span::styled(" …", styles::streaming(&options.theme)),
+            None => Span::styled(" cancelled", styles::dim()),
+        };
+        let call = format!("⚙ {}({})", step.name, step.arguments_summary());
         lines.push(Line::from(vec![
-            Span::styled(format!("{}:", role_prefix), role_style),
-            Span::raw(" "),
-            Span::styled(timestamp, styles::dim()),
-            if message.streaming {
-                Span::styled(" ⣾", styles::streaming())
-            } else {
-                Span::raw("")
-            },
+            Span::raw("  "),
+            Span::styled(call, styles::dim().add_modifier(Modifier::BOLD)),
+            status,
+        ]));
+        if let Some(result) = step.result.as_deref().filter(|_| options.expand_tools) {
+            for source_line in result.split('\n') {
+                for result_line in wrap_text(source_line, max_width.saturating_sub(2), options.break_cjk) {
+                    lines.push(Line::from(vec![
+                        Span::raw("  "),
+                        Span::styled("│ ", styles::dim()),
+                        Span::styled(result_line, styles::dim()),
+                    ]));
+                }
+            }
+        }
+    }
+    if !message.tool_steps.is_empty() && !answer.is_empty() {
+        lines.push(Line::from(""));
+    }
+
+    // Content lines, unless laid out already
+    match content {
+        Some(content) => lines.extend(content.lines()),
+        None => lines.extend(content_lines(answer, content_style, max_width, options, 0..usize::MAX)),
+    }
+
+    if message.interrupted {
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled("[interrupted]", styles::dim().add_modifier(Modifier::ITALIC)),
+        ]));
+    }
+
+    if message.is_error() && is_last {
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled("[R to retry]", styles::dim().add_modifier(Modifier::ITALIC)),
+        ]));
+    }
+
+    if message.truncated {
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled("[cut off · c to continue]", styles::dim().add_modifier(Modifier::ITALIC)),
         ]));
+    }
 
-        // Content lines (word-wrapped)
-        let content_lines = wrap_text(&message.content, max_width);
-        for content_line in content_lines {
+    if let Some(note) = message.feedback.as_ref().and_then(|f| f.note.as_deref()) {
+        for note_line in wrap_text(note, max_width.saturating_sub(2), options.break_cjk) {
             lines.push(Line::from(vec![
-                Span::raw("  "), // Indent content
-                Span::styled(content_line, content_style),
+                Span::raw("  "),
+                Span::styled(format!("✎ {}", note_line), styles::dim()),
             ]));
         }
     }
 
+    // Attached files stay collapsed; the model sees their content
+    for attachment in &message.attachments {
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled(
+                format!("[attached: {}, {} lines]", attachment.name, attachment.line_count()),
+                styles::dim(),
+            ),
+        ]));
+    }
+
+    // Image attachments: label plus reserved preview rows
+    for path in &message.images {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled(format!("[image: {}]", name), styles::dim()),
+        ]));
+        match options.image_rows {
+            // A preview that failed gets a note instead of its rows
+            Some(_) if options.broken_images.is_some_and(|broken| broken.contains(path)) => {
+                lines.push(Line::from(vec![
+                    Span::raw("  "),
+                    Span::styled("(no preview: the file is missing or not an image)", styles::dim()),
+                ]));
+            }
+            Some(rows) => {
+                images.push((lines.len(), path.clone()));
+                lines.extend((0..rows).map(|_| Line::from("")));
+            }
+            None => {}
+        }
+    }
+
+    (lines, images)
+}
+
+/// Role label, header style and content style of a message
+fn role_styles(message: &Message, theme: &Theme) -> (&'static str, Style, Style) {
+    match message.role {
+        Role::User => (
+            "You:",
+            Style::default().fg(theme.user).add_modifier(Modifier::BOLD),
+            Style::default().fg(theme.user),
+        ),
+        Role::Assistant => (
+            "Assistant:",
+            Style::default().fg(theme.assistant).add_modifier(Modifier::BOLD),
+            if message.streaming {
+                styles::streaming(theme)
+            } else {
+                Style::default().fg(theme.assistant)
+            },
+        ),
+        Role::System | Role::Tool => (
+            if message.role == Role::Tool { "Tool:" } else { "System:" },
+            Style::default().fg(theme.system).add_modifier(Modifier::BOLD),
+            Style::default().fg(theme.system),
+        ),
+    }
+}
+
+/// Lines for the `source` lines of a message's content: word-wrapped, with
+/// code blocks framed and numbered as in the copy picker, and diff lines
+/// colored
+///
+/// The whole content is classified, since a line's look depends on the
+/// fences before it and on the line after it.
+fn content_lines<'a>(
+    content: &'a str,
+    content_style: Style,
+    max_width: usize,
+    options: LineOptions,
+    source: Range<usize>,
+) -> Vec<Line<'a>> {
+    let diff_kinds = classify_lines(content);
+    let fences = fence_lines(content);
+    let mut lines = Vec::new();
+    let classified = content.split('\n').zip(diff_kinds).zip(fences).enumerate();
+    for (_, ((source_line, kind), fence)) in classified.filter(|(i, _)| source.contains(i)) {
+        let style = kind.map(|kind| diff_style(kind, &options.theme)).unwrap_or(content_style);
+        match fence {
+            Fence::Text => {
+                for content_line in wrap_text(source_line, max_width, options.break_cjk) {
+                    lines.push(Line::from(vec![
+                        Span::raw("  "), // Indent content
+                        Span::styled(content_line, style),
+                    ]));
+                }
+            }
+            Fence::Open { index, lang } => {
+                let label = match lang {
+                    "" => format!("╭─ [{}] ", index),
+                    lang => format!("╭─ [{}] {} ", index, lang),
+                };
+                let rule = "─".repeat(max_width.saturating_sub(label.width()));
+                lines.push(Line::from(vec![Span::raw("  "), Span::styled(label + &rule, styles::dim())]));
+            }
+            // Code keeps its indentation and is cut rather than wrapped at spaces
+            Fence::Code => {
+                let chunks = split_at_width(source_line, max_width.saturating_sub(2));
+                for chunk in chunks.into_iter().chain(source_line.is_empty().then_some("")) {
+                    lines.push(Line::from(vec![
+                        Span::raw("  "),
+                        Span::styled("│ ", styles::dim()),
+                        Span::styled(chunk, style),
+                    ]));
+                }
+            }
+            Fence::Close => {
+                let rule = format!("╰{}", "─".repeat(max_width.saturating_sub(1)));
+                lines.push(Line::from(vec![Span::raw("  "), Span::styled(rule, styles::dim())]));
+            }
+        }
+    }
     lines
 }
 
-/// Simple word wrapping
-fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
+/// What to keep of the content lines of the message being streamed, and
+/// the lines after those
+///
+/// A source line's look is settled once the line after it is complete:
+/// fences only depend on earlier lines, and a diff header on the next one.
+/// Settled lines are kept in `previous` between frames, so as tokens arrive
+/// only the last complete line and the partial one are laid out again.
+fn streamed_content<'a>(
+    message: &'a Message,
+    previous: &StreamLayout,
+    max_width: usize,
+    options: LineOptions,
+) -> (StreamLayout, Vec<Line<'a>>) {
+    let (_, content) = message.reasoning();
+    let (_, _, style) = role_styles(message, &options.theme);
+    let layout_key = |prefix: &str| {
+        let mut hasher = DefaultHasher::new();
+        (max_width, options.break_cjk, options.theme, message.timestamp, prefix).hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let mut layout = match content.get(..previous.bytes) {
+        Some(prefix) if layout_key(prefix) == previous.key => previous.clone(),
+        _ => StreamLayout { key: layout_key(""), ..Default::default() },
+    };
+
+    // Settle the lines that now have a complete line after them
+    let complete = content.matches('\n').count();
+    let settled = complete.saturating_sub(1);
+    if settled > layout.source_lines {
+        let added = content_lines(content, style, max_width, options, layout.source_lines..settled);
+        layout.chunks.push(added.into_iter().map(owned_line).collect());
+        layout.source_lines = settled;
+        layout.bytes = content.match_indices('\n').nth(settled - 1).map_or(0, |(i, _)| i + 1);
+        layout.key = layout_key(&content[..layout.bytes]);
+    }
+
+    let tail = content_lines(content, style, max_width, options, layout.source_lines..usize::MAX);
+    (layout, tail)
+}
+
+/// Fingerprint of everything that decides how many lines a message takes
+///
+/// Headers are always one line, so only the wrapped text and the optional
+/// rows below it count. The text is stood for by the message's id and
+/// revision rather than hashed, so a frame costs the same however long the
+/// messages are. Keep this in step with [`message_lines`].
+fn height_key(message: &Message, is_last: bool, max_width: usize, options: LineOptions) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (max_width, options.image_rows, options.expand_tools, options.break_cjk).hash(&mut hasher);
+    (message.id, message.revision, message.content.len(), message.thinking.len()).hash(&mut hasher);
+    (message.interrupted, message.truncated, message.thinking_expanded).hash(&mut hasher);
+    (is_last && message.is_error()).hash(&mut hasher);
+    (message.tool_steps.len(), message.attachments.len(), message.images.len()).hash(&mut hasher);
+    if let Some(broken) = options.broken_images.filter(|_| options.image_rows.is_some()) {
+        message.images.iter().filter(|path| broken.contains(*path)).count().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Height key and line count of each message
+///
+/// Counts from the last render are reused while a message's key is
+/// unchanged, so only new or changed messages are laid out. The message
+/// being streamed is counted from its `streamed` content.
+fn message_heights(
+    messages: &[Message],
+    previous: &[(u64, usize)],
+    session_model: &str,
+    max_width: usize,
+    options: LineOptions,
+    streamed: Option<Streamed<'_>>,
+) -> Vec<(u64, usize)> {
+    messages
+        .iter()
+        .enumerate()
+        .map(|(idx, message)| {
+            let is_last = idx + 1 == messages.len();
+            let key = height_key(message, is_last, max_width, options);
+            let height = match (previous.get(idx), streamed) {
+                (_, Some(streamed)) if streamed.idx == idx => {
+                    let empty = Streamed { settled: &[], tail: &[], ..streamed };
+                    message_lines(message, is_last, session_model, max_width, options, Some(empty)).0.len() + streamed.len()
+                }
+                (Some(&(cached, height)), _) if cached == key => height,
+                _ => message_lines(message, is_last, session_model, max_width, options, None).0.len(),
+            };
+            (key, height)
+        })
+        .collect()
+}
+
+/// Highlight case-insensitive occurrences of `query` in message text
+///
+/// Role headers are skipped. The `current` match gets its own style.
+/// Returns the line of each match, in order.
+fn highlight_matches(lines: &mut [Line<'_>], headers: &[usize], query: &str, current: usize, theme: &Theme) -> Vec<usize> {
+    let mut matches = Vec::new();
+    if query.trim().is_empty() {
+        return matches;
+    }
+    let needle = query.to_ascii_lowercase();
+
+    for (line_idx, line) in lines.iter_mut().enumerate() {
+        if headers.contains(&line_idx) {
+            continue;
+        }
+        let mut spans = Vec::with_capacity(line.spans.len());
+        for span in line.spans.drain(..) {
+            // ASCII lowercasing keeps byte offsets valid in the original
+            let haystack = span.content.to_ascii_lowercase();
+            let mut pos = 0;
+            for (start, _) in haystack.match_indices(&needle) {
+                if start < pos {
+                    continue;
+                }
+                let end = start + needle.len();
+                if start > pos {
+                    spans.push(Span::styled(span.content[pos..start].to_string(), span.style));
+                }
+                let style = if matches.len() == current {
+                    styles::find_current(theme)
+                } else {
+                    styles::find_match(theme)
+                };
+                spans.push(Span::styled(span.content[start..end].to_string(), style));
+                matches.push(line_idx);
+                pos = end;
+            }
+            if pos == 0 {
+                spans.push(span);
+            } else if pos < span.content.len() {
+                spans.push(Span::styled(span.content[pos..].to_string(), span.style));
+            }
+        }
+        line.spans = spans;
+    }
+    matches
+}
+
+/// `TOP`, `45%` or `BOT` for a view starting at `start_line`, the way a
+/// pager shows it; `None` when the whole chat fits
+fn scroll_position(start_line: usize, max_scroll: usize) -> Option<String> {
+    match start_line {
+        _ if max_scroll == 0 => None,
+        0 => Some("TOP".to_string()),
+        line if line >= max_scroll => Some("BOT".to_string()),
+        line => Some(format!("{}%", line * 100 / max_scroll)),
+    }
+}
+
+/// Mark a message's lines as selected: a bar in the indent and a
+/// highlighted role header
+fn frame_message(lines: &mut [Line<'_>], theme: &Theme) {
+    let bar = Style::default().fg(theme.highlight);
+    for (idx, line) in lines.iter_mut().enumerate() {
+        match line.spans.first_mut() {
+            Some(header) if idx == 0 => header.style = styles::selected(theme),
+            Some(indent) if indent.content == "  " => *indent = Span::styled("▌ ", bar),
+            // Reserved image rows are left blank for the preview
+            _ => {}
+        }
+    }
+}
+
+/// A line whose spans borrow the text of `line`
+fn borrow_line<'a>(line: &'a Line<'_>) -> Line<'a> {
+    Line {
+        spans: line.spans.iter().map(|span| Span::styled(span.content.as_ref(), span.style)).collect(),
+        style: line.style,
+        alignment: line.alignment,
+    }
+}
+
+/// A line that owns its text, to keep across frames
+fn owned_line(line: Line<'_>) -> Line<'static> {
+    Line {
+        spans: line.spans.into_iter().map(|span| Span::styled(span.content.into_owned(), span.style)).collect(),
+        style: line.style,
+        alignment: line.alignment,
+    }
+}
+
+/// Style for a line inside a unified diff
+fn diff_style(kind: DiffLineKind, theme: &Theme) -> Style {
+    match kind {
+        DiffLineKind::Header => Style::default().add_modifier(Modifier::BOLD),
+        DiffLineKind::HunkHeader => Style::default().fg(theme.highlight),
+        DiffLineKind::Add => Style::default().fg(theme.success),
+        DiffLineKind::Remove => Style::default().fg(theme.error),
+        DiffLineKind::Context => styles::dim(),
+    }
+}
+
+/// How long ago `time` was, e.g. `just now`, `5m ago` or `3d ago`
+///
+/// Anything older than a week shows its date instead.
+fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (now - time).num_seconds().max(0);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86_399 => format!("{}h ago", secs / 3600),
+        86_400..=604_799 => format!("{}d ago", secs / 86_400),
+        _ => time.with_timezone(&Local).format("%b %d").to_string(),
+    }
+}
+
+/// Word wrapping by display width
+///
+/// Lines break at spaces. With `break_cjk`, Chinese and Japanese text, which
+/// has no spaces between words, may also break between any two of its
+/// characters, short of leaving closing punctuation at the start of a line.
+fn wrap_text(text: &str, max_width: usize, break_cjk: bool) -> Vec<Cow<'_, str>> {
     if max_width == 0 {
-        return vec![text.to_string()];
+        return vec![Cow::Borrowed(text)];
     }
 
     let mut lines = Vec::new();
     
     for paragraph in text.split('\n') {
         if paragraph.is_empty() {
-            lines.push(String::new());
+            lines.push(Cow::Borrowed(""));
             continue;
         }
 
-        let mut current_line = String::new();
-        
+        // The line being filled, as a span of the paragraph
+        let mut current: Option<Range<usize>> = None;
+        let mut current_width = 0;
+
         for word in paragraph.split_whitespace() {
-            if current_line.is_empty() {
-                if word.len() > max_width {
-                    // Word is too long, split it
-                    for chunk in word.chars().collect::<Vec<_>>().chunks(max_width) {
-                        lines.push(chunk.iter().collect());
+            for (i, mut piece) in word_pieces(word, break_cjk).into_iter().enumerate() {
+                // Pieces of one word join without a space
+                let gap = usize::from(i == 0 && current.is_some());
+                let width = piece.width();
+                if let Some(range) = current.take() {
+                    if current_width + gap + width <= max_width {
+                        current = Some(range.start..offset_in(paragraph, piece) + piece.len());
+                        current_width += gap + width;
+                        continue;
                     }
-                } else {
-                    current_line = word.to_string();
+                    lines.push(joined(paragraph, range));
                 }
-            } else if current_line.len() + 1 + word.len() <= max_width {
-                current_line.push(' ');
-                current_line.push_str(word);
-            } else {
-                lines.push(current_line);
-                current_line = word.to_string();
+                if width > max_width {
+                    // Too wide for a line of its own, split it
+                    let mut chunks = split_at_width(piece, max_width);
+                    piece = chunks.pop().unwrap_or(piece);
+                    lines.extend(chunks.into_iter().map(Cow::Borrowed));
+                }
+                let start = offset_in(paragraph, piece);
+                current = Some(start..start + piece.len());
+                current_width = piece.width();
             }
         }
         
-        if !current_line.is_empty() {
-            lines.push(current_line);
+        if let Some(range) = current {
+            lines.push(joined(paragraph, range));
         }
     }
 
     if lines.is_empty() {
-        lines.push(String::new());
+        lines.push(Cow::Borrowed(""));
     }
 
     lines
 }
 
+/// Byte offset of `part`, a slice of `text`, within it
+fn offset_in(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+/// The words of `text[range]` joined by single spaces, borrowed when the
+/// text is already spaced that way
+fn joined(text: &str, range: Range<usize>) -> Cow<'_, str> {
+    let line = &text[range];
+    let single_spaced = !line.contains("  ") && !line.contains(|c: char| c.is_whitespace() && c != ' ');
+    if single_spaced {
+        Cow::Borrowed(line)
+    } else {
+        Cow::Owned(line.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// Split a word where a line may break inside it
+///
+/// Without `break_cjk` the word stays whole. With it, each CJK character is
+/// its own piece, except that closing punctuation sticks to the piece before
+/// it and opening punctuation to the piece after.
+fn word_pieces(word: &str, break_cjk: bool) -> Vec<&str> {
+    if !break_cjk {
+        return vec![word];
+    }
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    // Break between grapheme clusters, judged by their base characters
+    for (i, grapheme) in word.grapheme_indices(true) {
+        let Some(c) = grapheme.chars().next() else { continue };
+        if let Some(p) = prev {
+            let breakable = (is_cjk(p) || is_cjk(c)) && !NO_LINE_START.contains(c) && !NO_LINE_END.contains(p);
+            if breakable {
+                pieces.push(&word[start..i]);
+                start = i;
+            }
+        }
+        prev = Some(c);
+    }
+    pieces.push(&word[start..]);
+    pieces
+}
+
+/// Where a content line sits relative to fenced code blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fence<'a> {
+    Text,
+    /// Opening fence of the block numbered `index`, from 1
+    Open { index: usize, lang: &'a str },
+    Code,
+    Close,
+}
+
+/// Classify each line of `text` by code fence, numbering blocks the way
+/// [`code_blocks`](crate::app::code_blocks) finds them
+fn fence_lines(text: &str) -> Vec<Fence<'_>> {
+    let mut in_block = false;
+    let mut count = 0;
+    text.split('\n')
+        .map(|line| match line.trim_start().strip_prefix("```") {
+            Some(lang) if !in_block => {
+                in_block = true;
+                count += 1;
+                Fence::Open { index: count, lang: lang.trim() }
+            }
+            Some(_) => {
+                in_block = false;
+                Fence::Close
+            }
+            None if in_block => Fence::Code,
+            None => Fence::Text,
+        })
+        .collect()
+}
+
+/// Punctuation that shouldn't begin a line
+const NO_LINE_START: &str = ")]},.;:!?、。，．：；！？）」』】〉》〕”’ー々ゝゞぁぃぅぇぉっゃゅょァィゥェォッャュョ";
+
+/// Punctuation that shouldn't end a line
+const NO_LINE_END: &str = "([{（「『【〈《〔“‘";
+
+/// Whether `c` is from a script written without spaces between words
+///
+/// Hangul is left out: Korean puts spaces between words.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303F}'    // CJK symbols and punctuation
+        | '\u{3040}'..='\u{30FF}'  // Hiragana and Katakana
+        | '\u{31F0}'..='\u{31FF}'  // Katakana extensions
+        | '\u{3400}'..='\u{4DBF}'  // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}'  // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}'  // CJK compatibility ideographs
+        | '\u{FF00}'..='\u{FFEF}'  // Fullwidth forms
+        | '\u{20000}'..='\u{3FFFF}' // CJK extensions B and beyond
+    )
+}
+
+/// Cut `text` into chunks at most `max_width` columns wide
+///
+/// Cuts fall between grapheme clusters. A cluster wider than `max_width`
+/// still gets a chunk of its own.
+fn split_at_width(text: &str, max_width: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut width = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        let w = grapheme.width();
+        if width + w > max_width && i > start {
+            chunks.push(&text[start..i]);
+            start = i;
+            width = 0;
+        }
+        width += w;
+    }
+    if start < text.len() {
+        chunks.push(&text[start..]);
+    }
+    chunks
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::ToolStep;
+
+    #[test]
+    fn test_scroll_position() {
+        assert_eq!(scroll_position(0, 0), None);
+        assert_eq!(scroll_position(0, 200), Some("TOP".to_string()));
+        assert_eq!(scroll_position(90, 200), Some("45%".to_string()));
+        assert_eq!(scroll_position(200, 200), Some("BOT".to_string()));
+    }
 
     #[test]
     fn test_wrap_text_simple() {
-        let result = wrap_text("hello world", 20);
+        let result = wrap_text("hello world", 20, false);
         assert_eq!(result, vec!["hello world"]);
     }
 
     #[test]
     fn test_wrap_text_multiline() {
-        let result = wrap_text("hello world this is a test", 10);
+        let result = wrap_text("hello world this is a test", 10, false);
         assert_eq!(result, vec!["hello", "world this", "is a test"]);
     }
 
+    #[test]
+    fn test_thinking_precedes_content() {
+        let mut message = Message::assistant("42");
+        message.thinking = "6 times 7".to_string();
+        let messages = [message];
+        let (lines, _, starts) = build_chat_lines(&messages, 0..1, "llama3.2", 40, LineOptions::default(), None);
+        assert_eq!(starts, vec![0]);
+        let text: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(text[1], "  ┆ ▸ Thinking (1 line, t in select mode expands)");
+        assert_eq!(text[2], "");
+        assert_eq!(text[3], "  42");
+
+        let mut messages = messages;
+        messages[0].thinking_expanded = true;
+        let (lines, _, _) = build_chat_lines(&messages, 0..1, "llama3.2", 40, LineOptions::default(), None);
+        let text: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(text[1], "  ┆ ▾ Thinking");
+        assert_eq!(text[2], "  ┆ 6 times 7");
+        assert_eq!(text[3], "");
+        assert_eq!(text[4], "  42");
+    }
+
+    #[test]
+    fn test_inline_think_block() {
+        let messages = [Message::assistant("<think>\nLet me see.\nYes.\n</think>\n\nIt is 4.")];
+        let (lines, _, _) = build_chat_lines(&messages, 0..1, "llama3.2", 40, LineOptions::default(), None);
+        let text: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(text[1..], ["  ┆ ▸ Thinking (2 lines, t in select mode expands)", "", "  It is 4."]);
+
+        // Still open while streaming: everything so far is reasoning
+        let open = Message::assistant("<think>hmm");
+        assert_eq!(open.reasoning(), ("hmm", ""));
+        assert_eq!(Message::user("<think>x</think>").reasoning(), ("", "<think>x</think>"));
+    }
+
+    #[test]
+    fn test_tool_steps_collapse() {
+        let mut message = Message::assistant("Two files.");
+        message.tool_steps.push(ToolStep {
+            name: "list_files".to_string(),
+            arguments: serde_json::json!({ "path": "src" }),
+            result: Some("a.rs\nb.rs".to_string()),
+        });
+        let text = |expand| {
+            let (lines, _, _) = build_chat_lines(std::slice::from_ref(&message), 0..1, "llama3.2", 40, LineOptions { expand_tools: expand, ..Default::default() }, None);
+            lines.iter().map(|l| l.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(text(false)[1..], ["  ⚙ list_files(path: \"src\") ✓ 2 lines", "", "  Two files."]);
+        assert_eq!(text(true)[2..4], ["  │ a.rs", "  │ b.rs"]);
+    }
+
+    #[test]
+    fn test_header_names_other_models() {
+        let mut earlier = Message::assistant("Hi");
+        earlier.model = Some("mistral".to_string());
+        let mut current = Message::assistant("Hello");
+        current.model = Some("llama3.2".to_string());
+        let messages = [earlier, current];
+        let (lines, _, starts) = build_chat_lines(&messages, 0..2, "llama3.2", 40, LineOptions::default(), None);
+        assert!(lines[starts[0]].to_string().ends_with(" · mistral"));
+        assert!(!lines[starts[1]].to_string().contains('·'));
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let now = Utc::now();
+        let mut message = Message::user("Hi");
+        message.timestamp = now - chrono::Duration::minutes(5);
+        let header = |timestamps| {
+            let options = LineOptions { timestamps, now, ..Default::default() };
+            build_chat_lines(std::slice::from_ref(&message), 0..1, "llama3.2", 40, options, None).0[0].to_string()
+        };
+        assert_eq!(header(None), "You:");
+        assert_eq!(header(Some(TimestampStyle::Relative)), "You: 5m ago");
+        assert_eq!(header(Some(TimestampStyle::Clock)).len(), "You: 12:34".len());
+
+        assert_eq!(relative_time(now, now), "just now");
+        assert_eq!(relative_time(now - chrono::Duration::hours(26), now), "1d ago");
+    }
+
+    #[test]
+    fn test_settings_badge() {
+        let mut message = Message::assistant("Hi");
+        message.options = Some(GenerationOptions { temperature: Some(0.7), seed: Some(42), ..Default::default() });
+        let header = |show_settings| {
+            let options = LineOptions { show_settings, ..Default::default() };
+            build_chat_lines(std::slice::from_ref(&message), 0..1, "llama3.2", 40, options, None).0[0].to_string()
+        };
+        assert!(header(true).ends_with(" · temp 0.7 · seed 42"));
+        assert!(!header(false).contains("temp"));
+        assert_eq!(message.options.unwrap().describe(), "seed=42, temperature=0.7");
+    }
+
+    #[test]
+    fn test_highlight_matches() {
+        let messages = [Message::user("Rust is rusty"), Message::assistant("trust me")];
+        let (mut lines, _, starts) = build_chat_lines(&messages, 0..messages.len(), "llama3.2", 40, LineOptions::default(), None);
+        let matches = highlight_matches(&mut lines, &starts, "RUST", 1, &Theme::DEFAULT);
+        // Headers are skipped; the second match on line 1 is current
+        assert_eq!(matches, vec![1, 1, 4]);
+        assert_eq!(lines[1].to_string(), "  Rust is rusty");
+        let styled: Vec<(&str, Style)> = lines[1].spans.iter().map(|s| (s.content.as_ref(), s.style)).collect();
+        assert_eq!(styled[1], ("Rust", styles::find_match(&Theme::DEFAULT)));
+        assert_eq!(styled[3], ("rust", styles::find_current(&Theme::DEFAULT)));
+        assert!(highlight_matches(&mut lines, &starts, "  ", 0, &Theme::DEFAULT).is_empty());
+    }
+
     #[test]
     fn test_wrap_text_newlines() {
-        let result = wrap_text("line1\nline2", 20);
+        let result = wrap_text("line1\nline2", 20, false);
         assert_eq!(result, vec!["line1", "line2"]);
     }
+
+    #[test]
+    fn test_lazy_layout_matches_full_build() {
+        let messages: Vec<Message> = (0..20).map(|i| Message::user("word ".repeat(i * 3))).collect();
+        let options = LineOptions::default();
+        let (full, _, starts) = build_chat_lines(&messages, 0..20, "llama3.2", 30, options, None);
+        let heights = message_heights(&messages, &[], "llama3.2", 30, options, None);
+        for (idx, (_, height)) in heights.iter().enumerate() {
+            let end = starts.get(idx + 1).map_or(full.len(), |next| next - 1);
+            assert_eq!(*height, end - starts[idx]);
+        }
+
+        // A range starts at the separator above its first message
+        let (part, _, _) = build_chat_lines(&messages, 5..8, "llama3.2", 30, options, None);
+        let text = |lines: &[Line]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        assert_eq!(text(&part), text(&full[starts[5] - 1..starts[8] - 1]));
+
+        // Cached heights are kept until a message or the width changes
+        let mut changed = messages.clone();
+        changed[3].append(" and a few more words");
+        let again = message_heights(&changed, &heights, "llama3.2", 30, options, None);
+        assert_eq!(again[2], heights[2]);
+        assert_ne!(again[3], heights[3]);
+        assert_ne!(message_heights(&messages, &heights, "llama3.2", 40, options, None)[2].0, heights[2].0);
+    }
+
+    #[test]
+    fn test_streamed_content_matches_full_layout() {
+        let text = "Here:\n--- a/x\n+++ b/x\n@@ -1 +1 @@\n-old\n+new\n\n```rust\nfn main() {}\n```\ndone";
+        let mut message = Message::assistant("");
+        message.streaming = true;
+        let (_, _, style) = role_styles(&message, &Theme::DEFAULT);
+        let options = LineOptions::default();
+        let mut layout = StreamLayout::default();
+        for (end, _) in text.char_indices().skip(1).chain([(text.len(), ' ')]) {
+            message.content = text[..end].to_string();
+            let (next, tail) = streamed_content(&message, &layout, 24, options);
+            let lines: Vec<Line> = Streamed { idx: 0, settled: &next.chunks, tail: &tail }.lines().collect();
+            assert_eq!(lines, content_lines(&message.content, style, 24, options, 0..usize::MAX), "at {:?}", &text[..end]);
+            layout = next;
+        }
+        // All but the last complete line and the partial one are kept
+        assert_eq!(layout.source_lines, text.matches('\n').count() - 1);
+
+        // Edited content is laid out afresh
+        message.content = "Other\ntext\nhere".to_string();
+        let (next, tail) = streamed_content(&message, &layout, 24, options);
+        let lines: Vec<Line> = Streamed { idx: 0, settled: &next.chunks, tail: &tail }.lines().collect();
+        assert_eq!(lines, content_lines(&message.content, style, 24, options, 0..usize::MAX));
+    }
+
+    #[test]
+    fn test_wrapped_lines_borrow_text() {
+        let lines = wrap_text("the quick brown fox jumps", 10, false);
+        assert_eq!(lines, ["the quick", "brown fox", "jumps"]);
+        assert!(lines.iter().all(|line| matches!(line, Cow::Borrowed(_))));
+
+        // Collapsed runs of spaces need a copy; long words are cut in place
+        let lines = wrap_text("a  b\tc abcdefghijkl", 6, false);
+        assert_eq!(lines, ["a b c", "abcdef", "ghijkl"]);
+        assert!(matches!(lines[0], Cow::Owned(_)));
+        assert!(matches!(lines[1], Cow::Borrowed(_)) && matches!(lines[2], Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_code_blocks_framed() {
+        let message = Message::assistant("Run:\n```sh\nls\n\n    cd /\n```\nthen\n```\nx");
+        let messages = [message];
+        let (lines, _, _) = build_chat_lines(&messages, 0..1, "llama3.2", 16, LineOptions::default(), None);
+        let text: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            text[1..],
+            [
+                "  Run:",
+                "  ╭─ [1] sh ──────",
+                "  │ ls",
+                "  │ ",
+                "  │     cd /",
+                "  ╰───────────────",
+                "  then",
+                "  ╭─ [2] ─────────",
+                "  │ x",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_text_cjk() {
+        // Two columns a character: four fit in a line of 8
+        let text = "今天天气很好，我们去公园吧。";
+        assert_eq!(wrap_text(text, 8, true), ["今天天气", "很好，我", "们去公园", "吧。"]);
+        assert_eq!(wrap_text(text, 8, false), ["今天天气", "很好，我", "们去公园", "吧。"]);
+
+        // Latin words stay whole beside CJK, and punctuation doesn't start a line
+        assert_eq!(wrap_text("用 Rust 写程序。", 9, true), ["用 Rust", "写程序。"]);
+        assert_eq!(wrap_text("用Rust写的", 6, true), ["用Rust", "写的"]);
+        assert_eq!(wrap_text("東京「タワー」", 6, true), ["東京", "「タ", "ワー」"]);
+
+        // Without breaking, a CJK run is one word
+        assert_eq!(wrap_text("中文 中文中文中文", 9, false), ["中文", "中文中文", "中文"]);
+        assert_eq!(wrap_text("中文 中文中文中文", 9, true), ["中文 中文", "中文中文"]);
+    }
+
+    #[test]
+    fn test_wrap_text_graphemes() {
+        // A combining accent takes no column and never leaves its letter
+        let word = "e\u{301}".repeat(4);
+        assert_eq!(wrap_text(&word, 3, false), ["e\u{301}".repeat(3), "e\u{301}".to_string()]);
+        assert_eq!(wrap_text("re\u{301}sume\u{301} cafe\u{301}", 7, false), ["re\u{301}sume\u{301}", "cafe\u{301}"]);
+
+        // Decomposed か + dakuten (が) stays one piece when breaking CJK
+        assert_eq!(wrap_text("か\u{3099}か\u{3099}か\u{3099}", 4, true), ["か\u{3099}か\u{3099}", "か\u{3099}"]);
+
+        // Wide characters never straddle the edge, even in a narrow pane
+        assert_eq!(wrap_text("日本語", 3, false), ["日", "本", "語"]);
+        for line in wrap_text("한국어 텍스트와 emoji 🎉🎉🎉", 5, false) {
+            assert!(line.width() <= 5, "{:?} overflows", line);
+        }
+    }
 }
@@ -0,0 +1,342 @@
+//! Terminal graphics protocols
+//!
+//! Inline image previews via the kitty graphics protocol, iTerm2 inline
+//! images, or sixel. Support is detected from the environment (or forced via
+//! `ui.image_protocol`); without it the chat shows a text placeholder.
+//!
+//! Images are drawn after ratatui has flushed a frame, directly to the
+//! terminal at the cell areas the chat renderer reserved for them.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{cursor::MoveTo, queue};
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use ratatui::layout::Rect;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::ImageProtocolSetting;
+
+/// Fallback cell size in pixels when the terminal doesn't report one
+const DEFAULT_CELL_PX: (u32, u32) = (8, 16);
+
+/// Kitty transmits payloads in chunks of at most 4096 bytes
+const KITTY_CHUNK: usize = 4096;
+
+/// A supported graphics protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+}
+
+impl GraphicsProtocol {
+    /// Resolve the protocol to use from config and the environment
+    pub fn detect(setting: ImageProtocolSetting) -> Option<Self> {
+        match setting {
+            ImageProtocolSetting::Kitty => Some(Self::Kitty),
+            ImageProtocolSetting::Iterm2 => Some(Self::Iterm2),
+            ImageProtocolSetting::Sixel => Some(Self::Sixel),
+            ImageProtocolSetting::None => None,
+            ImageProtocolSetting::Auto => Self::from_env(|key| std::env::var(key).ok()),
+        }
+    }
+
+    /// Capability detection from terminal-identifying environment variables
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+
+        if term.contains("kitty") || var("KITTY_WINDOW_ID").is_some() || program == "ghostty" {
+            Some(Self::Kitty)
+        } else if program == "iTerm.app" || program == "WezTerm" || var("ITERM_SESSION_ID").is_some() {
+            Some(Self::Iterm2)
+        } else if term.contains("sixel") || term == "foot" || term.starts_with("mlterm") {
+            Some(Self::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where an image should be drawn on screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePlacement {
+    pub path: PathBuf,
+    pub area: Rect,
+}
+
+/// Draws image placements, caching encoded escape sequences
+#[derive(Debug)]
+pub struct ImageRenderer {
+    protocol: GraphicsProtocol,
+    cache: HashMap<(PathBuf, u16, u16), String>,
+    last: Vec<ImagePlacement>,
+    /// Images that couldn't be read or decoded, with their modification
+    /// time then; they are tried again once it changes
+    failed: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl ImageRenderer {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        Self {
+            protocol,
+            cache: HashMap::new(),
+            last: Vec::new(),
+            failed: HashMap::new(),
+        }
+    }
+
+    /// Images whose preview failed; the chat shows a note in their place
+    pub fn failed(&self) -> impl ExactSizeIterator<Item = &PathBuf> {
+        self.failed.keys()
+    }
+
+    /// Forget failures of images whose file has changed since, so they are
+    /// tried again; returns whether any were forgotten
+    pub fn retry_changed(&mut self) -> bool {
+        let before = self.failed.len();
+        self.failed.retain(|path, modified| modified_time(path) == *modified);
+        self.failed.len() != before
+    }
+
+    /// Whether the placements differ from what is currently on screen
+    pub fn changed(&self, placements: &[ImagePlacement]) -> bool {
+        self.last != placements
+    }
+
+    /// Whether stale images must be cleared by a full repaint
+    ///
+    /// Kitty images live on their own layer and are deleted explicitly;
+    /// iTerm2 and sixel images are painted into cells.
+    pub fn needs_repaint(&self, placements: &[ImagePlacement]) -> bool {
+        self.protocol != GraphicsProtocol::Kitty && !self.last.is_empty() && self.changed(placements)
+    }
+
+    /// Draw the placements if they changed since the last call
+    pub fn draw(&mut self, out: &mut impl Write, placements: Vec<ImagePlacement>) -> io::Result<()> {
+        if !self.changed(&placements) {
+            return Ok(());
+        }
+
+        if self.protocol == GraphicsProtocol::Kitty {
+            // Delete all visible placements
+            write!(out, "\x1b_Ga=d,q=2\x1b\\")?;
+        }
+
+        for placement in &placements {
+            if self.failed.contains_key(&placement.path) {
+                continue;
+            }
+            let key = (placement.path.clone(), placement.area.width, placement.area.height);
+            if !self.cache.contains_key(&key) {
+                match encode(self.protocol, &placement.path, placement.area) {
+                    Ok(seq) => {
+                        self.cache.insert(key.clone(), seq);
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to render image {:?}: {}", placement.path, e);
+                        self.failed.insert(placement.path.clone(), modified_time(&placement.path));
+                        continue;
+                    }
+                }
+            }
+            queue!(out, MoveTo(placement.area.x, placement.area.y))?;
+            out.write_all(self.cache[&key].as_bytes())?;
+        }
+
+        out.flush()?;
+        self.last = placements;
+        Ok(())
+    }
+}
+
+/// When the file at `path` was last modified, if it can be read
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Encode an image file as an escape sequence filling `area` (in cells)
+fn encode(protocol: GraphicsProtocol, path: &Path, area: Rect) -> io::Result<String> {
+    match protocol {
+        GraphicsProtocol::Iterm2 => {
+            // iTerm2 decodes the file itself
+            let bytes = std::fs::read(path)?;
+            Ok(format!(
+                "\x1b]1337;File=inline=1;size={};height={};preserveAspectRatio=1:{}\x07",
+                bytes.len(),
+                area.height,
+                STANDARD.encode(&bytes)
+            ))
+        }
+        GraphicsProtocol::Kitty => {
+            let img = load_thumbnail(path, area)?;
+            Ok(encode_kitty(&img, area.height))
+        }
+        GraphicsProtocol::Sixel => {
+            let img = load_thumbnail(path, area)?;
+            Ok(encode_sixel(&img))
+        }
+    }
+}
+
+/// Decode and downscale an image to fit the cell area
+fn load_thumbnail(path: &Path, area: Rect) -> io::Result<DynamicImage> {
+    let img = image::open(path).map_err(io::Error::other)?;
+    let (cell_w, cell_h) = cell_size();
+    let max_w = area.width as u32 * cell_w;
+    let max_h = area.height as u32 * cell_h;
+    Ok(img.resize(max_w.max(1), max_h.max(1), FilterType::Triangle))
+}
+
+/// Terminal cell size in pixels
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns).max(1) as u32,
+            (size.height / size.rows).max(1) as u32,
+        ),
+        _ => DEFAULT_CELL_PX,
+    }
+}
+
+/// Kitty graphics protocol: raw RGBA, chunked, scaled to `rows` cells
+fn encode_kitty(img: &DynamicImage, rows: u16) -> String {
+    let (w, h) = img.dimensions();
+    let payload = STANDARD.encode(img.to_rgba8().as_raw());
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            out.push_str(&format!("\x1b_Ga=T,f=32,s={w},v={h},r={rows},C=1,q=2,m={more};{chunk}\x1b\\"));
+        } else {
+            out.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+        }
+    }
+    out
+}
+
+/// Sixel encoding with a fixed 6x6x6 color cube palette
+fn encode_sixel(img: &DynamicImage) -> String {
+    let rgb = img.to_rgb8();
+    let (w, h) = rgb.dimensions();
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let index: Vec<u8> = rgb
+        .pixels()
+        .map(|p| (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as u8)
+        .collect();
+
+    let mut out = format!("\x1bPq\"1;1;{w};{h}");
+    for i in 0..216u32 {
+        let pct = |v: u32| v * 100 / 5;
+        out.push_str(&format!("#{};2;{};{};{}", i, pct(i / 36), pct(i / 6 % 6), pct(i % 6)));
+    }
+
+    for band in (0..h).step_by(6) {
+        let rows = (h - band).min(6);
+        let mut colors: Vec<u8> = (0..rows)
+            .flat_map(|dy| index[((band + dy) * w) as usize..((band + dy + 1) * w) as usize].iter().copied())
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for color in colors {
+            out.push_str(&format!("#{}", color));
+            let mut run_char = None;
+            let mut run_len = 0;
+            for x in 0..w {
+                let mut bits = 0u8;
+                for dy in 0..rows {
+                    if index[((band + dy) * w + x) as usize] == color {
+                        bits |= 1 << dy;
+                    }
+                }
+                let ch = (b'?' + bits) as char;
+                if Some(ch) == run_char {
+                    run_len += 1;
+                } else {
+                    push_sixel_run(&mut out, run_char, run_len);
+                    run_char = Some(ch);
+                    run_len = 1;
+                }
+            }
+            push_sixel_run(&mut out, run_char, run_len);
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_sixel_run(out: &mut String, ch: Option<char>, len: usize) {
+    match ch {
+        Some(c) if len > 3 => out.push_str(&format!("!{}{}", len, c)),
+        Some(c) => (0..len).for_each(|_| out.push(c)),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_from_env() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        assert_eq!(
+            GraphicsProtocol::from_env(env(&[("TERM", "xterm-kitty")])),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            GraphicsProtocol::from_env(env(&[("TERM_PROGRAM", "iTerm.app")])),
+            Some(GraphicsProtocol::Iterm2)
+        );
+        assert_eq!(GraphicsProtocol::from_env(env(&[("TERM", "foot")])), Some(GraphicsProtocol::Sixel));
+        assert_eq!(GraphicsProtocol::from_env(env(&[("TERM", "xterm-256color")])), None);
+    }
+
+    #[test]
+    fn test_sixel_frame() {
+        let img = DynamicImage::new_rgb8(4, 7);
+        let seq = encode_sixel(&img);
+        assert!(seq.starts_with("\x1bPq\"1;1;4;7"));
+        assert!(seq.ends_with("\x1b\\"));
+        // Two bands of six rows
+        assert_eq!(seq.matches('-').count(), 2);
+    }
+
+    #[test]
+    fn test_failed_preview_is_remembered() {
+        let mut renderer = ImageRenderer::new(GraphicsProtocol::Kitty);
+        let path = PathBuf::from("/nonexistent/cat.png");
+        let placement = ImagePlacement { path: path.clone(), area: Rect::new(2, 3, 20, 8) };
+        let mut out = Vec::new();
+        renderer.draw(&mut out, vec![placement.clone()]).unwrap();
+        assert!(renderer.failed().any(|p| *p == path));
+        // Only the deletion of earlier placements was written
+        assert_eq!(out, b"\x1b_Ga=d,q=2\x1b\\");
+        assert!(!renderer.retry_changed());
+
+        // A broken file is tried again once it changes
+        let dir = std::env::temp_dir().join(format!("ratatalk-graphics-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cat.png");
+        std::fs::write(&path, b"not a png").unwrap();
+        let placement = ImagePlacement { path: path.clone(), ..placement };
+        renderer.draw(&mut out, vec![placement]).unwrap();
+        assert_eq!(renderer.failed().len(), 2);
+        std::fs::remove_file(&path).unwrap();
+        assert!(renderer.retry_changed());
+        assert_eq!(renderer.failed().len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,73 +1,132 @@
 //! Input box rendering
 //!
-//! Renders the text input area with cursor.
+//! Renders the text input area with cursor. The input may span several
+//! lines; the box grows with them and then scrolls to keep the cursor in view.
 
 use ratatui::{
     layout::Rect,
+    style::Style,
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
     Frame,
 };
+use unicode_width::UnicodeWidthStr;
 
 use crate::app::{AppState, InputMode};
 
 use super::styles;
 
+/// Approximate tokens of the draft, and of the whole request when the
+/// context window is known
+///
+/// Turns yellow past 90% of the window and red once the request won't fit.
+fn token_estimate(state: &AppState) -> Option<Line<'static>> {
+    let draft = state.draft_tokens();
+    if draft == 0 {
+        return None;
+    }
+    let mut text = format!(" ~{} tokens ", format_tokens(draft));
+    let mut style = styles::dim();
+    if let Some(limit) = state.context_limit() {
+        let total = state.context_tokens();
+        text = format!(" ~{} tokens · {}/{} context ", format_tokens(draft), format_tokens(total), format_tokens(limit as usize));
+        if total > limit as usize {
+            style = Style::default().fg(state.theme.error);
+        } else if total * 10 > limit as usize * 9 {
+            style = Style::default().fg(state.theme.warning);
+        }
+    }
+    Some(Line::from(Span::styled(text, style)))
+}
+
+/// Token counts as `950` or `12.3k`
+fn format_tokens(tokens: usize) -> String {
+    if tokens < 1000 {
+        tokens.to_string()
+    } else {
+        format!("{:.1}k", tokens as f64 / 1000.0)
+    }
+}
+
 /// Render the input area
 pub fn render_input(frame: &mut Frame, state: &AppState, area: Rect) {
     let is_editing = state.input_mode == InputMode::Editing;
     
     let border_style = if is_editing {
-        styles::border_active()
+        styles::border_active(&state.theme)
     } else {
-        styles::border_normal()
+        styles::border_normal(&state.theme)
     };
 
     let title = if is_editing {
-        " Input (Enter to send, Esc to cancel) "
+        "Input (Enter to send, Alt+Enter for a new line, Esc to cancel)"
     } else if state.streaming {
-        " Input (waiting for response...) "
+        "Input (waiting for response...)"
     } else {
-        " Input (i or Enter to type) "
+        "Input (i or Enter to type)"
     };
 
-    let block = Block::default()
-        .title(title)
+    let mut block = Block::default()
+        .title(state.locale.title(title))
         .borders(Borders::ALL)
         .border_style(border_style);
+    if !state.pending_attachments.is_empty() {
+        let names: Vec<&str> = state.pending_attachments.iter().map(|a| a.name.as_str()).collect();
+        block = block.title_bottom(Line::from(format!(" 📎 {} ", names.join(", "))).style(styles::dim()));
+    }
+    if let Some(estimate) = token_estimate(state) {
+        block = block.title(estimate.right_aligned());
+    }
 
     let inner_area = block.inner(area);
 
-    // Build input line with cursor
-    let input_text = if is_editing {
-        // Show cursor
-        let (before, after) = state.input.split_at(
-            state.cursor_position.min(state.input.len())
-        );
-        
-        Line::from(vec![
-            Span::raw(before),
-            Span::styled("█", styles::highlight()), // Block cursor
-            Span::raw(after),
-        ])
+    let (cursor_line, _) = state.cursor_line_col();
+
+    // Build input lines, with the cursor on its line
+    let input_lines: Vec<Line> = if is_editing {
+        let cursor = state.cursor_position.min(state.input.len());
+        let line_start = state.input[..cursor].rfind('\n').map_or(0, |i| i + 1);
+        state
+            .input
+            .split('\n')
+            .enumerate()
+            .map(|(idx, text)| {
+                if idx != cursor_line {
+                    return Line::from(text.to_string());
+                }
+                let (before, after) = text.split_at(cursor - line_start);
+                Line::from(vec![
+                    Span::raw(before.to_string()),
+                    Span::styled("█", styles::highlight(&state.theme)), // Block cursor
+                    Span::raw(after.to_string()),
+                ])
+            })
+            .collect()
     } else if state.input.is_empty() {
-        Line::from(Span::styled(
+        vec![Line::from(Span::styled(
             "Press 'i' or Enter to start typing...",
             styles::dim(),
-        ))
+        ))]
     } else {
-        Line::from(state.input.clone())
+        state.input.split('\n').map(|text| Line::from(text.to_string())).collect()
     };
 
-    let paragraph = Paragraph::new(input_text).block(block);
+    // Scroll just far enough to show the cursor line
+    let visible = inner_area.height.max(1) as usize;
+    let scroll = if is_editing { cursor_line.saturating_sub(visible - 1) } else { 0 };
+
+    let paragraph = Paragraph::new(input_lines)
+        .block(block)
+        .scroll((scroll as u16, 0));
     
     frame.render_widget(paragraph, area);
 
     // Set cursor position for terminal cursor if editing
     if is_editing {
         // Calculate cursor position within the visible area
-        let cursor_x = inner_area.x + state.cursor_position.min(inner_area.width as usize) as u16;
-        let cursor_y = inner_area.y;
+        let column = state.input[..state.cursor_position].rsplit('\n').next().unwrap_or_default().width();
+        let cursor_x = inner_area.x + column.min(inner_area.width as usize) as u16;
+        let cursor_y = inner_area.y + (cursor_line - scroll) as u16;
         frame.set_cursor_position((cursor_x, cursor_y));
     }
 }
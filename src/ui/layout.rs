@@ -3,13 +3,18 @@
 //! Defines the main screen layout structure.
 
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Constraint, Direction, Flex, Layout, Rect},
+    text::{Line, Span},
     Frame,
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use crate::app::AppState;
+use crate::app::{AppState, ChatViewport};
 
-use super::{render_chat, render_input, render_sidebar};
+use super::graphics::ImagePlacement;
+use super::progress::{render_progress, GAUGE_WIDTH};
+use super::{render_chat, render_input, render_sidebar, render_split_pane, render_tabs};
 
 /// Layout areas for the application
 #[derive(Debug, Clone)]
@@ -17,30 +22,45 @@ use super::{render_chat, render_input, render_sidebar};
 pub struct AppLayout {
     /// Main content area (chat + input)
     pub main: Rect,
-    /// Chat history area
+    /// Chat history area (the focused pane in a split view)
     pub chat: Rect,
+    /// The split view's other pane (empty when not split)
+    pub split: Rect,
+    /// Warning and error banners, above the input (may be empty)
+    pub banner: Rect,
     /// Input area
     pub input: Rect,
-    /// Sidebar area
+    /// Sidebar area (empty with the tab bar)
     pub sidebar: Rect,
+    /// Session tab bar (empty without it)
+    pub tabs: Rect,
     /// Status bar area
     pub status: Rect,
 }
 
 impl AppLayout {
-    /// Calculate layout from terminal size
-    pub fn new(area: Rect, sidebar_width: u16) -> Self {
+    /// Calculate layout from terminal size and the UI state: the banners,
+    /// the input's height, the tab bar, the split view and zen mode
+    pub fn new(area: Rect, state: &AppState) -> Self {
+        let (banner_rows, input_rows) = (banner_rows(state), input_rows(state));
+        let show_tabs = state.config.ui.tabs && !state.zen;
         // First split: main content vs status bar
         let vertical = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Min(5),     // Main content
-                Constraint::Length(1),  // Status bar
+                Constraint::Min(5),                              // Main content
+                Constraint::Length(u16::from(!state.zen)),       // Status bar
             ])
             .split(area);
 
-        let main_area = vertical[0];
         let status = vertical[1];
+        let (tabs, main_area) = if show_tabs {
+            let [tabs, main] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(vertical[0]);
+            (tabs, main)
+        } else {
+            (Rect::default(), vertical[0])
+        };
+        let sidebar_width = if show_tabs || state.zen { 0 } else { state.config.ui.sidebar_width };
 
         // Second split: main content vs sidebar
         let horizontal = Layout::default()
@@ -51,70 +71,197 @@ impl AppLayout {
             ])
             .split(main_area);
 
-        let content_area = horizontal[0];
+        let content_area = match state.config.ui.zen_width {
+            0 => horizontal[0],
+            _ if !state.zen => horizontal[0],
+            // Each pane of a split view gets the width
+            width => {
+                let panes = if state.split.is_some() { 2 } else { 1 };
+                let [content] = Layout::horizontal([Constraint::Max(width.saturating_mul(panes))])
+                    .flex(Flex::Center)
+                    .areas(horizontal[0]);
+                content
+            }
+        };
         let sidebar = horizontal[1];
 
-        // Third split: chat area vs input area
+        // Third split: chat area, banners, input area
         let content_vertical = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Min(3),     // Chat
-                Constraint::Length(3), // Input (single line + borders)
+                Constraint::Min(3),              // Chat
+                Constraint::Length(banner_rows), // Banners
+                Constraint::Length(input_rows + 2), // Input lines + borders
             ])
             .split(content_area);
 
-        let chat = content_vertical[0];
-        let input = content_vertical[1];
+        let (chat, split) = match &state.split {
+            Some(split) => {
+                let [left, right] =
+                    Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(content_vertical[0]);
+                if split.focus_right { (right, left) } else { (left, right) }
+            }
+            None => (content_vertical[0], Rect::default()),
+        };
+        let banner = content_vertical[1];
+        let input = content_vertical[2];
 
         Self {
             main: main_area,
             chat,
+            split,
+            banner,
             input,
             sidebar,
+            tabs,
             status,
         }
     }
 }
 
 /// Render the main layout
-pub fn render_layout(frame: &mut Frame, state: &AppState) {
-    let layout = AppLayout::new(frame.area(), state.config.ui.sidebar_width);
+///
+/// Returns image placements to draw once the frame has been flushed, the
+/// chat line layout, and that of the split view's other pane.
+pub fn render_layout(frame: &mut Frame, state: &AppState) -> (Vec<ImagePlacement>, ChatViewport, ChatViewport) {
+    // The transcript view is the chat alone, above the status bar
+    if state.transcript {
+        let [chat, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let (placements, viewport) = render_chat(frame, state, chat);
+        render_status_bar(frame, state, status);
+        return (placements, viewport, ChatViewport::default());
+    }
+
+    let layout = AppLayout::new(frame.area(), state);
 
     // Render each section
-    render_chat(frame, state, layout.chat);
+    let (placements, viewport) = render_chat(frame, state, layout.chat);
+    let split = render_split_pane(frame, state, layout.split);
+    render_banners(frame, state, layout.banner);
     render_input(frame, state, layout.input);
-    render_sidebar(frame, state, layout.sidebar);
-    render_status_bar(frame, state, layout.status);
+    // Zen mode leaves only the chat, its banners and the input
+    if !state.zen {
+        if state.config.ui.tabs {
+            render_tabs(frame, state, layout.tabs);
+        } else {
+            render_sidebar(frame, state, layout.sidebar);
+        }
+        render_status_bar(frame, state, layout.status);
+    }
+
+    (placements, viewport, split)
+}
+
+/// Most banner lines shown above the input
+const MAX_BANNER_ROWS: usize = 3;
+
+/// Rows needed for the current banners
+pub fn banner_rows(state: &AppState) -> u16 {
+    state.banners().count().min(MAX_BANNER_ROWS) as u16
+}
+
+/// Text rows of the input box: one per line, up to `[ui] input_max_lines`
+pub fn input_rows(state: &AppState) -> u16 {
+    let lines = state.input.split('\n').count();
+    lines.clamp(1, state.config.ui.input_max_lines.max(1) as usize) as u16
+}
+
+/// Render warning, error and notice banners, newest on top
+///
+/// When there are more than fit, the last row counts the hidden ones.
+fn render_banners(frame: &mut Frame, state: &AppState, area: Rect) {
+    use super::styles;
+    use crate::app::Severity;
+
+    let banners: Vec<_> = state.banners().collect();
+    let rows = area.height as usize;
+    for (row, notice) in banners.iter().take(rows).enumerate() {
+        let hidden = banners.len() - rows;
+        let (color, icon) = match notice.severity {
+            Severity::Error => (state.theme.error, "✖"),
+            Severity::Notice => (state.theme.info, "↑"),
+            _ => (state.theme.warning, "⚠"),
+        };
+        let mut spans = vec![
+            Span::styled(format!(" {} ", icon), ratatui::style::Style::default().fg(color)),
+            Span::styled(state.locale.tr(&notice.text).to_string(), ratatui::style::Style::default().fg(color)),
+        ];
+        if row == 0 {
+            spans.push(Span::styled(format!("  {}", state.locale.tr("Esc to dismiss")), styles::dim()));
+        }
+        if row + 1 == rows && hidden > 0 {
+            spans.push(Span::styled(format!("  (+{} more)", hidden), styles::dim()));
+        }
+        let line_area = Rect { y: area.y + row as u16, height: 1, ..area };
+        frame.render_widget(Line::from(ellipsize(spans, area.width as usize)), line_area);
+    }
+}
+
+/// Health check round trips at or above this are shown as slow
+const SLOW_LATENCY_MS: u128 = 300;
+
+/// Context usage at which the gauge turns yellow, then red
+const CONTEXT_WARNING: f64 = 0.7;
+const CONTEXT_CRITICAL: f64 = 0.9;
+
+/// Cells in the context usage gauge
+const CONTEXT_CELLS: usize = 5;
+
+/// `ctx ▰▰▱▱▱ 45%`: how full the context window was after the last response
+fn context_gauge(usage: f64) -> String {
+    let filled = ((usage * CONTEXT_CELLS as f64).round() as usize).min(CONTEXT_CELLS);
+    format!("ctx {}{} {:.0}%", "▰".repeat(filled), "▱".repeat(CONTEXT_CELLS - filled), usage * 100.0)
 }
 
 /// Render the status bar
+///
+/// Three segments share the line: session details on the left, the status
+/// message in the middle, and the mode on the right. The mode always
+/// fits; the other two are ellipsized independently.
 fn render_status_bar(frame: &mut Frame, state: &AppState, area: Rect) {
-    use ratatui::{
-        text::{Line, Span},
-        widgets::Paragraph,
-    };
-    use super::{colors, styles};
+    use super::styles;
 
     let mut spans = Vec::new();
 
     // Connection status
     let status_icon = if state.server_connected { "●" } else { "○" };
     let status_color = if state.server_connected {
-        colors::SUCCESS
+        state.theme.success
     } else {
-        colors::ERROR
+        state.theme.error
     };
     spans.push(Span::styled(
         format!(" {} ", status_icon),
         ratatui::style::Style::default().fg(status_color),
     ));
+    if let Some(latency) = state.server_latency.filter(|_| state.server_connected) {
+        let ms = latency.as_millis();
+        let style = if ms >= SLOW_LATENCY_MS {
+            ratatui::style::Style::default().fg(state.theme.warning)
+        } else {
+            styles::dim()
+        };
+        spans.push(Span::styled(format!("{}ms ", ms), style));
+    }
 
     // Current model
     spans.push(Span::styled(
         format!("[{}]", state.current_model()),
-        styles::highlight(),
+        styles::highlight(&state.theme),
     ));
     spans.push(Span::raw(" "));
+    if state.dry_run {
+        spans.push(Span::styled(
+            "DRY RUN ",
+            ratatui::style::Style::default().fg(state.theme.warning),
+        ));
+    }
+    if let Some((register, _)) = &state.recording {
+        spans.push(Span::styled(
+            format!("● REC @{} ", register),
+            ratatui::style::Style::default().fg(state.theme.error),
+        ));
+    }
 
     // Session info
     if let Some(session) = state.active_session() {
@@ -131,53 +278,200 @@ fn render_status_bar(frame: &mut Frame, state: &AppState, area: Rect) {
     // Streaming indicator
     if state.streaming {
         spans.push(Span::raw(" "));
-        spans.push(Span::styled("⣾ Generating...", styles::streaming()));
+        let generating = format!("{} {}", state.spinner(), state.locale.tr("Generating..."));
+        spans.push(Span::styled(generating, styles::streaming(&state.theme)));
     }
 
     // Stats from last response
     if let Some(stats) = &state.last_response_stats {
         if !state.streaming {
             spans.push(Span::raw(" "));
-            spans.push(Span::styled(
-                format!("[{:.1} tok/s]", stats.tokens_per_second),
-                styles::dim(),
-            ));
+            let speed = match stats.first_token_ms {
+                Some(ms) => format!("[TTFT {} · {:.1} tok/s]", super::format_latency(ms), stats.tokens_per_second),
+                None => format!("[{:.1} tok/s]", stats.tokens_per_second),
+            };
+            spans.push(Span::styled(speed, styles::dim()));
         }
     }
 
-    // Status or error message (right-aligned conceptually, but we'll just append)
-    if let Some(error) = &state.error_message {
+    // Context window usage
+    if let Some(usage) = state.context_usage() {
+        let style = if usage >= CONTEXT_CRITICAL {
+            ratatui::style::Style::default().fg(state.theme.error)
+        } else if usage >= CONTEXT_WARNING {
+            ratatui::style::Style::default().fg(state.theme.warning)
+        } else {
+            styles::dim()
+        };
         spans.push(Span::raw(" "));
-        spans.push(Span::styled(format!("⚠ {}", error), styles::error()));
-    } else if let Some(status) = &state.status_message {
+        spans.push(Span::styled(context_gauge(usage), style));
+    }
+
+    // Find query and match counter
+    let finding = state.input_mode == crate::app::InputMode::Find;
+    if finding || !state.find_query.is_empty() {
+        let cursor = if finding { "█" } else { "" };
         spans.push(Span::raw(" "));
-        spans.push(Span::styled(status.clone(), styles::dim()));
+        spans.push(Span::styled(format!("Find: {}{}", state.find_query, cursor), styles::highlight(&state.theme)));
+        let count = state.chat_viewport.find_matches.len();
+        let counter = match count {
+            _ if state.find_query.is_empty() => String::new(),
+            0 => " [no matches]".to_string(),
+            _ => format!(" [{}/{}]", state.find_idx.min(count - 1) + 1, count),
+        };
+        spans.push(Span::styled(counter, styles::dim()));
     }
 
+    // Status message (warnings and errors have their own banner line)
+    let message = match state.status() {
+        Some(status) => vec![Span::raw(" "), Span::styled(state.locale.tr(status).to_string(), styles::dim())],
+        None => Vec::new(),
+    };
+
     // Mode indicator (far right)
     let mode_str = match state.input_mode {
+        crate::app::InputMode::Normal if state.transcript => "VIEW",
         crate::app::InputMode::Normal => "NORMAL",
         crate::app::InputMode::Editing => "INSERT",
         crate::app::InputMode::ModelSelect => "MODEL",
         crate::app::InputMode::SessionSelect => "SESSION",
         crate::app::InputMode::Help => "HELP",
         crate::app::InputMode::DeleteConfirm => "DELETE?",
+        crate::app::InputMode::PatchConfirm => "PATCH?",
+        crate::app::InputMode::Downloads => "PULL",
+        crate::app::InputMode::PullConfirm => "PULL?",
+        crate::app::InputMode::CodePicker => "COPY",
+        crate::app::InputMode::Yanks => "YANKS",
+        crate::app::InputMode::Snippets => "SNIPPETS",
+        crate::app::InputMode::Prompts => "PROMPTS",
+        crate::app::InputMode::RedactReview => "ANONYMIZE",
+        crate::app::InputMode::Cache => "CACHE",
+        crate::app::InputMode::DryRun => "DRY RUN",
+        crate::app::InputMode::ContextInspect => "CONTEXT",
+        crate::app::InputMode::MessageSelect if state.select_anchor.is_some() => "VISUAL",
+        crate::app::InputMode::MessageSelect => "SELECT",
+        crate::app::InputMode::MessageInspect => "INSPECT",
+        crate::app::InputMode::Rename => "RENAME",
+        crate::app::InputMode::FilePicker => "PICK",
+        crate::app::InputMode::Search => "SEARCH",
+        crate::app::InputMode::Find => "FIND",
+        crate::app::InputMode::Palette => "PALETTE",
     };
     
-    // Calculate padding to right-align mode
-    let content_width: usize = spans.iter().map(|s| s.content.len()).sum();
-    let padding = area.width.saturating_sub(content_width as u16 + mode_str.len() as u16 + 2) as usize;
-    
-    if padding > 0 {
-        spans.push(Span::raw(" ".repeat(padding)));
+    let mode = Span::styled(state.locale.title(mode_str), styles::status_bar(&state.theme));
+    let mode_width = (mode.width() as u16).min(area.width);
+    // A gauge for long operations sits left of the mode
+    let gauge_width = if state.progress.is_empty() { 0 } else { GAUGE_WIDTH.min(area.width / 3) };
+    let (left_width, message_width) = split_status_width(
+        Line::from(spans.clone()).width(),
+        Line::from(message.clone()).width(),
+        area.width.saturating_sub(mode_width).saturating_sub(gauge_width),
+    );
+    let [left_area, message_area, gauge_area, mode_area] = Layout::horizontal([
+        Constraint::Length(left_width),
+        Constraint::Length(message_width),
+        Constraint::Length(gauge_width),
+        Constraint::Length(mode_width),
+    ])
+    .areas(area);
+
+    frame.render_widget(Line::from(ellipsize(spans, left_width as usize)), left_area);
+    frame.render_widget(Line::from(ellipsize(message, message_width as usize)), message_area);
+    render_progress(frame, state, gauge_area);
+    frame.render_widget(Line::from(mode), mode_area);
+}
+
+/// Split the status bar width left of the mode between details and message
+///
+/// The message gets at least half when both overflow, so a long session name
+/// can't hide it; spare room goes to the message, which is left-aligned
+/// against the details.
+fn split_status_width(left: usize, message: usize, available: u16) -> (u16, u16) {
+    let available_len = available as usize;
+    let left = if message == 0 {
+        left.min(available_len)
+    } else {
+        left.min(available_len.saturating_sub(message).max(available_len / 2))
+    };
+    (left as u16, available - left as u16)
+}
+
+/// Cut spans to `width` columns, ending in `…` when anything was dropped
+fn ellipsize(spans: Vec<Span<'_>>, width: usize) -> Vec<Span<'_>> {
+    if Line::from(spans.clone()).width() <= width {
+        return spans;
     }
-    spans.push(Span::styled(
-        format!(" {} ", mode_str),
-        styles::status_bar(),
-    ));
+    let mut out = Vec::new();
+    let mut used = 0;
+    let budget = width.saturating_sub(1);
+    for span in spans {
+        let mut text = String::new();
+        for grapheme in span.content.graphemes(true) {
+            let w = grapheme.width();
+            if used + w > budget {
+                break;
+            }
+            used += w;
+            text.push_str(grapheme);
+        }
+        let full = text.len() == span.content.len();
+        out.push(Span::styled(text, span.style));
+        if !full {
+            break;
+        }
+    }
+    if width > 0 {
+        let style = out.last().map(|span| span.style).unwrap_or_default();
+        out.push(Span::styled("…", style));
+    }
+    out
+}
 
-    let line = Line::from(spans);
-    let paragraph = Paragraph::new(line);
-    
-    frame.render_widget(paragraph, area);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_status_width() {
+        // Everything fits: the message takes the rest of the line
+        assert_eq!(split_status_width(20, 10, 80), (20, 60));
+        // Long details give way to the message, down to half
+        assert_eq!(split_status_width(70, 20, 80), (60, 20));
+        assert_eq!(split_status_width(70, 60, 80), (40, 40));
+        // No message: details may use everything
+        assert_eq!(split_status_width(70, 0, 60), (60, 0));
+    }
+
+    #[test]
+    fn test_context_gauge() {
+        assert_eq!(context_gauge(0.45), "ctx ▰▰▱▱▱ 45%");
+        assert_eq!(context_gauge(0.0), "ctx ▱▱▱▱▱ 0%");
+        assert_eq!(context_gauge(1.3), "ctx ▰▰▰▰▰ 130%");
+    }
+
+    #[test]
+    fn test_ellipsize() {
+        let spans = vec![Span::raw("● "), Span::raw("[llama3.2]"), Span::raw(" Session: notes")];
+        let text = |spans: Vec<Span>| Line::from(spans).to_string();
+        assert_eq!(text(ellipsize(spans.clone(), 40)), "● [llama3.2] Session: notes");
+        assert_eq!(text(ellipsize(spans.clone(), 8)), "● [llam…");
+        assert_eq!(Line::from(ellipsize(spans.clone(), 8)).width(), 8);
+        assert_eq!(text(ellipsize(spans, 0)), "");
+    }
+
+    #[test]
+    fn test_zen_layout() {
+        let mut state = AppState::new(crate::config::Config::default());
+        let area = Rect::new(0, 0, 200, 50);
+        let normal = AppLayout::new(area, &state);
+        assert_eq!((normal.status.height, normal.sidebar.width), (1, 30));
+
+        // Nothing but the chat and input, centered at the zen width
+        state.zen = true;
+        let zen = AppLayout::new(area, &state);
+        assert_eq!((zen.status.height, zen.sidebar.width), (0, 0));
+        assert_eq!((zen.chat.x, zen.chat.width), (50, 100));
+        assert_eq!(zen.input.width, 100);
+        assert_eq!(zen.chat.height + zen.input.height, 50);
+    }
 }
@@ -3,100 +3,107 @@
 //! All ratatui rendering logic for ratatalk.
 
 mod chat;
+mod graphics;
 mod input;
 mod layout;
 mod popup;
+mod progress;
 mod sidebar;
+mod tabs;
 
-pub use chat::render_chat;
+pub use chat::{render_chat, render_split_pane};
+pub use graphics::{GraphicsProtocol, ImagePlacement, ImageRenderer};
 pub use input::render_input;
 pub use layout::{render_layout, AppLayout};
-pub use popup::{render_help_popup, render_model_popup, render_delete_confirm_popup};
+pub use popup::{
+    render_code_picker_popup, render_delete_confirm_popup, render_downloads_popup, render_help_popup,
+    render_message_inspect_popup, render_model_popup, render_patch_confirm_popup, render_pull_confirm_popup,
+    render_file_picker_popup, render_cache_popup, render_context_popup, render_dry_run_popup, render_redact_review_popup, render_rename_popup, render_search_popup, render_palette_popup, render_snippets_popup, render_prompts_popup, render_yanks_popup,
+};
 pub use sidebar::render_sidebar;
+pub use tabs::{render_tabs, tab_at};
 
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::style::{Modifier, Style};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-/// Color scheme for the application
-pub mod colors {
-    use super::*;
-
-    #[allow(dead_code)]
-    pub const BG: Color = Color::Reset;
-    #[allow(dead_code)]
-    pub const FG: Color = Color::Reset;
-    
-    pub const BORDER: Color = Color::DarkGray;
-    pub const BORDER_FOCUSED: Color = Color::Cyan;
-    pub const BORDER_ACTIVE: Color = Color::Green;
-    
-    pub const USER_MSG: Color = Color::Cyan;
-    pub const ASSISTANT_MSG: Color = Color::Green;
-    pub const SYSTEM_MSG: Color = Color::Yellow;
-    
-    pub const ERROR: Color = Color::Red;
-    pub const WARNING: Color = Color::Yellow;
-    pub const SUCCESS: Color = Color::Green;
-    #[allow(dead_code)]
-    pub const INFO: Color = Color::Blue;
-    
-    pub const SELECTED: Color = Color::Yellow;
-    pub const HIGHLIGHT: Color = Color::Cyan;
-    
-    pub const STATUS_BG: Color = Color::DarkGray;
-    pub const STATUS_FG: Color = Color::White;
-}
+use crate::theme::Theme;
 
 /// Common styles
 pub mod styles {
     use super::*;
 
-    pub fn border_normal() -> Style {
-        Style::default().fg(colors::BORDER)
+    pub fn border_normal(theme: &Theme) -> Style {
+        Style::default().fg(theme.border)
     }
 
-    pub fn border_focused() -> Style {
-        Style::default().fg(colors::BORDER_FOCUSED)
+    pub fn border_focused(theme: &Theme) -> Style {
+        Style::default().fg(theme.border_focused)
     }
 
-    pub fn border_active() -> Style {
-        Style::default().fg(colors::BORDER_ACTIVE)
+    pub fn border_active(theme: &Theme) -> Style {
+        Style::default().fg(theme.border_active)
     }
 
     #[allow(dead_code)]
-    pub fn user_message() -> Style {
-        Style::default().fg(colors::USER_MSG)
+    pub fn user_message(theme: &Theme) -> Style {
+        Style::default().fg(theme.user)
     }
 
     #[allow(dead_code)]
-    pub fn assistant_message() -> Style {
-        Style::default().fg(colors::ASSISTANT_MSG)
+    pub fn assistant_message(theme: &Theme) -> Style {
+        Style::default().fg(theme.assistant)
     }
 
     #[allow(dead_code)]
-    pub fn system_message() -> Style {
-        Style::default().fg(colors::SYSTEM_MSG)
+    pub fn system_message(theme: &Theme) -> Style {
+        Style::default().fg(theme.system)
     }
 
-    pub fn error() -> Style {
-        Style::default().fg(colors::ERROR)
+    pub fn error(theme: &Theme) -> Style {
+        Style::default().fg(theme.error)
     }
 
-    pub fn selected() -> Style {
+    pub fn selected(theme: &Theme) -> Style {
         Style::default()
-            .fg(colors::SELECTED)
+            .fg(theme.selected)
             .add_modifier(Modifier::BOLD)
     }
 
-    pub fn highlight() -> Style {
+    pub fn highlight(theme: &Theme) -> Style {
         Style::default()
-            .fg(colors::HIGHLIGHT)
+            .fg(theme.highlight)
             .add_modifier(Modifier::BOLD)
     }
 
-    pub fn status_bar() -> Style {
+    pub fn status_bar(theme: &Theme) -> Style {
         Style::default()
-            .bg(colors::STATUS_BG)
-            .fg(colors::STATUS_FG)
+            .bg(theme.status_bg)
+            .fg(theme.status_fg)
+    }
+
+    pub fn find_match(theme: &Theme) -> Style {
+        Style::default().bg(theme.warning).fg(theme.match_fg)
+    }
+
+    pub fn find_current(theme: &Theme) -> Style {
+        Style::default()
+            .bg(theme.highlight)
+            .fg(theme.match_fg)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    /// Badge for sessions whose model runs remotely
+    pub fn remote(theme: &Theme) -> Style {
+        Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)
+    }
+
+    pub fn popup(theme: &Theme) -> Style {
+        Style::default().bg(theme.popup_bg)
+    }
+
+    pub fn popup_border(theme: &Theme) -> Style {
+        Style::default().fg(theme.popup_border)
     }
 
     pub fn dim() -> Style {
@@ -108,9 +115,66 @@ pub mod styles {
         Style::default().add_modifier(Modifier::BOLD)
     }
 
-    pub fn streaming() -> Style {
+    pub fn streaming(theme: &Theme) -> Style {
         Style::default()
-            .fg(colors::ASSISTANT_MSG)
+            .fg(theme.assistant)
             .add_modifier(Modifier::DIM)
     }
 }
+
+/// Cut `text` to at most `max_width` columns, ending in `…` when shortened
+///
+/// Cuts fall between grapheme clusters, so a combining mark stays with its
+/// base character and a wide character is never split.
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let budget = max_width.saturating_sub(1);
+    let mut out = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        let width = grapheme.width();
+        if used + width > budget {
+            break;
+        }
+        used += width;
+        out.push_str(grapheme);
+    }
+    if max_width > 0 {
+        out.push('…');
+    }
+    out
+}
+
+/// Latency as `840ms` or `2.4s`
+pub fn format_latency(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_latency() {
+        assert_eq!(format_latency(840), "840ms");
+        assert_eq!(format_latency(2449), "2.4s");
+    }
+
+    #[test]
+    fn test_truncate_to_width() {
+        assert_eq!(truncate_to_width("Notes", 10), "Notes");
+        assert_eq!(truncate_to_width("Refactoring", 6), "Refac…");
+        // Two columns per ideograph: the third one no longer fits
+        assert_eq!(truncate_to_width("日本語の会話", 6), "日本…");
+        // "e" + combining acute is one column and is kept whole
+        assert_eq!(truncate_to_width("cafe\u{301} au lait", 5), "cafe\u{301}…");
+        assert_eq!(truncate_to_width("cafe\u{301}", 4), "cafe\u{301}");
+        assert_eq!(truncate_to_width("anything", 0), "");
+    }
+}
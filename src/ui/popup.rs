@@ -10,10 +10,13 @@ use ratatui::{
     Frame,
 };
 
-use crate::app::{AppState, InputMode};
+use crate::app::{AppState, Download, DownloadStatus, InputMode, Rating, POPULAR_MODELS};
 use crate::events::get_help_text;
+use crate::ollama::{GenerationOptions, Role};
+use crate::picker::{PickPurpose, THIS_DIR};
+use crate::theme::Theme;
 
-use super::{colors, styles};
+use super::{styles, truncate_to_width};
 
 /// Render the model selection popup
 pub fn render_model_popup(frame: &mut Frame, state: &AppState) {
@@ -26,14 +29,29 @@ pub fn render_model_popup(frame: &mut Frame, state: &AppState) {
     // Clear the background
     frame.render_widget(Clear, area);
 
+    // Only the page holding the selection is built, so huge registries stay cheap
+    let rows = area.height.saturating_sub(2).max(1) as usize;
+    let (start, end) = page_bounds(state.selected_model_idx, state.chat.models.len(), rows);
+    let pages = state.chat.models.len().div_ceil(rows);
+    let footer = if pages > 1 {
+        format!(
+            " l: load  u: unload  c: compare  A-Z: jump  PgUp/PgDn  [page {}/{}, {} models] ",
+            start / rows + 1,
+            pages,
+            state.chat.models.len()
+        )
+    } else {
+        " l: load into memory  u: unload  c: compare with current ".to_string()
+    };
+
     let block = Block::default()
-        .title(" Select Model (↑/↓ to navigate, Enter to select, Esc to cancel) ")
+        .title(state.locale.title("Select Model (↑/↓ to navigate, Enter to select, Esc to cancel)"))
+        .title_bottom(footer)
         .borders(Borders::ALL)
-        .border_style(styles::border_focused());
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
 
-    let _inner_area = block.inner(area);
-
-    if state.models.is_empty() {
+    if state.chat.models.is_empty() {
         let msg = if state.loading {
             "Loading models..."
         } else {
@@ -47,11 +65,11 @@ pub fn render_model_popup(frame: &mut Frame, state: &AppState) {
     }
 
     // Build list items
-    let items: Vec<ListItem> = state
-        .models
+    let items: Vec<ListItem> = state.chat.models[start..end]
         .iter()
         .enumerate()
-        .map(|(idx, model)| {
+        .map(|(offset, model)| {
+            let idx = start + offset;
             let is_selected = idx == state.selected_model_idx;
             let is_current = state.current_model() == model.name;
 
@@ -61,13 +79,18 @@ pub fn render_model_popup(frame: &mut Frame, state: &AppState) {
                 " "
             };
 
-            let current_marker = if is_current { " (current)" } else { "" };
+            let is_compared = state.compare.as_ref().is_some_and(|c| c.model == model.name);
+            let current_marker = match (is_current, is_compared) {
+                (true, _) => " (current)",
+                (false, true) => " (compare)",
+                (false, false) => "",
+            };
 
             // Format size
             let size_str = format_size(model.size);
 
             let style = if is_selected {
-                styles::selected()
+                styles::selected(&state.theme)
             } else {
                 Style::default()
             };
@@ -100,11 +123,12 @@ pub fn render_help_popup(frame: &mut Frame, state: &AppState) {
     frame.render_widget(Clear, area);
 
     let block = Block::default()
-        .title(" Help (press ? or Esc to close) ")
+        .title(state.locale.title("Help (press ? or Esc to close)"))
         .borders(Borders::ALL)
-        .border_style(styles::border_focused());
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
 
-    let help_items = get_help_text();
+    let help_items = get_help_text(&state.keymap);
     
     let lines: Vec<Line> = help_items
         .iter()
@@ -114,13 +138,13 @@ pub fn render_help_popup(frame: &mut Frame, state: &AppState) {
             } else if desc.is_empty() {
                 // Section header
                 Line::from(Span::styled(
-                    *key,
-                    Style::default().add_modifier(Modifier::BOLD).fg(colors::HIGHLIGHT),
+                    state.locale.tr(key),
+                    Style::default().add_modifier(Modifier::BOLD).fg(state.theme.highlight),
                 ))
             } else {
                 Line::from(vec![
-                    Span::styled(format!("{:<16}", key), styles::highlight()),
-                    Span::raw(*desc),
+                    Span::styled(format!("{:<16}", key), styles::highlight(&state.theme)),
+                    Span::raw(state.locale.tr(desc)),
                 ])
             }
         })
@@ -145,9 +169,10 @@ pub fn render_delete_confirm_popup(frame: &mut Frame, state: &AppState) {
     frame.render_widget(Clear, area);
 
     let block = Block::default()
-        .title(" Delete Session? ")
+        .title(state.locale.title("Delete Session?"))
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(colors::ERROR));
+        .border_style(Style::default().fg(state.theme.error))
+        .style(styles::popup(&state.theme));
 
     let _inner_area = block.inner(area);
 
@@ -158,11 +183,7 @@ pub fn render_delete_confirm_popup(frame: &mut Frame, state: &AppState) {
         .unwrap_or_else(|| ("Unknown".to_string(), 0));
 
     // Truncate long session names
-    let display_name = if session_name.len() > 35 {
-        format!("{}...", &session_name[..32])
-    } else {
-        session_name.clone()
-    };
+    let display_name = truncate_to_width(&session_name, 35);
 
     // Build confirmation message
     let lines = vec![
@@ -178,14 +199,14 @@ pub fn render_delete_confirm_popup(frame: &mut Frame, state: &AppState) {
         Line::from(""),
         Line::from(Span::styled(
             "⚠ This action cannot be undone.",
-            Style::default().fg(colors::WARNING),
+            Style::default().fg(state.theme.warning),
         )),
         Line::from(""),
         Line::from(""),
         Line::from(vec![
-            Span::styled("[Y]", styles::highlight()),
+            Span::styled("[Y]", styles::highlight(&state.theme)),
             Span::raw(" Yes, delete    "),
-            Span::styled("[N]", styles::highlight()),
+            Span::styled("[N]", styles::highlight(&state.theme)),
             Span::raw(" No, cancel"),
         ]),
     ];
@@ -197,6 +218,878 @@ pub fn render_delete_confirm_popup(frame: &mut Frame, state: &AppState) {
     frame.render_widget(paragraph, area);
 }
 
+/// Render the session rename popup
+pub fn render_rename_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Rename {
+        return;
+    }
+
+    let area = centered_rect(50, 20, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Rename Session (Enter to save, Esc to cancel)"))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+
+    let lines = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Name: ", styles::highlight(&state.theme)),
+            Span::raw(state.rename_input.clone()),
+            Span::styled("█", styles::dim()),
+        ]),
+    ];
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the file picker: the folder, a filter and the matching entries
+pub fn render_file_picker_popup(frame: &mut Frame, state: &AppState) {
+    let Some(picker) = state.file_picker.as_ref() else {
+        return;
+    };
+    if state.input_mode != InputMode::FilePicker {
+        return;
+    }
+
+    let area = centered_rect(60, 70, frame.area());
+    frame.render_widget(Clear, area);
+
+    let mut block = Block::default()
+        .title(format!(
+            " {} ({}) ",
+            state.locale.tr(picker.purpose.title()),
+            state.locale.tr("Enter to open or pick, Esc to cancel")
+        ))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+    if picker.purpose == PickPurpose::AttachFile && !state.pending_attachments.is_empty() {
+        let names: Vec<&str> = state.pending_attachments.iter().map(|a| a.name.as_str()).collect();
+        block = block.title_bottom(format!(" 📎 {} · Ctrl+d removes the last ", names.join(", ")));
+    }
+
+    let mut lines = vec![
+        Line::from(Span::styled(picker.dir.display().to_string(), styles::dim())),
+        Line::from(vec![
+            Span::styled("Filter: ", styles::highlight(&state.theme)),
+            Span::raw(picker.query.clone()),
+            Span::styled("█", styles::dim()),
+        ]),
+        Line::from(""),
+    ];
+
+    let entries = picker.visible();
+    if let Some(error) = &picker.error {
+        lines.push(Line::from(Span::styled(format!("  {}", error), Style::default().fg(state.theme.error))));
+    } else if entries.is_empty() {
+        lines.push(Line::from(Span::styled("  Nothing here (Backspace goes up)", styles::dim())));
+    }
+
+    let rows = area.height.saturating_sub(5).max(1) as usize;
+    let (start, end) = page_bounds(picker.selected, entries.len(), rows);
+    for (idx, entry) in entries[start..end].iter().enumerate().map(|(i, e)| (start + i, e)) {
+        let is_selected = idx == picker.selected;
+        let indicator = if is_selected { "▶ " } else { "  " };
+        let style = if is_selected { styles::selected(&state.theme) } else { Style::default() };
+        let name = if entry.is_dir && entry.name != THIS_DIR {
+            format!("{}/", entry.name)
+        } else {
+            entry.name.clone()
+        };
+        lines.push(Line::from(Span::styled(format!("{}{}", indicator, name), style)));
+    }
+
+    let paragraph = Paragraph::new(lines).block(block);
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the search popup
+pub fn render_search_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Search {
+        return;
+    }
+
+    let area = centered_rect(70, 70, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Search Sessions (↑/↓ to navigate, Enter to open, Esc to close)"))
+        .title_bottom(format!(" {} results ", state.search_results.len()))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Search: ", styles::highlight(&state.theme)),
+            Span::raw(state.search_query.clone()),
+            Span::styled("█", styles::dim()),
+        ]),
+        Line::from(""),
+    ];
+
+    if state.search_results.is_empty() && !state.search_query.trim().is_empty() {
+        lines.push(Line::from(Span::styled("  No matches", styles::dim())));
+    }
+
+    // Two lines per result, paged around the selection
+    let rows = (area.height.saturating_sub(4) / 2).max(1) as usize;
+    let (start, end) = page_bounds(state.search_idx, state.search_results.len(), rows);
+    for (idx, hit) in state.search_results[start..end].iter().enumerate().map(|(i, h)| (start + i, h)) {
+        let is_selected = idx == state.search_idx;
+        let indicator = if is_selected { "▶" } else { " " };
+        let style = if is_selected { styles::selected(&state.theme) } else { Style::default() };
+        let name = state.chat.sessions.get(hit.session_idx).map_or("?", |s| s.name.as_str());
+        let location = match hit.message_idx {
+            Some(msg) => format!("  #{}", msg + 1),
+            None => "  (name)".to_string(),
+        };
+        lines.push(Line::from(vec![
+            Span::raw(format!("{} ", indicator)),
+            Span::styled(name.to_string(), style.add_modifier(Modifier::BOLD)),
+            Span::styled(location, styles::dim()),
+        ]));
+        lines.push(Line::from(Span::styled(format!("    {}", hit.snippet), styles::dim())));
+    }
+
+    // Not wrapped, so each result keeps to its two lines
+    let paragraph = Paragraph::new(lines).block(block);
+    
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the command palette
+pub fn render_palette_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Palette {
+        return;
+    }
+
+    let area = centered_rect(60, 60, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let entries = state.palette_entries();
+    let block = Block::default()
+        .title(state.locale.title("Command Palette (↑/↓ to navigate, Enter to run, Esc to close)"))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(": ", styles::highlight(&state.theme)),
+            Span::raw(state.palette_query.clone()),
+            Span::styled("█", styles::dim()),
+        ]),
+        Line::from(""),
+    ];
+
+    if entries.is_empty() {
+        lines.push(Line::from(Span::styled("  No matching actions", styles::dim())));
+    }
+
+    // Each action with its keys as bound, paged around the selection
+    let rows = area.height.saturating_sub(4).max(1) as usize;
+    let (start, end) = page_bounds(state.palette_idx, entries.len(), rows);
+    for (idx, def) in entries[start..end].iter().enumerate().map(|(i, d)| (start + i, d)) {
+        let is_selected = idx == state.palette_idx;
+        let indicator = if is_selected { "▶" } else { " " };
+        let style = if is_selected { styles::selected(&state.theme) } else { Style::default() };
+        let keys: Vec<String> = state.keymap.keys_for(def.name).iter().map(ToString::to_string).collect();
+        lines.push(Line::from(vec![
+            Span::raw(format!("{} ", indicator)),
+            Span::styled(state.locale.tr(def.description).to_string(), style),
+            Span::styled(format!("  {}", keys.join(" / ")), styles::dim()),
+        ]));
+    }
+
+    let paragraph = Paragraph::new(lines).block(block);
+    
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the apply-patch confirmation popup
+pub fn render_patch_confirm_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::PatchConfirm {
+        return;
+    }
+    let Some(patches) = &state.pending_patch else {
+        return;
+    };
+
+    let area = centered_rect(60, 50, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Apply Patch?"))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(state.theme.warning))
+        .style(styles::popup(&state.theme));
+
+    let mut lines = vec![Line::from("")];
+    for file_patch in patches {
+        let verb = match (file_patch.new_file, file_patch.deleted) {
+            (true, _) => "create",
+            (_, true) => "delete",
+            _ => "modify",
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} ", verb), styles::dim()),
+            Span::styled(
+                file_patch.path.display().to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(format!(" ({} hunks)", file_patch.hunks.len()), styles::dim()),
+        ]));
+    }
+    lines.extend([
+        Line::from(""),
+        Line::from(Span::styled(
+            "⚠ Files in the working directory will be overwritten.",
+            Style::default().fg(state.theme.warning),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("[Y]", styles::highlight(&state.theme)),
+            Span::raw(" Yes, apply    "),
+            Span::styled("[N]", styles::highlight(&state.theme)),
+            Span::raw(" No, cancel"),
+        ]),
+    ]);
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .alignment(Alignment::Center);
+    
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the picker for copying one of several code blocks
+pub fn render_code_picker_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::CodePicker {
+        return;
+    }
+
+    let area = centered_rect(60, 50, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Copy Code Block (↑/↓ or 1-9, Enter to copy, Esc to cancel)"))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+
+    let items: Vec<ListItem> = state
+        .code_picker
+        .iter()
+        .enumerate()
+        .map(|(idx, code)| {
+            let is_selected = idx == state.code_picker_idx;
+            let indicator = if is_selected { "▶" } else { " " };
+            let style = if is_selected {
+                styles::selected(&state.theme)
+            } else {
+                Style::default()
+            };
+            let lang = if code.lang.is_empty() { "text" } else { &code.lang };
+            let first_line = code.code.lines().find(|l| !l.trim().is_empty()).unwrap_or_default();
+
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} [{}] ", indicator, idx + 1)),
+                Span::styled(format!("{:<8}", lang), style),
+                Span::styled(format!(" {:>3} lines  ", code.code.lines().count()), styles::dim()),
+                Span::styled(first_line.trim().to_string(), styles::dim()),
+            ]))
+        })
+        .collect();
+
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// Render the yank history
+pub fn render_yanks_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Yanks {
+        return;
+    }
+    let popup = ListPopup {
+        title: state.locale.title("Yank History (Enter copy, Tab insert, d delete, Esc close)"),
+        empty: " Nothing yanked yet.",
+        size: (60, 50),
+    };
+    popup.render(frame, state, state.yanks.iter(), state.yank_idx, |text| {
+        (format!("{:>3} lines  ", text.lines().count()), first_line(text))
+    });
+}
+
+/// Render the saved snippet picker
+pub fn render_snippets_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Snippets {
+        return;
+    }
+    let popup = ListPopup {
+        title: state.locale.title("Snippets (Enter insert, a save input, d delete, Esc close)"),
+        empty: " No snippets yet. Type text in the input and press a to save it.",
+        size: (60, 50),
+    };
+    popup.render(frame, state, state.snippets.iter(), state.snippet_idx, |text| {
+        (format!("{:>3} lines  ", text.lines().count()), first_line(text))
+    });
+}
+
+/// Render the system prompt library
+pub fn render_prompts_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Prompts {
+        return;
+    }
+
+    let area = centered_rect(70, 60, frame.area());
+
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("System Prompts (Enter use, c clear, e edit library, Esc close)"))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let library = &state.prompts.prompts;
+    if library.is_empty() {
+        let hint = Paragraph::new(Line::styled(" The library is empty. Press e to add prompts.", styles::dim()));
+        frame.render_widget(hint, inner);
+        return;
+    }
+
+    // Names on top, the highlighted prompt's text below
+    let list_rows = (library.len() as u16).min(inner.height / 2).max(1);
+    let [list_area, text_area] =
+        Layout::vertical([Constraint::Length(list_rows), Constraint::Min(0)]).areas(inner);
+
+    let current = state.active_session().and_then(|s| s.prompt_name.as_deref());
+    let (start, end) = page_bounds(state.prompt_idx, library.len(), list_rows as usize);
+    let items: Vec<ListItem> = library
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(end - start)
+        .map(|(idx, prompt)| {
+            let is_selected = idx == state.prompt_idx;
+            let indicator = if is_selected { "▶" } else { " " };
+            let style = if is_selected {
+                styles::selected(&state.theme)
+            } else {
+                Style::default()
+            };
+            let mut spans = vec![
+                Span::raw(format!("{} ", indicator)),
+                Span::styled(prompt.name.clone(), style),
+            ];
+            if current == Some(prompt.name.as_str()) {
+                spans.push(Span::styled("  (this session)", styles::dim()));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    frame.render_widget(List::new(items), list_area);
+
+    if let Some(prompt) = library.get(state.prompt_idx) {
+        let text = Paragraph::new(prompt.text.as_str())
+            .block(Block::default().borders(Borders::TOP).border_style(styles::dim()))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(text, text_area);
+    }
+}
+
+/// Render the request a dry run would have sent
+pub fn render_dry_run_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::DryRun {
+        return;
+    }
+    let Some(report) = &state.dry_run_report else {
+        return;
+    };
+
+    let area = centered_rect(80, 80, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Dry Run: not sent (j/k scroll, y copy, Esc close)"))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(state.theme.warning))
+        .style(styles::popup(&state.theme));
+
+    let paragraph = Paragraph::new(report.as_str())
+        .block(block)
+        .scroll((state.dry_run_scroll, 0));
+    
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the context inspector: each part of the next request, its
+/// estimated tokens and whether it fits in the prompt budget
+pub fn render_context_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::ContextInspect {
+        return;
+    }
+
+    let area = centered_rect(80, 70, frame.area());
+
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let entries = state.context_entries();
+    let (sent, left_out): (Vec<_>, Vec<_>) = entries.iter().partition(|e| e.included);
+    let sent_tokens: usize = sent.iter().map(|e| e.tokens).sum();
+    let summary = match state.prompt_budget() {
+        Some(budget) => format!(
+            " ~{} of {} prompt tokens sent, {} left out ",
+            sent_tokens,
+            budget,
+            left_out.len()
+        ),
+        None => format!(" ~{} tokens; context window unknown, so everything is sent ", sent_tokens),
+    };
+    let block = Block::default()
+        .title(state.locale.title("Context (j/k scroll, Esc close)"))
+        .title_bottom(summary)
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+
+    if entries.is_empty() {
+        let hint = Paragraph::new(Line::styled(" Nothing to send yet.", styles::dim())).block(block);
+        frame.render_widget(hint, area);
+        return;
+    }
+
+    let preview_width = area.width.saturating_sub(26) as usize;
+    let selected = state.context_scroll as usize;
+    let (start, end) = page_bounds(selected, entries.len(), area.height.saturating_sub(2) as usize);
+    let items: Vec<ListItem> = entries
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(end - start)
+        .map(|(idx, entry)| {
+            let (marker, color) = if entry.included { ("✓", state.theme.success) } else { ("✗", state.theme.error) };
+            let mut text = Style::default();
+            if !entry.included {
+                text = text.add_modifier(Modifier::DIM);
+            }
+            if idx == selected {
+                text = text.add_modifier(Modifier::REVERSED);
+            }
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {} ", marker), Style::default().fg(color)),
+                Span::styled(format!("{:>6}  ", entry.tokens), Style::default().fg(color)),
+                Span::styled(format!("{:<10} ", entry.label), styles::dim()),
+                Span::styled(truncate_to_width(&entry.preview, preview_width), text),
+            ]))
+        })
+        .collect();
+
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// Render the response cache inspector
+pub fn render_cache_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Cache {
+        return;
+    }
+    let popup = ListPopup {
+        title: format!(
+            " Response Cache: {} entries{} (d delete, D clear, Esc close) ",
+            state.cache.entries.len(),
+            if state.config.cache.enabled { "" } else { ", disabled" }
+        ),
+        empty: if state.config.cache.enabled {
+            " Nothing cached yet."
+        } else {
+            " Nothing cached. Set enabled = true under [cache] to reuse answers."
+        },
+        size: (70, 60),
+    };
+    let now = chrono::Utc::now();
+    popup.render(frame, state, state.cache.entries.iter(), state.cache_idx, |entry| {
+        let age = match (now - entry.created).num_minutes() {
+            m if m < 60 => format!("{}m", m),
+            m if m < 60 * 24 => format!("{}h", m / 60),
+            m => format!("{}d", m / (60 * 24)),
+        };
+        let details = format!("{:>4} ago  {:>3} hits  {}  ", age, entry.hits, entry.model);
+        (details, first_line(&entry.prompt))
+    });
+}
+
+/// A popup listing entries one per line, a page at a time
+struct ListPopup<'a> {
+    title: String,
+    /// Shown in place of the list when there are no entries
+    empty: &'a str,
+    /// Width and height in percent of the screen
+    size: (u16, u16),
+}
+
+impl ListPopup<'_> {
+    /// Render `entries`, or the empty hint when there are none
+    ///
+    /// `row` gives each entry's dimmed details and its text, which is
+    /// highlighted on the `selected` entry.
+    fn render<T>(
+        self,
+        frame: &mut Frame,
+        state: &AppState,
+        entries: impl ExactSizeIterator<Item = T>,
+        selected: usize,
+        row: impl Fn(T) -> (String, String),
+    ) {
+        let area = centered_rect(self.size.0, self.size.1, frame.area());
+
+        // Clear the background
+        frame.render_widget(Clear, area);
+
+        let block = Block::default()
+            .title(self.title)
+            .borders(Borders::ALL)
+            .border_style(styles::popup_border(&state.theme))
+            .style(styles::popup(&state.theme));
+
+        if entries.len() == 0 {
+            frame.render_widget(Paragraph::new(Line::styled(self.empty, styles::dim())).block(block), area);
+            return;
+        }
+
+        let (start, end) = page_bounds(selected, entries.len(), area.height.saturating_sub(2) as usize);
+        let items: Vec<ListItem> = entries
+            .enumerate()
+            .skip(start)
+            .take(end - start)
+            .map(|(idx, entry)| {
+                let is_selected = idx == selected;
+                let indicator = if is_selected { "▶" } else { " " };
+                let style = if is_selected {
+                    styles::selected(&state.theme)
+                } else {
+                    Style::default()
+                };
+                let (details, text) = row(entry);
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{} ", indicator)),
+                    Span::styled(details, styles::dim()),
+                    Span::styled(text, style),
+                ]))
+            })
+            .collect();
+
+        frame.render_widget(List::new(items).block(block), area);
+    }
+}
+
+/// First non-blank line of `text`, trimmed
+fn first_line(text: &str) -> String {
+    text.lines().find(|l| !l.trim().is_empty()).unwrap_or_default().trim().to_string()
+}
+
+/// Render the before/after review of an anonymized export
+pub fn render_redact_review_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::RedactReview {
+        return;
+    }
+    let Some(review) = &state.redact_review else {
+        return;
+    };
+
+    let area = centered_rect(80, 70, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Anonymized Export (j/k changes, m model pass, Enter export, Esc cancel)"))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let [summary_area, panes] = Layout::vertical([Constraint::Length(2), Constraint::Min(1)]).areas(inner);
+    let changed = review.changed();
+    let mut summary = vec![Span::raw(format!(
+        " {} of {} messages changed",
+        changed.len(),
+        review.before.messages.len()
+    ))];
+    if let Some(pos) = changed.iter().position(|&idx| idx == review.idx) {
+        summary.push(Span::styled(format!("  ·  change {} of {}", pos + 1, changed.len()), styles::dim()));
+    }
+    if review.pending > 0 {
+        summary.push(Span::styled(
+            format!("  ·  model pass: {} left", review.pending),
+            Style::default().fg(state.theme.warning),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(summary)), summary_area);
+
+    let [before_area, after_area] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(panes);
+    let panes = [("Before", &review.before, before_area), ("After", &review.after, after_area)];
+    for (title, session, pane) in panes {
+        let role = session.messages.get(review.idx).map(|m| m.role.to_string()).unwrap_or_default();
+        let text = session.messages.get(review.idx).map(|m| m.content.as_str()).unwrap_or_default();
+        let block = Block::default()
+            .title(format!(" {} · {} ", title, role))
+            .borders(Borders::TOP)
+            .border_style(styles::dim());
+        let paragraph = Paragraph::new(text.to_string())
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, pane);
+    }
+}
+
+/// Render details of the selected message
+pub fn render_message_inspect_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::MessageInspect {
+        return;
+    }
+    let Some(message) = state.selected() else {
+        return;
+    };
+
+    let area = centered_rect(50, 50, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Message (any key to close)"))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+
+    let marks: String = state
+        .active_session()
+        .map(|s| s.marks.iter().filter(|(_, id)| **id == message.id).map(|(c, _)| *c).collect())
+        .unwrap_or_default();
+    let feedback = match &message.feedback {
+        Some(f) => {
+            let rating = if f.rating == Rating::Up { "+" } else { "-" };
+            match &f.note {
+                Some(note) => format!("{} ({})", rating, note),
+                None => rating.to_string(),
+            }
+        }
+        None => "none".to_string(),
+    };
+
+    let mut fields = vec![
+        ("Role", message.role.to_string()),
+        ("Sent", message.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string()),
+        ("Id", message.id.to_string()),
+        ("Length", format!(
+            "{} chars, {} words, {} lines",
+            message.content.chars().count(),
+            message.content.split_whitespace().count(),
+            message.content.lines().count()
+        )),
+    ];
+    if !message.thinking.is_empty() {
+        fields.push(("Reasoning", format!("{} chars", message.thinking.chars().count())));
+    }
+    if !message.images.is_empty() {
+        fields.push(("Images", message.images.len().to_string()));
+    }
+    if !marks.is_empty() {
+        fields.push(("Marks", marks));
+    }
+    if message.role == Role::Assistant {
+        fields.push(("Model", message.model.clone().unwrap_or_else(|| "unknown".to_string())));
+        fields.push(("Rating", feedback));
+        fields.push(("Flagged", if message.flagged { "yes" } else { "no" }.to_string()));
+        fields.push(("Interrupted", if message.interrupted { "yes" } else { "no" }.to_string()));
+        let first_token = message.first_token_ms.map(super::format_latency);
+        fields.push(("First token", first_token.unwrap_or_else(|| "not recorded".to_string())));
+        let settings = message.options.as_ref().map(GenerationOptions::describe);
+        fields.push(("Settings", settings.unwrap_or_else(|| "not recorded".to_string())));
+    }
+
+    let mut lines = vec![Line::from("")];
+    lines.extend(fields.into_iter().map(|(label, value)| {
+        Line::from(vec![
+            Span::styled(format!(" {:<12}", label), styles::dim()),
+            Span::raw(value),
+        ])
+    }));
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the popup offering to pull a missing model
+pub fn render_pull_confirm_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::PullConfirm {
+        return;
+    }
+    let Some(model) = &state.missing_model else {
+        return;
+    };
+
+    let area = centered_rect(50, 40, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Model Not Installed"))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(state.theme.warning))
+        .style(styles::popup(&state.theme));
+
+    let lines = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Model: "),
+            Span::styled(model.clone(), Style::default().add_modifier(Modifier::BOLD)),
+        ]),
+        Line::from(""),
+        Line::from("This model isn't available on the server."),
+        Line::from("Download it now?"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("[Y]", styles::highlight(&state.theme)),
+            Span::raw(" Yes, pull    "),
+            Span::styled("[N]", styles::highlight(&state.theme)),
+            Span::raw(" No, cancel"),
+        ]),
+    ];
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .alignment(Alignment::Center);
+    
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the model downloads popup
+pub fn render_downloads_popup(frame: &mut Frame, state: &AppState) {
+    if state.input_mode != InputMode::Downloads {
+        return;
+    }
+
+    let area = centered_rect(60, 70, frame.area());
+    
+    // Clear the background
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(state.locale.title("Download Models (type a name or pick one, Enter to queue, Esc to close)"))
+        .title_bottom(state.locale.title("Ctrl+l: clear finished"))
+        .borders(Borders::ALL)
+        .border_style(styles::popup_border(&state.theme))
+        .style(styles::popup(&state.theme));
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Model: ", styles::highlight(&state.theme)),
+            Span::raw(state.pull_input.clone()),
+            Span::styled("█", styles::dim()),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Popular",
+            Style::default().add_modifier(Modifier::BOLD).fg(state.theme.highlight),
+        )),
+    ];
+
+    let typing = !state.pull_input.trim().is_empty();
+    for (idx, model) in POPULAR_MODELS.iter().enumerate() {
+        let is_selected = !typing && idx == state.pull_selected_idx;
+        let indicator = if is_selected { "▶" } else { " " };
+        let style = if is_selected { styles::selected(&state.theme) } else { Style::default() };
+        lines.push(Line::from(vec![
+            Span::raw(format!("{} ", indicator)),
+            Span::styled(*model, style),
+        ]));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Downloads",
+        Style::default().add_modifier(Modifier::BOLD).fg(state.theme.highlight),
+    )));
+    if state.downloads.is_empty() {
+        lines.push(Line::from(Span::styled("  Nothing queued", styles::dim())));
+    }
+    for download in &state.downloads {
+        lines.push(download_line(download, &state.theme));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    
+    frame.render_widget(paragraph, area);
+}
+
+/// One row of the downloads list: status, name, and progress
+fn download_line(download: &Download, theme: &Theme) -> Line<'static> {
+    let (icon, icon_style, detail) = match &download.status {
+        DownloadStatus::Queued => ("…", styles::dim(), "queued".to_string()),
+        DownloadStatus::Active => {
+            let bar = progress_bar(download.progress(), 20);
+            let detail = if download.total > 0 {
+                format!(
+                    "{} {:>3.0}% {}",
+                    bar,
+                    download.progress() * 100.0,
+                    download.detail
+                )
+            } else {
+                download.detail.clone()
+            };
+            ("↓", styles::highlight(theme), detail)
+        }
+        DownloadStatus::Done => ("✓", Style::default().fg(theme.success), "done".to_string()),
+        DownloadStatus::Failed(err) => ("✗", Style::default().fg(theme.error), err.clone()),
+    };
+
+    Line::from(vec![
+        Span::styled(format!("  {} ", icon), icon_style),
+        Span::styled(
+            format!("{:<24} ", download.model),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(detail, styles::dim()),
+    ])
+}
+
+/// Text progress bar of `width` cells
+fn progress_bar(fraction: f64, width: usize) -> String {
+    let filled = (fraction * width as f64).round() as usize;
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(width - filled.min(width)))
+}
+
 /// Create a centered rect with percentage of parent
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -218,6 +1111,13 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
+/// Range of the page of `rows` entries containing `selected`
+fn page_bounds(selected: usize, len: usize, rows: usize) -> (usize, usize) {
+    let rows = rows.max(1);
+    let start = (selected.min(len.saturating_sub(1)) / rows) * rows;
+    (start, (start + rows).min(len))
+}
+
 /// Format file size in human-readable form
 fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
@@ -246,4 +1146,20 @@ mod tests {
         assert_eq!(format_size(1_500_000), "1.4 MB");
         assert_eq!(format_size(4_000_000_000), "3.7 GB");
     }
+
+    #[test]
+    fn test_page_bounds() {
+        assert_eq!(page_bounds(0, 5, 10), (0, 5));
+        assert_eq!(page_bounds(9, 300, 10), (0, 10));
+        assert_eq!(page_bounds(10, 300, 10), (10, 20));
+        assert_eq!(page_bounds(299, 300, 7), (294, 300));
+        assert_eq!(page_bounds(0, 0, 10), (0, 0));
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(0.0, 4), "[----]");
+        assert_eq!(progress_bar(0.5, 4), "[##--]");
+        assert_eq!(progress_bar(1.0, 4), "[####]");
+    }
 }
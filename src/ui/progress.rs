@@ -0,0 +1,33 @@
+//! Progress gauge
+//!
+//! Long operations (model pulls, dataset exports) report how far along they
+//! are with `AppEvent::Progress`. The status bar shows the oldest one as a
+//! gauge, with a count of any others running.
+
+use ratatui::{layout::Rect, style::Style, widgets::Gauge, Frame};
+
+use crate::app::AppState;
+
+/// Columns the gauge takes in the status bar
+pub const GAUGE_WIDTH: u16 = 32;
+
+/// Render the gauge of the oldest running operation, if any
+pub fn render_progress(frame: &mut Frame, state: &AppState, area: Rect) {
+    let Some(first) = state.progress.first() else {
+        return;
+    };
+    let percent = if first.total > 0 {
+        format!("{:.0}%", first.ratio() * 100.0)
+    } else {
+        "…".to_string()
+    };
+    let label = match state.progress.len() - 1 {
+        0 => format!("{} {}", first.id, percent),
+        more => format!("{} {} +{}", first.id, percent, more),
+    };
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(state.theme.info).bg(state.theme.status_bg))
+        .ratio(first.ratio())
+        .label(label);
+    frame.render_widget(gauge, area);
+}
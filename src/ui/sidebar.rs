@@ -8,10 +8,11 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, Paragraph},
     Frame,
 };
+use unicode_width::UnicodeWidthStr;
 
 use crate::app::AppState;
 
-use super::styles;
+use super::{styles, truncate_to_width};
 
 /// Render the sidebar
 pub fn render_sidebar(frame: &mut Frame, state: &AppState, area: Rect) {
@@ -31,13 +32,13 @@ pub fn render_sidebar(frame: &mut Frame, state: &AppState, area: Rect) {
 /// Render the sessions list
 fn render_sessions_list(frame: &mut Frame, state: &AppState, area: Rect) {
     let block = Block::default()
-        .title(" Sessions ")
+        .title(state.locale.title("Sessions"))
         .borders(Borders::ALL)
-        .border_style(styles::border_normal());
+        .border_style(styles::border_normal(&state.theme));
 
     let inner_area = block.inner(area);
 
-    if state.sessions.is_empty() {
+    if state.chat.sessions.is_empty() {
         let empty = Paragraph::new(Span::styled("No sessions", styles::dim()))
             .block(block);
         frame.render_widget(empty, area);
@@ -46,38 +47,40 @@ fn render_sessions_list(frame: &mut Frame, state: &AppState, area: Rect) {
 
     // Build list items
     let items: Vec<ListItem> = state
+        .chat
         .sessions
         .iter()
         .enumerate()
         .map(|(idx, session)| {
-            let is_selected = idx == state.active_session_idx;
+            let is_selected = idx == state.chat.active_session_idx;
             let is_streaming = session.is_streaming();
             
             // Session indicator
             let indicator = if is_streaming {
-                "⣾"
+                state.spinner()
             } else if is_selected {
-                "▶"
+                '▶'
             } else {
-                " "
+                ' '
             };
 
-            // Truncate name to fit
-            let max_name_len = area.width.saturating_sub(6) as usize;
-            let name = if session.name.len() > max_name_len {
-                format!("{}…", &session.name[..max_name_len.saturating_sub(1)])
-            } else {
-                session.name.clone()
-            };
+            // Truncate name to fit, leaving room for the pin and remote markers
+            let pin = if session.pinned { "★ " } else { "" };
+            let remote = if state.config.is_remote_model(&session.model) { "☁ " } else { "" };
+            let markers = (pin.width() + remote.width()) as u16;
+            let max_name_len = area.width.saturating_sub(6 + markers) as usize;
+            let name = truncate_to_width(&session.name, max_name_len);
 
             let style = if is_selected {
-                styles::selected()
+                styles::selected(&state.theme)
             } else {
                 ratatui::style::Style::default()
             };
 
             let line = Line::from(vec![
                 Span::raw(format!("{} ", indicator)),
+                Span::styled(pin, styles::highlight(&state.theme)),
+                Span::styled(remote, styles::remote(&state.theme)),
                 Span::styled(name, style),
             ]);
 
@@ -89,7 +92,7 @@ fn render_sessions_list(frame: &mut Frame, state: &AppState, area: Rect) {
     frame.render_widget(list, area);
 
     // Show hint at bottom if there's space
-    if inner_area.height > state.sessions.len() as u16 + 2 {
+    if inner_area.height > state.chat.sessions.len() as u16 + 2 {
         let hint_y = area.y + area.height - 2;
         let hint = Paragraph::new(Span::styled("Ctrl+n: new", styles::dim()));
         frame.render_widget(
@@ -107,25 +110,30 @@ fn render_sessions_list(frame: &mut Frame, state: &AppState, area: Rect) {
 /// Render the model info box
 fn render_model_info(frame: &mut Frame, state: &AppState, area: Rect) {
     let block = Block::default()
-        .title(" Model ")
+        .title(state.locale.title("Model"))
         .borders(Borders::ALL)
-        .border_style(styles::border_normal());
+        .border_style(styles::border_normal(&state.theme));
 
     let inner_area = block.inner(area);
 
     // Current model name
     let model_name = state.current_model();
     let max_len = inner_area.width as usize;
-    let display_name = if model_name.len() > max_len {
-        format!("{}…", &model_name[..max_len.saturating_sub(1)])
-    } else {
-        model_name.to_string()
-    };
+    let display_name = truncate_to_width(model_name, max_len);
 
+    let status = match state.refresh_spinner() {
+        Some(frame) => Line::from(Span::styled(format!("{} refreshing…", frame), styles::dim())),
+        None => Line::from(""),
+    };
+    // The hint follows the binding, and goes when nothing is bound
+    let hint = match state.keymap.keys_for("select_model").first() {
+        Some(key) => format!("{}: change", key),
+        None => String::new(),
+    };
     let lines = vec![
-        Line::from(Span::styled(display_name, styles::highlight())),
-        Line::from(""),
-        Line::from(Span::styled("m: change", styles::dim())),
+        Line::from(Span::styled(display_name, styles::highlight(&state.theme))),
+        status,
+        Line::from(Span::styled(hint, styles::dim())),
     ];
 
     let paragraph = Paragraph::new(lines).block(block);
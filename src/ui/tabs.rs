@@ -0,0 +1,145 @@
+//! Session tab bar
+//!
+//! With `[ui] tabs = true`, a row of tabs across the top replaces the
+//! sidebar: one per session, numbered for Alt+1..9, with a spinner on a
+//! session that is streaming. When they don't all fit, the row scrolls to
+//! keep the active tab in view.
+
+use ratatui::{
+    layout::Rect,
+    style::Modifier,
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::{AppState, ChatSession};
+
+use super::{styles, truncate_to_width};
+
+/// Widest a session name gets in its tab
+const MAX_NAME_WIDTH: usize = 20;
+
+/// Render the tab bar
+pub fn render_tabs(frame: &mut Frame, state: &AppState, area: Rect) {
+    let mut spans = Vec::new();
+    for tab in tab_positions(state, area.width) {
+        let style = if tab.idx == state.chat.active_session_idx {
+            styles::selected(&state.theme).add_modifier(Modifier::REVERSED)
+        } else {
+            styles::dim()
+        };
+        spans.push(Span::styled(tab.label, style));
+        if tab.start + tab.width < usize::from(area.width) {
+            spans.push(Span::styled("│", styles::border_normal(&state.theme)));
+        }
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// The session whose tab is at column `x` of the tab bar in `area`
+pub fn tab_at(state: &AppState, area: Rect, x: u16) -> Option<usize> {
+    let column = x.checked_sub(area.x)?;
+    let column = usize::from(column);
+    tab_positions(state, area.width)
+        .into_iter()
+        .find(|tab| (tab.start..=tab.start + tab.width).contains(&column))
+        .map(|tab| tab.idx)
+}
+
+/// One tab laid out in the bar
+struct Tab {
+    idx: usize,
+    label: String,
+    /// First column of the tab
+    start: usize,
+    /// Width of the label; the separator after it takes one more column
+    width: usize,
+}
+
+/// Tabs that fit in `width` columns, scrolled so the active tab is shown
+///
+/// An active tab wider than the whole bar is cut down to fit, without its
+/// separator, rather than leaving the bar empty.
+fn tab_positions(state: &AppState, width: u16) -> Vec<Tab> {
+    let width = usize::from(width);
+    let labels: Vec<String> = state
+        .chat
+        .sessions
+        .iter()
+        .enumerate()
+        .map(|(idx, session)| label(idx, session, state.spinner()))
+        .collect();
+    let widths: Vec<usize> = labels.iter().map(|label| label.width() + 1).collect();
+    if labels.is_empty() {
+        return Vec::new();
+    }
+    let active = state.chat.active_session_idx.min(labels.len() - 1);
+    if widths[active] > width {
+        let label = truncate_to_width(&labels[active], width);
+        return vec![Tab { idx: active, width: label.width(), label, start: 0 }];
+    }
+
+    // Drop tabs from the left until the run up to the active one fits
+    let mut first = 0;
+    let mut used: usize = widths[..=active].iter().sum();
+    while used > width {
+        used -= widths[first];
+        first += 1;
+    }
+
+    let mut positions = Vec::new();
+    let mut x = 0;
+    for (idx, label) in labels.into_iter().enumerate().skip(first) {
+        if x + widths[idx] > width {
+            break;
+        }
+        positions.push(Tab { idx, label, start: x, width: widths[idx] - 1 });
+        x += widths[idx];
+    }
+    positions
+}
+
+/// ` 2 ⠋ Name `: the Alt shortcut, a streaming (`spinner`) or pinned marker
+/// and the name
+fn label(idx: usize, session: &ChatSession, spinner: char) -> String {
+    let number = if idx < 9 { format!("{} ", idx + 1) } else { String::new() };
+    let marker = if session.is_streaming() {
+        format!("{} ", spinner)
+    } else if session.pinned {
+        "★ ".to_string()
+    } else {
+        String::new()
+    };
+    format!(" {}{}{} ", number, marker, truncate_to_width(&session.name, MAX_NAME_WIDTH))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_tabs_scroll_to_active() {
+        let mut state = AppState::new(Config::default());
+        state.chat.sessions = (0..12).map(|i| ChatSession::new(format!("s{}", i), "llama3.2")).collect();
+        let area = Rect::new(0, 0, 40, 1);
+
+        // " 1 s0 │" is 7 columns wide
+        assert_eq!(tab_at(&state, area, 0), Some(0));
+        assert_eq!(tab_at(&state, area, 7), Some(1));
+
+        state.chat.active_session_idx = 11;
+        let shown: Vec<usize> = tab_positions(&state, area.width).iter().map(|tab| tab.idx).collect();
+        assert_eq!(shown.last(), Some(&11));
+        assert!(!shown.contains(&0));
+
+        // A tab too wide for the bar is cut down instead of dropped
+        state.chat.sessions[11].name = "a rather long session name".to_string();
+        let narrow = tab_positions(&state, 10);
+        assert_eq!(narrow.len(), 1);
+        assert_eq!(narrow[0].idx, 11);
+        assert_eq!(narrow[0].label.width(), 10);
+    }
+}
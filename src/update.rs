@@ -0,0 +1,303 @@
+//! Updates from GitHub releases
+//!
+//! `ratatalk self-update` downloads the binary for this platform from the
+//! latest release, checks it against the release's `SHA256SUMS` and swaps
+//! it in place of the running executable. With `[update] check` the TUI
+//! also looks for a newer release on the configured channel, at most once
+//! per interval, and shows a one-line notice until it is dismissed.
+
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::{UpdateChannel, UpdateConfig};
+use crate::error::UpdateError;
+
+/// Repository the releases are published from
+const REPO: &str = "mohammad-albarham/ratatalk";
+
+/// Checksum file attached to each release
+const CHECKSUMS: &str = "SHA256SUMS";
+
+/// Version of the running binary
+pub const CURRENT: &str = env!("CARGO_PKG_VERSION");
+
+/// A semantic version, e.g. `1.4.0` or `1.5.0-rc.1`
+///
+/// Ordered by semver precedence, so pre-releases compare identifier by
+/// identifier and `1.0.0-rc.10` comes after `1.0.0-rc.2`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(semver::Version);
+
+impl Version {
+    /// Parse a version or release tag, with or without a leading `v`
+    pub fn parse(text: &str) -> Result<Self, UpdateError> {
+        let trimmed = text.trim();
+        semver::Version::parse(trimmed.strip_prefix('v').unwrap_or(trimmed))
+            .map(Self)
+            .map_err(|_| UpdateError::BadVersion(text.to_string()))
+    }
+
+    /// The running binary's version
+    pub fn current() -> Self {
+        Self::parse(CURRENT).expect("package version is valid semver")
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A GitHub release
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+/// A file attached to a release
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    fn asset_url(&self, name: &str) -> Option<&str> {
+        self.assets
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.browser_download_url.as_str())
+    }
+}
+
+/// Name of the release asset for this platform, e.g. `ratatalk-x86_64-linux`
+pub fn asset_name() -> String {
+    format!(
+        "ratatalk-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+fn http_client() -> Result<reqwest::Client, UpdateError> {
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!("ratatalk/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(60))
+        .build()?)
+}
+
+/// Newest release published on `channel`
+///
+/// GitHub's "latest" release never is a pre-release, so the pre-release
+/// channel looks through the recent releases instead.
+pub async fn newest_release(channel: UpdateChannel) -> Result<(Version, Release), UpdateError> {
+    let client = http_client()?;
+    match channel {
+        UpdateChannel::Stable => {
+            let url = format!("https://api.github.com/repos/{}/releases/latest", REPO);
+            let release: Release = client.get(url).send().await?.error_for_status()?.json().await?;
+            Ok((Version::parse(&release.tag_name)?, release))
+        }
+        UpdateChannel::Prerelease => {
+            let url = format!("https://api.github.com/repos/{}/releases?per_page=20", REPO);
+            let releases: Vec<Release> = client.get(url).send().await?.error_for_status()?.json().await?;
+            releases
+                .into_iter()
+                .filter(|r| !r.draft)
+                .filter_map(|r| Some((Version::parse(&r.tag_name).ok()?, r)))
+                .max_by(|(a, _), (b, _)| a.cmp(b))
+                .ok_or_else(|| UpdateError::BadVersion("no releases".to_string()))
+        }
+    }
+}
+
+/// Outcome of the last update check, cached between runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateCheck {
+    pub checked: DateTime<Utc>,
+    pub channel: UpdateChannel,
+    /// Newest version found on the channel
+    pub latest: String,
+    /// Version whose notice was dismissed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dismissed: Option<String>,
+}
+
+impl UpdateCheck {
+    /// Whether this result can stand in for a check now
+    pub fn is_fresh(&self, config: &UpdateConfig, now: DateTime<Utc>) -> bool {
+        self.channel == config.channel && now - self.checked < chrono::Duration::hours(config.interval_hours as i64)
+    }
+
+    /// Version to announce: newer than this binary and not dismissed
+    pub fn announce(&self) -> Option<Version> {
+        let latest = Version::parse(&self.latest).ok()?;
+        let dismissed = self.dismissed.as_deref().and_then(|d| Version::parse(d).ok());
+        (latest > Version::current() && dismissed.as_ref() != Some(&latest)).then_some(latest)
+    }
+}
+
+/// Check for an update, using the cached result while it is fresh
+///
+/// Returns the version to announce, if any.
+pub async fn check(config: &UpdateConfig) -> Result<Option<Version>, UpdateError> {
+    let now = Utc::now();
+    let cached = crate::persistence::load_update_check().ok().flatten();
+    if let Some(cached) = cached.as_ref().filter(|c| c.is_fresh(config, now)) {
+        return Ok(cached.announce());
+    }
+    let check = UpdateCheck {
+        checked: now,
+        channel: config.channel,
+        latest: newest_release(config.channel).await?.0.to_string(),
+        dismissed: cached.and_then(|c| c.dismissed),
+    };
+    if let Err(e) = crate::persistence::save_update_check(&check) {
+        tracing::warn!("Failed to cache update check: {}", e);
+    }
+    Ok(check.announce())
+}
+
+/// Stop announcing `version`
+pub fn dismiss(version: &str) -> Result<(), crate::error::PersistenceError> {
+    let Some(mut check) = crate::persistence::load_update_check()? else {
+        return Ok(());
+    };
+    check.dismissed = Some(version.to_string());
+    crate::persistence::save_update_check(&check)
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, UpdateError> {
+    Ok(client.get(url).send().await?.error_for_status()?.bytes().await?.to_vec())
+}
+
+/// Checksum listed for `name` in a `sha256sum`-style file
+fn expected_checksum<'a>(sums: &'a str, name: &str) -> Option<&'a str> {
+    sums.lines().find_map(|line| {
+        let (hash, file) = line.split_once(char::is_whitespace)?;
+        let file = file.trim_start();
+        (file.strip_prefix('*').unwrap_or(file) == name).then_some(hash)
+    })
+}
+
+/// Fail unless `data` hashes to the hex digest `expected`
+fn verify(data: &[u8], expected: &str) -> Result<(), UpdateError> {
+    let actual: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(UpdateError::ChecksumMismatch { expected: expected.to_string(), actual })
+    }
+}
+
+/// Put `binary` in place of the executable at `exe`
+///
+/// The new file is written next to the old one and renamed over it, so an
+/// interrupted update leaves the old binary working. Windows cannot replace
+/// a running executable, so there it is moved aside first.
+fn replace_exe(exe: &Path, binary: &[u8]) -> Result<(), UpdateError> {
+    let staged = exe.with_extension("new");
+    std::fs::write(&staged, binary).map_err(UpdateError::Install)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755)).map_err(UpdateError::Install)?;
+    }
+    if cfg!(windows) {
+        let old = exe.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(exe, &old).map_err(UpdateError::Install)?;
+    }
+    std::fs::rename(&staged, exe).map_err(UpdateError::Install)
+}
+
+/// Run `ratatalk self-update`, printing progress
+pub async fn self_update(channel: UpdateChannel) -> Result<(), UpdateError> {
+    let (latest, release) = newest_release(channel).await?;
+    let current = Version::current();
+    if latest <= current {
+        println!("ratatalk {} is up to date", current);
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let binary_url = release.asset_url(&name).ok_or_else(|| UpdateError::NoAsset(name.clone()))?;
+    let sums_url = release.asset_url(CHECKSUMS).ok_or_else(|| UpdateError::NoChecksum(name.clone()))?;
+
+    println!("Downloading ratatalk {} ({})...", latest, name);
+    let client = http_client()?;
+    let binary = download(&client, binary_url).await?;
+    let sums = String::from_utf8_lossy(&download(&client, sums_url).await?).into_owned();
+    let expected = expected_checksum(&sums, &name).ok_or_else(|| UpdateError::NoChecksum(name.clone()))?;
+    verify(&binary, expected)?;
+
+    let exe = std::env::current_exe().map_err(UpdateError::Install)?;
+    replace_exe(&exe, &binary)?;
+    println!("Updated ratatalk {} -> {} ({})", current, latest, exe.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_ordering() {
+        let v = |s| Version::parse(s).unwrap();
+        assert!(v("v0.2.0") > v("0.1.9"));
+        assert!(v("1.0.0") > v("1.0.0-rc.1"));
+        assert!(v("1.0.0-rc.2") > v("1.0.0-rc.1"));
+        assert!(v("1.0.0-rc.10") > v("1.0.0-rc.2"));
+        assert_eq!(v("v1.2.3"), v("1.2.3"));
+        assert_eq!(v("1.5.0-beta").to_string(), "1.5.0-beta");
+        for bad in ["1.2", "1.2.3.4", "latest", "v1.x.0"] {
+            assert!(Version::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_cached_check_freshness_and_dismissal() {
+        let now = Utc::now();
+        let config = UpdateConfig::default();
+        let mut check = UpdateCheck {
+            checked: now - chrono::Duration::hours(2),
+            channel: UpdateChannel::Stable,
+            latest: "99.0.0".to_string(),
+            dismissed: None,
+        };
+        assert!(check.is_fresh(&config, now));
+        assert!(!check.is_fresh(&config, now + chrono::Duration::days(1)));
+        assert!(!check.is_fresh(&UpdateConfig { channel: UpdateChannel::Prerelease, ..config.clone() }, now));
+
+        assert_eq!(check.announce().map(|v| v.to_string()).as_deref(), Some("99.0.0"));
+        check.dismissed = Some("v99.0.0".to_string());
+        assert!(check.announce().is_none());
+        check.latest = CURRENT.to_string();
+        check.dismissed = None;
+        assert!(check.announce().is_none());
+    }
+
+    #[test]
+    fn test_checksum_lookup_and_verify() {
+        let sums = "\
+2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  ratatalk-x86_64-linux
+0000000000000000000000000000000000000000000000000000000000000000 *ratatalk-aarch64-macos
+";
+        let hash = expected_checksum(sums, "ratatalk-x86_64-linux").unwrap();
+        assert!(verify(b"hello", hash).is_ok());
+        assert!(verify(b"hello!", hash).is_err());
+        assert!(expected_checksum(sums, "ratatalk-aarch64-macos").unwrap().starts_with("0000"));
+        assert!(expected_checksum(sums, "ratatalk-x86_64-windows.exe").is_none());
+    }
+}